@@ -0,0 +1,65 @@
+//! Compares the scalar and SIMD slab tests over a grid of boxes, some hit
+//! and some missed, including rays parallel to a pair of faces.
+//!
+//! cargo bench -p geometry3d --bench aabb
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use geometry3d::*;
+
+fn boxes() -> Vec<AABB> {
+    let mut boxes = Vec::new();
+    for x in -8..8 {
+        for y in -8..8 {
+            let lo = Point3::new(x as Float, y as Float, -1.0);
+            boxes.push(AABB::new(lo, lo + Vec3::new(0.8, 0.8, 2.0)));
+        }
+    }
+    boxes
+}
+
+fn rays() -> Vec<SlabRay> {
+    let origin = Point3::new(0.5, 0.5, 10.0);
+    (0..64)
+        .filter_map(|i| {
+            let a = i as Float * 0.1;
+            // every eighth ray is parallel to the x faces
+            let dx = if i % 8 == 0 { 0.0 } else { a.sin() };
+            Ray3::new(origin, Vec3::new(dx, a.cos(), -10.0), 0.0)
+        })
+        .map(Ray3::precompute)
+        .collect()
+}
+
+fn slab_tests(c: &mut Criterion) {
+    let (boxes, rays) = (boxes(), rays());
+    let range = TRange::new(0.001, Float::INFINITY);
+    let mut group = c.benchmark_group("hit_slab");
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            let mut hits = 0;
+            for ray in &rays {
+                hits += boxes
+                    .iter()
+                    .filter(|aabb| aabb.hit_slab_scalar(black_box(ray), range).is_some())
+                    .count();
+            }
+            hits
+        })
+    });
+    group.bench_function("simd", |b| {
+        b.iter(|| {
+            let mut hits = 0;
+            for ray in &rays {
+                hits += boxes
+                    .iter()
+                    .filter(|aabb| aabb.hit_slab_simd(black_box(ray), range).is_some())
+                    .count();
+            }
+            hits
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, slab_tests);
+criterion_main!(benches);
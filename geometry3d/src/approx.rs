@@ -0,0 +1,145 @@
+use crate::*;
+
+/// The tolerance used by `assert_approx_eq!` when none is given, and for
+/// telling when nearly parallel directions are parallel.
+#[cfg(not(feature = "f32"))]
+pub const DEFAULT_EPSILON: Float = 1e-9;
+/// The tolerance used by `assert_approx_eq!` when none is given, and for
+/// telling when nearly parallel directions are parallel.
+#[cfg(feature = "f32")]
+pub const DEFAULT_EPSILON: Float = 1e-5;
+
+/// How far apart two values may be while still counted as equal. Each pair
+/// of components may differ by up to `absolute`, or by up to `relative` times
+/// the larger of their magnitudes, whichever is looser. The absolute
+/// tolerance matters near zero, where relative differences blow up, and the
+/// relative tolerance for large magnitudes, where rounding alone exceeds any
+/// sensible absolute tolerance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    pub absolute: Float,
+    pub relative: Float,
+}
+
+impl Tolerance {
+    /// The same `epsilon` as both the absolute and relative tolerance.
+    pub fn new(epsilon: Float) -> Tolerance {
+        Tolerance {
+            absolute: epsilon,
+            relative: epsilon,
+        }
+    }
+
+    /// Whether `a` and `b` are equal to within the tolerance. NaN is never
+    /// equal to anything, and infinities only to themselves.
+    pub fn equal(self, a: Float, b: Float) -> bool {
+        if a == b {
+            return true;
+        }
+        if a.is_infinite() || b.is_infinite() {
+            return false;
+        }
+        let difference = (a - b).abs();
+        difference <= self.absolute || difference <= self.relative * a.abs().max(b.abs())
+    }
+}
+
+/// Equality up to floating-point error.
+///
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// let v = Vec3::new(0.1, 0.2, 0.3);
+/// assert_ne!(v * 3.0, Vec3::new(0.3, 0.6, 0.9));
+/// assert!((v * 3.0).approx_eq(Vec3::new(0.3, 0.6, 0.9), DEFAULT_EPSILON));
+/// ```
+pub trait ApproxEq: Copy + std::fmt::Debug {
+    /// Whether every component of `self` and `rhs` is equal to within
+    /// `tolerance`.
+    fn approx_eq_within(self, rhs: Self, tolerance: Tolerance) -> bool;
+
+    /// Whether every component of `self` and `rhs` is equal to within
+    /// `epsilon`, as either an absolute or relative difference.
+    fn approx_eq(self, rhs: Self, epsilon: Float) -> bool {
+        self.approx_eq_within(rhs, Tolerance::new(epsilon))
+    }
+}
+
+impl ApproxEq for Float {
+    fn approx_eq_within(self, rhs: Float, tolerance: Tolerance) -> bool {
+        tolerance.equal(self, rhs)
+    }
+}
+
+impl ApproxEq for Vec3 {
+    fn approx_eq_within(self, rhs: Vec3, tolerance: Tolerance) -> bool {
+        approx_eq_ntuple(self.0, rhs.0, tolerance)
+    }
+}
+
+impl ApproxEq for Point3 {
+    fn approx_eq_within(self, rhs: Point3, tolerance: Tolerance) -> bool {
+        approx_eq_ntuple(self.0, rhs.0, tolerance)
+    }
+}
+
+impl ApproxEq for Ray3 {
+    fn approx_eq_within(self, rhs: Ray3, tolerance: Tolerance) -> bool {
+        self.origin.approx_eq_within(rhs.origin, tolerance)
+            && self.direction.approx_eq_within(rhs.direction, tolerance)
+            && self.time.approx_eq_within(rhs.time, tolerance)
+    }
+}
+
+impl ApproxEq for AABB {
+    fn approx_eq_within(self, rhs: AABB, tolerance: Tolerance) -> bool {
+        self.lo.approx_eq_within(rhs.lo, tolerance) && self.hi.approx_eq_within(rhs.hi, tolerance)
+    }
+}
+
+fn approx_eq_ntuple(a: NTuple<Float, 3>, b: NTuple<Float, 3>, tolerance: Tolerance) -> bool {
+    a.combine(b, |a, b| tolerance.equal(a, b)).all(|equal| equal)
+}
+
+/// Asserts that two values are equal up to floating-point error, as
+/// `ApproxEq`, printing both on failure.
+///
+/// The tolerance defaults to `DEFAULT_EPSILON`, or may be given as a single
+/// epsilon or separately with `abs = ` and `rel = `.
+///
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// assert_approx_eq!(0.1 + 0.2, 0.3);
+/// assert_approx_eq!(Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 1e-4, 0.0), 1e-3);
+/// assert_approx_eq!(1e12 + 1.0, 1e12, abs = 0.0, rel = 1e-9);
+/// ```
+#[macro_export]
+macro_rules! assert_approx_eq {
+    (@within $left:expr, $right:expr, $tolerance:expr) => {
+        match (&$left, &$right, $tolerance) {
+            (left, right, tolerance) => {
+                if !$crate::ApproxEq::approx_eq_within(*left, *right, tolerance) {
+                    panic!(
+                        "assertion `left ≈ right` failed\n  left: {:#?}\n right: {:#?}\n  with: {:?}",
+                        left, right, tolerance
+                    );
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::assert_approx_eq!($left, $right, $crate::DEFAULT_EPSILON)
+    };
+    ($left:expr, $right:expr, abs = $absolute:expr, rel = $relative:expr $(,)?) => {
+        $crate::assert_approx_eq!(@within $left, $right, $crate::Tolerance {
+            absolute: $absolute,
+            relative: $relative,
+        })
+    };
+    ($left:expr, $right:expr, $epsilon:expr $(,)?) => {
+        $crate::assert_approx_eq!(@within $left, $right, $crate::Tolerance::new($epsilon))
+    };
+}
@@ -0,0 +1,122 @@
+//! `Display` for the geometry types, as short forms such as
+//! `(1.000, 2.000, 3.000)` for logs and error messages, and `Debug` with a
+//! compact alternate form, `{:#?}`, that leaves out the `NTuple` inside
+//! `Vec3` and `Point3`.
+//!
+//! Display prints three decimal places unless the formatter gives a
+//! precision, e.g. `{:.1}`. A width applies to each coordinate.
+
+use crate::*;
+use std::fmt;
+
+const DEFAULT_PRECISION: usize = 3;
+
+fn precision(f: &fmt::Formatter) -> usize {
+    f.precision().unwrap_or(DEFAULT_PRECISION)
+}
+
+fn display_triple(f: &mut fmt::Formatter, t: NTuple<Float, 3>) -> fmt::Result {
+    let (p, w) = (precision(f), f.width().unwrap_or(0));
+    write!(f, "{t:w$.p$}")
+}
+
+fn debug_triple(f: &mut fmt::Formatter, name: &str, t: NTuple<Float, 3>) -> fmt::Result {
+    if f.alternate() {
+        write!(f, "{name}{t:#?}")
+    } else {
+        f.debug_tuple(name).field(&t).finish()
+    }
+}
+
+/// # Examples
+/// ```
+/// use geometry3d::Vec3;
+///
+/// let v = Vec3::new(1.0, -2.5, 0.125);
+/// assert_eq!(format!("{v}"), "(1.000, -2.500, 0.125)");
+/// assert_eq!(format!("{v:.1}"), "(1.0, -2.5, 0.1)");
+/// assert_eq!(format!("{v:#?}"), "Vec3(1.0, -2.5, 0.125)");
+/// ```
+impl fmt::Display for Vec3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        display_triple(f, self.0)
+    }
+}
+
+impl fmt::Debug for Vec3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_triple(f, "Vec3", self.0)
+    }
+}
+
+impl fmt::Display for Point3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        display_triple(f, self.0)
+    }
+}
+
+impl fmt::Debug for Point3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        debug_triple(f, "Point3", self.0)
+    }
+}
+
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// let ray = Ray3 {
+///     origin: Point3::new(0.0, 1.0, 0.0),
+///     direction: Vec3::e2(),
+///     time: 0.5,
+/// };
+/// assert_eq!(format!("{ray:.1}"), "Ray{o=(0.0, 1.0, 0.0), d=(0.0, 0.0, 1.0), t=0.5}");
+/// ```
+impl fmt::Display for Ray3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let p = precision(f);
+        write!(f, "Ray{{o={:.p$}, d={:.p$}, t={:.p$}}}", self.origin, self.direction, self.time)
+    }
+}
+
+impl fmt::Debug for Ray3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            write!(
+                f,
+                "Ray3 {{ origin: {:#?}, direction: {:#?}, time: {:?} }}",
+                self.origin, self.direction, self.time
+            )
+        } else {
+            f.debug_struct("Ray3")
+                .field("origin", &self.origin)
+                .field("direction", &self.direction)
+                .field("time", &self.time)
+                .finish()
+        }
+    }
+}
+
+impl fmt::Display for AABB {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let p = precision(f);
+        write!(f, "AABB{{lo={:.p$}, hi={:.p$}}}", self.lo, self.hi)
+    }
+}
+
+impl fmt::Debug for AABB {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "AABB {{ lo: {:#?}, hi: {:#?} }}", self.lo, self.hi)
+        } else {
+            f.debug_struct("AABB").field("lo", &self.lo).field("hi", &self.hi).finish()
+        }
+    }
+}
+
+impl fmt::Display for TRange<Float> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let p = precision(f);
+        write!(f, "[{:.p$}, {:.p$}]", self.start, self.end)
+    }
+}
@@ -2,49 +2,134 @@
 //!
 //! `geometry3d` is a collection of structures and associated functions that
 //! reperesent 3D Cartesian geometry concepts such as points, vectors, and rays
-//! using double precision floating-point values.
+//! using double precision floating-point values, or single precision with the
+//! `f32` feature.
 //!
 //! ## Caution
 //!
 //! It should be noted that the values and methods here are all floating-point
 //! approximations and as such will potentially have small errors and will
 //! display numeric instability in some cases.
+//! `ApproxEq` and `assert_approx_eq!` compare values up to such errors.
 
 use ntuple::*;
 use ntuple_derive::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// The floating-point type of all of the geometry. It is `f64` unless the
+/// `f32` feature is enabled, which halves the size of every vector, point
+/// and bounding box for less precision.
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+/// The floating-point type of all of the geometry. It is `f64` unless the
+/// `f32` feature is enabled, which halves the size of every vector, point
+/// and bounding box for less precision.
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+/// The mathematical constants as `Float`.
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
+/// The mathematical constants as `Float`.
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+
+mod approx;
+mod format;
+mod permutation;
+mod simd;
+mod transform;
+mod triangle;
+pub use approx::{ApproxEq, Tolerance, DEFAULT_EPSILON};
+pub use permutation::{Permutation, Permute};
+pub use transform::Transform;
+pub use triangle::{barycentric, interpolate, intersect_ray_triangle, is_inside, TriangleHit, TriangleTest};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Axis {
     X = 0,
     Y = 1,
     Z = 2,
 }
 
-pub trait Permute {
-    fn permute(self, axes: [Axis; 3]) -> Self;
-    fn unpermute(self, axes: [Axis; 3]) -> Self;
+impl Axis {
+    /// The axes in order, for iterating over them.
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    /// The other two axes, in order.
+    ///
+    /// # Examples
+    /// ```
+    /// use geometry3d::Axis;
+    ///
+    /// assert_eq!(Axis::Y.others(), [Axis::X, Axis::Z]);
+    /// ```
+    pub fn others(self) -> [Axis; 2] {
+        match self {
+            Axis::X => [Axis::Y, Axis::Z],
+            Axis::Y => [Axis::X, Axis::Z],
+            Axis::Z => [Axis::X, Axis::Y],
+        }
+    }
 }
 
-impl<T> Permute for T
-where
-    T: NTupleNewtype<f64, 3>,
-{
-    fn permute(self, axes: [Axis; 3]) -> T {
-        let perms = axes.map(|x| x as usize);
-        T::from(self.ntuple().permute(perms))
+/// The number of the axis, from 0 for x to 2 for z.
+impl From<Axis> for usize {
+    fn from(axis: Axis) -> usize {
+        axis as usize
     }
+}
 
-    fn unpermute(self, axes: [Axis; 3]) -> T {
-        let perms = axes.map(|x| x as usize);
-        let mut unperm = perms;
-        for i in 0..3 {
-            unperm[perms[i]] = i;
+/// The axis numbered `i`, from 0 for x to 2 for z.
+impl TryFrom<usize> for Axis {
+    type Error = String;
+
+    fn try_from(i: usize) -> Result<Axis, String> {
+        Axis::ALL
+            .get(i)
+            .copied()
+            .ok_or_else(|| format!("There is no axis {i}, the axes are numbered 0 to 2."))
+    }
+}
+
+impl std::fmt::Display for Axis {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Axis::X => "X",
+            Axis::Y => "Y",
+            Axis::Z => "Z",
+        };
+        f.pad(name)
+    }
+}
+
+/// Reads the name of an axis, as written by `Display`, in either case.
+impl std::str::FromStr for Axis {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Axis, String> {
+        match name {
+            "X" | "x" => Ok(Axis::X),
+            "Y" | "y" => Ok(Axis::Y),
+            "Z" | "z" => Ok(Axis::Z),
+            _ => Err(format!("There is no axis named {name:?}, the axes are X, Y and Z.")),
         }
-        T::from(self.ntuple().permute(unperm))
     }
 }
 
+/// The pair of elements for the axes `i`.
+fn pair(t: NTuple<Float, 3>, i: [usize; 2]) -> (Float, Float) {
+    let pair = t.select(i);
+    (pair[0], pair[1])
+}
+
+/// Each element of `x` restricted to the range between the elements of `lo`
+/// and `hi`, leaving NaN elements as NaN.
+fn clamp(x: NTuple<Float, 3>, lo: NTuple<Float, 3>, hi: NTuple<Float, 3>) -> NTuple<Float, 3> {
+    x.combine(lo, |x, lo| if x < lo { lo } else { x })
+        .combine(hi, |x, hi| if x > hi { hi } else { x })
+}
+
 /// 3D Euclidean vector.
 ///
 /// # Examples
@@ -59,54 +144,78 @@ where
 ///
 /// `e0()` through `e2()` are the standard basis vectors.
 ///
-#[derive(Debug, PartialEq, Copy, Clone, Default, Serialize, Deserialize, NTupleNewtype)]
-pub struct Vec3(NTuple<f64, 3>);
+/// Vectors are indexed by `Axis` or by its number, panicking if that is not
+/// 0, 1 or 2:
+/// ```
+/// use geometry3d::{Axis, Vec3};
+///
+/// let v = Vec3::new(1.0, 2.0, 3.0);
+/// assert_eq!(v[Axis::Y], 2.0);
+/// assert_eq!(v[2], 3.0);
+/// ```
+#[derive(PartialEq, Copy, Clone, Default, Serialize, Deserialize, NTupleNewtype)]
+#[ntuple(index(Axis))]
+pub struct Vec3(NTuple<Float, 3>);
 
 impl Vec3 {
     /// The first standard basis vector (1, 0, 0).
     pub fn e0() -> Self {
-        Vec3(ntuple!(1.0, 0.0, 0.0))
+        Vec3(NTuple::basis(0, 1.0, 0.0))
     }
 
     /// The second standard basis vector (0, 1, 0).
     pub fn e1() -> Self {
-        Vec3(ntuple!(0.0, 1.0, 0.0))
+        Vec3(NTuple::basis(1, 1.0, 0.0))
     }
 
     /// The third standard basis vector (0, 0, 1).
     pub fn e2() -> Self {
-        Vec3(ntuple!(0.0, 0.0, 1.0))
+        Vec3(NTuple::basis(2, 1.0, 0.0))
     }
 
     /// Create a new `Vec3`. The vector (x, y, z) is equivalent to
     /// `x * e0 + y * e1 + z * e2`.
-    pub fn new(x: f64, y: f64, z: f64) -> Self {
+    pub fn new(x: Float, y: Float, z: Float) -> Self {
         Self(ntuple!(x, y, z))
     }
 
     /// The coefficient of the first basis vector.
-    pub fn x(self) -> f64 {
+    pub fn x(self) -> Float {
         self.0[0]
     }
 
     /// The coefficient of the second basis vector.
-    pub fn y(self) -> f64 {
+    pub fn y(self) -> Float {
         self.0[1]
     }
 
     /// The coefficient of the third basis vector.
-    pub fn z(self) -> f64 {
+    pub fn z(self) -> Float {
         self.0[2]
     }
 
+    /// The x and y components, dropping z.
+    pub fn xy(self) -> (Float, Float) {
+        pair(self.0, [0, 1])
+    }
+
+    /// The x and z components, dropping y.
+    pub fn xz(self) -> (Float, Float) {
+        pair(self.0, [0, 2])
+    }
+
+    /// The y and z components, dropping x.
+    pub fn yz(self) -> (Float, Float) {
+        pair(self.0, [1, 2])
+    }
+
     /// The square of the Euclidean length of the vector.
-    pub fn quadrance(self) -> f64 {
-        let s = Vec3(self.0.map(|x| x * x));
-        s.x() + s.y() + s.z()
+    pub fn quadrance(self) -> Float {
+        (self.0 * self.0).sum()
     }
 
     /// The Euclidean length of the vector.
-    pub fn length(self) -> f64 {
+    pub fn length(self) -> Float {
         self.quadrance().sqrt()
     }
 
@@ -116,13 +225,13 @@ impl Vec3 {
         if length == 0.0 {
             None
         } else {
-            Some(Vec3(self.0.map(|x| x / length)))
+            Some(Vec3(self.0 / length))
         }
     }
 
     /// The dot product of two vectors.
-    pub fn dot(self, rhs: Vec3) -> f64 {
-        self.0.combine(rhs.0, |x, y| x * y).reduce(|acc, x| acc + x)
+    pub fn dot(self, rhs: Vec3) -> Float {
+        (self.0 * rhs.0).sum()
     }
 
     /// The cross product of the left hand vector by the right hand vector i.e.
@@ -147,6 +256,140 @@ impl Vec3 {
     pub fn reflection(self, normal: Self) -> Self {
         self - 2.0 * self.projection(normal)
     }
+
+    /// The refraction of the left vector through a surface with the given
+    /// normal, by Snell's law, where `eta_ratio` is the refractive index on
+    /// the side the vector comes from over the index on the far side. Returns
+    /// None where the vector is totally internally reflected instead.
+    ///
+    /// Both vectors must be unit vectors, and the normal must face the side
+    /// the vector comes from, so that their dot product is not positive. The
+    /// result is then a unit vector.
+    ///
+    /// # Examples
+    /// ```
+    /// use geometry3d::Vec3;
+    ///
+    /// let down = -Vec3::e1();
+    /// assert_eq!(down.refraction(Vec3::e1(), 1.0 / 1.5), Some(down));
+    ///
+    /// let grazing = Vec3::new(1.0, -0.1, 0.0).unit().unwrap();
+    /// assert_eq!(grazing.refraction(Vec3::e1(), 1.5), None);
+    /// ```
+    pub fn refraction(self, normal: Self, eta_ratio: Float) -> Option<Self> {
+        let cos_theta = (-self.dot(normal)).min(1.0);
+        let out_perpendicular = eta_ratio * (self + cos_theta * normal);
+        let parallel_quadrance = 1.0 - out_perpendicular.quadrance();
+        if parallel_quadrance < 0.0 {
+            return None;
+        }
+        Some(out_perpendicular - parallel_quadrance.sqrt() * normal)
+    }
+
+    /// The componentwise minimum of two vectors. Where one of a pair of
+    /// components is NaN the other is taken, as with `f64::min`.
+    pub fn min(self, rhs: Self) -> Self {
+        Self(self.0.combine(rhs.0, Float::min))
+    }
+
+    /// The componentwise maximum of two vectors. Where one of a pair of
+    /// components is NaN the other is taken, as with `f64::max`.
+    pub fn max(self, rhs: Self) -> Self {
+        Self(self.0.combine(rhs.0, Float::max))
+    }
+
+    /// The absolute value of each component.
+    pub fn abs(self) -> Self {
+        Self(self.0.map(Float::abs))
+    }
+
+    /// Each component restricted to the range between the components of
+    /// `lo` and `hi`, where `lo` should not be greater than `hi`. NaN
+    /// components are left as NaN, as with `f64::clamp`.
+    ///
+    /// # Examples
+    /// ```
+    /// use geometry3d::Vec3;
+    ///
+    /// let v = Vec3::new(-2.0, 0.5, 2.0).clamp(Vec3::default(), Vec3::new(1.0, 1.0, 1.0));
+    /// assert_eq!(v, Vec3::new(0.0, 0.5, 1.0));
+    /// ```
+    pub fn clamp(self, lo: Self, hi: Self) -> Self {
+        Self(clamp(self.0, lo.0, hi.0))
+    }
+
+    /// The largest component. NaN components are passed over unless every
+    /// component is NaN.
+    pub fn max_component(self) -> Float {
+        self.0.reduce(Float::max)
+    }
+
+    /// The smallest component. NaN components are passed over unless every
+    /// component is NaN.
+    pub fn min_component(self) -> Float {
+        self.0.reduce(Float::min)
+    }
+
+    /// The axis of the largest component, the first of them when several are
+    /// equal. NaN components are passed over unless every component is NaN.
+    ///
+    /// # Examples
+    /// ```
+    /// use geometry3d::{Axis, Vec3};
+    ///
+    /// assert_eq!(Vec3::new(1.0, 3.0, 2.0).max_axis(), Axis::Y);
+    /// ```
+    pub fn max_axis(self) -> Axis {
+        Axis::ALL[self.0.argmax()]
+    }
+
+    /// Linear interpolation from `self` at `t = 0` to `other` at `t = 1`.
+    /// Values of `t` outside of [0, 1] extrapolate along the same line.
+    ///
+    /// # Examples
+    /// ```
+    /// use geometry3d::Vec3;
+    ///
+    /// let a = Vec3::new(0.0, 2.0, 0.0);
+    /// let b = Vec3::new(4.0, 2.0, 0.0);
+    /// assert_eq!(a.lerp(b, 0.25), Vec3::new(1.0, 2.0, 0.0));
+    /// assert_eq!(a.lerp(b, -1.0), Vec3::new(-4.0, 2.0, 0.0));
+    /// ```
+    pub fn lerp(self, other: Self, t: Float) -> Self {
+        self + t * (other - self)
+    }
+
+    /// Spherical linear interpolation between the unit vectors `self` at
+    /// `t = 0` and `other` at `t = 1`, turning at a constant rate through
+    /// the plane of the two. The result is a unit vector.
+    ///
+    /// Antiparallel vectors have no single plane between them, so they are
+    /// turned through an arbitrary plane containing both.
+    ///
+    /// # Examples
+    /// ```
+    /// use geometry3d::Vec3;
+    ///
+    /// let v = Vec3::e0().slerp(Vec3::e1(), 0.5);
+    /// let expected = Vec3::new(1.0, 1.0, 0.0).unit().unwrap();
+    /// assert!((v - expected).length() < 1e-6);
+    /// ```
+    pub fn slerp(self, other: Self, t: Float) -> Self {
+        let cos = self.dot(other).clamp(-1.0, 1.0);
+        let angle = cos.acos();
+        let sin = angle.sin();
+        if sin.abs() < DEFAULT_EPSILON {
+            if cos > 0.0 {
+                return self;
+            }
+            // any direction perpendicular to `self` gives a plane to turn in
+            let axis = if self.x().abs() < 0.9 { Vec3::e0() } else { Vec3::e1() };
+            let perpendicular = self.cross(axis).unit().expect("The axis is not parallel.");
+            let angle = t * consts::PI;
+            return angle.cos() * self + angle.sin() * perpendicular;
+        }
+        ((1.0 - t) * angle).sin() / sin * self + (t * angle).sin() / sin * other
+    }
 }
 
 /// Sum of two vectors.
@@ -154,7 +397,7 @@ impl std::ops::Add for Vec3 {
     type Output = Vec3;
 
     fn add(self, rhs: Self) -> Vec3 {
-        Self(self.0.combine(rhs.0, |x, y| x + y))
+        Self(self.0 + rhs.0)
     }
 }
 
@@ -163,7 +406,7 @@ impl std::ops::Sub for Vec3 {
     type Output = Vec3;
 
     fn sub(self, rhs: Self) -> Vec3 {
-        Self(self.0.combine(rhs.0, |x, y| x - y))
+        Self(self.0 - rhs.0)
     }
 }
 
@@ -172,25 +415,138 @@ impl std::ops::Neg for Vec3 {
     type Output = Vec3;
 
     fn neg(self) -> Vec3 {
-        Self(self.0.map(|x| -x))
+        Self(-self.0)
     }
 }
 
 /// Scale the vector by the scalar.
-impl std::ops::Mul<Vec3> for f64 {
+impl std::ops::Mul<Vec3> for Float {
     type Output = Vec3;
 
     fn mul(self, rhs: Vec3) -> Vec3 {
-        Vec3(rhs.0.map(|x| x * self))
+        Vec3(rhs.0 * self)
+    }
+}
+
+/// Scale the vector by the scalar, the same as `rhs * self`.
+///
+/// # Examples
+/// ```
+/// use geometry3d::Vec3;
+///
+/// let v = Vec3::new(1.0, -2.0, 3.0);
+/// assert_eq!(v * 2.0, Vec3::new(2.0, -4.0, 6.0));
+/// assert_eq!(v * 2.0, 2.0 * v);
+/// ```
+impl std::ops::Mul<Float> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Float) -> Vec3 {
+        rhs * self
     }
 }
 
 /// Scale vector by the reciprocal of the divisor.
-impl std::ops::Div<f64> for Vec3 {
+impl std::ops::Div<Float> for Vec3 {
     type Output = Self;
 
-    fn div(self, rhs: f64) -> Vec3 {
-        Self(self.0.map(|x| x / rhs))
+    fn div(self, rhs: Float) -> Vec3 {
+        Self(self.0 / rhs)
+    }
+}
+
+/// Add the vector in place.
+///
+/// # Examples
+/// ```
+/// use geometry3d::Vec3;
+///
+/// let mut v = Vec3::e0();
+/// v += Vec3::e1();
+/// assert_eq!(v, Vec3::new(1.0, 1.0, 0.0));
+/// ```
+impl std::ops::AddAssign for Vec3 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+/// Subtract the vector in place.
+///
+/// # Examples
+/// ```
+/// use geometry3d::Vec3;
+///
+/// let mut v = Vec3::new(1.0, 1.0, 0.0);
+/// v -= Vec3::e1();
+/// assert_eq!(v, Vec3::e0());
+/// ```
+impl std::ops::SubAssign for Vec3 {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+/// Scale the vector by the scalar in place.
+///
+/// # Examples
+/// ```
+/// use geometry3d::Vec3;
+///
+/// let mut v = Vec3::new(1.0, 2.0, 3.0);
+/// v *= 2.0;
+/// assert_eq!(v, Vec3::new(2.0, 4.0, 6.0));
+/// ```
+impl std::ops::MulAssign<Float> for Vec3 {
+    fn mul_assign(&mut self, rhs: Float) {
+        *self = *self * rhs;
+    }
+}
+
+/// Scale the vector by the reciprocal of the divisor in place.
+///
+/// # Examples
+/// ```
+/// use geometry3d::Vec3;
+///
+/// let mut v = Vec3::new(2.0, 4.0, 6.0);
+/// v /= 2.0;
+/// assert_eq!(v, Vec3::new(1.0, 2.0, 3.0));
+/// ```
+impl std::ops::DivAssign<Float> for Vec3 {
+    fn div_assign(&mut self, rhs: Float) {
+        *self = *self / rhs;
+    }
+}
+
+/// Sum of the vectors, the zero vector when there are none.
+///
+/// # Examples
+/// ```
+/// use geometry3d::Vec3;
+///
+/// let sum: Vec3 = [Vec3::e0(), Vec3::e1(), Vec3::e2()].into_iter().sum();
+/// assert_eq!(sum, Vec3::new(1.0, 1.0, 1.0));
+/// assert_eq!(std::iter::empty::<Vec3>().sum::<Vec3>(), Vec3::default());
+/// ```
+impl std::iter::Sum for Vec3 {
+    fn sum<I: Iterator<Item = Vec3>>(iter: I) -> Vec3 {
+        iter.fold(Vec3::default(), |acc, v| acc + v)
+    }
+}
+
+/// Sum of the borrowed vectors, the zero vector when there are none.
+///
+/// # Examples
+/// ```
+/// use geometry3d::Vec3;
+///
+/// let vectors = vec![Vec3::e0(), Vec3::e0()];
+/// assert_eq!(vectors.iter().sum::<Vec3>(), Vec3::new(2.0, 0.0, 0.0));
+/// ```
+impl<'a> std::iter::Sum<&'a Vec3> for Vec3 {
+    fn sum<I: Iterator<Item = &'a Vec3>>(iter: I) -> Vec3 {
+        iter.copied().sum()
     }
 }
 
@@ -212,29 +568,91 @@ impl std::convert::From<Point3> for Vec3 {
 ///
 /// assert_eq!(p1 - p2, difference);
 /// ```
-#[derive(Debug, PartialEq, Copy, Clone, Default, Serialize, Deserialize, NTupleNewtype)]
-pub struct Point3(NTuple<f64, 3>);
+///
+/// Points are indexed as vectors are:
+/// ```
+/// use geometry3d::{Axis, Point3};
+///
+/// let v = Point3::new(1.0, 2.0, 3.0);
+/// assert_eq!(v[Axis::Y], 2.0);
+/// assert_eq!(v[2], 3.0);
+/// ```
+#[derive(PartialEq, Copy, Clone, Default, Serialize, Deserialize, NTupleNewtype)]
+#[ntuple(index(Axis))]
+pub struct Point3(NTuple<Float, 3>);
 
 impl Point3 {
     /// Create a new `Point3` with cartesian coordinates (`x`, `y`, `z`).
-    pub fn new(x: f64, y: f64, z: f64) -> Self {
+    pub fn new(x: Float, y: Float, z: Float) -> Self {
         Self(ntuple!(x, y, z))
     }
 
     /// The x coordinate.
-    pub fn x(self) -> f64 {
+    pub fn x(self) -> Float {
         self.0[0]
     }
 
     /// The y coordinate.
-    pub fn y(self) -> f64 {
+    pub fn y(self) -> Float {
         self.0[1]
     }
 
     /// The z coordinate.
-    pub fn z(self) -> f64 {
+    pub fn z(self) -> Float {
         self.0[2]
     }
+
+    /// The x and y components, dropping z.
+    pub fn xy(self) -> (Float, Float) {
+        pair(self.0, [0, 1])
+    }
+
+    /// The x and z components, dropping y.
+    pub fn xz(self) -> (Float, Float) {
+        pair(self.0, [0, 2])
+    }
+
+    /// The y and z components, dropping x.
+    pub fn yz(self) -> (Float, Float) {
+        pair(self.0, [1, 2])
+    }
+
+    /// The componentwise minimum of two points, i.e. the lowest corner of
+    /// the box they span. Where one of a pair of coordinates is NaN the
+    /// other is taken, as with `f64::min`.
+    pub fn min(self, rhs: Self) -> Self {
+        Self(self.0.combine(rhs.0, Float::min))
+    }
+
+    /// The componentwise maximum of two points, i.e. the highest corner of
+    /// the box they span. Where one of a pair of coordinates is NaN the
+    /// other is taken, as with `f64::max`.
+    pub fn max(self, rhs: Self) -> Self {
+        Self(self.0.combine(rhs.0, Float::max))
+    }
+
+    /// The closest point to `self` within the axis aligned box from `lo` to
+    /// `hi`, where `lo` should not be greater than `hi`. NaN coordinates are
+    /// left as NaN, as with `f64::clamp`.
+    pub fn clamp(self, lo: Self, hi: Self) -> Self {
+        Self(clamp(self.0, lo.0, hi.0))
+    }
+
+    /// Linear interpolation from `self` at `t = 0` to `other` at `t = 1`.
+    /// Values of `t` outside of [0, 1] extrapolate along the same line.
+    ///
+    /// # Examples
+    /// ```
+    /// use geometry3d::Point3;
+    ///
+    /// let a = Point3::new(0.0, 0.0, 0.0);
+    /// let b = Point3::new(2.0, 4.0, 8.0);
+    /// assert_eq!(a.lerp(b, 0.5), Point3::new(1.0, 2.0, 4.0));
+    /// assert_eq!(a.lerp(b, 2.0), Point3::new(4.0, 8.0, 16.0));
+    /// ```
+    pub fn lerp(self, other: Self, t: Float) -> Self {
+        self + t * (other - self)
+    }
 }
 
 /// Convert from position vector to point.
@@ -249,7 +667,7 @@ impl std::ops::Add<Vec3> for Point3 {
     type Output = Self;
 
     fn add(self, rhs: Vec3) -> Self::Output {
-        Self(self.0.combine(rhs.0, |x, y| x + y))
+        Self(self.0 + rhs.0)
     }
 }
 
@@ -258,7 +676,39 @@ impl std::ops::Sub<Vec3> for Point3 {
     type Output = Self;
 
     fn sub(self, rhs: Vec3) -> Self::Output {
-        Self(self.0.combine(rhs.0, |x, y| x - y))
+        Self(self.0 - rhs.0)
+    }
+}
+
+/// Displace the point by the vector in place.
+///
+/// # Examples
+/// ```
+/// use geometry3d::{Point3, Vec3};
+///
+/// let mut p = Point3::new(1.0, 2.0, 3.0);
+/// p += Vec3::e2();
+/// assert_eq!(p, Point3::new(1.0, 2.0, 4.0));
+/// ```
+impl std::ops::AddAssign<Vec3> for Point3 {
+    fn add_assign(&mut self, rhs: Vec3) {
+        *self = *self + rhs;
+    }
+}
+
+/// Displace the point by the negative of the vector in place.
+///
+/// # Examples
+/// ```
+/// use geometry3d::{Point3, Vec3};
+///
+/// let mut p = Point3::new(1.0, 2.0, 3.0);
+/// p -= Vec3::e2();
+/// assert_eq!(p, Point3::new(1.0, 2.0, 2.0));
+/// ```
+impl std::ops::SubAssign<Vec3> for Point3 {
+    fn sub_assign(&mut self, rhs: Vec3) {
+        *self = *self - rhs;
     }
 }
 
@@ -267,45 +717,241 @@ impl std::ops::Sub<Point3> for Point3 {
     type Output = Vec3;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        Vec3(self.0.combine(rhs.0, |x, y| x - y))
+        Vec3(self.0 - rhs.0)
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone, Default, Serialize, Deserialize)]
+/// A ray from `origin` along `direction` at the given `time`.
+///
+/// Rays cast into a scene have a unit `direction`, so that `at(t)` is a
+/// distance of `t` along the ray, and should be made with `Ray3::new` to
+/// ensure it. The fields stay public as the same type also describes the
+/// paths of moving objects, whose `direction` is a velocity of any length.
+#[derive(PartialEq, Copy, Clone, Default, Serialize, Deserialize)]
 pub struct Ray3 {
     pub origin: Point3,
     pub direction: Vec3,
-    pub time: f64,
+    pub time: Float,
 }
 
 impl Ray3 {
-    pub fn at(self, t: f64) -> Point3 {
+    /// The ray from `origin` along `direction`, scaled to a unit vector, or
+    /// None if `direction` is zero or not finite.
+    ///
+    /// # Examples
+    /// ```
+    /// use geometry3d::*;
+    ///
+    /// let ray = Ray3::new(Point3::default(), Vec3::new(0.0, 2.0, 0.0), 0.0).unwrap();
+    /// assert_eq!(ray.at(3.0), Point3::new(0.0, 3.0, 0.0));
+    /// assert!(Ray3::new(Point3::default(), Vec3::default(), 0.0).is_none());
+    /// ```
+    pub fn new(origin: Point3, direction: Vec3, time: Float) -> Option<Ray3> {
+        if !direction.length().is_finite() {
+            return None;
+        }
+        let direction = direction.unit()?;
+        Some(Ray3 { origin, direction, time })
+    }
+
+    /// Whether `direction` is a unit vector, to within rounding.
+    pub fn is_normalized(self) -> bool {
+        self.direction.length().approx_eq(1.0, DEFAULT_EPSILON)
+    }
+
+    pub fn at(self, t: Float) -> Point3 {
         self.origin + t * self.direction
     }
+
+    /// The point on the ray closest to `p`. Points behind the origin are
+    /// closest to the origin, as the ray only goes forwards, as are all
+    /// points for a ray with a zero direction.
+    ///
+    /// # Examples
+    /// ```
+    /// use geometry3d::*;
+    ///
+    /// let ray = Ray3::new(Point3::default(), Vec3::e0(), 0.0).unwrap();
+    /// assert_eq!(ray.closest_point_on_ray(Point3::new(2.0, 1.0, 0.0)), Point3::new(2.0, 0.0, 0.0));
+    /// assert_eq!(ray.distance_to_point(Point3::new(-3.0, 4.0, 0.0)), 5.0);
+    /// ```
+    pub fn closest_point_on_ray(self, p: Point3) -> Point3 {
+        let t = (p - self.origin).dot(self.direction) / self.direction.quadrance();
+        // max also takes 0 over the NaN of a zero direction
+        self.at(t.max(0.0))
+    }
+
+    /// The distance from `p` to the closest point on the ray.
+    pub fn distance_to_point(self, p: Point3) -> Float {
+        (p - self.closest_point_on_ray(p)).length()
+    }
+
+    /// The ray along with the reciprocals of its direction, for testing it
+    /// against many bounding boxes.
+    pub fn precompute(self) -> SlabRay {
+        let inv_direction = Vec3(self.direction.0.map(|x| 1.0 / x));
+        SlabRay {
+            ray: self,
+            inv_direction,
+            lanes: simd::SlabLanes::new(self.origin, inv_direction),
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
-pub struct AABB {
-    lo: Point3,
-    hi: Point3,
+/// The signed distance from `point` to the plane through `plane_point` with
+/// the given `normal`, positive on the side the normal points to, or None if
+/// the normal is zero. The normal need not be a unit vector.
+///
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// let normal = Vec3::new(0.0, 2.0, 0.0);
+/// let plane_point = Point3::new(5.0, 1.0, 5.0);
+/// assert_eq!(distance_point_to_plane(Point3::new(0.0, 3.0, 0.0), plane_point, normal), Some(2.0));
+/// assert_eq!(distance_point_to_plane(Point3::new(0.0, 0.0, 0.0), plane_point, normal), Some(-1.0));
+/// ```
+pub fn distance_point_to_plane(point: Point3, plane_point: Point3, normal: Vec3) -> Option<Float> {
+    Some((point - plane_point).dot(normal.unit()?))
 }
 
-impl AABB {
-    fn pmin(a: Point3, b: Point3) -> Point3 {
-        Point3(a.0.combine(b.0, |x, y| x.min(y)))
+/// The point on the plane through `plane_point` with the given `normal`
+/// closest to `point`, or None if the normal is zero. The normal need not be
+/// a unit vector.
+pub fn project_point_onto_plane(point: Point3, plane_point: Point3, normal: Vec3) -> Option<Point3> {
+    let normal = normal.unit()?;
+    Some(point - (point - plane_point).dot(normal) * normal)
+}
+
+/// The t at which `ray` crosses the plane through `plane_point` with the
+/// given `normal`, if it is strictly within `t_range`. A ray parallel to the
+/// plane, to within `DEFAULT_EPSILON` of the cosine of the angle between its
+/// direction and the normal, does not cross it, even when it lies in the
+/// plane. Neither the direction nor the normal need be a unit vector.
+///
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// let ray = Ray3::new(Point3::default(), Vec3::new(0.0, 1.0, 1.0), 0.0).unwrap();
+/// let t = intersect_ray_plane(ray, Point3::new(0.0, 0.0, 2.0), Vec3::e2(), TRange::new(0.0, Float::INFINITY));
+/// assert_approx_eq!(ray.at(t.unwrap()), Point3::new(0.0, 2.0, 2.0));
+/// ```
+pub fn intersect_ray_plane(ray: Ray3, plane_point: Point3, normal: Vec3, t_range: TRange<Float>) -> Option<Float> {
+    let denominator = ray.direction.dot(normal);
+    if denominator.abs() <= DEFAULT_EPSILON * ray.direction.length() * normal.length() {
+        return None;
+    }
+    let t = (plane_point - ray.origin).dot(normal) / denominator;
+    t_range.contains_exclusive(&t).then_some(t)
+}
+
+/// Both t at which `ray` crosses the sphere, in increasing order, or None if
+/// the ray's line misses it. The roots may be negative, behind the origin,
+/// and are equal where the line only touches the sphere.
+///
+/// The roots are solved for in a form that stays accurate for spheres far
+/// from the ray origin compared to their radius. The discriminant is worked
+/// out from the distance between the centre and the line, rather than as the
+/// difference of two large, nearly equal products, and the second root is
+/// found from the first without subtracting nearly equal values.
+///
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// let ray = Ray3::new(Point3::default(), Vec3::e2(), 0.0).unwrap();
+/// let center = Point3::new(0.0, 0.0, 5.0);
+/// assert_eq!(intersect_ray_sphere_roots(ray, center, 2.0), Some((3.0, 7.0)));
+/// ```
+pub fn intersect_ray_sphere_roots(ray: Ray3, center: Point3, radius: Float) -> Option<(Float, Float)> {
+    let oc = ray.origin - center;
+    let a = ray.direction.quadrance();
+    let half_b = ray.direction.dot(oc);
+    let c = oc.quadrance() - radius * radius;
+    let perpendicular = oc - (half_b / a) * ray.direction;
+    let delta = a * (radius * radius - perpendicular.quadrance());
+    // a zero direction gives a NaN discriminant, which also misses
+    if delta < 0.0 || delta.is_nan() {
+        return None;
+    }
+
+    let q = -(half_b + delta.sqrt().copysign(half_b));
+    if q == 0.0 {
+        // the line touches the sphere at the origin
+        return Some((0.0, 0.0));
+    }
+    let (t0, t1) = (q / a, c / q);
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+/// The nearest t strictly within `t_range` at which `ray` crosses the
+/// sphere, as `intersect_ray_sphere_roots`.
+pub fn intersect_ray_sphere(ray: Ray3, center: Point3, radius: Float, t_range: TRange<Float>) -> Option<Float> {
+    let (t0, t1) = intersect_ray_sphere_roots(ray, center, radius)?;
+    [t0, t1].into_iter().find(|t| t_range.contains_exclusive(t))
+}
+
+/// The t-values over which `ray` is between the planes at `lo` and `hi`
+/// along `axis`, where `lo <= hi`. A ray parallel to the planes is between
+/// them for every t if its origin is, and otherwise never is.
+///
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// let ray = Ray3::new(Point3::default(), -Vec3::e0(), 0.0).unwrap();
+/// let t = intersect_ray_slab(ray, Axis::X, -3.0, -1.0).unwrap();
+/// assert_eq!((t.start, t.end), (1.0, 3.0));
+/// ```
+pub fn intersect_ray_slab(ray: Ray3, axis: Axis, lo: Float, hi: Float) -> Option<TRange<Float>> {
+    let origin = ray.origin[axis];
+    let direction = ray.direction[axis];
+    if direction == 0.0 {
+        return (lo <= origin && origin <= hi).then_some(TRange::new(Float::NEG_INFINITY, Float::INFINITY));
     }
+    let t0 = (lo - origin) / direction;
+    let t1 = (hi - origin) / direction;
+    Some(TRange::new(t0.min(t1), t0.max(t1)))
+}
 
-    fn pmax(a: Point3, b: Point3) -> Point3 {
-        Point3(a.0.combine(b.0, |x, y| x.max(y)))
+/// A ray with the reciprocals of its direction worked out once, so that
+/// `AABB::hit_slab` multiplies rather than divides. Components of the
+/// direction that are zero give infinite reciprocals of the same sign.
+///
+/// Made with `Ray3::precompute`, and kept apart from `Ray3` so that the
+/// reciprocals cannot fall out of step with a changed direction.
+#[derive(Debug, Copy, Clone)]
+pub struct SlabRay {
+    pub ray: Ray3,
+    inv_direction: Vec3,
+    lanes: simd::SlabLanes,
+}
+
+impl SlabRay {
+    pub fn inv_direction(&self) -> Vec3 {
+        self.inv_direction
     }
+}
 
+/// Whether every coordinate of `a` is at most that of `b`.
+fn all_le(a: Point3, b: Point3) -> bool {
+    a.0.combine(b.0, |x, y| x <= y).all(|le| le)
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct AABB {
+    lo: Point3,
+    hi: Point3,
+}
+
+impl AABB {
+    /// The box with opposite corners `a` and `b`. The corners may share a
+    /// coordinate, giving a box that is flat in that dimension, as for an
+    /// axis-aligned rectangle.
     pub fn new(a: Point3, b: Point3) -> AABB {
-        assert!(
-            a.0.combine(b.0, |x, y| x != y).reduce(|acc, x| acc && x),
-            "AABB extents must have a non-zero distance in all 3 dimensions.\n{a:?}\n{b:?}"
-        );
-        let lo = Self::pmin(a, b);
-        let hi = Self::pmax(a, b);
+        let lo = a.min(b);
+        let hi = a.max(b);
         AABB { lo, hi }
     }
 
@@ -317,45 +963,93 @@ impl AABB {
         self.hi
     }
 
-    /// Calculates the per axis t-values for the ray passing through the planes
-    /// that intersect at the given point.
-    fn t(extent: Point3, ray: Ray3) -> NTuple<f64, 3> {
-        (extent - ray.origin)
-            .0
-            .combine(ray.direction.0, |x, y| x / y)
+    /// The interval of t-values within `t_range` over which the ray is inside
+    /// the box, or None if it misses.
+    ///
+    /// The box is closed, so a ray that only touches it, at a corner or
+    /// along an edge or face, hits it. Where it touches a single point the
+    /// interval has the same start and end. A ray starting inside the box
+    /// has an interval starting at `t_range.start`.
+    ///
+    /// Note that `t_range.start` must be strictly less than `t_range.end`.
+    pub fn hit(self, ray: Ray3, t_range: TRange<Float>) -> Option<TRange<Float>> {
+        self.hit_slab(&ray.precompute(), t_range)
     }
 
-    /// Determine if a ray intersects with the axis-aligned bounding box for
-    /// t-values between `t_min` and `t_max`.
+    /// Whether the ray hits the box, as `hit`.
+    pub fn hit_bool(self, ray: Ray3, t_range: TRange<Float>) -> bool {
+        self.hit(ray, t_range).is_some()
+    }
+
+    /// `hit` for a ray whose reciprocal direction has already been worked
+    /// out, as when it is tested against every box down a hierarchy.
     ///
-    /// Note that `t_min` must be strictly less than `t_max`.
-    pub fn hit(self, ray: Ray3, t_range: TRange<f64>) -> bool {
+    /// This is `hit_slab_simd` with the simd feature and `hit_slab_scalar`
+    /// without it.
+    pub fn hit_slab(self, ray: &SlabRay, t_range: TRange<Float>) -> Option<TRange<Float>> {
+        #[cfg(feature = "simd")]
+        return self.hit_slab_simd(ray, t_range);
+        #[cfg(not(feature = "simd"))]
+        return self.hit_slab_scalar(ray, t_range);
+    }
+
+    /// `hit_slab` one axis at a time.
+    pub fn hit_slab_scalar(self, ray: &SlabRay, t_range: TRange<Float>) -> Option<TRange<Float>> {
         assert!(
             t_range.start < t_range.end,
             "t_min must be less than t_max for aabb hit calculation."
         );
 
-        // Calculate t-values for the ray intersections with the planes
-        // described by the extents.
-        //
-        // 0 values in the direction vector still work as origins that lie
-        // between the planes will produce + and - infinities which will be
-        // dropped when compared with t_max/t_min, but outside of/on the
-        // boundary of will produce a situtaion where both the lower and upper
-        // values are + or - infinity which will always lead to t_min >= t_max.
-        let t0 = Self::t(self.lo(), ray);
-        let t1 = Self::t(self.hi(), ray);
+        // narrow the range to where the ray is between each pair of planes
+        // bounding the box
+        let (mut start, mut end) = (t_range.start, t_range.end);
+        for axis in Axis::ALL {
+            let origin = ray.ray.origin[axis];
+            let inv_direction = ray.inv_direction[axis];
+            let (lo, hi) = (self.lo[axis], self.hi[axis]);
+            if inv_direction.is_infinite() {
+                // parallel to the planes, so between them for all t or none
+                if origin < lo || origin > hi {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (lo - origin) * inv_direction;
+            let t1 = (hi - origin) * inv_direction;
+            start = start.max(t0.min(t1));
+            end = end.min(t0.max(t1));
+        }
+
+        (start <= end).then_some(TRange { start, end })
+    }
+
+    /// `hit_slab` for all three axes at once, as vectors of four lanes. For
+    /// boxes and rays with finite coordinates it gives exactly the same
+    /// intervals as `hit_slab_scalar`.
+    pub fn hit_slab_simd(self, ray: &SlabRay, t_range: TRange<Float>) -> Option<TRange<Float>> {
+        assert!(
+            t_range.start < t_range.end,
+            "t_min must be less than t_max for aabb hit calculation."
+        );
+        simd::hit_slab(self.lo, self.hi, &ray.lanes, t_range)
+    }
 
-        // intersection t values sorted
-        let t_lower = t0.combine(t1, |x, y| x.min(y));
-        let t_upper = t0.combine(t1, |x, y| x.max(y));
+    /// Whether `p` is inside the box. The box is closed, so points on its
+    /// faces are inside it.
+    pub fn contains_point(self, p: Point3) -> bool {
+        all_le(self.lo, p) && all_le(p, self.hi)
+    }
 
-        // determine largest lower t-value, and smallest upper t-value
-        let t_min = t_lower.fold(t_range.start, |x, y| x.max(y));
-        let t_max = t_upper.fold(t_range.end, |x, y| x.min(y));
+    /// Whether all of `other` is inside the box, including when they share
+    /// faces.
+    pub fn contains_aabb(self, other: AABB) -> bool {
+        all_le(self.lo, other.lo) && all_le(other.hi, self.hi)
+    }
 
-        // if the ray passes through the volume of the AABB (not just the edge)
-        t_min < t_max
+    /// Whether the two boxes overlap. As the boxes are closed, boxes that
+    /// only touch, along a face, an edge or at a corner, intersect.
+    pub fn intersects(self, other: AABB) -> bool {
+        all_le(self.lo, other.hi) && all_le(other.lo, self.hi)
     }
 
     /// Takes two optioned AABBs and merges them. If both are Some then a true
@@ -364,8 +1058,8 @@ impl AABB {
     pub fn merge(a: Option<AABB>, b: Option<AABB>) -> Option<AABB> {
         if let Some(a) = a {
             if let Some(b) = b {
-                let lo = Self::pmin(a.lo, b.lo);
-                let hi = Self::pmax(a.hi, b.hi);
+                let lo = a.lo.min(b.lo);
+                let hi = a.hi.max(b.hi);
                 Some(AABB { lo, hi })
             } else {
                 Some(a)
@@ -375,11 +1069,116 @@ impl AABB {
         }
     }
 
-    pub fn volume(self) -> f64 {
-        (self.hi - self.lo).0.reduce(|acc, x| acc * x).abs()
+    pub fn volume(self) -> Float {
+        (self.hi - self.lo).0.product().abs()
+    }
+
+    /// Grows the box about its centre in any dimension thinner than `delta`
+    /// so that it is `delta` thick, leaving thicker dimensions alone.
+    pub fn pad(self, delta: Float) -> AABB {
+        let grow = Vec3(self.extent().0.map(|e| 0.5 * (delta - e).max(0.0)));
+        AABB {
+            lo: self.lo - grow,
+            hi: self.hi + grow,
+        }
+    }
+
+    /// The total area of the six faces of the box, which is positive for a
+    /// box that is flat in one dimension where its volume is zero.
+    pub fn surface_area(self) -> Float {
+        let e = self.extent();
+        2.0 * (e.x() * e.y() + e.y() * e.z() + e.z() * e.x())
+    }
+
+    /// The point at the centre of the box.
+    pub fn centroid(self) -> Point3 {
+        self.lo.lerp(self.hi, 0.5)
+    }
+
+    /// The size of the box along each axis, from `lo` to `hi`.
+    pub fn extent(self) -> Vec3 {
+        self.hi - self.lo
+    }
+
+    /// The axis along which the box is longest, the first of them on a tie.
+    pub fn longest_axis(self) -> Axis {
+        self.extent().max_axis()
+    }
+}
+
+/// Up to four boxes stored axis by axis, the coordinates of all four boxes
+/// along each axis together, so that a ray is tested against them all at
+/// once with the same operations on each, which the compiler turns into
+/// SIMD instructions. Slots without a box are never hit.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct AABB4 {
+    lo: [[Float; 4]; 3],
+    hi: [[Float; 4]; 3],
+    present: [bool; 4],
+}
+
+impl AABB4 {
+    pub fn new(boxes: [Option<AABB>; 4]) -> AABB4 {
+        let coordinates = |corner: fn(AABB) -> Point3| {
+            std::array::from_fn(|axis| std::array::from_fn(|i| boxes[i].map_or(0.0, |aabb| corner(aabb).0[axis])))
+        };
+        AABB4 {
+            lo: coordinates(AABB::lo),
+            hi: coordinates(AABB::hi),
+            present: boxes.map(|aabb| aabb.is_some()),
+        }
+    }
+
+    /// The box in slot `i`, if there is one.
+    pub fn get(&self, i: usize) -> Option<AABB> {
+        self.present[i].then(|| AABB {
+            lo: Point3::new(self.lo[0][i], self.lo[1][i], self.lo[2][i]),
+            hi: Point3::new(self.hi[0][i], self.hi[1][i], self.hi[2][i]),
+        })
+    }
+
+    /// `AABB::hit_slab` for each of the boxes, giving exactly the same
+    /// intervals as `AABB::hit_slab_scalar` does for each box alone.
+    pub fn hit_slab(&self, ray: &SlabRay, t_range: TRange<Float>) -> [Option<TRange<Float>>; 4] {
+        assert!(
+            t_range.start < t_range.end,
+            "t_min must be less than t_max for aabb hit calculation."
+        );
+
+        let (mut start, mut end) = ([t_range.start; 4], [t_range.end; 4]);
+        let mut miss = self.present.map(|present| !present);
+        for axis in Axis::ALL {
+            let origin = ray.ray.origin[axis];
+            let inv_direction = ray.inv_direction[axis];
+            let (lo, hi) = (self.lo[usize::from(axis)], self.hi[usize::from(axis)]);
+            if inv_direction.is_infinite() {
+                // parallel to the planes, so between them for all t or none
+                miss = std::array::from_fn(|i| miss[i] || origin < lo[i] || origin > hi[i]);
+                continue;
+            }
+            let t0: [Float; 4] = std::array::from_fn(|i| (lo[i] - origin) * inv_direction);
+            let t1: [Float; 4] = std::array::from_fn(|i| (hi[i] - origin) * inv_direction);
+            start = std::array::from_fn(|i| start[i].max(t0[i].min(t1[i])));
+            end = std::array::from_fn(|i| end[i].min(t0[i].max(t1[i])));
+        }
+
+        std::array::from_fn(|i| {
+            (!miss[i] && start[i] <= end[i]).then_some(TRange {
+                start: start[i],
+                end: end[i],
+            })
+        })
     }
 }
 
+/// A range of values from `start` to `end`, most often of t along a ray.
+///
+/// The object hit routines accept t strictly inside the range, with
+/// `contains_exclusive`, so that a ray leaving a surface at `start` does not
+/// hit it again and a later object hit at exactly the closest t so far does
+/// not replace it. Bounding boxes and the extents of rectangles are closed,
+/// with `contains_inclusive`, so that rays along their edges are not lost
+/// between neighbours.
 #[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
 pub struct TRange<T> {
     pub start: T,
@@ -394,7 +1193,83 @@ where
         TRange { start, end }
     }
 
+    /// Whether `start <= i <= end`, the same as `contains_inclusive`.
     pub fn contains(&self, i: &T) -> bool {
+        self.contains_inclusive(i)
+    }
+
+    /// Whether `start <= i <= end`.
+    pub fn contains_inclusive(&self, i: &T) -> bool {
         self.start.le(i) && self.end.ge(i)
     }
+
+    /// Whether `start < i < end`.
+    pub fn contains_exclusive(&self, i: &T) -> bool {
+        self.start.lt(i) && self.end.gt(i)
+    }
+
+    /// Whether the range has no values in it, i.e. `start` is after `end`
+    /// or they are unordered, as with NaN. A range with `start == end` is
+    /// not empty, holding that one value inclusively.
+    pub fn is_empty(&self) -> bool {
+        !self.start.le(&self.end)
+    }
+}
+
+impl TRange<Float> {
+    pub fn length(self) -> Float {
+        self.end - self.start
+    }
+
+    pub fn midpoint(self) -> Float {
+        0.5 * (self.start + self.end)
+    }
+
+    /// The closest value to `value` within the range. Unlike `f64::clamp`
+    /// this does not panic for an empty range, giving `end` instead.
+    pub fn clamp(self, value: Float) -> Float {
+        value.max(self.start).min(self.end)
+    }
+
+    /// The values in both ranges, or None if they do not overlap. Ranges
+    /// that only meet at one value intersect there.
+    pub fn intersect(self, other: TRange<Float>) -> Option<TRange<Float>> {
+        let range = TRange::new(self.start.max(other.start), self.end.min(other.end));
+        (!range.is_empty()).then_some(range)
+    }
+
+    /// The smallest range holding both ranges, including any gap between
+    /// them.
+    pub fn union(self, other: TRange<Float>) -> TRange<Float> {
+        TRange::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// The range grown by `eps` at both ends.
+    pub fn expand(self, eps: Float) -> TRange<Float> {
+        TRange::new(self.start - eps, self.end + eps)
+    }
+}
+
+/// # Examples
+/// ```
+/// use geometry3d::TRange;
+///
+/// let range = TRange::from(0.5..2.0);
+/// assert_eq!((range.start, range.end), (0.5, 2.0));
+/// assert!(!range.contains_exclusive(&2.0));
+/// ```
+impl<T> From<std::ops::Range<T>> for TRange<T> {
+    fn from(range: std::ops::Range<T>) -> TRange<T> {
+        TRange {
+            start: range.start,
+            end: range.end,
+        }
+    }
+}
+
+impl<T> From<std::ops::RangeInclusive<T>> for TRange<T> {
+    fn from(range: std::ops::RangeInclusive<T>) -> TRange<T> {
+        let (start, end) = range.into_inner();
+        TRange { start, end }
+    }
 }
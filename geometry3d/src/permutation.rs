@@ -0,0 +1,143 @@
+use crate::*;
+
+/// An ordering of the three axes, each named once, such as the world axes
+/// that a rectangle's own x, y and z lie along.
+///
+/// Only orderings that name each axis once can be made, so a permutation
+/// never duplicates one component and drops another.
+///
+/// In scene files a permutation is written either as the name of one of
+/// the rectangle orientations, `"XY"`, `"XZ"` or `"YZ"`, or as its axes,
+/// e.g. `["Y", "Z", "X"]`. Those with a name are written back by name.
+///
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// assert_eq!(Permutation::try_new([Axis::X, Axis::Z, Axis::Y]), Ok(Permutation::XZ));
+/// assert!(Permutation::try_new([Axis::X, Axis::X, Axis::Z]).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "PermutationRepr", into = "PermutationRepr")]
+pub struct Permutation([Axis; 3]);
+
+impl Permutation {
+    /// The orientation of an `XYRect`, which is also the identity.
+    pub const XY: Permutation = Permutation([Axis::X, Axis::Y, Axis::Z]);
+    /// The orientation of an `XZRect`.
+    pub const XZ: Permutation = Permutation([Axis::X, Axis::Z, Axis::Y]);
+    /// The orientation of a `YZRect`.
+    pub const YZ: Permutation = Permutation([Axis::Y, Axis::Z, Axis::X]);
+
+    const NAMED: [(&'static str, Permutation); 3] = [("XY", Self::XY), ("XZ", Self::XZ), ("YZ", Self::YZ)];
+
+    /// The permutation of `axes`, or an error if they do not name each axis
+    /// once.
+    pub fn try_new(axes: [Axis; 3]) -> Result<Permutation, String> {
+        if axes[0] != axes[1] && axes[1] != axes[2] && axes[0] != axes[2] {
+            Ok(Permutation(axes))
+        } else {
+            Err(format!(
+                "{axes:?} is not a permutation of the axes, each axis must appear once."
+            ))
+        }
+    }
+
+    pub fn axes(self) -> [Axis; 3] {
+        self.0
+    }
+
+    /// The permutation that undoes this one.
+    pub fn inverse(self) -> Permutation {
+        let mut inverse = self.0;
+        for (i, axis) in Axis::ALL.into_iter().enumerate() {
+            inverse[usize::from(self.0[i])] = axis;
+        }
+        Permutation(inverse)
+    }
+
+    /// The name of the permutation if it is one of the rectangle
+    /// orientations.
+    pub fn name(self) -> Option<&'static str> {
+        Self::NAMED.iter().find(|(_, p)| *p == self).map(|(name, _)| *name)
+    }
+}
+
+impl Default for Permutation {
+    fn default() -> Permutation {
+        Permutation::XY
+    }
+}
+
+impl std::str::FromStr for Permutation {
+    type Err = String;
+
+    /// Reads the name of one of the rectangle orientations.
+    fn from_str(name: &str) -> Result<Permutation, String> {
+        Self::NAMED
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, p)| *p)
+            .ok_or_else(|| format!("There is no permutation named {name:?}, the names are \"XY\", \"XZ\" and \"YZ\"."))
+    }
+}
+
+impl TryFrom<[Axis; 3]> for Permutation {
+    type Error = String;
+
+    fn try_from(axes: [Axis; 3]) -> Result<Permutation, String> {
+        Permutation::try_new(axes)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PermutationRepr {
+    Name(String),
+    Axes([Axis; 3]),
+}
+
+impl TryFrom<PermutationRepr> for Permutation {
+    type Error = String;
+
+    fn try_from(repr: PermutationRepr) -> Result<Permutation, String> {
+        match repr {
+            PermutationRepr::Name(name) => name.parse(),
+            PermutationRepr::Axes(axes) => Permutation::try_new(axes),
+        }
+    }
+}
+
+impl From<Permutation> for PermutationRepr {
+    fn from(permutation: Permutation) -> PermutationRepr {
+        match permutation.name() {
+            Some(name) => PermutationRepr::Name(name.to_string()),
+            None => PermutationRepr::Axes(permutation.0),
+        }
+    }
+}
+
+/// Reordering the components of a vector or point, as for rectangles whose
+/// own x, y and z are some order of the world's axes.
+pub trait Permute {
+    /// Element `i` of the result is the component for `axes()[i]`, e.g. with
+    /// `Permutation::YZ`, `(x, y, z)` becomes `(y, z, x)`.
+    fn permute(self, permutation: Permutation) -> Self;
+    /// The inverse of `permute`, putting element `i` back as the component
+    /// for `axes()[i]`, so that `x.permute(p).unpermute(p) == x`.
+    fn unpermute(self, permutation: Permutation) -> Self;
+}
+
+impl<T> Permute for T
+where
+    T: NTupleNewtype<Float, 3>,
+{
+    fn permute(self, permutation: Permutation) -> T {
+        let perms = permutation.0.map(usize::from);
+        T::from(self.ntuple().permute(perms))
+    }
+
+    fn unpermute(self, permutation: Permutation) -> T {
+        self.permute(permutation.inverse())
+    }
+}
@@ -0,0 +1,89 @@
+//! The slab test of `AABB::hit_slab` over four lanes at once, one for each
+//! axis and a fourth of padding, written as element-wise operations on
+//! aligned arrays that the compiler turns into SIMD instructions, without
+//! the per-axis branches of the scalar test.
+//!
+//! The padding lane never narrows the range, and the lanes are reduced in
+//! the same order as the scalar test goes through the axes, so for boxes
+//! and rays with finite coordinates the two give exactly the same intervals.
+
+use crate::*;
+
+/// Four values, one per axis and a fourth of padding, aligned so that they
+/// load as whole vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(not(feature = "f32"), repr(align(32)))]
+#[cfg_attr(feature = "f32", repr(align(16)))]
+pub(crate) struct Lanes([Float; 4]);
+
+impl Lanes {
+    pub(crate) fn new(t: NTuple<Float, 3>, pad: Float) -> Lanes {
+        Lanes(t.push::<4>(pad).into_array())
+    }
+}
+
+/// The ray's origin and reciprocal direction as lanes, padded so that the
+/// fourth lane's slab is all of t.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SlabLanes {
+    origin: Lanes,
+    inv_direction: Lanes,
+    parallel: [bool; 4],
+}
+
+impl SlabLanes {
+    pub(crate) fn new(origin: Point3, inv_direction: Vec3) -> SlabLanes {
+        SlabLanes {
+            origin: Lanes::new(origin.0, 0.0),
+            inv_direction: Lanes::new(inv_direction.0, 1.0),
+            parallel: [0, 1, 2, 3].map(|i| i < 3 && inv_direction.0[i].is_infinite()),
+        }
+    }
+}
+
+// `Float::min` and `Float::max` as a compare and select, which unlike them
+// become single vector instructions. They differ only in which argument
+// they return when one is NaN, which for finite boxes and rays they never
+// are.
+fn min(a: Float, b: Float) -> Float {
+    if b < a {
+        b
+    } else {
+        a
+    }
+}
+
+fn max(a: Float, b: Float) -> Float {
+    if b > a {
+        b
+    } else {
+        a
+    }
+}
+
+pub(crate) fn hit_slab(lo: Point3, hi: Point3, ray: &SlabLanes, t_range: TRange<Float>) -> Option<TRange<Float>> {
+    // the padding lane is from -infinity to infinity, so its slab is too
+    let lo = Lanes::new(lo.0, Float::NEG_INFINITY).0;
+    let hi = Lanes::new(hi.0, Float::INFINITY).0;
+    let (origin, inv_direction) = (ray.origin.0, ray.inv_direction.0);
+
+    let t0: [Float; 4] = std::array::from_fn(|i| (lo[i] - origin[i]) * inv_direction[i]);
+    let t1: [Float; 4] = std::array::from_fn(|i| (hi[i] - origin[i]) * inv_direction[i]);
+    // a ray parallel to the planes is between them for all t or none, so
+    // its slab is either everything or empty
+    let outside: [bool; 4] = std::array::from_fn(|i| origin[i] < lo[i] || origin[i] > hi[i]);
+    let near: [Float; 4] = std::array::from_fn(|i| match (ray.parallel[i], outside[i]) {
+        (false, _) => min(t0[i], t1[i]),
+        (true, false) => Float::NEG_INFINITY,
+        (true, true) => Float::INFINITY,
+    });
+    let far: [Float; 4] = std::array::from_fn(|i| match (ray.parallel[i], outside[i]) {
+        (false, _) => max(t0[i], t1[i]),
+        (true, false) => Float::INFINITY,
+        (true, true) => Float::NEG_INFINITY,
+    });
+
+    let start = near.into_iter().fold(t_range.start, max);
+    let end = far.into_iter().fold(t_range.end, min);
+    (start <= end).then_some(TRange { start, end })
+}
@@ -0,0 +1,306 @@
+use crate::*;
+
+type Matrix = [[Float; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// An affine transformation of 3D space, stored as a 4x4 matrix acting on
+/// column vectors along with its inverse.
+///
+/// Transforms compose with `*` like matrices, so `a * b` applies `b` first
+/// and then `a`.
+///
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// let t = Transform::translate(Vec3::new(1.0, 0.0, 0.0)) * Transform::scale(Vec3::new(2.0, 2.0, 2.0));
+/// assert_eq!(t.transform_point(Point3::new(1.0, 1.0, 1.0)), Point3::new(3.0, 2.0, 2.0));
+/// assert_eq!(t.transform_vector(Vec3::new(1.0, 1.0, 1.0)), Vec3::new(2.0, 2.0, 2.0));
+/// ```
+///
+/// In scene files a transform is written either as its matrix,
+/// `{"matrix": [[...], [...], [...], [0.0, 0.0, 0.0, 1.0]]}`, or as a list of
+/// steps applied in the order given, e.g.
+/// `[{"Scale": [2.0, 2.0, 2.0]}, {"Rotate": {"axis": [0.0, 1.0, 0.0], "degrees": 45.0}}, {"Translate": [0.0, 1.0, 0.0]}]`.
+/// It is always written back as its matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "TransformRepr", into = "TransformRepr")]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Transform {
+    /// The transform that leaves everything in place.
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: IDENTITY,
+            inverse: IDENTITY,
+        }
+    }
+
+    /// Moves points by `offset`, leaving vectors unchanged.
+    pub fn translate(offset: Vec3) -> Transform {
+        let translation = |v: Vec3| {
+            let mut m = IDENTITY;
+            m[0][3] = v.x();
+            m[1][3] = v.y();
+            m[2][3] = v.z();
+            m
+        };
+        Transform {
+            matrix: translation(offset),
+            inverse: translation(-offset),
+        }
+    }
+
+    /// Rotates anticlockwise by `radians` about `axis` through the origin,
+    /// looking down the axis towards the origin. The axis may be an `Axis`
+    /// or any non-zero `Vec3`.
+    pub fn rotate<V: Into<Vec3>>(axis: V, radians: Float) -> Transform {
+        let axis = axis
+            .into()
+            .unit()
+            .expect("The axis of a rotation cannot be the zero vector.");
+        let (sin, cos) = radians.sin_cos();
+        let (x, y, z) = (axis.x(), axis.y(), axis.z());
+        let c = 1.0 - cos;
+        let matrix = [
+            [cos + x * x * c, x * y * c - z * sin, x * z * c + y * sin, 0.0],
+            [y * x * c + z * sin, cos + y * y * c, y * z * c - x * sin, 0.0],
+            [z * x * c - y * sin, z * y * c + x * sin, cos + z * z * c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        // the inverse of a rotation is its transpose
+        Transform {
+            matrix,
+            inverse: transpose(matrix),
+        }
+    }
+
+    /// Scales each axis by the matching component of `factors`, none of
+    /// which may be zero.
+    pub fn scale(factors: Vec3) -> Transform {
+        assert!(
+            factors.x() != 0.0 && factors.y() != 0.0 && factors.z() != 0.0,
+            "Scale factors must be non-zero."
+        );
+        let scaling = |x: Float, y: Float, z: Float| {
+            let mut m = IDENTITY;
+            m[0][0] = x;
+            m[1][1] = y;
+            m[2][2] = z;
+            m
+        };
+        Transform {
+            matrix: scaling(factors.x(), factors.y(), factors.z()),
+            inverse: scaling(1.0 / factors.x(), 1.0 / factors.y(), 1.0 / factors.z()),
+        }
+    }
+
+    /// The affine transform with the given matrix, whose last row must be
+    /// (0, 0, 0, 1), or None if it has no inverse.
+    pub fn from_matrix(matrix: [[Float; 4]; 4]) -> Option<Transform> {
+        if matrix[3] != [0.0, 0.0, 0.0, 1.0] {
+            return None;
+        }
+        let inverse = affine_inverse(matrix)?;
+        Some(Transform { matrix, inverse })
+    }
+
+    /// The matrix of the transform, row by row.
+    pub fn matrix(&self) -> [[Float; 4]; 4] {
+        self.matrix
+    }
+
+    /// The transform that undoes this one.
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        Point3(apply(&self.matrix, p.0, 1.0))
+    }
+
+    /// Transforms a direction or displacement, which is unaffected by
+    /// translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        Vec3(apply(&self.matrix, v.0, 0.0))
+    }
+
+    /// Transforms a surface normal by the inverse transpose, so that it stays
+    /// perpendicular to the transformed surface. The result is not
+    /// normalized.
+    pub fn transform_normal(&self, n: Vec3) -> Vec3 {
+        Vec3(apply(&transpose(self.inverse), n.0, 0.0))
+    }
+
+    /// Transforms the origin and direction of the ray, keeping its time.
+    /// The direction is not normalized, so that the ray reaches the same
+    /// transformed points at the same values of `t`.
+    pub fn transform_ray(&self, ray: Ray3) -> Ray3 {
+        Ray3 {
+            origin: self.transform_point(ray.origin),
+            direction: self.transform_vector(ray.direction),
+            time: ray.time,
+        }
+    }
+
+    /// The smallest axis aligned box holding the eight transformed corners
+    /// of `aabb`, and so all of the transformed box.
+    pub fn transform_aabb(&self, aabb: AABB) -> AABB {
+        let (lo, hi) = (aabb.lo(), aabb.hi());
+        let corners = (0..8).map(|i| {
+            let pick = |bit: usize, lo: Float, hi: Float| if i & bit == 0 { lo } else { hi };
+            self.transform_point(Point3::new(
+                pick(1, lo.x(), hi.x()),
+                pick(2, lo.y(), hi.y()),
+                pick(4, lo.z(), hi.z()),
+            ))
+        });
+        let first = self.transform_point(lo);
+        let (lo, hi) = corners.fold((first, first), |(lo, hi), p| (lo.min(p), hi.max(p)));
+        AABB { lo, hi }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// The transform applying the right-hand transform and then the left-hand
+/// one.
+impl std::ops::Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform {
+            matrix: multiply(&self.matrix, &rhs.matrix),
+            inverse: multiply(&rhs.inverse, &self.inverse),
+        }
+    }
+}
+
+/// The unit vector along the axis.
+impl std::convert::From<Axis> for Vec3 {
+    fn from(axis: Axis) -> Vec3 {
+        match axis {
+            Axis::X => Vec3::e0(),
+            Axis::Y => Vec3::e1(),
+            Axis::Z => Vec3::e2(),
+        }
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+fn transpose(m: Matrix) -> Matrix {
+    let mut t = m;
+    for (i, row) in t.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = m[j][i];
+        }
+    }
+    t
+}
+
+/// The first three components of `m * (v, w)`.
+fn apply(m: &Matrix, v: NTuple<Float, 3>, w: Float) -> NTuple<Float, 3> {
+    let v: NTuple<Float, 4> = v.push(w);
+    let row = |r: [Float; 4]| r[0] * v[0] + r[1] * v[1] + r[2] * v[2] + r[3] * v[3];
+    ntuple!(row(m[0]), row(m[1]), row(m[2]))
+}
+
+/// The inverse of an affine matrix, from the inverse of its linear part
+/// found by cofactors, or None if it is singular.
+fn affine_inverse(m: Matrix) -> Option<Matrix> {
+    let a = |i: usize, j: usize| m[i % 3][j % 3];
+    let cofactor = |i: usize, j: usize| a(i + 1, j + 1) * a(i + 2, j + 2) - a(i + 1, j + 2) * a(i + 2, j + 1);
+    let det: Float = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+
+    let mut inverse = IDENTITY;
+    for (i, row) in inverse.iter_mut().take(3).enumerate() {
+        for (j, value) in row.iter_mut().take(3).enumerate() {
+            *value = cofactor(j, i) / det;
+        }
+    }
+    let translation = ntuple!(m[0][3], m[1][3], m[2][3]);
+    let offset = apply(&inverse, translation, 0.0);
+    for (row, x) in inverse.iter_mut().zip(offset) {
+        row[3] = -x;
+    }
+    Some(inverse)
+}
+
+/// A step of a transform as written in a scene file.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum Step {
+    Translate(Vec3),
+    Rotate { axis: Vec3, degrees: Float },
+    Scale(Vec3),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum TransformRepr {
+    Matrix { matrix: Matrix },
+    Steps(Vec<Step>),
+}
+
+impl TryFrom<TransformRepr> for Transform {
+    type Error = String;
+
+    fn try_from(repr: TransformRepr) -> Result<Transform, String> {
+        match repr {
+            TransformRepr::Matrix { matrix } => {
+                Transform::from_matrix(matrix).ok_or_else(|| String::from("The matrix must be affine and invertible."))
+            }
+            TransformRepr::Steps(steps) => {
+                let mut transform = Transform::identity();
+                for step in steps {
+                    let step = match step {
+                        Step::Translate(offset) => Transform::translate(offset),
+                        Step::Rotate { axis, degrees } if axis.unit().is_some() => {
+                            Transform::rotate(axis, degrees.to_radians())
+                        }
+                        Step::Scale(factors) if factors.abs().min_component() > 0.0 => Transform::scale(factors),
+                        Step::Rotate { .. } => return Err("The axis of a rotation cannot be zero.".into()),
+                        Step::Scale(_) => return Err("Scale factors must be non-zero.".into()),
+                    };
+                    transform = step * transform;
+                }
+                Ok(transform)
+            }
+        }
+    }
+}
+
+impl From<Transform> for TransformRepr {
+    fn from(transform: Transform) -> TransformRepr {
+        TransformRepr::Matrix {
+            matrix: transform.matrix,
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use crate::*;
+
+/// Where a ray crosses a triangle, found by `intersect_ray_triangle`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangleHit {
+    /// The distance along the ray, in multiples of its direction.
+    pub t: Float,
+    /// The barycentric weight of `v1` at the hit.
+    pub u: Float,
+    /// The barycentric weight of `v2` at the hit. The weight of `v0` is
+    /// `1 - u - v`.
+    pub v: Float,
+    /// The unit normal of the triangle's plane, `(v1 - v0) x (v2 - v0)`, on
+    /// the side that the vertices go anticlockwise, whichever side the ray
+    /// comes from.
+    pub normal: Vec3,
+}
+
+impl TriangleHit {
+    /// The barycentric weights of `v0`, `v1` and `v2` at the hit, for
+    /// `interpolate`.
+    pub fn barycentric(self) -> (Float, Float, Float) {
+        (1.0 - self.u - self.v, self.u, self.v)
+    }
+}
+
+/// The settings for a ray-triangle test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangleTest {
+    /// Rays within this cosine of parallel to the triangle miss it. The
+    /// test is relative to the size of the triangle, so thin slivers are
+    /// not missed just for having a small area.
+    pub epsilon: Float,
+    /// Only hit triangles from the side their normal faces.
+    pub cull_backfaces: bool,
+}
+
+impl Default for TriangleTest {
+    fn default() -> TriangleTest {
+        TriangleTest {
+            epsilon: DEFAULT_EPSILON,
+            cull_backfaces: false,
+        }
+    }
+}
+
+impl TriangleTest {
+    /// Where `ray` crosses the triangle `v0`, `v1`, `v2` at a positive t,
+    /// using the Möller–Trumbore algorithm, or None if it misses. The
+    /// triangle is closed, so rays through its edges and corners hit it.
+    pub fn intersect(self, ray: Ray3, v0: Point3, v1: Point3, v2: Point3) -> Option<TriangleHit> {
+        let e1 = v1 - v0;
+        let e2 = v2 - v0;
+        let normal = e1.cross(e2);
+
+        // the determinant is -direction . normal, so is positive for rays
+        // hitting the front of the triangle
+        let p = ray.direction.cross(e2);
+        let det = e1.dot(p);
+        let threshold = self.epsilon * ray.direction.length() * normal.length();
+        let parallel = match self.cull_backfaces {
+            true => det <= threshold,
+            false => det.abs() <= threshold,
+        };
+        if parallel {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let s = ray.origin - v0;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(e1);
+        let v = ray.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = e2.dot(q) * inv_det;
+        if t <= 0.0 {
+            return None;
+        }
+
+        Some(TriangleHit {
+            t,
+            u,
+            v,
+            normal: normal / normal.length(),
+        })
+    }
+}
+
+/// `TriangleTest::intersect` with the default settings, hitting either side
+/// of the triangle.
+///
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// let ray = Ray3::new(Point3::new(0.25, 0.5, 1.0), -Vec3::e2(), 0.0).unwrap();
+/// let (v0, v1, v2) = (Point3::default(), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0));
+/// let hit = intersect_ray_triangle(ray, v0, v1, v2).unwrap();
+/// assert_eq!((hit.t, hit.u, hit.v), (1.0, 0.25, 0.5));
+/// assert_eq!(hit.normal, Vec3::e2());
+/// ```
+pub fn intersect_ray_triangle(ray: Ray3, v0: Point3, v1: Point3, v2: Point3) -> Option<TriangleHit> {
+    TriangleTest::default().intersect(ray, v0, v1, v2)
+}
+
+/// The barycentric weights of `a`, `b` and `c` that give `p`, which should be
+/// in the plane of the triangle. The weights add up to one. For a point off
+/// the plane they are those of its projection onto the plane, and for a
+/// degenerate triangle they are NaN.
+///
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// let (a, b, c) = (Point3::default(), Point3::new(2.0, 0.0, 0.0), Point3::new(0.0, 2.0, 0.0));
+/// assert_eq!(barycentric(Point3::new(0.5, 1.0, 0.0), a, b, c), (0.25, 0.25, 0.5));
+/// ```
+pub fn barycentric(p: Point3, a: Point3, b: Point3, c: Point3) -> (Float, Float, Float) {
+    let (e1, e2, ep) = (b - a, c - a, p - a);
+    let (d11, d12, d22) = (e1.dot(e1), e1.dot(e2), e2.dot(e2));
+    let (dp1, dp2) = (ep.dot(e1), ep.dot(e2));
+    let denominator = d11 * d22 - d12 * d12;
+    let v = (d22 * dp1 - d12 * dp2) / denominator;
+    let w = (d11 * dp2 - d12 * dp1) / denominator;
+    (1.0 - v - w, v, w)
+}
+
+/// The values at the three vertices of a triangle weighted by the
+/// barycentric weights `bary`, for normals, texture coordinates, colors and
+/// the like.
+///
+/// # Examples
+/// ```
+/// use geometry3d::*;
+///
+/// let uv = interpolate((0.5, 0.25, 0.25), 0.0, 1.0, 2.0);
+/// assert_eq!(uv, 0.75);
+/// ```
+pub fn interpolate<T>(bary: (Float, Float, Float), va: T, vb: T, vc: T) -> T
+where
+    T: std::ops::Add<Output = T> + std::ops::Mul<Float, Output = T>,
+{
+    va * bary.0 + vb * bary.1 + vc * bary.2
+}
+
+/// Whether the barycentric weights are of a point in the triangle, with
+/// none of them more than `epsilon` below zero.
+pub fn is_inside(bary: (Float, Float, Float), epsilon: Float) -> bool {
+    bary.0 >= -epsilon && bary.1 >= -epsilon && bary.2 >= -epsilon
+}
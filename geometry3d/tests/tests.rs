@@ -2,6 +2,12 @@
 mod vec3_tests {
     use geometry3d::*;
 
+    // the error that rounding alone leaves in a short calculation
+    #[cfg(not(feature = "f32"))]
+    const ROUNDING: Float = 1e-12;
+    #[cfg(feature = "f32")]
+    const ROUNDING: Float = 1e-6;
+
     #[test]
     fn cannonical_basis_vectors() {
         assert_eq!(Vec3::e0().x(), 1.0);
@@ -17,6 +23,12 @@ mod vec3_tests {
         assert_eq!(Vec3::e2().z(), 1.0);
     }
 
+    #[test]
+    fn pairs_of_components() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!((v.xy(), v.xz(), v.yz()), ((1.0, 2.0), (1.0, 3.0), (2.0, 3.0)));
+    }
+
     #[test]
     fn new_vector() {
         let v = Vec3::new(1.0, 2.0, 3.0);
@@ -87,6 +99,43 @@ mod vec3_tests {
         assert_eq!(3.0 * v1, v2);
     }
 
+    #[test]
+    fn scalar_multiplication_on_the_right() {
+        let v = Vec3::new(1.0, -2.0, 0.5);
+        assert_eq!(v * 3.0, Vec3::new(3.0, -6.0, 1.5));
+        assert_eq!(v * 3.0, 3.0 * v);
+    }
+
+    #[test]
+    fn negation_is_scaling_by_minus_one() {
+        let v = Vec3::new(1.0, -2.0, 0.5);
+        assert_eq!(-v, -1.0 * v);
+        assert_eq!(-v, v * -1.0);
+        assert_eq!(-(-v), v);
+        assert_eq!(v + -v, Vec3::default());
+    }
+
+    #[test]
+    fn assigning_operators() {
+        let mut v = Vec3::new(1.0, 2.0, 3.0);
+        v += Vec3::new(1.0, 1.0, 1.0);
+        assert_eq!(v, Vec3::new(2.0, 3.0, 4.0));
+        v -= Vec3::e0();
+        assert_eq!(v, Vec3::new(1.0, 3.0, 4.0));
+        v *= -2.0;
+        assert_eq!(v, Vec3::new(-2.0, -6.0, -8.0));
+        v /= 4.0;
+        assert_eq!(v, Vec3::new(-0.5, -1.5, -2.0));
+    }
+
+    #[test]
+    fn sum_of_vectors() {
+        let vectors = [Vec3::new(1.0, 2.0, 3.0), Vec3::new(-1.0, 0.5, 0.0), Vec3::e2()];
+        assert_eq!(vectors.iter().sum::<Vec3>(), Vec3::new(0.0, 2.5, 4.0));
+        assert_eq!(vectors.into_iter().sum::<Vec3>(), Vec3::new(0.0, 2.5, 4.0));
+        assert_eq!(Vec::<Vec3>::new().into_iter().sum::<Vec3>(), Vec3::default());
+    }
+
     #[test]
     fn scalar_division() {
         let v1 = Vec3::new(5.0, 5.0, 5.0);
@@ -101,6 +150,95 @@ mod vec3_tests {
         assert_eq!(v1.projection(v2), Vec3::e0());
     }
 
+    #[test]
+    fn componentwise_min_and_max() {
+        let a = Vec3::new(-1.0, 2.0, -3.0);
+        let b = Vec3::new(1.0, -2.0, -4.0);
+        assert_eq!(a.min(b), Vec3::new(-1.0, -2.0, -4.0));
+        assert_eq!(a.max(b), Vec3::new(1.0, 2.0, -3.0));
+    }
+
+    #[test]
+    fn min_and_max_pass_over_nan() {
+        let a = Vec3::new(Float::NAN, 2.0, Float::NAN);
+        let b = Vec3::new(1.0, Float::NAN, Float::NAN);
+        let min = a.min(b);
+        assert_eq!((min.x(), min.y()), (1.0, 2.0));
+        assert!(min.z().is_nan());
+        let max = a.max(b);
+        assert_eq!((max.x(), max.y()), (1.0, 2.0));
+        assert!(max.z().is_nan());
+    }
+
+    #[test]
+    fn abs() {
+        assert_eq!(Vec3::new(-1.0, 0.0, 2.5).abs(), Vec3::new(1.0, 0.0, 2.5));
+        assert!(Vec3::new(Float::NAN, 0.0, 0.0).abs().x().is_nan());
+    }
+
+    #[test]
+    fn clamp() {
+        let lo = Vec3::new(-1.0, -1.0, -1.0);
+        let hi = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!(Vec3::new(-5.0, 1.5, 5.0).clamp(lo, hi), Vec3::new(-1.0, 1.5, 3.0));
+        let v = Vec3::new(Float::NAN, -5.0, 0.0).clamp(lo, hi);
+        assert!(v.x().is_nan());
+        assert_eq!((v.y(), v.z()), (-1.0, 0.0));
+    }
+
+    #[test]
+    fn extreme_components() {
+        let v = Vec3::new(-4.0, 2.0, -1.0);
+        assert_eq!(v.max_component(), 2.0);
+        assert_eq!(v.min_component(), -4.0);
+        assert_eq!(v.max_axis(), Axis::Y);
+        assert_eq!(Vec3::new(-4.0, -2.0, -1.0).max_axis(), Axis::Z);
+        assert_eq!(Vec3::new(3.0, 3.0, 3.0).max_axis(), Axis::X);
+
+        let v = Vec3::new(Float::NAN, 1.0, -1.0);
+        assert_eq!((v.max_component(), v.min_component()), (1.0, -1.0));
+        assert_eq!(v.max_axis(), Axis::Y);
+        assert!(Vec3::new(Float::NAN, Float::NAN, Float::NAN).max_component().is_nan());
+    }
+
+    #[test]
+    fn lerp() {
+        let a = Vec3::new(1.0, -2.0, 4.0);
+        let b = Vec3::new(3.0, 2.0, 0.0);
+        assert_eq!(a.lerp(b, 0.0), a);
+        assert_eq!(a.lerp(b, 1.0), b);
+        assert_eq!(a.lerp(b, 0.5), Vec3::new(2.0, 0.0, 2.0));
+        assert_eq!(a.lerp(b, 1.5), Vec3::new(4.0, 4.0, -2.0));
+        assert_eq!(a.lerp(b, -0.5), Vec3::new(0.0, -4.0, 6.0));
+    }
+
+    #[test]
+    fn slerp_of_orthogonal_unit_vectors() {
+        let (a, b) = (Vec3::e0(), Vec3::e2());
+        assert_approx_eq!(a.slerp(b, 0.0), a, ROUNDING);
+        assert_approx_eq!(a.slerp(b, 1.0), b, ROUNDING);
+        let angle = geometry3d::consts::FRAC_PI_6;
+        assert_approx_eq!(a.slerp(b, 1.0 / 3.0), Vec3::new(angle.cos(), 0.0, angle.sin()), ROUNDING);
+        for t in [0.1, 0.5, 0.9] {
+            assert_approx_eq!(a.slerp(b, t).length(), 1.0, ROUNDING);
+        }
+    }
+
+    #[test]
+    fn slerp_of_parallel_and_antiparallel_vectors() {
+        let a = Vec3::e1();
+        assert_approx_eq!(a.slerp(a, 0.5), a, ROUNDING);
+
+        let b = -a;
+        let halfway = a.slerp(b, 0.5);
+        assert_approx_eq!(halfway.length(), 1.0, ROUNDING);
+        assert_approx_eq!(halfway.dot(a), 0.0, ROUNDING);
+        assert_approx_eq!(a.slerp(b, 1.0), b, ROUNDING);
+
+        let x = Vec3::e0();
+        assert_approx_eq!(x.slerp(-x, 0.5).dot(x), 0.0, ROUNDING);
+    }
+
     #[test]
     fn position_vector() {
         let p = Point3::new(0.1, 0.2, 0.3);
@@ -109,6 +247,38 @@ mod vec3_tests {
         assert_eq!(v.y(), 0.2);
         assert_eq!(v.z(), 0.3);
     }
+
+    #[test]
+    fn refraction_at_normal_incidence() {
+        let down = -Vec3::e1();
+        for eta_ratio in [1.0 / 1.5, 1.0, 1.5] {
+            assert_approx_eq!(down.refraction(Vec3::e1(), eta_ratio).unwrap(), down);
+        }
+    }
+
+    #[test]
+    fn refraction_follows_snells_law() {
+        // from air into glass at 45 degrees
+        let angle = geometry3d::consts::FRAC_PI_4;
+        let incoming = Vec3::new(angle.sin(), -angle.cos(), 0.0);
+        let refracted = incoming.refraction(Vec3::e1(), 1.0 / 1.5).unwrap();
+        assert_approx_eq!(refracted.length(), 1.0);
+        let sin_out = refracted.x();
+        assert_approx_eq!(1.0 * angle.sin(), 1.5 * sin_out);
+        assert!(refracted.y() < 0.0);
+        assert_approx_eq!(refracted.z(), 0.0);
+    }
+
+    #[test]
+    fn refraction_at_the_critical_angle() {
+        // from glass into air, where the critical angle has sine 1 / 1.5
+        let critical = ((1.0 as Float) / 1.5).asin();
+        let at = |angle: Float| Vec3::new(angle.sin(), -angle.cos(), 0.0).refraction(Vec3::e1(), 1.5);
+        let below = at(critical - 1e-6).unwrap();
+        assert_approx_eq!(below, Vec3::e0(), 1e-2);
+        assert!(at(critical + 1e-6).is_none());
+        assert!(at(1.5).is_none());
+    }
 }
 
 #[cfg(test)]
@@ -123,6 +293,12 @@ mod point3_tests {
         assert_eq!(p.z(), 44.0);
     }
 
+    #[test]
+    fn pairs_of_coordinates() {
+        let p = Point3::new(22.0, 33.0, 44.0);
+        assert_eq!((p.xy(), p.xz(), p.yz()), ((22.0, 33.0), (22.0, 44.0), (33.0, 44.0)));
+    }
+
     #[test]
     fn from_vector() {
         let v = Vec3::new(-5.0, -8.0, -13.0);
@@ -148,6 +324,36 @@ mod point3_tests {
         assert_eq!(p2, (-v).into());
     }
 
+    #[test]
+    fn componentwise_min_max_and_clamp() {
+        let a = Point3::new(-1.0, 5.0, 0.0);
+        let b = Point3::new(2.0, -5.0, 0.0);
+        assert_eq!(a.min(b), Point3::new(-1.0, -5.0, 0.0));
+        assert_eq!(a.max(b), Point3::new(2.0, 5.0, 0.0));
+        assert_eq!(Point3::new(3.0, 0.0, -3.0).clamp(a.min(b), a.max(b)), Point3::new(2.0, 0.0, 0.0));
+        assert!(Point3::new(0.0, Float::NAN, 0.0).clamp(a.min(b), a.max(b)).y().is_nan());
+    }
+
+    #[test]
+    fn lerp() {
+        let a = Point3::new(1.0, 1.0, 1.0);
+        let b = Point3::new(3.0, -1.0, 5.0);
+        assert_eq!(a.lerp(b, 0.0), a);
+        assert_eq!(a.lerp(b, 1.0), b);
+        assert_eq!(a.lerp(b, 0.5), Point3::new(2.0, 0.0, 3.0));
+        assert_eq!(a.lerp(b, 2.0), Point3::new(5.0, -3.0, 9.0));
+        assert_eq!(a.lerp(b, -1.0), Point3::new(-1.0, 3.0, -3.0));
+    }
+
+    #[test]
+    fn translate_point_in_place() {
+        let mut p = Point3::new(1.0, 2.0, 3.0);
+        p += Vec3::new(0.5, -1.0, 0.0);
+        assert_eq!(p, Point3::new(1.5, 1.0, 3.0));
+        p -= Vec3::new(0.5, -1.0, 0.0);
+        assert_eq!(p, Point3::new(1.0, 2.0, 3.0));
+    }
+
     #[test]
     fn difference_of_two_ponits() {
         let p1 = Point3::new(0.0, 0.0, 0.0);
@@ -191,11 +397,112 @@ mod ray3_tests {
         assert_eq!(ray.at(1.0), p1);
         assert_eq!(ray.at(2.0), p2);
     }
+
+    #[test]
+    fn new_normalizes_the_direction() {
+        let origin = Point3::new(1.0, 2.0, 3.0);
+        let ray = Ray3::new(origin, Vec3::new(0.0, 0.0, -4.0), 0.5).unwrap();
+        assert_eq!(ray.origin, origin);
+        assert_eq!(ray.direction, -Vec3::e2());
+        assert_eq!(ray.time, 0.5);
+        assert!(ray.is_normalized());
+        assert_eq!(ray.at(2.0), Point3::new(1.0, 2.0, 1.0));
+    }
+
+    #[test]
+    fn new_rejects_zero_and_non_finite_directions() {
+        let origin = Point3::default();
+        assert!(Ray3::new(origin, Vec3::default(), 0.0).is_none());
+        assert!(Ray3::new(origin, Vec3::new(Float::INFINITY, 0.0, 0.0), 0.0).is_none());
+        assert!(Ray3::new(origin, Vec3::new(Float::NAN, 1.0, 0.0), 0.0).is_none());
+    }
+
+    #[test]
+    fn distance_to_point() {
+        let ray = Ray3::new(Point3::new(1.0, 1.0, 0.0), Vec3::new(1.0, 1.0, 0.0), 0.0).unwrap();
+
+        // beside the ray, at a right angle to (2, 2, 0) on it
+        let p = Point3::new(3.0, 1.0, 0.0);
+        assert_approx_eq!(ray.closest_point_on_ray(p), Point3::new(2.0, 2.0, 0.0));
+        assert_approx_eq!(ray.distance_to_point(p), (2.0 as Float).sqrt());
+
+        // on the ray
+        let p = Point3::new(4.0, 4.0, 0.0);
+        assert_approx_eq!(ray.closest_point_on_ray(p), p);
+        assert_approx_eq!(ray.distance_to_point(p), 0.0);
+
+        // behind the origin, which is the closest point
+        let p = Point3::new(0.0, -1.0, 2.0);
+        assert_eq!(ray.closest_point_on_ray(p), ray.origin);
+        assert_approx_eq!(ray.distance_to_point(p), 3.0);
+    }
+
+    #[test]
+    fn distance_to_point_with_an_unnormalized_direction() {
+        let ray = Ray3 {
+            origin: Point3::default(),
+            direction: Vec3::new(0.0, 0.0, 4.0),
+            time: 0.0,
+        };
+        assert_eq!(ray.closest_point_on_ray(Point3::new(3.0, 0.0, 2.0)), Point3::new(0.0, 0.0, 2.0));
+        assert_eq!(ray.distance_to_point(Point3::new(3.0, 0.0, 2.0)), 3.0);
+
+        let ray = Ray3 {
+            direction: Vec3::default(),
+            ..ray
+        };
+        assert_eq!(ray.closest_point_on_ray(Point3::new(3.0, 0.0, 4.0)), ray.origin);
+        assert_eq!(ray.distance_to_point(Point3::new(3.0, 0.0, 4.0)), 5.0);
+    }
+}
+
+#[cfg(test)]
+mod plane_tests {
+    use geometry3d::*;
+
+    #[test]
+    fn signed_distance_to_plane() {
+        // the plane x + y + z = 3
+        let plane_point = Point3::new(1.0, 1.0, 1.0);
+        let normal = Vec3::new(1.0, 1.0, 1.0);
+        let d = distance_point_to_plane(Point3::new(2.0, 2.0, 2.0), plane_point, normal);
+        assert_approx_eq!(d.unwrap(), (3.0 as Float).sqrt());
+        let d = distance_point_to_plane(Point3::default(), plane_point, normal);
+        assert_approx_eq!(d.unwrap(), -(3.0 as Float).sqrt());
+        let d = distance_point_to_plane(Point3::new(3.0, 0.0, 0.0), plane_point, normal);
+        assert_approx_eq!(d.unwrap(), 0.0);
+
+        // the length of the normal does not matter
+        let d = distance_point_to_plane(Point3::new(2.0, 2.0, 2.0), plane_point, 10.0 * normal);
+        assert_approx_eq!(d.unwrap(), (3.0 as Float).sqrt());
+    }
+
+    #[test]
+    fn projection_onto_plane() {
+        let plane_point = Point3::new(0.0, 1.0, 0.0);
+        let normal = Vec3::new(0.0, -3.0, 0.0);
+        let p = project_point_onto_plane(Point3::new(2.0, 5.0, -1.0), plane_point, normal).unwrap();
+        assert_approx_eq!(p, Point3::new(2.0, 1.0, -1.0));
+
+        let plane_point = Point3::new(1.0, 1.0, 1.0);
+        let normal = Vec3::new(1.0, 1.0, 1.0);
+        let p = project_point_onto_plane(Point3::default(), plane_point, normal).unwrap();
+        assert_approx_eq!(p, plane_point);
+    }
+
+    #[test]
+    fn zero_normal_is_rejected() {
+        let p = Point3::new(1.0, 2.0, 3.0);
+        assert_eq!(distance_point_to_plane(p, Point3::default(), Vec3::default()), None);
+        assert_eq!(project_point_onto_plane(p, Point3::default(), Vec3::default()), None);
+    }
 }
 
 #[cfg(test)]
 mod aabb_tests {
     use geometry3d::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
 
     #[test]
     fn create() {
@@ -206,6 +513,8 @@ mod aabb_tests {
 
     #[test]
     fn hit() {
+        // the box is closed, so rays along its edges and through its corners
+        // touch it
         let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
 
         let mut r1 = Ray3 {
@@ -214,50 +523,107 @@ mod aabb_tests {
             time: 0.0,
         };
 
-        assert_eq!(aabb.hit(r1, TRange::new(0.0, 1.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(0.1, 0.9)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(1.0, 2.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, 0.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, 2.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, -0.5)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(1.5, 2.0)), false);
+        assert!(aabb.hit_bool(r1, TRange::new(0.0, 1.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(0.1, 0.9)));
+        assert!(aabb.hit_bool(r1, TRange::new(1.0, 2.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(-1.0, 0.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(-1.0, 2.0)));
+        assert!(!aabb.hit_bool(r1, TRange::new(-1.0, -0.5)));
+        assert!(!aabb.hit_bool(r1, TRange::new(1.5, 2.0)));
 
         r1.direction = -Vec3::e0();
-        assert_eq!(aabb.hit(r1, TRange::new(0.0, 1.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(0.1, 0.9)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(1.0, 2.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, 0.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-2.0, 2.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, -0.5)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-2.0, -1.5)), false);
+        assert!(aabb.hit_bool(r1, TRange::new(0.0, 1.0)));
+        assert!(!aabb.hit_bool(r1, TRange::new(0.1, 0.9)));
+        assert!(!aabb.hit_bool(r1, TRange::new(1.0, 2.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(-1.0, 0.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(-2.0, 2.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(-1.0, -0.5)));
+        assert!(!aabb.hit_bool(r1, TRange::new(-2.0, -1.5)));
 
         r1.direction = Vec3::new(1.0, 1.0, 1.0);
-        assert_eq!(aabb.hit(r1, TRange::new(0.0, 1.0)), true);
-        assert_eq!(aabb.hit(r1, TRange::new(0.1, 0.9)), true);
-        assert_eq!(aabb.hit(r1, TRange::new(1.0, 2.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, 0.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, 2.0)), true);
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, -0.5)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(1.5, 2.0)), false);
+        assert!(aabb.hit_bool(r1, TRange::new(0.0, 1.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(0.1, 0.9)));
+        assert!(aabb.hit_bool(r1, TRange::new(1.0, 2.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(-1.0, 0.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(-1.0, 2.0)));
+        assert!(!aabb.hit_bool(r1, TRange::new(-1.0, -0.5)));
+        assert!(!aabb.hit_bool(r1, TRange::new(1.5, 2.0)));
 
         r1.direction = -r1.direction;
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, 0.0)), true);
-        assert_eq!(aabb.hit(r1, TRange::new(-0.9, -0.1)), true);
-        assert_eq!(aabb.hit(r1, TRange::new(-2.0, -1.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(0.0, 1.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-2.0, 1.0)), true);
-        assert_eq!(aabb.hit(r1, TRange::new(0.5, 1.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-2.0, -1.5)), false);
+        assert!(aabb.hit_bool(r1, TRange::new(-1.0, 0.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(-0.9, -0.1)));
+        assert!(aabb.hit_bool(r1, TRange::new(-2.0, -1.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(0.0, 1.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(-2.0, 1.0)));
+        assert!(!aabb.hit_bool(r1, TRange::new(0.5, 1.0)));
+        assert!(!aabb.hit_bool(r1, TRange::new(-2.0, -1.5)));
 
+        // only the corner at the origin is touched
         r1.direction = -r1.direction;
         r1.origin = Point3::new(1.0, 0.0, 0.0);
-        assert_eq!(aabb.hit(r1, TRange::new(0.0, 1.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(0.1, 0.9)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(1.0, 2.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, 0.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, 2.0)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(-1.0, -0.5)), false);
-        assert_eq!(aabb.hit(r1, TRange::new(1.5, 2.0)), false);
+        assert!(aabb.hit_bool(r1, TRange::new(0.0, 1.0)));
+        assert!(!aabb.hit_bool(r1, TRange::new(0.1, 0.9)));
+        assert!(!aabb.hit_bool(r1, TRange::new(1.0, 2.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(-1.0, 0.0)));
+        assert!(aabb.hit_bool(r1, TRange::new(-1.0, 2.0)));
+        assert!(!aabb.hit_bool(r1, TRange::new(-1.0, -0.5)));
+        assert!(!aabb.hit_bool(r1, TRange::new(1.5, 2.0)));
+    }
+
+    fn ray(origin: Point3, direction: Vec3) -> Ray3 {
+        Ray3 {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    #[test]
+    fn hit_interval_entering() {
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let r = ray(Point3::new(0.5, 0.5, -2.0), Vec3::e2());
+        let interval = aabb.hit(r, TRange::new(0.0, Float::INFINITY)).unwrap();
+        assert_eq!((interval.start, interval.end), (2.0, 3.0));
+
+        // clipped to the range
+        let interval = aabb.hit(r, TRange::new(2.5, 10.0)).unwrap();
+        assert_eq!((interval.start, interval.end), (2.5, 3.0));
+    }
+
+    #[test]
+    fn hit_interval_exiting_and_inside() {
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 2.0, 2.0));
+        let r = ray(Point3::new(1.0, 1.0, 1.0), Vec3::new(1.0, 0.0, 0.0));
+        let interval = aabb.hit(r, TRange::new(0.001, Float::INFINITY)).unwrap();
+        assert_eq!((interval.start, interval.end), (0.001, 1.0));
+
+        // backwards along the ray the box is left at t = -1
+        let interval = aabb.hit(r, TRange::new(Float::NEG_INFINITY, Float::INFINITY)).unwrap();
+        assert_eq!((interval.start, interval.end), (-1.0, 1.0));
+    }
+
+    #[test]
+    fn hit_interval_missing() {
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let range = TRange::new(0.0, Float::INFINITY);
+        assert!(aabb.hit(ray(Point3::new(2.0, 0.5, 0.5), Vec3::e0()), range).is_none());
+        assert!(aabb.hit(ray(Point3::new(0.5, 2.0, -1.0), Vec3::e2()), range).is_none());
+        assert!(aabb.hit(ray(Point3::new(-1.0, -1.0, 0.5), Vec3::new(1.0, 3.0, 0.0)), range).is_none());
+        // the box is entered only after the end of the range
+        assert!(aabb.hit(ray(Point3::new(0.5, 0.5, -5.0), Vec3::e2()), TRange::new(0.0, 4.0)).is_none());
+    }
+
+    #[test]
+    fn hit_interval_touching() {
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let range = TRange::new(0.0, Float::INFINITY);
+        // through a single corner
+        let interval = aabb.hit(ray(Point3::new(2.0, 0.0, 0.0), Vec3::new(-1.0, 1.0, 0.0)), range);
+        let interval = interval.unwrap();
+        assert_eq!((interval.start, interval.end), (1.0, 1.0));
+        // along a face, parallel to two axes
+        let interval = aabb.hit(ray(Point3::new(0.5, 1.0, -1.0), Vec3::e2()), range).unwrap();
+        assert_eq!((interval.start, interval.end), (1.0, 2.0));
     }
 
     #[test]
@@ -270,7 +636,7 @@ mod aabb_tests {
         assert_eq!(merged.hi(), Point3::new(2.0, 2.0, 2.0));
 
         let merged = AABB::merge(None, None);
-        assert_eq!(merged.is_none(), true);
+        assert!(merged.is_none());
 
         let merged = AABB::merge(Some(aabb1), None).unwrap();
         assert_eq!(merged.lo(), Point3::new(0.0, 0.0, 0.0));
@@ -280,4 +646,1122 @@ mod aabb_tests {
         assert_eq!(merged.lo(), Point3::new(1.0, 1.0, 1.0));
         assert_eq!(merged.hi(), Point3::new(2.0, 2.0, 2.0));
     }
+
+    #[test]
+    fn precompute_takes_reciprocals() {
+        let ray = Ray3 {
+            origin: Point3::default(),
+            direction: Vec3::new(2.0, -0.0, 0.0),
+            time: 0.0,
+        };
+        let slab = ray.precompute();
+        assert_eq!(slab.ray, ray);
+        assert_eq!(slab.inv_direction().x(), 0.5);
+        assert_eq!(slab.inv_direction().y(), Float::NEG_INFINITY);
+        assert_eq!(slab.inv_direction().z(), Float::INFINITY);
+    }
+
+    #[test]
+    fn measures() {
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        assert_eq!(aabb.surface_area(), 6.0);
+        assert_eq!(aabb.volume(), 1.0);
+        assert_eq!(aabb.centroid(), Point3::new(0.5, 0.5, 0.5));
+        assert_eq!(aabb.extent(), Vec3::new(1.0, 1.0, 1.0));
+
+        let aabb = AABB::new(Point3::new(-1.0, 2.0, 0.0), Point3::new(3.0, 4.0, 1.0));
+        assert_eq!(aabb.surface_area(), 2.0 * (8.0 + 2.0 + 4.0));
+        assert_eq!(aabb.centroid(), Point3::new(1.0, 3.0, 0.5));
+        assert_eq!(aabb.extent(), Vec3::new(4.0, 2.0, 1.0));
+        assert_eq!(aabb.longest_axis(), Axis::X);
+    }
+
+    #[test]
+    fn flat_box_has_area() {
+        // a flat box has no volume but keeps the area of its two faces
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 0.0, 3.0));
+        assert_eq!(aabb.volume(), 0.0);
+        assert!(aabb.surface_area() > 0.0);
+        assert_eq!(aabb.surface_area(), 12.0);
+        assert_eq!(aabb.longest_axis(), Axis::Z);
+    }
+
+    #[test]
+    fn create_flat_and_point_boxes() {
+        let aabb = AABB::new(Point3::new(1.0, 2.0, 0.0), Point3::new(0.0, 2.0, 1.0));
+        assert_eq!(aabb.lo(), Point3::new(0.0, 2.0, 0.0));
+        assert_eq!(aabb.hi(), Point3::new(1.0, 2.0, 1.0));
+
+        let p = Point3::new(1.0, 2.0, 3.0);
+        let aabb = AABB::new(p, p);
+        assert_eq!(aabb.lo(), p);
+        assert_eq!(aabb.hi(), p);
+        assert_eq!(aabb.surface_area(), 0.0);
+    }
+
+    #[test]
+    fn pad_grows_thin_dimensions() {
+        let aabb = AABB::new(Point3::new(0.0, 1.0, 0.0), Point3::new(2.0, 1.0, 1e-5)).pad(1e-4);
+        assert_approx_eq!(aabb.lo(), Point3::new(0.0, 1.0 - 5e-5, 5e-6 - 5e-5));
+        assert_approx_eq!(aabb.hi(), Point3::new(2.0, 1.0 + 5e-5, 5e-6 + 5e-5));
+        assert_approx_eq!(aabb.extent(), Vec3::new(2.0, 1e-4, 1e-4));
+
+        let unit = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        assert_approx_eq!(unit.pad(1e-4), unit, 0.0);
+    }
+
+    #[test]
+    fn hit_flat_box() {
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 1.0), Point3::new(1.0, 1.0, 1.0));
+        let range = TRange::new(0.0, Float::INFINITY);
+
+        // straight through the plane of the box
+        let t = aabb.hit(ray(Point3::new(0.5, 0.5, 0.0), Vec3::new(0.0, 0.0, 1.0)), range).unwrap();
+        assert_eq!((t.start, t.end), (1.0, 1.0));
+
+        // at an angle, through and beside it
+        let t = aabb.hit(ray(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 2.0)), range).unwrap();
+        assert_eq!((t.start, t.end), (0.5, 0.5));
+        assert!(!aabb.hit_bool(ray(Point3::new(0.0, 0.0, 0.0), Vec3::new(2.0, 1.0, 1.0)), range));
+
+        // within the plane of the box, across it
+        let t = aabb.hit(ray(Point3::new(-1.0, 0.5, 1.0), Vec3::new(1.0, 0.0, 0.0)), range).unwrap();
+        assert_eq!((t.start, t.end), (1.0, 2.0));
+
+        // parallel to the plane, just off it
+        assert!(!aabb.hit_bool(ray(Point3::new(-1.0, 0.5, 1.0 + DEFAULT_EPSILON), Vec3::new(1.0, 0.0, 0.0)), range));
+
+        // the plane is behind the ray
+        assert!(!aabb.hit_bool(ray(Point3::new(0.5, 0.5, 2.0), Vec3::new(0.0, 0.0, 1.0)), range));
+    }
+
+    #[test]
+    fn contains_point() {
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0));
+        assert!(aabb.contains_point(Point3::new(0.5, 1.0, 1.5)));
+        // on a face, an edge and a corner
+        assert!(aabb.contains_point(Point3::new(1.0, 1.0, 1.5)));
+        assert!(aabb.contains_point(Point3::new(0.0, 2.0, 1.5)));
+        assert!(aabb.contains_point(Point3::new(1.0, 2.0, 3.0)));
+        assert!(!aabb.contains_point(Point3::new(1.0 + DEFAULT_EPSILON, 1.0, 1.5)));
+        assert!(!aabb.contains_point(Point3::new(-1.0, -1.0, -1.0)));
+
+        let flat = AABB::new(Point3::new(0.0, 0.0, 1.0), Point3::new(1.0, 1.0, 1.0));
+        assert!(flat.contains_point(Point3::new(0.5, 0.5, 1.0)));
+        assert!(!flat.contains_point(Point3::new(0.5, 0.5, 1.1)));
+    }
+
+    #[test]
+    fn contains_aabb() {
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 2.0, 2.0));
+        let inner = AABB::new(Point3::new(0.5, 0.5, 0.5), Point3::new(1.5, 1.5, 1.5));
+        let sharing_a_face = AABB::new(Point3::new(1.0, 0.0, 0.0), Point3::new(2.0, 2.0, 2.0));
+        let overlapping = AABB::new(Point3::new(1.0, 1.0, 1.0), Point3::new(3.0, 3.0, 3.0));
+        let disjoint = AABB::new(Point3::new(3.0, 3.0, 3.0), Point3::new(4.0, 4.0, 4.0));
+        assert!(aabb.contains_aabb(inner));
+        assert!(aabb.contains_aabb(aabb));
+        assert!(aabb.contains_aabb(sharing_a_face));
+        assert!(!aabb.contains_aabb(overlapping));
+        assert!(!aabb.contains_aabb(disjoint));
+        assert!(!inner.contains_aabb(aabb));
+    }
+
+    #[test]
+    fn intersects() {
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let inner = AABB::new(Point3::new(0.25, 0.25, 0.25), Point3::new(0.75, 0.75, 0.75));
+        let overlapping = AABB::new(Point3::new(0.5, 0.5, 0.5), Point3::new(2.0, 2.0, 2.0));
+        let touching_face = AABB::new(Point3::new(1.0, 0.0, 0.0), Point3::new(2.0, 1.0, 1.0));
+        let touching_corner = AABB::new(Point3::new(1.0, 1.0, 1.0), Point3::new(2.0, 2.0, 2.0));
+        let disjoint = AABB::new(Point3::new(1.0 + DEFAULT_EPSILON, 0.0, 0.0), Point3::new(2.0, 1.0, 1.0));
+        for other in [inner, overlapping, touching_face, touching_corner] {
+            assert!(aabb.intersects(other), "{other}");
+            assert!(other.intersects(aabb), "{other}");
+        }
+        assert!(!aabb.intersects(disjoint));
+        assert!(!disjoint.intersects(aabb));
+    }
+
+    #[test]
+    fn longest_axis_ties_go_to_the_first_axis() {
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 2.0));
+        assert_eq!(aabb.longest_axis(), Axis::Y);
+    }
+
+    fn slab_tests_agree(aabb: AABB, ray: Ray3, t_range: TRange<Float>) {
+        let ray = ray.precompute();
+        let scalar = aabb.hit_slab_scalar(&ray, t_range).map(|t| (t.start, t.end));
+        let simd = aabb.hit_slab_simd(&ray, t_range).map(|t| (t.start, t.end));
+        assert_eq!(scalar, simd, "{aabb} {} {t_range:?}", ray.ray);
+    }
+
+    #[test]
+    fn slab_tests_agree_on_faces_edges_and_corners() {
+        // rays from on, inside and outside the faces of solid, flat and
+        // point boxes, along and across the axes
+        let aabbs = [
+            AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)),
+            AABB::new(Point3::new(0.0, 0.0, 1.0), Point3::new(1.0, 1.0, 1.0)),
+            AABB::new(Point3::new(1.0, 1.0, 1.0), Point3::new(1.0, 1.0, 1.0)),
+        ];
+        let coordinates = [-1.0, 0.0, 0.5, 1.0, 2.0];
+        let steps = [-1.0, 0.0, 1.0];
+        let ranges = [TRange::new(0.0, 1.0), TRange::new(0.1, 0.9), TRange::new(-2.0, 2.0), TRange::new(1.0, 2.0)];
+        for aabb in aabbs {
+            for x in coordinates {
+                for y in coordinates {
+                    for z in coordinates {
+                        for dx in steps {
+                            for dy in steps {
+                                for dz in steps {
+                                    let origin = Point3::new(x, y, z);
+                                    let direction = Vec3::new(dx, dy, dz);
+                                    for range in ranges {
+                                        slab_tests_agree(aabb, ray(origin, direction), range);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn slab_tests_agree_on_random_rays() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut point = || Point3::new(rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0));
+        let cases: Vec<_> = (0..10_000).map(|_| (point(), point(), point(), point())).collect();
+        for (lo, hi, origin, towards) in cases {
+            let aabb = AABB::new(lo, hi);
+            let mut direction = towards - Point3::default();
+            // often parallel to a pair of faces
+            if rng.gen_bool(0.25) {
+                let axis: usize = rng.gen_range(0..3);
+                let zero = |i: usize, x: Float| if i == axis { 0.0 } else { x };
+                direction = Vec3::new(zero(0, direction.x()), zero(1, direction.y()), zero(2, direction.z()));
+            }
+            let start = rng.gen_range(-1.0..1.0);
+            slab_tests_agree(aabb, ray(origin, direction), TRange::new(start, start + rng.gen_range(0.1..10.0)));
+        }
+    }
+
+    #[test]
+    fn four_boxes_agree_with_each_alone() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut point = || Point3::new(rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0));
+        for i in 0..2_000 {
+            let boxes: [Option<AABB>; 4] = std::array::from_fn(|slot| (slot < 1 + i % 4).then(|| AABB::new(point(), point())));
+            let (origin, mut direction) = (point(), point() - Point3::default());
+            if i % 3 == 0 {
+                direction = Vec3::new(direction.x(), 0.0, direction.z());
+            }
+            let ray = ray(origin, direction).precompute();
+            let range = TRange::new(0.0, 20.0);
+
+            let aabb4 = AABB4::new(boxes);
+            let hits = aabb4.hit_slab(&ray, range);
+            for slot in 0..4 {
+                assert_eq!(aabb4.get(slot).map(|b| (b.lo(), b.hi())), boxes[slot].map(|b| (b.lo(), b.hi())));
+                let alone = boxes[slot].and_then(|aabb| aabb.hit_slab_scalar(&ray, range));
+                assert_eq!(hits[slot].map(|t| (t.start, t.end)), alone.map(|t| (t.start, t.end)), "{i} {slot}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod transform_tests {
+    use geometry3d::*;
+    use geometry3d::consts::FRAC_PI_2;
+
+    fn compound() -> Transform {
+        Transform::translate(Vec3::new(1.0, -2.0, 3.0))
+            * Transform::rotate(Vec3::new(1.0, 1.0, 0.5), 0.7)
+            * Transform::scale(Vec3::new(2.0, 0.5, -3.0))
+    }
+
+    #[test]
+    fn rotate_about_axis() {
+        let t = Transform::rotate(Axis::Z, FRAC_PI_2);
+        assert_approx_eq!(t.transform_vector(Vec3::e0()), Vec3::e1());
+        let t = Transform::rotate(Axis::Y, FRAC_PI_2);
+        assert_approx_eq!(t.transform_vector(Vec3::e2()), Vec3::e0());
+    }
+
+    #[test]
+    fn composition_order() {
+        let t = Transform::translate(Vec3::e0()) * Transform::rotate(Axis::Z, FRAC_PI_2);
+        let p = t.transform_point(Point3::new(1.0, 0.0, 0.0));
+        assert_approx_eq!(p, Point3::new(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn inverse_round_trip() {
+        let t = compound();
+        let p = Point3::new(0.3, -4.0, 2.5);
+        let v = Vec3::new(-1.0, 0.2, 7.0);
+        assert_approx_eq!(t.inverse().transform_point(t.transform_point(p)), p);
+        assert_approx_eq!(t.inverse().transform_vector(t.transform_vector(v)), v);
+
+        let identity = (t * t.inverse()).matrix();
+        let expected = Transform::identity().matrix();
+        for (row, expected) in identity.iter().zip(expected) {
+            for (value, expected) in row.iter().zip(expected) {
+                assert_approx_eq!(*value, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn from_matrix_inverse() {
+        let t = compound();
+        let from_matrix = Transform::from_matrix(t.matrix()).unwrap();
+        let p = Point3::new(1.0, 2.0, 3.0);
+        assert_approx_eq!(from_matrix.inverse().transform_point(p), t.inverse().transform_point(p));
+    }
+
+    #[test]
+    fn from_matrix_rejects_singular_and_projective() {
+        let mut singular = Transform::identity().matrix();
+        singular[1][1] = 0.0;
+        assert!(Transform::from_matrix(singular).is_none());
+
+        let mut projective = Transform::identity().matrix();
+        projective[3][0] = 1.0;
+        assert!(Transform::from_matrix(projective).is_none());
+    }
+
+    #[test]
+    fn translation_ignores_vectors() {
+        let t = Transform::translate(Vec3::new(1.0, 2.0, 3.0));
+        let v = Vec3::new(4.0, 5.0, 6.0);
+        assert_eq!(t.transform_vector(v), v);
+        assert_eq!(t.transform_normal(v), v);
+    }
+
+    #[test]
+    fn normal_under_non_uniform_scale() {
+        // the plane x + y = 1 is scaled into 0.5x + y = 1
+        let t = Transform::scale(Vec3::new(2.0, 1.0, 1.0));
+        let tangent = t.transform_vector(Vec3::new(1.0, -1.0, 0.0));
+        let normal = t.transform_normal(Vec3::new(1.0, 1.0, 0.0));
+        assert_approx_eq!(tangent.dot(normal), 0.0);
+        assert_approx_eq!(normal.unit().unwrap(), Vec3::new(0.5, 1.0, 0.0).unit().unwrap());
+
+        // transforming the tangent's normal naively is not perpendicular
+        assert!(!tangent.dot(t.transform_vector(Vec3::new(1.0, 1.0, 0.0))).approx_eq(0.0, DEFAULT_EPSILON));
+    }
+
+    #[test]
+    fn ray_keeps_parameter() {
+        let t = compound();
+        let ray = Ray3 {
+            origin: Point3::new(1.0, 1.0, 1.0),
+            direction: Vec3::new(0.0, 2.0, -1.0),
+            time: 0.5,
+        };
+        let transformed = t.transform_ray(ray);
+        assert_eq!(transformed.time, 0.5);
+        assert_approx_eq!(transformed.at(1.5), t.transform_point(ray.at(1.5)));
+    }
+
+    #[test]
+    fn aabb_is_conservative() {
+        let aabb = AABB::new(Point3::new(-1.0, 0.0, 2.0), Point3::new(1.0, 3.0, 2.5));
+        let t = compound();
+        let bound = t.transform_aabb(aabb);
+        let inflate = Vec3::new(DEFAULT_EPSILON, DEFAULT_EPSILON, DEFAULT_EPSILON);
+        for i in 0..=4 {
+            for j in 0..=4 {
+                for k in 0..=4 {
+                    let f = |n: i32| n as Float / 4.0;
+                    let p = aabb.lo() + Vec3::new(f(i) * 2.0, f(j) * 3.0, f(k) * 0.5);
+                    let p = t.transform_point(p);
+                    assert_eq!(p.clamp(bound.lo() - inflate, bound.hi() + inflate), p);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn aabb_under_rotation() {
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let bound = Transform::rotate(Axis::Z, FRAC_PI_2).transform_aabb(aabb);
+        assert_approx_eq!(bound.lo(), Point3::new(-1.0, 0.0, 0.0));
+        assert_approx_eq!(bound.hi(), Point3::new(0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn deserialize_steps() {
+        let json = r#"[
+            {"Scale": [2.0, 2.0, 2.0]},
+            {"Rotate": {"axis": [0.0, 0.0, 1.0], "degrees": 90.0}},
+            {"Translate": [0.0, 0.0, 1.0]}
+        ]"#;
+        let t: Transform = serde_json::from_str(json).unwrap();
+        let p = t.transform_point(Point3::new(1.0, 0.0, 0.0));
+        assert_approx_eq!(p, Point3::new(0.0, 2.0, 1.0));
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let t = compound();
+        let json = serde_json::to_string(&t).unwrap();
+        let back: Transform = serde_json::from_str(&json).unwrap();
+        let p = Point3::new(1.0, 2.0, 3.0);
+        assert_approx_eq!(back.transform_point(p), t.transform_point(p));
+        assert_approx_eq!(back.inverse().transform_point(p), t.inverse().transform_point(p));
+    }
+
+    #[test]
+    fn deserialize_rejects_bad_steps() {
+        assert!(serde_json::from_str::<Transform>(r#"[{"Scale": [1.0, 0.0, 1.0]}]"#).is_err());
+        assert!(serde_json::from_str::<Transform>(
+            r#"{"matrix": [[0,0,0,0],[0,1,0,0],[0,0,1,0],[0,0,0,1]]}"#
+        )
+        .is_err());
+    }
+}
+
+#[cfg(test)]
+mod approx_tests {
+    use geometry3d::*;
+
+    #[test]
+    fn absolute_tolerance_near_zero() {
+        // relative to values this small, any difference is huge
+        assert!(1e-12.approx_eq(-1e-12, 1e-9));
+        assert!(!1e-12.approx_eq_within(-1e-12, Tolerance { absolute: 0.0, relative: 1e-9 }));
+        assert!(!1e-6.approx_eq(0.0, 1e-9));
+        assert_approx_eq!(Vec3::new(1e-10, 0.0, -1e-10), Vec3::default());
+    }
+
+    #[test]
+    #[cfg(not(feature = "f32"))]
+    fn relative_tolerance_for_large_magnitudes() {
+        // adjacent doubles this large are far more than an absolute epsilon apart
+        let big = 1e12;
+        let next = big + 1e-3;
+        assert!(big.approx_eq(next, 1e-9));
+        assert!(!big.approx_eq_within(next, Tolerance { absolute: 1e-9, relative: 0.0 }));
+        assert!(!big.approx_eq(2.0 * big, 1e-9));
+        assert_approx_eq!(Point3::new(big, -big, 1.0), Point3::new(next, -next, 1.0));
+    }
+
+    #[test]
+    fn nan_and_infinity() {
+        assert!(!Float::NAN.approx_eq(Float::NAN, 1.0));
+        assert!(Float::INFINITY.approx_eq(Float::INFINITY, 0.0));
+        assert!(!Float::INFINITY.approx_eq(Float::NEG_INFINITY, 1.0));
+        assert!(!Float::INFINITY.approx_eq(Float::MAX, 1e-9));
+    }
+
+    #[test]
+    fn compares_every_component() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        assert!(v.approx_eq(v + Vec3::new(1e-12, -1e-12, 0.0), 1e-9));
+        assert!(!v.approx_eq(v + Vec3::new(0.0, 0.0, 1e-3), 1e-9));
+
+        let ray = Ray3 {
+            origin: Point3::new(1.0, 2.0, 3.0),
+            direction: v,
+            time: 0.5,
+        };
+        assert_approx_eq!(ray, Ray3 { time: 0.5 + 1e-12, ..ray });
+        assert!(!ray.approx_eq(Ray3 { time: 0.6, ..ray }, 1e-9));
+
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let shifted = AABB::new(Point3::new(1e-12, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0 + 1e-12));
+        assert_approx_eq!(aabb, shifted);
+        let grown = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.1));
+        assert!(!aabb.approx_eq(grown, 1e-9));
+    }
+
+    #[test]
+    #[should_panic(expected = "left ≈ right")]
+    fn assertion_fails_outside_tolerance() {
+        assert_approx_eq!(Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.1, 0.0, 0.0), abs = 0.05, rel = 0.05);
+    }
+}
+
+#[cfg(test)]
+mod format_tests {
+    use geometry3d::*;
+
+    #[test]
+    fn display_vectors_and_points() {
+        assert_eq!(Vec3::new(1.0, 2.0, 3.0).to_string(), "(1.000, 2.000, 3.000)");
+        assert_eq!(format!("{:.2}", Point3::new(0.5, -1.25, 10.0)), "(0.50, -1.25, 10.00)");
+        assert_eq!(format!("{:.0}", Vec3::new(1.4, 2.6, -0.4)), "(1, 3, -0)");
+        assert_eq!(format!("{:6.1}", Vec3::new(1.0, -2.0, 30.0)), "(   1.0,   -2.0,   30.0)");
+    }
+
+    #[test]
+    fn display_negative_zero_and_infinity() {
+        let v = Vec3::new(-0.0, Float::INFINITY, Float::NEG_INFINITY);
+        assert_eq!(v.to_string(), "(-0.000, inf, -inf)");
+        assert_eq!(Point3::new(Float::NAN, 0.0, 0.0).to_string(), "(NaN, 0.000, 0.000)");
+    }
+
+    #[test]
+    fn display_rays_boxes_and_ranges() {
+        let ray = Ray3 {
+            origin: Point3::new(1.0, 2.0, 3.0),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+            time: 0.5,
+        };
+        assert_eq!(ray.to_string(), "Ray{o=(1.000, 2.000, 3.000), d=(0.000, -1.000, 0.000), t=0.500}");
+
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0));
+        assert_eq!(format!("{aabb:.1}"), "AABB{lo=(0.0, 0.0, 0.0), hi=(1.0, 2.0, 3.0)}");
+
+        assert_eq!(TRange::new(0.001, Float::INFINITY).to_string(), "[0.001, inf]");
+    }
+
+    #[test]
+    fn debug_is_unchanged() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!(format!("{v:?}"), "Vec3(NTuple([1.0, 2.0, 3.0]))");
+        let ray = Ray3 {
+            origin: Point3::default(),
+            direction: v,
+            time: 0.0,
+        };
+        assert_eq!(
+            format!("{ray:?}"),
+            "Ray3 { origin: Point3(NTuple([0.0, 0.0, 0.0])), direction: Vec3(NTuple([1.0, 2.0, 3.0])), time: 0.0 }"
+        );
+    }
+
+    #[test]
+    fn alternate_debug_is_compact() {
+        assert_eq!(format!("{:#?}", Point3::new(-0.0, 1.5, Float::INFINITY)), "Point3(-0.0, 1.5, inf)");
+        let ray = Ray3 {
+            origin: Point3::default(),
+            direction: Vec3::e0(),
+            time: 0.25,
+        };
+        assert_eq!(
+            format!("{ray:#?}"),
+            "Ray3 { origin: Point3(0.0, 0.0, 0.0), direction: Vec3(1.0, 0.0, 0.0), time: 0.25 }"
+        );
+        let aabb = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        assert_eq!(format!("{aabb:#?}"), "AABB { lo: Point3(0.0, 0.0, 0.0), hi: Point3(1.0, 1.0, 1.0) }");
+    }
+}
+
+#[cfg(test)]
+mod axis_tests {
+    use geometry3d::*;
+
+    #[test]
+    fn index_by_axis() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        let p = Point3::new(4.0, 5.0, 6.0);
+        let components: Vec<_> = Axis::ALL.iter().map(|&axis| (v[axis], p[axis])).collect();
+        assert_eq!(components, [(1.0, 4.0), (2.0, 5.0), (3.0, 6.0)]);
+    }
+
+    #[test]
+    fn index_by_usize() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        let p = Point3::new(4.0, 5.0, 6.0);
+        assert_eq!((v[0], v[1], v[2]), (v.x(), v.y(), v.z()));
+        assert_eq!((p[0], p[1], p[2]), (p.x(), p.y(), p.z()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 3 is out of range")]
+    fn index_out_of_range() {
+        let _ = Point3::default()[3];
+    }
+
+    #[test]
+    fn others() {
+        assert_eq!(Axis::X.others(), [Axis::Y, Axis::Z]);
+        assert_eq!(Axis::Y.others(), [Axis::X, Axis::Z]);
+        assert_eq!(Axis::Z.others(), [Axis::X, Axis::Y]);
+        for axis in Axis::ALL {
+            assert!(!axis.others().contains(&axis));
+        }
+    }
+
+    #[test]
+    fn usize_round_trip() {
+        for axis in Axis::ALL {
+            assert_eq!(Axis::try_from(usize::from(axis)), Ok(axis));
+        }
+    }
+
+    #[test]
+    fn display_and_from_str() {
+        for axis in Axis::ALL {
+            assert_eq!(axis.to_string().parse(), Ok(axis));
+            assert_eq!(axis.to_string().to_lowercase().parse(), Ok(axis));
+            assert_eq!(serde_json::to_string(&axis).unwrap(), format!("\"{axis}\""));
+        }
+        assert_eq!(format!("{:>3}", Axis::Y), "  Y");
+        assert_eq!(
+            "W".parse::<Axis>(),
+            Err(String::from("There is no axis named \"W\", the axes are X, Y and Z."))
+        );
+    }
+
+    #[test]
+    fn try_from_usize() {
+        for (i, axis) in Axis::ALL.into_iter().enumerate() {
+            assert_eq!(Axis::try_from(i), Ok(axis));
+            assert_eq!(axis as usize, i);
+        }
+        assert_eq!(
+            Axis::try_from(3),
+            Err(String::from("There is no axis 3, the axes are numbered 0 to 2."))
+        );
+    }
+}
+
+#[cfg(test)]
+mod trange_tests {
+    use geometry3d::*;
+
+    #[test]
+    fn contains() {
+        let range = TRange::new(1.0, 2.0);
+        let cases = [(0.5, false, false), (1.0, true, false), (1.5, true, true), (2.0, true, false)];
+        for (t, inclusive, exclusive) in cases {
+            assert_eq!(range.contains_inclusive(&t), inclusive, "{t}");
+            assert_eq!(range.contains(&t), inclusive, "{t}");
+            assert_eq!(range.contains_exclusive(&t), exclusive, "{t}");
+        }
+        assert!(!range.contains_inclusive(&Float::NAN));
+        assert!(!range.contains_exclusive(&Float::NAN));
+    }
+
+    #[test]
+    fn is_empty() {
+        assert!(!TRange::new(1.0, 2.0).is_empty());
+        assert!(!TRange::new(1.0, 1.0).is_empty());
+        assert!(TRange::new(2.0, 1.0).is_empty());
+        assert!(TRange::new(Float::NAN, 1.0).is_empty());
+    }
+
+    #[test]
+    fn measures() {
+        let range = TRange::new(-1.0, 3.0);
+        assert_eq!(range.length(), 4.0);
+        assert_eq!(range.midpoint(), 1.0);
+        assert_eq!(range.clamp(-2.0), -1.0);
+        assert_eq!(range.clamp(0.5), 0.5);
+        assert_eq!(range.clamp(4.0), 3.0);
+        assert_eq!(TRange::new(0.0, Float::INFINITY).length(), Float::INFINITY);
+    }
+
+    #[test]
+    fn intersect_and_union() {
+        let a = TRange::new(0.0, 2.0);
+        let b = TRange::new(1.0, 3.0);
+        let c = TRange::new(2.0, 4.0);
+        let d = TRange::new(5.0, 6.0);
+
+        let ab = a.intersect(b).unwrap();
+        assert_eq!((ab.start, ab.end), (1.0, 2.0));
+        // ranges meeting at one value intersect there
+        let ac = a.intersect(c).unwrap();
+        assert_eq!((ac.start, ac.end), (2.0, 2.0));
+        assert!(a.intersect(d).is_none());
+
+        let ad = a.union(d);
+        assert_eq!((ad.start, ad.end), (0.0, 6.0));
+        assert_eq!(format!("{:.1}", b.union(a)), "[0.0, 3.0]");
+    }
+
+    #[test]
+    fn expand() {
+        let range = TRange::new(1.0, 2.0).expand(0.5);
+        assert_eq!((range.start, range.end), (0.5, 2.5));
+    }
+
+    #[test]
+    fn from_ranges() {
+        let range: TRange<Float> = (0.001..Float::INFINITY).into();
+        assert_eq!((range.start, range.end), (0.001, Float::INFINITY));
+        let range = TRange::from(-1.0..=1.0);
+        assert_eq!((range.start, range.end), (-1.0, 1.0));
+        let range = TRange::from(2..5);
+        assert!(range.contains_exclusive(&3) && !range.contains_exclusive(&5));
+    }
+}
+
+#[cfg(test)]
+mod permutation_tests {
+    use geometry3d::*;
+
+    const AXES: [[Axis; 3]; 6] = [
+        [Axis::X, Axis::Y, Axis::Z],
+        [Axis::X, Axis::Z, Axis::Y],
+        [Axis::Y, Axis::X, Axis::Z],
+        [Axis::Y, Axis::Z, Axis::X],
+        [Axis::Z, Axis::X, Axis::Y],
+        [Axis::Z, Axis::Y, Axis::X],
+    ];
+
+    fn permutations() -> impl Iterator<Item = Permutation> {
+        AXES.into_iter().map(|axes| Permutation::try_new(axes).unwrap())
+    }
+
+    #[test]
+    fn try_new_rejects_repeated_axes() {
+        for axes in [
+            [Axis::X, Axis::X, Axis::X],
+            [Axis::X, Axis::X, Axis::Z],
+            [Axis::Y, Axis::Z, Axis::Y],
+            [Axis::Z, Axis::Y, Axis::Y],
+        ] {
+            assert_eq!(
+                Permutation::try_new(axes),
+                Err(format!("{axes:?} is not a permutation of the axes, each axis must appear once."))
+            );
+        }
+        assert_eq!(permutations().count(), 6);
+    }
+
+    #[test]
+    fn permute() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!(v.permute(Permutation::YZ), Vec3::new(2.0, 3.0, 1.0));
+        assert_eq!(v.unpermute(Permutation::YZ), Vec3::new(3.0, 1.0, 2.0));
+        for p in permutations() {
+            let permuted = v.permute(p);
+            for (i, axis) in p.axes().into_iter().enumerate() {
+                assert_eq!(permuted[i], v[axis], "{p:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn unpermute_inverts_permute() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        let point = Point3::new(4.0, 5.0, 6.0);
+        for p in permutations() {
+            assert_eq!(v.permute(p).unpermute(p), v, "{p:?}");
+            assert_eq!(v.unpermute(p).permute(p), v, "{p:?}");
+            assert_eq!(point.permute(p).unpermute(p), point, "{p:?}");
+            assert_eq!(p.inverse().inverse(), p);
+        }
+    }
+
+    #[test]
+    fn named() {
+        assert_eq!(Permutation::XY.axes(), [Axis::X, Axis::Y, Axis::Z]);
+        assert_eq!(Permutation::XZ.axes(), [Axis::X, Axis::Z, Axis::Y]);
+        assert_eq!(Permutation::YZ.axes(), [Axis::Y, Axis::Z, Axis::X]);
+        assert_eq!(Permutation::default(), Permutation::XY);
+        assert_eq!("XZ".parse(), Ok(Permutation::XZ));
+        assert!("ZX".parse::<Permutation>().is_err());
+        let named: Vec<_> = permutations().filter_map(Permutation::name).collect();
+        assert_eq!(named, ["XY", "XZ", "YZ"]);
+    }
+
+    #[test]
+    fn serde() {
+        let p: Permutation = serde_json::from_str(r#""YZ""#).unwrap();
+        assert_eq!(p, Permutation::YZ);
+        let p: Permutation = serde_json::from_str(r#"["X", "Z", "Y"]"#).unwrap();
+        assert_eq!(p, Permutation::XZ);
+        let p: Permutation = serde_json::from_str(r#"["Z", "X", "Y"]"#).unwrap();
+        assert_eq!(p.axes(), [Axis::Z, Axis::X, Axis::Y]);
+
+        assert_eq!(serde_json::to_string(&Permutation::XZ).unwrap(), r#""XZ""#);
+        assert_eq!(serde_json::to_string(&p).unwrap(), r#"["Z","X","Y"]"#);
+
+        assert!(serde_json::from_str::<Permutation>(r#"["X", "X", "X"]"#).is_err());
+        assert!(serde_json::from_str::<Permutation>(r#""XX""#).is_err());
+    }
+}
+
+#[cfg(test)]
+mod intersection_tests {
+    use geometry3d::*;
+
+    const FORWARDS: TRange<Float> = TRange {
+        start: 0.0,
+        end: Float::INFINITY,
+    };
+
+    fn ray(origin: Point3, direction: Vec3) -> Ray3 {
+        Ray3 {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    #[test]
+    fn ray_plane() {
+        let plane_point = Point3::new(0.0, 0.0, 2.0);
+        let normal = Vec3::new(0.0, 0.0, -5.0);
+        let t = intersect_ray_plane(ray(Point3::default(), Vec3::new(1.0, 0.0, 1.0)), plane_point, normal, FORWARDS);
+        assert_eq!(t, Some(2.0));
+
+        // from the other side, and with the plane behind the ray
+        let t = intersect_ray_plane(ray(Point3::new(0.0, 0.0, 4.0), -Vec3::e2()), plane_point, normal, FORWARDS);
+        assert_eq!(t, Some(2.0));
+        let t = intersect_ray_plane(ray(Point3::new(0.0, 0.0, 4.0), Vec3::e2()), plane_point, normal, FORWARDS);
+        assert_eq!(t, None);
+    }
+
+    #[test]
+    fn ray_plane_range_is_exclusive() {
+        let r = ray(Point3::default(), Vec3::e2());
+        let plane_point = Point3::new(0.0, 0.0, 2.0);
+        assert_eq!(intersect_ray_plane(r, plane_point, Vec3::e2(), TRange::new(0.0, 3.0)), Some(2.0));
+        assert_eq!(intersect_ray_plane(r, plane_point, Vec3::e2(), TRange::new(0.0, 2.0)), None);
+        assert_eq!(intersect_ray_plane(r, plane_point, Vec3::e2(), TRange::new(2.0, 3.0)), None);
+        // a ray starting on the plane does not hit it again
+        assert_eq!(intersect_ray_plane(r, Point3::default(), Vec3::e2(), FORWARDS), None);
+    }
+
+    #[test]
+    fn ray_plane_parallel() {
+        let plane_point = Point3::new(0.0, 0.0, 2.0);
+        let off_plane = ray(Point3::default(), Vec3::e0());
+        assert_eq!(intersect_ray_plane(off_plane, plane_point, Vec3::e2(), FORWARDS), None);
+        // a ray in the plane does not cross it either
+        let in_plane = ray(plane_point, Vec3::new(1.0, 1.0, 0.0));
+        let range = TRange::new(Float::NEG_INFINITY, Float::INFINITY);
+        assert_eq!(intersect_ray_plane(in_plane, plane_point, Vec3::e2(), range), None);
+        // nor does one that is parallel to within rounding
+        let nearly = ray(Point3::default(), Vec3::new(1.0, 0.0, 1e-12));
+        assert_eq!(intersect_ray_plane(nearly, plane_point, Vec3::e2(), FORWARDS), None);
+        assert_eq!(intersect_ray_plane(off_plane, plane_point, Vec3::default(), FORWARDS), None);
+    }
+
+    #[test]
+    fn ray_plane_grazing() {
+        // about a thousandth of a degree off parallel still crosses
+        let r = ray(Point3::default(), Vec3::new(1.0, 0.0, 2e-5));
+        let t = intersect_ray_plane(r, Point3::new(0.0, 0.0, 2.0), Vec3::e2(), FORWARDS).unwrap();
+        assert_approx_eq!(t, 1e5);
+        assert_approx_eq!(r.at(t).z(), 2.0);
+    }
+
+    #[test]
+    fn ray_slab() {
+        let r = ray(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 2.0, 1.0));
+        let t = intersect_ray_slab(r, Axis::Y, 2.0, 5.0).unwrap();
+        assert_eq!((t.start, t.end), (0.5, 2.0));
+        // the range is not limited to t >= 0
+        let t = intersect_ray_slab(r, Axis::Y, -3.0, -1.0).unwrap();
+        assert_eq!((t.start, t.end), (-2.0, -1.0));
+        // a flat slab is crossed at a single t
+        let t = intersect_ray_slab(r, Axis::Z, 3.0, 3.0).unwrap();
+        assert_eq!((t.start, t.end), (3.0, 3.0));
+    }
+
+    #[test]
+    fn ray_slab_parallel() {
+        let r = ray(Point3::new(0.0, 1.0, 0.0), Vec3::e0());
+        let t = intersect_ray_slab(r, Axis::Y, 0.0, 2.0).unwrap();
+        assert_eq!((t.start, t.end), (Float::NEG_INFINITY, Float::INFINITY));
+        assert!(intersect_ray_slab(r, Axis::Y, 1.0, 1.0).is_some());
+        assert!(intersect_ray_slab(r, Axis::Y, 1.5, 2.0).is_none());
+    }
+}
+
+#[cfg(test)]
+mod triangle_tests {
+    use geometry3d::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // how closely the coordinates of a hit on a random triangle are recovered
+    #[cfg(not(feature = "f32"))]
+    const RECOVERED: Float = 1e-9;
+    #[cfg(feature = "f32")]
+    const RECOVERED: Float = 1e-3;
+
+    fn ray(origin: Point3, direction: Vec3) -> Ray3 {
+        Ray3 {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    fn random_point(rng: &mut StdRng) -> Point3 {
+        Point3::new(rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0))
+    }
+
+    fn unit_triangle() -> (Point3, Point3, Point3) {
+        (Point3::default(), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0))
+    }
+
+    #[test]
+    fn recovers_barycentric_coordinates() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut tested = 0;
+        while tested < 1000 {
+            let (v0, v1, v2) = (random_point(&mut rng), random_point(&mut rng), random_point(&mut rng));
+            let (e1, e2) = (v1 - v0, v2 - v0);
+            let normal = e1.cross(e2);
+            // leave out triangles too thin for the coordinates to be
+            // recovered to within RECOVERED
+            let longest = e1.quadrance().max(e2.quadrance()).max((v2 - v1).quadrance());
+            if normal.length() < 0.01 * longest {
+                continue;
+            }
+
+            let u = rng.gen_range(0.0..1.0);
+            let v = rng.gen_range(0.0..1.0 - u);
+            let target = v0 + u * e1 + v * e2;
+            let origin = random_point(&mut rng);
+            let direction = target - origin;
+            if direction.dot(normal).abs() < 0.1 * direction.length() * normal.length() {
+                continue;
+            }
+
+            let hit = intersect_ray_triangle(ray(origin, direction), v0, v1, v2).unwrap();
+            assert_approx_eq!(hit.t, 1.0, RECOVERED);
+            assert_approx_eq!(hit.u, u, RECOVERED);
+            assert_approx_eq!(hit.v, v, RECOVERED);
+            assert_approx_eq!(hit.normal, normal.unit().unwrap());
+            tested += 1;
+        }
+    }
+
+    #[test]
+    fn rays_in_the_plane_miss() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let (v0, v1, v2) = (random_point(&mut rng), random_point(&mut rng), random_point(&mut rng));
+            let (e1, e2) = (v1 - v0, v2 - v0);
+            let origin = v0 + rng.gen_range(-2.0..2.0) * e1 + rng.gen_range(-2.0..2.0) * e2;
+            let direction = rng.gen_range(-1.0..1.0) * e1 + rng.gen_range(-1.0..1.0) * e2;
+            assert_eq!(intersect_ray_triangle(ray(origin, direction), v0, v1, v2), None);
+        }
+    }
+
+    #[test]
+    fn misses_outside_and_behind() {
+        let (v0, v1, v2) = unit_triangle();
+        let down = -Vec3::e2();
+        assert!(intersect_ray_triangle(ray(Point3::new(0.6, 0.6, 1.0), down), v0, v1, v2).is_none());
+        assert!(intersect_ray_triangle(ray(Point3::new(-0.1, 0.5, 1.0), down), v0, v1, v2).is_none());
+        assert!(intersect_ray_triangle(ray(Point3::new(0.2, 0.2, 1.0), -down), v0, v1, v2).is_none());
+    }
+
+    #[test]
+    fn edges_and_corners_hit() {
+        let (v0, v1, v2) = unit_triangle();
+        let down = -Vec3::e2();
+        for (x, y) in [(0.5, 0.0), (0.0, 0.5), (0.5, 0.5), (0.0, 0.0), (1.0, 0.0), (0.0, 1.0)] {
+            let hit = intersect_ray_triangle(ray(Point3::new(x, y, 1.0), down), v0, v1, v2);
+            assert_eq!(hit.map(|hit| (hit.u, hit.v)), Some((x, y)));
+        }
+    }
+
+    #[test]
+    fn backface_culling() {
+        let (v0, v1, v2) = unit_triangle();
+        let culling = TriangleTest {
+            cull_backfaces: true,
+            ..TriangleTest::default()
+        };
+        let from_front = ray(Point3::new(0.2, 0.2, 1.0), -Vec3::e2());
+        let from_back = ray(Point3::new(0.2, 0.2, -1.0), Vec3::e2());
+        assert!(culling.intersect(from_front, v0, v1, v2).is_some());
+        assert!(culling.intersect(from_back, v0, v1, v2).is_none());
+        // the normal is the same from either side without culling
+        let back = intersect_ray_triangle(from_back, v0, v1, v2).unwrap();
+        assert_eq!(back.normal, Vec3::e2());
+    }
+
+    #[test]
+    fn thin_slivers_hit() {
+        let (v0, v1, v2) = (Point3::default(), Point3::new(1.0, 0.0, 0.0), Point3::new(0.5, 1e-8, 0.0));
+        let hit = intersect_ray_triangle(ray(Point3::new(0.5, 0.25e-8, 1.0), -Vec3::e2()), v0, v1, v2).unwrap();
+        assert_approx_eq!(hit.t, 1.0);
+        assert_approx_eq!(hit.v, 0.25);
+        assert!(intersect_ray_triangle(ray(Point3::new(0.5, 2e-8, 1.0), -Vec3::e2()), v0, v1, v2).is_none());
+    }
+
+    #[test]
+    fn degenerate_triangles_miss() {
+        let (v0, v1) = (Point3::default(), Point3::new(1.0, 0.0, 0.0));
+        let r = ray(Point3::new(0.5, 0.0, 1.0), -Vec3::e2());
+        assert!(intersect_ray_triangle(r, v0, v1, Point3::new(2.0, 0.0, 0.0)).is_none());
+        assert!(intersect_ray_triangle(r, v0, v0, v0).is_none());
+    }
+
+    #[test]
+    fn barycentric_of_vertices() {
+        let (a, b, c) = (Point3::new(1.0, 2.0, 3.0), Point3::new(-2.0, 0.5, 1.0), Point3::new(4.0, -1.0, 0.0));
+        assert_approx_eq!(barycentric(a, a, b, c).0, 1.0);
+        let weights = [barycentric(a, a, b, c), barycentric(b, a, b, c), barycentric(c, a, b, c)];
+        let expected = [(1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0)];
+        for (bary, expected) in weights.into_iter().zip(expected) {
+            assert_approx_eq!(Vec3::new(bary.0, bary.1, bary.2), Vec3::new(expected.0, expected.1, expected.2));
+        }
+    }
+
+    #[test]
+    fn interpolating_positions_gives_the_point() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..1000 {
+            let (a, b, c) = (random_point(&mut rng), random_point(&mut rng), random_point(&mut rng));
+            if (b - a).cross(c - a).length() < 1.0 {
+                continue;
+            }
+            let (u, v) = (rng.gen_range(-0.5..1.5), rng.gen_range(-0.5..1.5));
+            let p = a + u * (b - a) + v * (c - a);
+            let bary = barycentric(p, a, b, c);
+            assert_approx_eq!(bary.0 + bary.1 + bary.2, 1.0);
+            let q = interpolate(bary, Vec3::from(a), Vec3::from(b), Vec3::from(c));
+            assert_approx_eq!(q, Vec3::from(p), 10.0 * RECOVERED);
+            assert_eq!(is_inside(bary, 1e-9), u >= 0.0 && v >= 0.0 && u + v <= 1.0, "{u} {v}");
+        }
+    }
+
+    #[test]
+    fn hit_barycentric_interpolates_the_hit_point() {
+        let (v0, v1, v2) = (Point3::new(0.0, 0.0, 1.0), Point3::new(2.0, 0.0, 0.0), Point3::new(0.0, 3.0, 0.0));
+        let r = ray(Point3::new(0.3, 0.4, 5.0), Vec3::new(0.1, 0.2, -1.0));
+        let hit = intersect_ray_triangle(r, v0, v1, v2).unwrap();
+        let p = interpolate(hit.barycentric(), Vec3::from(v0), Vec3::from(v1), Vec3::from(v2));
+        assert_approx_eq!(Point3::from(p), r.at(hit.t));
+        let bary = barycentric(r.at(hit.t), v0, v1, v2);
+        assert_approx_eq!(Vec3::new(bary.0, bary.1, bary.2), Vec3::new(1.0 - hit.u - hit.v, hit.u, hit.v));
+    }
+
+    #[test]
+    fn interpolate_scalars_and_is_inside() {
+        assert_eq!(interpolate((1.0, 0.0, 0.0), 2.0, 3.0, 4.0), 2.0);
+        assert_eq!(interpolate((0.0, 0.5, 0.5), 2.0, 3.0, 4.0), 3.5);
+        assert!(is_inside((0.0, 0.5, 0.5), 0.0));
+        assert!(!is_inside((-1e-6, 0.5, 0.5 + 1e-6), 1e-9));
+        assert!(is_inside((-1e-6, 0.5, 0.5 + 1e-6), 1e-5));
+    }
+
+    #[test]
+    fn epsilon_is_configurable() {
+        let (v0, v1, v2) = unit_triangle();
+        let grazing = ray(Point3::new(-1.0, 0.2, 1e-3), Vec3::new(1.0, 0.0, -1e-3));
+        assert!(intersect_ray_triangle(grazing, v0, v1, v2).is_some());
+        let strict = TriangleTest {
+            epsilon: 0.01,
+            ..TriangleTest::default()
+        };
+        assert!(strict.intersect(grazing, v0, v1, v2).is_none());
+    }
+}
+
+#[cfg(test)]
+mod sphere_intersection_tests {
+    use geometry3d::*;
+    #[cfg(not(feature = "f32"))]
+    use rand::rngs::StdRng;
+    #[cfg(not(feature = "f32"))]
+    use rand::{Rng, SeedableRng};
+
+    fn ray(origin: Point3, direction: Vec3) -> Ray3 {
+        Ray3 {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    // the textbook solution that Sphere::hit used before
+    #[cfg(not(feature = "f32"))]
+    fn textbook_roots(ray: Ray3, center: Point3, radius: Float) -> Option<(Float, Float)> {
+        let oc = ray.origin - center;
+        let a = ray.direction.dot(ray.direction);
+        let half_b = ray.direction.dot(oc);
+        let c = oc.dot(oc) - radius * radius;
+        let delta = half_b * half_b - a * c;
+        if delta < 0.0 {
+            return None;
+        }
+        let sqrtd = delta.sqrt();
+        Some(((-half_b - sqrtd) / a, (-half_b + sqrtd) / a))
+    }
+
+    #[test]
+    fn roots() {
+        let r = ray(Point3::new(0.0, 0.0, -4.0), Vec3::new(0.0, 0.0, 2.0));
+        assert_eq!(intersect_ray_sphere_roots(r, Point3::default(), 1.0), Some((1.5, 2.5)));
+        // from inside, the first root is behind the origin
+        let r = ray(Point3::default(), Vec3::e0());
+        assert_eq!(intersect_ray_sphere_roots(r, Point3::default(), 2.0), Some((-2.0, 2.0)));
+        // touching and missing
+        let r = ray(Point3::new(-5.0, 1.0, 0.0), Vec3::e0());
+        assert_eq!(intersect_ray_sphere_roots(r, Point3::default(), 1.0), Some((5.0, 5.0)));
+        let r = ray(Point3::new(-5.0, 1.5, 0.0), Vec3::e0());
+        assert_eq!(intersect_ray_sphere_roots(r, Point3::default(), 1.0), None);
+        assert_eq!(intersect_ray_sphere_roots(ray(Point3::default(), Vec3::default()), Point3::default(), 1.0), None);
+    }
+
+    #[test]
+    fn nearest_root_in_range() {
+        let r = ray(Point3::new(0.0, 0.0, -4.0), Vec3::e2());
+        let forwards = TRange::new(0.0, Float::INFINITY);
+        assert_eq!(intersect_ray_sphere(r, Point3::default(), 1.0, forwards), Some(3.0));
+        assert_eq!(intersect_ray_sphere(r, Point3::default(), 1.0, TRange::new(3.0, 10.0)), Some(5.0));
+        assert_eq!(intersect_ray_sphere(r, Point3::default(), 1.0, TRange::new(0.0, 3.0)), None);
+        assert_eq!(intersect_ray_sphere(r, Point3::new(0.0, 0.0, -10.0), 1.0, forwards), None);
+        // a negative radius, as for the inside of a hollow sphere, is the same
+        assert_eq!(intersect_ray_sphere(r, Point3::default(), -1.0, forwards), Some(3.0));
+    }
+
+    #[test]
+    #[cfg(not(feature = "f32"))]
+    fn matches_the_textbook_solution() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut hits = 0;
+        for _ in 0..10_000 {
+            let mut coordinate = || rng.gen_range(-5.0..5.0);
+            let mut point = || Point3::new(coordinate(), coordinate(), coordinate());
+            let (origin, center, target) = (point(), point(), point());
+            let radius = rng.gen_range(0.1..3.0);
+            let r = ray(origin, target - origin);
+            let expected = textbook_roots(r, center, radius);
+            let actual = intersect_ray_sphere_roots(r, center, radius);
+            match (expected, actual) {
+                (Some(expected), Some(actual)) => {
+                    assert_approx_eq!(actual.0, expected.0, 1e-6);
+                    assert_approx_eq!(actual.1, expected.1, 1e-6);
+                    hits += 1;
+                }
+                (None, None) => (),
+                // only where the ray all but touches the sphere may they
+                // disagree on whether it hits
+                (expected, actual) => {
+                    let (t0, t1) = expected.or(actual).unwrap();
+                    assert_approx_eq!(t0, t1, 1e-4);
+                }
+            }
+        }
+        assert!(hits > 500, "{hits}");
+    }
+
+    #[test]
+    #[cfg(not(feature = "f32"))]
+    fn far_away_small_sphere() {
+        let center = Point3::new(1e8, 0.0, 0.0);
+        let radius = 1.0;
+        let r = ray(Point3::default(), (center + Vec3::new(0.0, 0.5, 0.0)) - Point3::default());
+        let (t0, t1) = intersect_ray_sphere_roots(r, center, radius).unwrap();
+        for t in [t0, t1] {
+            assert_approx_eq!((r.at(t) - center).length(), radius, 1e-6);
+        }
+        // the chord through the sphere is 2 * sqrt(1 - 0.5^2) long
+        let chord = (t1 - t0) * r.direction.length();
+        assert_approx_eq!(chord, (3.0 as Float).sqrt(), 1e-6);
+
+        // the textbook solution loses the chord to cancellation
+        let (t0, t1) = textbook_roots(r, center, radius).unwrap_or((0.0, 0.0));
+        assert!(((t1 - t0) * r.direction.length() - (3.0 as Float).sqrt()).abs() > 1e-3);
+    }
 }
@@ -1,13 +1,45 @@
-use serde::de::{Deserialize, Deserializer};
+use serde::de::{Deserialize, Deserializer, Error, IgnoredAny, SeqAccess, Visitor};
 use serde::ser::{Serialize, SerializeSeq, Serializer};
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+/// A tuple of N elements of the same type.
+///
+/// Tuples compare, order and hash as their elements do, ordering
+/// lexicographically, so tuples of floats are only `PartialOrd`:
+/// ```compile_fail
+/// use ntuple::*;
+///
+/// let mut points = vec![ntuple!(1.0, 2.0), ntuple!(0.5, 3.0)];
+/// points.sort();
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct NTuple<T, const N: usize>([T; N]);
 
+/// The elements that every operation on an `NTuple` can be used with, which
+/// is every type that is `Copy` and has a `Default`.
+pub trait TupleMember: Default + Copy + Clone {}
+
+impl<T> TupleMember for T where T: Default + Copy + Clone {}
+
 pub trait NTupleNewtype<T, const N: usize>: From<NTuple<T, N>> {
     fn ntuple(&self) -> NTuple<T, N>;
 }
 
+/// The error of making an `NTuple` from a number of elements other than its
+/// length.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LengthError {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl std::fmt::Display for LengthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "expected {} elements, got {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for LengthError {}
+
 #[macro_export]
 macro_rules! ntuple {
     ($($element:expr),*) => { NTuple::from([$($element, )*]) }
@@ -15,7 +47,7 @@ macro_rules! ntuple {
 
 impl<T, const N: usize> NTuple<T, N>
 where
-    T: Default + Copy + Clone,
+    T: TupleMember,
 {
     pub fn map<F, U>(self, f: F) -> NTuple<U, N>
     where
@@ -25,16 +57,12 @@ where
         NTuple(self.0.map(f))
     }
 
-    pub fn combine<F, U>(self, rhs: Self, f: F) -> NTuple<U, N>
+    pub fn combine<U, V, F>(self, rhs: NTuple<U, N>, f: F) -> NTuple<V, N>
     where
-        F: Fn(T, T) -> U,
-        U: Default + Copy + Clone,
+        F: Fn(T, U) -> V,
+        U: Copy,
     {
-        let mut result: NTuple<U, N> = NTuple::default();
-        for i in 0..N {
-            result.0[i] = f(self[i], rhs[i]);
-        }
-        result
+        NTuple(std::array::from_fn(|i| f(self[i], rhs[i])))
     }
 
     pub fn reduce<F>(self, f: F) -> T
@@ -53,36 +81,379 @@ where
     where
         F: Fn(T, T) -> T,
     {
-        let mut acc = start;
-        for i in 0..N {
-            acc = f(acc, self[i]);
-        }
-        acc
+        self.into_iter().fold(start, f)
+    }
+
+    pub fn sum(self) -> T
+    where
+        T: std::ops::Add<Output = T>,
+    {
+        self.reduce(T::add)
+    }
+
+    pub fn product(self) -> T
+    where
+        T: std::ops::Mul<Output = T>,
+    {
+        self.reduce(T::mul)
+    }
+
+    /// The smallest element. Elements that are not comparable with
+    /// themselves, such as NaN, are passed over unless every element is, as
+    /// with `f64::min`; of equal elements the first is taken.
+    pub fn min_element(self) -> T
+    where
+        T: PartialOrd,
+    {
+        self.extreme(T::lt).1
+    }
+
+    /// The largest element, passing over NaN as `min_element` does.
+    pub fn max_element(self) -> T
+    where
+        T: PartialOrd,
+    {
+        self.extreme(T::gt).1
+    }
+
+    /// The index of `min_element`, the first of equal elements.
+    pub fn argmin(self) -> usize
+    where
+        T: PartialOrd,
+    {
+        self.extreme(T::lt).0
+    }
+
+    /// The index of `max_element`, the first of equal elements.
+    pub fn argmax(self) -> usize
+    where
+        T: PartialOrd,
+    {
+        self.extreme(T::gt).0
+    }
+
+    /// The first element, and its index, that no later element is `beyond`,
+    /// passing over elements not comparable with themselves.
+    fn extreme<F>(self, beyond: F) -> (usize, T)
+    where
+        T: PartialOrd,
+        F: Fn(&T, &T) -> bool,
+    {
+        assert!(N > 0, "Cannot reduce the 0-tuple.");
+        let pairs = self.enumerate().into_iter();
+        let further = |acc: &(usize, T), x: &(usize, T)| {
+            (incomparable(&acc.1) && !incomparable(&x.1)) || beyond(&x.1, &acc.1)
+        };
+        pairs.reduce(|acc, x| if further(&acc, &x) { x } else { acc }).unwrap()
+    }
+
+    /// Whether any element satisfies `f`, false for the 0-tuple.
+    pub fn any<F>(self, f: F) -> bool
+    where
+        F: Fn(T) -> bool,
+    {
+        self.into_iter().any(f)
+    }
+
+    /// Whether every element satisfies `f`, true for the 0-tuple.
+    pub fn all<F>(self, f: F) -> bool
+    where
+        F: Fn(T) -> bool,
+    {
+        self.into_iter().all(f)
+    }
+
+    /// The elements at `indices`, in that order, which may leave elements out
+    /// or repeat them. Panics if an index is out of range.
+    pub fn select<const M: usize>(self, indices: [usize; M]) -> NTuple<T, M> {
+        NTuple(indices.map(|i| {
+            assert!(i < N, "Index {i} is out of range for a tuple of {N} elements.");
+            self[i]
+        }))
     }
 
     pub fn permute(self, perms: [usize; N]) -> NTuple<T,N> {
-        let mut arr = [T::default(); N];
+        let mut result = NTuple::default();
         for i in 0..N {
-            arr[i] = self[perms[i]];
+            result[i] = self[perms[i]];
         }
-        NTuple::from(arr)
+        result
     }
 }
 
+impl<T, const N: usize> NTuple<T, N> {
+    /// The tuple whose element `i` is `f(i)`, called in order from 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use ntuple::*;
+    ///
+    /// // rotate the elements one place to the left
+    /// let rotate = NTuple::<usize, 4>::from_fn(|i| (i + 1) % 4);
+    /// assert_eq!(rotate, ntuple!(1, 2, 3, 0));
+    /// assert_eq!(ntuple!('a', 'b', 'c', 'd').permute(rotate.into_array()), ntuple!('b', 'c', 'd', 'a'));
+    /// ```
+    pub fn from_fn<F>(f: F) -> Self
+    where
+        F: FnMut(usize) -> T,
+    {
+        NTuple(std::array::from_fn(f))
+    }
+
+    /// The tuple with every element `value`.
+    pub fn repeat(value: T) -> Self
+    where
+        T: Copy,
+    {
+        NTuple([value; N])
+    }
+
+    /// The tuple with element `i` `one` and every other element `zero`.
+    pub fn basis(i: usize, one: T, zero: T) -> Self
+    where
+        T: Copy,
+    {
+        assert!(i < N, "Index {i} is out of range for a tuple of {N} elements.");
+        NTuple::from_fn(|j| if j == i { one } else { zero })
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.0.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.0.get_mut(index)
+    }
+
+    pub fn as_array(&self) -> &[T; N] {
+        &self.0
+    }
+
+    pub fn into_array(self) -> [T; N] {
+        self.0
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.0.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.0.iter_mut()
+    }
+
+    /// Collects exactly N elements, or errors with the number there were.
+    pub fn try_collect<I>(iter: I) -> Result<Self, LengthError>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let vec: Vec<T> = iter.into_iter().collect();
+        NTuple::try_from(vec)
+    }
+
+    /// The elements of `self` followed by those of `rhs`. The length of the
+    /// result, usually inferred, must be N + M, which is checked when the
+    /// crate is built as stable Rust cannot yet write it in the signature.
+    pub fn concat<const M: usize, const K: usize>(self, rhs: NTuple<T, M>) -> NTuple<T, K>
+    where
+        T: Copy,
+    {
+        const { assert!(N + M == K, "The length of a concatenation must be the sum of the lengths.") };
+        NTuple(std::array::from_fn(|i| if i < N { self.0[i] } else { rhs.0[i - N] }))
+    }
+
+    /// The first K elements and the rest, where the lengths of the two must
+    /// add up to N, as with `concat`.
+    pub fn split_at<const K: usize, const M: usize>(self) -> (NTuple<T, K>, NTuple<T, M>)
+    where
+        T: Copy,
+    {
+        const { assert!(K + M == N, "The lengths of the parts of a split must add up to the length.") };
+        (NTuple(std::array::from_fn(|i| self.0[i])), NTuple(std::array::from_fn(|i| self.0[K + i])))
+    }
+
+    /// The tuple with `value` added at the end, of length N + 1.
+    pub fn push<const K: usize>(self, value: T) -> NTuple<T, K>
+    where
+        T: Copy,
+    {
+        self.concat(NTuple([value]))
+    }
+
+    /// The tuple without its last element, of length N - 1, and that
+    /// element.
+    pub fn pop<const K: usize>(self) -> (NTuple<T, K>, T)
+    where
+        T: Copy,
+    {
+        let (rest, NTuple([last])) = self.split_at::<K, 1>();
+        (rest, last)
+    }
+
+    pub fn zip<U>(self, rhs: NTuple<U, N>) -> NTuple<(T, U), N> {
+        self.zip_with(rhs, |x, y| (x, y))
+    }
+
+    /// Each element paired with its index.
+    pub fn enumerate(self) -> NTuple<(usize, T), N> {
+        NTuple::from_fn(|i| i).zip(self)
+    }
+
+    pub fn zip_with<U, V, F>(self, rhs: NTuple<U, N>, f: F) -> NTuple<V, N>
+    where
+        F: Fn(T, U) -> V,
+    {
+        let mut pairs = self.into_iter().zip(rhs);
+        NTuple(std::array::from_fn(|_| {
+            let (lhs, rhs) = pairs.next().unwrap();
+            f(lhs, rhs)
+        }))
+    }
+}
+
+impl<T, U, const N: usize> NTuple<(T, U), N> {
+    /// The tuples of the first and of the second elements of the pairs, the
+    /// reverse of `zip`.
+    pub fn unzip(self) -> (NTuple<T, N>, NTuple<U, N>)
+    where
+        T: Copy,
+        U: Copy,
+    {
+        (NTuple(self.0.map(|(x, _)| x)), NTuple(self.0.map(|(_, y)| y)))
+    }
+}
+
+impl<T, const N: usize> IntoIterator for NTuple<T, N> {
+    type Item = T;
+    type IntoIter = std::array::IntoIter<T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a NTuple<T, N> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a mut NTuple<T, N> {
+    type Item = &'a mut T;
+    type IntoIter = std::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
+/// Panics unless the iterator has exactly N elements; see `try_collect`.
+impl<T, const N: usize> FromIterator<T> for NTuple<T, N> {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        NTuple::try_collect(iter).unwrap_or_else(|e| panic!("Cannot collect an NTuple: {e}."))
+    }
+}
+
+fn incomparable<T: PartialOrd>(x: &T) -> bool {
+    x.partial_cmp(x).is_none()
+}
+
 impl<T, const N: usize> Default for NTuple<T, N>
 where
-    T: Copy + Clone + Default,
+    T: TupleMember,
 {
     fn default() -> Self {
         NTuple([T::default(); N])
     }
 }
 
+/// Elementwise arithmetic between tuples, and of each element by a scalar,
+/// with the elements' own operators; integer elements that overflow panic
+/// or wrap as those do, by whether overflow checks are enabled.
+macro_rules! elementwise_op {
+    ($op:ident, $method:ident, $op_assign:ident, $method_assign:ident) => {
+        impl<T, const N: usize> std::ops::$op for NTuple<T, N>
+        where
+            T: std::ops::$op<Output = T> + Copy,
+        {
+            type Output = Self;
+
+            fn $method(self, rhs: Self) -> Self {
+                NTuple(std::array::from_fn(|i| self.0[i].$method(rhs.0[i])))
+            }
+        }
+
+        impl<T, const N: usize> std::ops::$op<T> for NTuple<T, N>
+        where
+            T: std::ops::$op<Output = T> + Copy,
+        {
+            type Output = Self;
+
+            fn $method(self, rhs: T) -> Self {
+                NTuple(self.0.map(|x| x.$method(rhs)))
+            }
+        }
+
+        impl<T, const N: usize> std::ops::$op_assign for NTuple<T, N>
+        where
+            T: std::ops::$op_assign + Copy,
+        {
+            fn $method_assign(&mut self, rhs: Self) {
+                for i in 0..N {
+                    self.0[i].$method_assign(rhs.0[i]);
+                }
+            }
+        }
+
+        impl<T, const N: usize> std::ops::$op_assign<T> for NTuple<T, N>
+        where
+            T: std::ops::$op_assign + Copy,
+        {
+            fn $method_assign(&mut self, rhs: T) {
+                for x in &mut self.0 {
+                    x.$method_assign(rhs);
+                }
+            }
+        }
+    };
+}
+
+elementwise_op!(Add, add, AddAssign, add_assign);
+elementwise_op!(Sub, sub, SubAssign, sub_assign);
+elementwise_op!(Mul, mul, MulAssign, mul_assign);
+elementwise_op!(Div, div, DivAssign, div_assign);
+
+impl<T, const N: usize> std::ops::Neg for NTuple<T, N>
+where
+    T: std::ops::Neg<Output = T>,
+{
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        NTuple(self.0.map(T::neg))
+    }
+}
+
 impl<T, const N: usize> std::ops::Index<usize> for NTuple<T, N> {
     type Output = T;
 
     fn index(&self, index: usize) -> &Self::Output {
-        &self.0[index]
+        self.0
+            .get(index)
+            .unwrap_or_else(|| panic!("Index {index} is out of range for a tuple of {N} elements."))
+    }
+}
+
+impl<T, const N: usize> std::ops::IndexMut<usize> for NTuple<T, N> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.0
+            .get_mut(index)
+            .unwrap_or_else(|| panic!("Index {index} is out of range for a tuple of {N} elements."))
     }
 }
 
@@ -92,22 +463,72 @@ impl<T, const N: usize> std::convert::From<[T; N]> for NTuple<T, N> {
     }
 }
 
-impl<T, const N: usize> std::convert::From<&[T]> for NTuple<T, N>
+impl<T, const N: usize> std::convert::TryFrom<&[T]> for NTuple<T, N>
+where
+    T: Copy,
+{
+    type Error = LengthError;
+
+    fn try_from(slice: &[T]) -> Result<Self, Self::Error> {
+        let found = slice.len();
+        let array: [T; N] = slice.try_into().map_err(|_| LengthError { expected: N, found })?;
+        Ok(NTuple::from(array))
+    }
+}
+
+impl<T, const N: usize> std::convert::TryFrom<Vec<T>> for NTuple<T, N> {
+    type Error = LengthError;
+
+    fn try_from(vec: Vec<T>) -> Result<Self, Self::Error> {
+        let found = vec.len();
+        let array: [T; N] = vec.try_into().map_err(|_| LengthError { expected: N, found })?;
+        Ok(NTuple::from(array))
+    }
+}
+
+/// Writes the elements as `(a, b, c)`, each with `fmt`, which is given the
+/// formatter's options such as its width and precision.
+fn write_elements<T, F>(f: &mut std::fmt::Formatter, elements: &[T], fmt: F) -> std::fmt::Result
+where
+    F: Fn(&T, &mut std::fmt::Formatter) -> std::fmt::Result,
+{
+    f.write_str("(")?;
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        fmt(element, f)?;
+    }
+    f.write_str(")")
+}
+
+/// # Examples
+/// ```
+/// use ntuple::*;
+///
+/// assert_eq!(format!("{}", ntuple!(1, 2, 3)), "(1, 2, 3)");
+/// assert_eq!(format!("{:.2}", ntuple!(1.0, 0.5)), "(1.00, 0.50)");
+/// ```
+impl<T, const N: usize> std::fmt::Display for NTuple<T, N>
 where
-    T: Copy + Clone + std::fmt::Debug,
+    T: std::fmt::Display,
 {
-    fn from(slice: &[T]) -> Self {
-        let array: [T; N] = slice.try_into().unwrap();
-        NTuple::from(array)
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write_elements(f, &self.0, T::fmt)
     }
 }
 
-impl<T, const N: usize> std::convert::From<Vec<T>> for NTuple<T, N>
+/// The alternate form, `{:#?}`, leaves out the name, as `(a, b, c)`.
+impl<T, const N: usize> std::fmt::Debug for NTuple<T, N>
 where
-    T: Copy + Clone + std::fmt::Debug,
+    T: std::fmt::Debug,
 {
-    fn from(vec: Vec<T>) -> Self {
-        NTuple::from(&vec[..])
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if f.alternate() {
+            write_elements(f, &self.0, T::fmt)
+        } else {
+            f.debug_tuple("NTuple").field(&self.0).finish()
+        }
     }
 }
 
@@ -127,20 +548,63 @@ where
     }
 }
 
+/// Reads exactly N elements of a sequence, without collecting them first.
+struct NTupleVisitor<T, const N: usize>(std::marker::PhantomData<T>);
+
+impl<'de, T, const N: usize> Visitor<'de> for NTupleVisitor<T, N>
+where
+    T: Deserialize<'de>,
+{
+    type Value = NTuple<T, N>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "an array of {N} elements")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut error = None;
+        let elements: [Option<T>; N] = std::array::from_fn(|i| {
+            if error.is_some() {
+                return None;
+            }
+            match seq.next_element() {
+                Ok(Some(element)) => Some(element),
+                Ok(None) => {
+                    error = Some(A::Error::invalid_length(i, &self));
+                    None
+                }
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            }
+        });
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        let mut found = N;
+        while seq.next_element::<IgnoredAny>()?.is_some() {
+            found += 1;
+        }
+        if found != N {
+            return Err(A::Error::invalid_length(found, &self));
+        }
+        Ok(NTuple(elements.map(|element| element.unwrap())))
+    }
+}
+
 impl<'de, T, const N: usize> Deserialize<'de> for NTuple<T, N>
 where
-    T: Deserialize<'de> + Copy + Clone + std::fmt::Debug,
+    T: Deserialize<'de>,
 {
     fn deserialize<D>(deserializer: D) -> Result<NTuple<T, N>, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let seq: Vec<T> = Deserialize::deserialize(deserializer)?;
-        assert!(
-            seq.len() == N,
-            "Cannot convert {:?} into NTuple of length {N}",
-            &seq
-        );
-        Ok(NTuple::from(seq))
+        deserializer.deserialize_seq(NTupleVisitor(std::marker::PhantomData))
     }
 }
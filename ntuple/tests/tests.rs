@@ -15,6 +15,98 @@ mod tests {
         assert_ne!(t1, t2);
     }
 
+    #[test]
+    fn mutate_through_index() {
+        let mut t = ntuple!(0, 1, 2);
+        t[1] = 5;
+        t[2] += 1;
+        *t.get_mut(0).unwrap() = 4;
+        assert_eq!(t, ntuple!(4, 5, 3));
+        assert_eq!(t.as_array(), &[4, 5, 3]);
+        assert_eq!(t.into_array(), [4, 5, 3]);
+    }
+
+    #[test]
+    fn get_out_of_bounds() {
+        let mut t = ntuple!(0, 1, 2);
+        assert_eq!(t.get(2), Some(&2));
+        assert_eq!(t.get(3), None);
+        assert_eq!(t.get_mut(3), None);
+    }
+
+    #[test]
+    fn iterate_tuple() {
+        let mut t = ntuple!(1, 2, 3);
+        assert_eq!((&t).into_iter().sum::<i32>(), 6);
+        for x in &mut t {
+            *x *= 2;
+        }
+        t.iter_mut().for_each(|x| *x += 1);
+        assert_eq!(t.iter().copied().collect::<Vec<_>>(), [3, 5, 7]);
+        assert_eq!(t.into_iter().collect::<Vec<_>>(), [3, 5, 7]);
+    }
+
+    #[test]
+    fn collect_tuple() {
+        let t: NTuple<i32, 3> = (1..4).collect();
+        assert_eq!(t, ntuple!(1, 2, 3));
+        assert_eq!(NTuple::<i32, 3>::try_collect(1..4), Ok(t));
+        let short = NTuple::<i32, 3>::try_collect(1..3).unwrap_err();
+        assert_eq!(short, LengthError { expected: 3, found: 2 });
+        assert_eq!(short.to_string(), "expected 3 elements, got 2");
+        assert!(NTuple::<i32, 3>::try_collect(1..5).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 3 elements, got 4")]
+    fn collect_wrong_length() {
+        let _t: NTuple<i32, 3> = (1..5).collect();
+    }
+
+    #[test]
+    fn zip_tuples_with() {
+        let t1 = ntuple!(1, 2, 3);
+        let t2 = ntuple!(0.5, 1.0, 1.5);
+        assert_eq!(t1.zip_with(t2, |x, y| x as f64 * y), ntuple!(0.5, 2.0, 4.5));
+        assert_eq!(t1.zip_with(t1, |x, y| x + y), t1.combine(t1, |x, y| x + y));
+    }
+
+    #[test]
+    fn convert_slices_and_vecs() {
+        let t = ntuple!(1, 2, 3);
+        assert_eq!(NTuple::try_from(&[1, 2, 3][..]), Ok(t));
+        assert_eq!(NTuple::try_from(vec![1, 2, 3]), Ok(t));
+
+        let short = NTuple::<i32, 3>::try_from(&[1, 2][..]);
+        assert_eq!(short, Err(LengthError { expected: 3, found: 2 }));
+        let long = NTuple::<i32, 3>::try_from(vec![1, 2, 3, 4]);
+        assert_eq!(long, Err(LengthError { expected: 3, found: 4 }));
+    }
+
+    #[test]
+    fn construct_from_fn() {
+        let mut calls = Vec::new();
+        let t = NTuple::<usize, 4>::from_fn(|i| {
+            calls.push(i);
+            i * i
+        });
+        assert_eq!(t, ntuple!(0, 1, 4, 9));
+        assert_eq!(calls, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn construct_repeat_and_basis() {
+        assert_eq!(NTuple::<f64, 3>::repeat(0.5), ntuple!(0.5, 0.5, 0.5));
+        assert_eq!(NTuple::<f64, 3>::basis(1, 1.0, 0.0), ntuple!(0.0, 1.0, 0.0));
+        assert_eq!(NTuple::<bool, 2>::basis(0, true, false), ntuple!(true, false));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn basis_out_of_range() {
+        NTuple::<f64, 3>::basis(3, 1.0, 0.0);
+    }
+
     #[test]
     fn transform_tuple() {
         let t1 = NTuple::from([0; 4]);
@@ -34,6 +126,76 @@ mod tests {
         assert_eq!(t1.combine(t2, |x, y| x + y), t3);
     }
 
+    #[test]
+    fn combine_different_types() {
+        let t1 = ntuple!(0.5, 1.5, 2.5);
+        let t2 = ntuple!(2i64, -1, 0);
+        assert_eq!(t1.combine(t2, |x, y| x * y as f64), ntuple!(1.0, -1.5, 0.0));
+        let t3 = ntuple!(true, false, true);
+        assert_eq!(t1.combine(t3, |x, keep| if keep { x } else { 0.0 }), ntuple!(0.5, 0.0, 2.5));
+        assert_eq!(t2.combine(t1, |x, y| (x as f64) < y), ntuple!(false, true, true));
+    }
+
+    #[test]
+    fn arithmetic() {
+        let t1 = ntuple!(1, 2, 3, 4);
+        let t2 = ntuple!(4, 3, 2, 1);
+        assert_eq!(t1 + t2, ntuple!(5, 5, 5, 5));
+        assert_eq!(t1 - t2, ntuple!(-3, -1, 1, 3));
+        assert_eq!(t1 * t2, ntuple!(4, 6, 6, 4));
+        assert_eq!(t1 / t2, ntuple!(0, 0, 1, 4));
+        assert_eq!(-t1, ntuple!(-1, -2, -3, -4));
+        assert_eq!(t1 * 2, ntuple!(2, 4, 6, 8));
+        assert_eq!(t1 / 2, ntuple!(0, 1, 1, 2));
+
+        let t3 = ntuple!(1.0, 2.0);
+        assert_eq!(t3 + t3, ntuple!(2.0, 4.0));
+        assert_eq!(t3 / 4.0, ntuple!(0.25, 0.5));
+        assert_eq!(NTuple::from([7u8]) - NTuple::from([2u8]), NTuple::from([5u8]));
+    }
+
+    #[test]
+    fn assign_arithmetic() {
+        let mut t = ntuple!(1.0, 2.0, 3.0);
+        t += ntuple!(1.0, 1.0, 1.0);
+        t *= 2.0;
+        t -= ntuple!(0.0, 1.0, 2.0);
+        t /= ntuple!(4.0, 5.0, 6.0);
+        assert_eq!(t, ntuple!(1.0, 1.0, 1.0));
+        t -= 1.0;
+        t += 0.5;
+        assert_eq!(t, ntuple!(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn arithmetic_wraps_explicitly() {
+        let t = NTuple::from([std::num::Wrapping(255u8); 2]);
+        assert_eq!(t + NTuple::from([std::num::Wrapping(1u8); 2]), NTuple::from([std::num::Wrapping(0u8); 2]));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "overflow")]
+    fn arithmetic_overflow_panics_with_overflow_checks() {
+        let _t = NTuple::from([255u8; 2]) + NTuple::from([1u8; 2]);
+    }
+
+    #[test]
+    fn concat_and_split() {
+        let point = ntuple!(1.0, 2.0, 3.0);
+        let homogeneous: NTuple<f64, 4> = point.concat(ntuple!(1.0));
+        assert_eq!(homogeneous, ntuple!(1.0, 2.0, 3.0, 1.0));
+        assert_eq!(point.push::<4>(1.0), homogeneous);
+        assert_eq!(homogeneous.pop::<3>(), (point, 1.0));
+
+        let t = ntuple!(1, 2, 3, 4, 5, 6);
+        let (front, back): (NTuple<i32, 2>, NTuple<i32, 4>) = t.split_at();
+        assert_eq!((front, back), (ntuple!(1, 2), ntuple!(3, 4, 5, 6)));
+        assert_eq!(front.concat::<4, 6>(back), t);
+        let (all, none) = t.split_at::<6, 0>();
+        assert_eq!((all, none), (t, NTuple::from([])));
+    }
+
     #[test]
     fn fold_tuples() {
         let t1 = ntuple!(1, 2, 3);
@@ -41,6 +203,52 @@ mod tests {
         assert_eq!(t1.fold(10, |acc, x| acc + x), 16);
     }
 
+    #[test]
+    fn reductions() {
+        let t = ntuple!(3, 1, 4, 1, 5);
+        assert_eq!((t.sum(), t.product()), (14, 60));
+        assert_eq!((t.min_element(), t.max_element()), (1, 5));
+        assert!(t.any(|x| x > 4));
+        assert!(!t.all(|x| x > 1));
+        assert!(t.all(|x| x > 0));
+        let empty = NTuple::<i32, 0>::from([]);
+        assert!(!empty.any(|_| true) && empty.all(|_| false));
+    }
+
+    #[test]
+    fn min_and_max_pass_over_nan() {
+        let t = ntuple!(f64::NAN, 2.0, f64::NAN, -1.0, 3.0);
+        assert_eq!((t.min_element(), t.max_element()), (-1.0, 3.0));
+        let t = ntuple!(2.0, f64::NAN);
+        assert_eq!((t.min_element(), t.max_element()), (2.0, 2.0));
+        assert!(NTuple::from([f64::NAN; 2]).max_element().is_nan());
+    }
+
+    #[test]
+    fn zip_and_unzip() {
+        let t1 = ntuple!(1, 2, 3);
+        let t2 = ntuple!('a', 'b', 'c');
+        let zipped = t1.zip(t2);
+        assert_eq!(zipped, ntuple!((1, 'a'), (2, 'b'), (3, 'c')));
+        assert_eq!(zipped.unzip(), (t1, t2));
+        assert_eq!(t2.enumerate(), ntuple!((0, 'a'), (1, 'b'), (2, 'c')));
+    }
+
+    #[test]
+    fn argmin_and_argmax_take_the_first_of_ties() {
+        let t = ntuple!(2, 5, 1, 5, 1);
+        assert_eq!((t.argmin(), t.argmax()), (2, 1));
+        let t = ntuple!(f64::NAN, 1.0, 1.0, f64::NAN);
+        assert_eq!((t.argmin(), t.argmax()), (1, 1));
+        assert_eq!(NTuple::from([f64::NAN; 3]).argmax(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot reduce the 0-tuple.")]
+    fn sum_zero_tuple() {
+        NTuple::<f64, 0>::from([]).sum();
+    }
+
     #[test]
     #[should_panic]
     fn reduce_zero_tuple() {
@@ -48,6 +256,39 @@ mod tests {
         zero.reduce(|acc, x| acc + x);
     }
 
+    #[test]
+    fn sort_lexicographically() {
+        let mut tiles = vec![ntuple!(1, 2), ntuple!(0, 5), ntuple!(1, 0), ntuple!(0, 1)];
+        tiles.sort();
+        assert_eq!(tiles, [ntuple!(0, 1), ntuple!(0, 5), ntuple!(1, 0), ntuple!(1, 2)]);
+        assert!(ntuple!(0.5, 2.0) < ntuple!(1.0, 0.0));
+        assert_eq!(ntuple!(f64::NAN, 0.0).partial_cmp(&ntuple!(0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn hash_map_keys() {
+        let mut cache = std::collections::HashMap::new();
+        cache.insert(ntuple!(3u32, 4u32), "tile");
+        assert_eq!(cache.get(&ntuple!(3, 4)), Some(&"tile"));
+        assert_eq!(cache.get(&ntuple!(4, 3)), None);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(ntuple!(1, -2, 3).to_string(), "(1, -2, 3)");
+        assert_eq!(format!("{:.1}", ntuple!(1.25, -0.5)), "(1.2, -0.5)");
+        assert_eq!(format!("{:6.2}", ntuple!(1.0, 10.0)), "(  1.00,  10.00)");
+        assert_eq!(format!("{:03}", ntuple!(7u8)), "(007)");
+        assert_eq!(NTuple::<i32, 0>::from([]).to_string(), "()");
+    }
+
+    #[test]
+    fn debug() {
+        assert_eq!(format!("{:?}", ntuple!(1.0, 2.5)), "NTuple([1.0, 2.5])");
+        assert_eq!(format!("{:#?}", ntuple!(1.0, 2.5)), "(1.0, 2.5)");
+        assert_eq!(format!("{:#?}", ntuple!("a")), r#"("a")"#);
+    }
+
     #[test]
     fn de_serialize() {
         let t = ntuple!(1, 2, 3);
@@ -56,6 +297,40 @@ mod tests {
         assert_eq!(t_de, t);
     }
 
+    #[test]
+    fn de_serialize_longer_than_32() {
+        let t = NTuple::<u8, 40>::from_fn(|i| i as u8);
+        let s = serde_json::to_string(&t).unwrap();
+        assert_eq!(s, serde_json::to_string(&(0..40).collect::<Vec<u8>>()).unwrap());
+        assert_eq!(serde_json::from_str::<NTuple<u8, 40>>(&s).unwrap(), t);
+    }
+
+    #[test]
+    fn generic_over_tuple_members() {
+        fn shift<T: TupleMember, const N: usize>(t: NTuple<T, N>) -> NTuple<T, N> {
+            t.permute(std::array::from_fn(|i| (i + 1) % N))
+        }
+        assert_eq!(shift(ntuple!(1, 2, 3)), ntuple!(2, 3, 1));
+        assert_eq!(shift(ntuple!(true, false)), ntuple!(false, true));
+    }
+
+    #[test]
+    fn de_serialize_wrong_length() {
+        let error = serde_json::from_str::<NTuple<f64, 3>>("[1.0, 2.0]").unwrap_err();
+        assert!(error.to_string().contains("invalid length 2, expected an array of 3 elements"));
+        let error = serde_json::from_str::<NTuple<f64, 3>>("[1.0, 2.0, 3.0, 4.0]").unwrap_err();
+        assert!(error.to_string().contains("invalid length 4, expected an array of 3 elements"));
+        let error = serde_json::from_str::<NTuple<f64, 3>>("1.0").unwrap_err();
+        assert!(error.to_string().contains("expected an array of 3 elements"));
+        assert!(serde_json::from_str::<NTuple<f64, 3>>(r#"[1.0, "2", 3.0]"#).is_err());
+    }
+
+    #[test]
+    fn de_serialize_elements_without_copy() {
+        let t: NTuple<String, 2> = serde_json::from_str(r#"["a", "b"]"#).unwrap();
+        assert_eq!(t, NTuple::from([String::from("a"), String::from("b")]));
+    }
+
     #[test]
     fn permute() {
         let t0 = ntuple!(1, 2, 4, 8, 16);
@@ -66,12 +341,26 @@ mod tests {
         assert_eq!(t0, t2);
     }
 
+    #[test]
+    fn select() {
+        let t = ntuple!('x', 'y', 'z');
+        assert_eq!(t.select([2, 0]), ntuple!('z', 'x'));
+        assert_eq!(t.select([1, 1, 1, 0]), ntuple!('y', 'y', 'y', 'x'));
+        assert_eq!(t.select([]), NTuple::from([]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 3 is out of range for a tuple of 3 elements.")]
+    fn select_out_of_range() {
+        ntuple!(1, 2, 3).select([0, 3]);
+    }
+
     #[test]
     #[should_panic]
     fn bad_permute() {
         let t0 = ntuple!(0);
         let p = [1];
-        let t2 = t0.permute(p);
+        let _t2 = t0.permute(p);
     }
 }
 
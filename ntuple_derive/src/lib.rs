@@ -2,99 +2,150 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn;
-use syn::visit::{self, Visit};
+use syn::spanned::Spanned;
 
-#[proc_macro_derive(NTupleNewtype)]
+/// Derives `NTupleNewtype`, and conversions to and from the `NTuple`, for a
+/// struct with a single field of an `NTuple`, named or not. The struct's
+/// generic parameters and where clause carry over to the impls.
+///
+/// With `#[ntuple(index)]` the struct is also indexed by `usize` as its
+/// `NTuple` is, and borrows it with `as_ntuple`. Other index types that
+/// convert into `usize` can be listed, as in `#[ntuple(index(Axis))]`.
+#[proc_macro_derive(NTupleNewtype, attributes(ntuple))]
 pub fn ntuple_newtype_derive(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
 
     impl_ntuple_newtype(&ast)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
 }
 
-fn impl_ntuple_newtype(ast: &syn::DeriveInput) -> TokenStream {
-    let mut visitor = DeriveVisitor {
-        ident: None,
-        nttp: None,
-        gargs: None,
+fn impl_ntuple_newtype(ast: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let field = newtype_field(ast)?;
+    let (nttp, gargs) = ntuple_type(&field.ty)?;
+    let member = match &field.ident {
+        Some(ident) => quote!(#ident),
+        None => quote!(0),
     };
-    visitor.visit_derive_input(ast);
-    let ident = visitor.ident.unwrap();
-    let nttp = visitor.nttp.unwrap();
-    let gargs = visitor.gargs.unwrap();
-    let gen = quote! {
-        impl NTupleNewtype #gargs for #ident {
+
+    let ident = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let index = match index_types(ast)? {
+        Some(types) => {
+            let element = &gargs.args[0];
+            quote! {
+                impl #impl_generics #ident #ty_generics #where_clause {
+                    pub fn as_ntuple(&self) -> &#nttp {
+                        &self.#member
+                    }
+                }
+
+                impl #impl_generics std::ops::Index<usize> for #ident #ty_generics #where_clause {
+                    type Output = #element;
+
+                    fn index(&self, i: usize) -> &#element {
+                        &self.#member[i]
+                    }
+                }
+
+                #(
+                    impl #impl_generics std::ops::Index<#types> for #ident #ty_generics #where_clause {
+                        type Output = #element;
+
+                        fn index(&self, i: #types) -> &#element {
+                            &self.#member[usize::from(i)]
+                        }
+                    }
+                )*
+            }
+        }
+        None => quote!(),
+    };
+    Ok(quote! {
+        #index
+
+        impl #impl_generics NTupleNewtype #gargs for #ident #ty_generics #where_clause {
             fn ntuple(&self) -> #nttp {
-                self.0
+                self.#member
             }
         }
 
-        impl std::convert::From< #nttp > for #ident {
-            fn from(ntuple: #nttp) -> #ident {
-                Self(ntuple)
+        impl #impl_generics std::convert::From< #nttp > for #ident #ty_generics #where_clause {
+            fn from(ntuple: #nttp) -> Self {
+                Self { #member: ntuple }
             }
         }
 
-        impl std::convert::From< #ident > for #nttp {
-            fn from(i: #ident) -> #nttp {
+        impl #impl_generics std::convert::From< #ident #ty_generics > for #nttp #where_clause {
+            fn from(i: #ident #ty_generics) -> #nttp {
                 i.ntuple()
             }
         }
-    };
-    gen.into()
+    })
 }
 
-fn panic() {
-    panic!("NTupleNewtype can only be derived on newtype structs of NTuple");
-}
+/// The index types other than `usize` given by `#[ntuple(index(..))]`, or
+/// None without `#[ntuple(index)]`.
+fn index_types(ast: &syn::DeriveInput) -> syn::Result<Option<Vec<syn::Path>>> {
+    const MESSAGE: &str = "expected `#[ntuple(index)]` or `#[ntuple(index(Type, ...))]`";
 
-struct DeriveVisitor<'ast> {
-    pub ident: Option<&'ast syn::Ident>,
-    pub nttp: Option<&'ast syn::TypePath>,
-    pub gargs: Option<&'ast syn::AngleBracketedGenericArguments>,
-}
-
-impl<'ast> syn::visit::Visit<'ast> for DeriveVisitor<'ast> {
-    fn visit_derive_input(&mut self, node: &'ast syn::DeriveInput) {
-        self.ident = Some(&node.ident);
-        visit::visit_data(self, &node.data);
-    }
-
-    fn visit_data(&mut self, node: &'ast syn::Data) {
-        if let syn::Data::Struct(ds) = node {
-            visit::visit_data_struct(self, &ds);
-        } else {
-            panic();
+    let mut types = None;
+    for attr in ast.attrs.iter().filter(|attr| attr.path.is_ident("ntuple")) {
+        let syn::Meta::List(list) = attr.parse_meta()? else {
+            return Err(syn::Error::new_spanned(attr, MESSAGE));
+        };
+        for nested in list.nested {
+            let types = types.get_or_insert_with(Vec::new);
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("index") => {}
+                syn::NestedMeta::Meta(syn::Meta::List(list)) if list.path.is_ident("index") => {
+                    for nested in list.nested {
+                        match nested {
+                            syn::NestedMeta::Meta(syn::Meta::Path(path)) => types.push(path),
+                            nested => return Err(syn::Error::new_spanned(nested, MESSAGE)),
+                        }
+                    }
+                }
+                nested => return Err(syn::Error::new_spanned(nested, MESSAGE)),
+            }
         }
     }
+    Ok(types)
+}
 
-    fn visit_fields(&mut self, node: &'ast syn::Fields) {
-        if let syn::Fields::Unnamed(fu) = node {
-            visit::visit_fields_unnamed(self, &fu);
-        } else {
-            panic();
-        }
-    }
+/// The one field of the struct.
+fn newtype_field(ast: &syn::DeriveInput) -> syn::Result<&syn::Field> {
+    const MESSAGE: &str = "NTupleNewtype can only be derived on structs with a single field of an NTuple";
 
-    fn visit_fields_unnamed(&mut self, node: &'ast syn::FieldsUnnamed) {
-        let fields = &node.unnamed;
-        if fields.len() != 1 {
-            panic();
-        } else {
-            visit::visit_field(self, fields.first().unwrap());
-        }
+    let syn::Data::Struct(data) = &ast.data else {
+        return Err(syn::Error::new(ast.ident.span(), MESSAGE));
+    };
+    let mut fields = data.fields.iter();
+    match (fields.next(), fields.next()) {
+        (Some(field), None) => Ok(field),
+        _ if data.fields.is_empty() => Err(syn::Error::new(ast.ident.span(), MESSAGE)),
+        _ => Err(syn::Error::new(data.fields.span(), MESSAGE)),
     }
+}
 
-    fn visit_type_path(&mut self, node: &'ast syn::TypePath) {
-        let last = node.path.segments.last().unwrap();
-        if last.ident.to_string() != "NTuple" {
-            panic();
-        }
-        self.nttp = Some(node);
-        visit::visit_path(self, &node.path);
-    }
+/// The type of the field, which must be written as `NTuple<T, N>`, and its
+/// generic arguments.
+fn ntuple_type(ty: &syn::Type) -> syn::Result<(&syn::TypePath, &syn::AngleBracketedGenericArguments)> {
+    let error = || {
+        syn::Error::new_spanned(
+            ty,
+            "the field of an NTupleNewtype must be written as `NTuple<T, N>`, as aliases of it are not recognized",
+        )
+    };
 
-    fn visit_angle_bracketed_generic_arguments(&mut self, node: &'ast syn::AngleBracketedGenericArguments) {
-        self.gargs = Some(node);
+    let syn::Type::Path(path) = ty else {
+        return Err(error());
+    };
+    let last = path.path.segments.last().ok_or_else(error)?;
+    match &last.arguments {
+        syn::PathArguments::AngleBracketed(gargs) if last.ident == "NTuple" && gargs.args.len() == 2 => {
+            Ok((path, gargs))
+        }
+        _ => Err(error()),
     }
 }
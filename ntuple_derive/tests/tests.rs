@@ -4,6 +4,45 @@ use ntuple_derive::*;
 #[derive(NTupleNewtype)]
 struct Test(NTuple<f64, 3>);
 
+#[derive(NTupleNewtype)]
+struct Named {
+    inner: NTuple<f64, 3>,
+}
+
+#[derive(NTupleNewtype)]
+struct Wrapped<T: Copy>(NTuple<T, 3>);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
+impl From<Channel> for usize {
+    fn from(channel: Channel) -> usize {
+        channel as usize
+    }
+}
+
+#[derive(NTupleNewtype)]
+#[ntuple(index(Channel))]
+struct Color(NTuple<u8, 3>);
+
+#[derive(NTupleNewtype)]
+#[ntuple(index)]
+struct Indexed<T: Copy> {
+    elements: NTuple<T, 2>,
+}
+
+#[derive(NTupleNewtype)]
+struct Bounded<T, const N: usize>
+where
+    T: Copy + Default,
+{
+    elements: ntuple::NTuple<T, N>,
+}
+
 #[test]
 fn basic_test() {
     let n = ntuple!(0.0, 0.0, 0.0);
@@ -20,3 +59,47 @@ fn to_from() {
     assert_eq!(n, b);
 }
 
+
+#[test]
+fn named_field() {
+    let n = ntuple!(1.0, 2.0, 3.0);
+    let a = Named::from(n);
+    assert_eq!(a.inner, n);
+    assert_eq!(NTuple::from(a), n);
+}
+
+#[test]
+fn generic_newtypes() {
+    let w = Wrapped::from(ntuple!(1u8, 2, 3));
+    assert_eq!(w.ntuple(), ntuple!(1, 2, 3));
+    assert_eq!(w.0, ntuple!(1, 2, 3));
+
+    let b: Bounded<i32, 2> = Bounded::from(ntuple!(4, 5));
+    assert_eq!(b.elements, ntuple!(4, 5));
+    assert_eq!(NTuple::from(b), ntuple!(4, 5));
+}
+
+#[test]
+fn unsupported_shapes() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
+
+#[test]
+fn index_by_usize_and_listed_types() {
+    let c = Color(ntuple!(10, 20, 30));
+    assert_eq!((c[0], c[1], c[2]), (10, 20, 30));
+    assert_eq!((c[Channel::Red], c[Channel::Green], c[Channel::Blue]), (10, 20, 30));
+    assert_eq!(c.as_ntuple(), &ntuple!(10, 20, 30));
+
+    let i = Indexed::from(ntuple!('a', 'b'));
+    assert_eq!((i[0], i[1]), ('a', 'b'));
+    assert_eq!(i.as_ntuple(), &i.elements);
+}
+
+#[test]
+#[should_panic(expected = "Index 3 is out of range for a tuple of 3 elements.")]
+fn index_out_of_range() {
+    let c = Color(ntuple!(10, 20, 30));
+    let _ = c[3];
+}
@@ -0,0 +1,9 @@
+use ntuple::*;
+use ntuple_derive::*;
+
+type Triple = NTuple<f64, 3>;
+
+#[derive(NTupleNewtype)]
+struct Aliased(Triple);
+
+fn main() {}
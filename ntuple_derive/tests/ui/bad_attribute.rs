@@ -0,0 +1,8 @@
+use ntuple::*;
+use ntuple_derive::*;
+
+#[derive(NTupleNewtype)]
+#[ntuple(deref)]
+struct Wrapper(NTuple<f64, 3>);
+
+fn main() {}
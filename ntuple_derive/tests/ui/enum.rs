@@ -0,0 +1,10 @@
+use ntuple::*;
+use ntuple_derive::*;
+
+#[derive(NTupleNewtype)]
+enum Either {
+    Left(NTuple<f64, 3>),
+    Right(NTuple<f64, 3>),
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use ntuple_derive::*;
+
+#[derive(NTupleNewtype)]
+struct Array([f64; 3]);
+
+fn main() {}
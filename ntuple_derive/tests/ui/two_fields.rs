@@ -0,0 +1,7 @@
+use ntuple::*;
+use ntuple_derive::*;
+
+#[derive(NTupleNewtype)]
+struct Pair(NTuple<f64, 3>, NTuple<f64, 3>);
+
+fn main() {}
@@ -0,0 +1,6 @@
+use ntuple_derive::*;
+
+#[derive(NTupleNewtype)]
+struct Nothing;
+
+fn main() {}
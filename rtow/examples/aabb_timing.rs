@@ -0,0 +1,56 @@
+//! Times testing rays against every bounding box of the random spheres
+//! scene's BVH, working out the reciprocal of each ray's direction once per
+//! ray as the BVH does, against once per box.
+//!
+//! cargo run --release --example aabb_timing
+
+use geometry3d::*;
+use rtow::generate::random_spheres;
+use rtow::object::Object;
+use rtow::renderer::Renderer;
+use std::hint::black_box;
+use std::time::Instant;
+
+fn boxes(object: &Object, out: &mut Vec<AABB>) {
+    if let Object::FlatBVH(bvh) = object {
+        out.extend(bvh.nodes().iter().map(|node| node.aabb));
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let renderer = Renderer::new(random_spheres(0))?;
+    let mut aabbs = Vec::new();
+    boxes(renderer.scene(), &mut aabbs);
+
+    let origin = Point3::new(13.0, 2.0, 3.0);
+    let rays: Vec<_> = (0..2000)
+        .filter_map(|i| {
+            let a = i as Float * 0.001;
+            Ray3::new(origin, Vec3::new(-13.0 + 4.0 * a.sin(), -2.0 + a.cos(), -3.0 + a), 0.0)
+        })
+        .collect();
+    let range = TRange::new(0.001, Float::INFINITY);
+
+    let start = Instant::now();
+    let mut per_box = 0;
+    for &ray in &rays {
+        per_box += aabbs.iter().filter(|aabb| aabb.hit_bool(black_box(ray), range)).count();
+    }
+    let per_box_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut per_ray = 0;
+    for &ray in &rays {
+        let ray = black_box(ray).precompute();
+        per_ray += aabbs.iter().filter(|aabb| aabb.hit_slab(&ray, range).is_some()).count();
+    }
+    let per_ray_time = start.elapsed();
+
+    assert_eq!(per_box, per_ray);
+    let tests = rays.len() * aabbs.len();
+    println!("{} rays against {} boxes, {per_ray} hits", rays.len(), aabbs.len());
+    println!("reciprocals per box: {per_box_time:?} ({:.1} ns/test)", per_box_time.as_nanos() as f64 / tests as f64);
+    println!("reciprocals per ray: {per_ray_time:?} ({:.1} ns/test)", per_ray_time.as_nanos() as f64 / tests as f64);
+    println!("speedup: {:.2}x", per_box_time.as_secs_f64() / per_ray_time.as_secs_f64());
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! Times finding the closest hit of rays through the random spheres scene's
+//! BVH, as built by each of the builders as a tree, flattened and collapsed
+//! into four-way nodes, and reports the size of the scene's objects, at whichever precision the
+//! crate is built with. Running it with and without the f32 feature gives
+//! the difference that precision makes to traversal.
+//!
+//! cargo run --release --example bvh_timing
+//! cargo run --release --example bvh_timing --features f32
+
+use geometry3d::*;
+use rtow::config::BvhBuilder;
+use rtow::generate::random_spheres;
+use rtow::hit_record::HitContext;
+use rtow::object::*;
+use std::hint::black_box;
+use std::time::Instant;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let origin = Point3::new(13.0, 2.0, 3.0);
+    let rays: Vec<_> = (0..200_000)
+        .filter_map(|i| {
+            let a = i as Float * 0.00001;
+            Ray3::new(origin, Vec3::new(-13.0 + 4.0 * a.sin(), -2.0 + a.cos(), -3.0 + a), 0.0)
+        })
+        .collect();
+    let range = TRange::new(0.001, Float::INFINITY);
+
+    println!("precision: {}", std::any::type_name::<Float>());
+    println!("size of an object: {} bytes", std::mem::size_of::<Object>());
+    println!("size of a flattened node: {} bytes", std::mem::size_of::<FlatNode>());
+    for builder in [BvhBuilder::Sah, BvhBuilder::Median] {
+        let mut config = random_spheres(0);
+        config.scene_list.expand();
+        let t_range = TRange::new(config.camera.time_min, config.camera.time_max);
+        let Object::BVHNode(tree) = BVHNode::from_list(&mut config.scene_list, t_range, builder)? else {
+            return Err("The scene has too few objects for a BVH.".into());
+        };
+        let flat = FlatBVH::from(tree.clone());
+        let bvh4 = BVH4::new(tree.clone(), t_range);
+        println!("{builder:?}: {} nodes, depth {}", flat.nodes().len(), flat.depth());
+        println!("{builder:?} bvh4: {} nodes, depth {}", bvh4.nodes().len(), bvh4.depth());
+
+        let layouts = [("tree", Object::from(tree)), ("flat", Object::from(flat)), ("bvh4", Object::from(bvh4))];
+        for (layout, mut scene) in layouts {
+            let mut ctx = HitContext::default();
+            let start = Instant::now();
+            let mut hits = 0;
+            for &ray in &rays {
+                hits += scene.hit(black_box(ray), range, &mut ctx).is_some() as usize;
+            }
+            let time = start.elapsed();
+
+            println!(
+                "{builder:?} {layout}: {} rays, {hits} hits, {} boxes tested in {} nodes",
+                rays.len(),
+                ctx.aabb_tests,
+                ctx.node_tests
+            );
+            println!(
+                "{builder:?} {layout} traversal: {time:?} ({:.1} ns/ray)",
+                time.as_nanos() as f64 / rays.len() as f64
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Times finding the closest hit of rays through a block of spheres, with
+//! and without a wall in front of it. With the wall the BVH visits it first,
+//! as the ray enters it first, and skips the spheres behind it, so far fewer
+//! bounding boxes are tested.
+//!
+//! cargo run --release --example occlusion_timing
+
+use geometry3d::*;
+use rtow::color::FloatRgb;
+use rtow::config::BvhBuilder;
+use rtow::hit_record::HitContext;
+use rtow::material::Material;
+use rtow::object::*;
+use std::hint::black_box;
+use std::time::Instant;
+
+fn spheres() -> List {
+    let mut list = List::new();
+    for x in -10..10 {
+        for y in -10..10 {
+            for z in -20..0 {
+                let location = Ray3 {
+                    origin: Point3::new(x as Float, y as Float, z as Float),
+                    direction: Vec3::default(),
+                    time: 0.0,
+                };
+                let material = Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5));
+                list.add(Sphere::new(location, 0.3, material).into());
+            }
+        }
+    }
+    list
+}
+
+fn main() {
+    let origin = Point3::new(0.0, 0.0, 10.0);
+    let rays: Vec<_> = (0..100_000)
+        .filter_map(|i| {
+            let a = i as Float * 0.001;
+            Ray3::new(origin, Vec3::new(0.3 * a.sin(), 0.3 * (1.3 * a).cos(), -1.0), 0.0)
+        })
+        .collect();
+    let range = TRange::new(0.001, Float::INFINITY);
+
+    for wall in [false, true] {
+        let mut list = spheres();
+        if wall {
+            let material = Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5));
+            list.add(XYRect::new(TRange::new(-20.0, 20.0), TRange::new(-20.0, 20.0), 2.0, material).into());
+        }
+        let mut scene = BVHNode::from_list(&mut list, TRange::new(0.0, 0.0), BvhBuilder::Sah).unwrap();
+
+        let mut ctx = HitContext::default();
+        let start = Instant::now();
+        let mut hits = 0;
+        for &ray in &rays {
+            hits += scene.hit(black_box(ray), range, &mut ctx).is_some() as usize;
+        }
+        let time = start.elapsed();
+
+        let name = if wall { "with the wall" } else { "without the wall" };
+        let per_ray = ctx.aabb_tests as f64 / rays.len() as f64;
+        println!(
+            "{name}: {} rays, {hits} hits, {per_ray:.1} boxes tested per ray",
+            rays.len()
+        );
+        println!(
+            "{name}: {time:?} ({:.1} ns/ray)",
+            time.as_nanos() as f64 / rays.len() as f64
+        );
+    }
+}
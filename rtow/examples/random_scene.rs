@@ -0,0 +1,14 @@
+//! The final scene of Ray Tracing in One Weekend, with the moving spheres of
+//! The Next Week, built with `SceneBuilder` and written to stdout as JSON.
+//!
+//! cargo run --example random_scene > scene.json
+//!
+//! The same scene with a chosen seed is written by
+//! `rtow generate random-spheres --seed <n>`.
+
+use rtow::generate::random_spheres;
+
+fn main() -> serde_json::Result<()> {
+    println!("{}", serde_json::to_string_pretty(&random_spheres(rand::random()))?);
+    Ok(())
+}
@@ -0,0 +1,34 @@
+//! Times sampling unit vectors from each of the generators that the `rng`
+//! option of a config chooses between.
+//!
+//! cargo run --release --example rng_timing
+
+use geometry3d::*;
+use rtow::config::RngKind;
+use rtow::random::{Generator, Random};
+use std::hint::black_box;
+use std::time::Instant;
+
+const SAMPLES: u32 = 10_000_000;
+
+fn main() {
+    let mut baseline = None;
+    for kind in [RngKind::Std, RngKind::Pcg32, RngKind::Xoshiro256] {
+        let mut rng = Random::new(Generator::seed_from_u64(kind, 0));
+        let start = Instant::now();
+        let mut sum = Vec3::default();
+        for _ in 0..SAMPLES {
+            sum += rng.unit_vector();
+        }
+        let elapsed = start.elapsed();
+        black_box(sum);
+
+        let baseline = *baseline.get_or_insert(elapsed);
+        println!(
+            "{:>10}: {elapsed:?} ({:.1} ns/vector, {:.2}x std)",
+            format!("{kind:?}").to_lowercase(),
+            elapsed.as_nanos() as f64 / SAMPLES as f64,
+            baseline.as_secs_f64() / elapsed.as_secs_f64()
+        );
+    }
+}
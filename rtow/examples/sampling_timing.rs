@@ -0,0 +1,93 @@
+//! Times sampling unit vectors and points in the unit sphere and disk with
+//! the closed-form samplers of `Random`, against the rejection loops they
+//! replaced, counting the random numbers each draws.
+//!
+//! cargo run --release --example sampling_timing
+
+use geometry3d::*;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use rtow::random::Random;
+use std::hint::black_box;
+use std::time::Instant;
+
+/// Counts the random numbers drawn from the generator it wraps.
+struct Counting(StdRng, u64);
+
+impl RngCore for Counting {
+    fn next_u32(&mut self) -> u32 {
+        self.1 += 1;
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.1 += 1;
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.1 += 1;
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.1 += 1;
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+fn rejection_in_sphere(rng: &mut Random<&mut Counting>) -> Vec3 {
+    loop {
+        let v = rng.in_unit_cube();
+        if v.quadrance() <= 1.0 {
+            return v;
+        }
+    }
+}
+
+fn rejection_unit_vector(rng: &mut Random<&mut Counting>) -> Vec3 {
+    loop {
+        if let Some(v) = rejection_in_sphere(rng).unit() {
+            return v;
+        }
+    }
+}
+
+fn rejection_in_disk(rng: &mut Random<&mut Counting>) -> Vec3 {
+    loop {
+        let v = rng.in_unit_cube();
+        let v = Vec3::new(v.x(), v.y(), 0.0);
+        if v.quadrance() <= 1.0 {
+            return v;
+        }
+    }
+}
+
+const SAMPLES: u64 = 5_000_000;
+
+fn time(name: &str, sample: fn(&mut Random<&mut Counting>) -> Vec3) {
+    let mut counting = Counting(StdRng::seed_from_u64(0), 0);
+    let mut rng = Random::new(&mut counting);
+    let start = Instant::now();
+    let mut sum = Vec3::default();
+    for _ in 0..SAMPLES {
+        sum += black_box(sample(&mut rng));
+    }
+    let elapsed = start.elapsed();
+    black_box(sum);
+    let draws = counting.1;
+    println!(
+        "{name:>22}: {elapsed:?} ({:.1} ns/sample, {:.2} draws/sample)",
+        elapsed.as_nanos() as f64 / SAMPLES as f64,
+        draws as f64 / SAMPLES as f64
+    );
+}
+
+fn main() {
+    time("rejection unit vector", rejection_unit_vector);
+    time("unit vector", |rng| rng.unit_vector());
+    time("rejection in sphere", rejection_in_sphere);
+    time("in sphere", |rng| rng.in_unit_sphere());
+    time("rejection in disk", rejection_in_disk);
+    time("in disk", |rng| rng.in_unit_disk());
+}
@@ -0,0 +1,188 @@
+use crate::color::*;
+use crate::config::Aov;
+use crate::image::{write_file, Image};
+use crate::render::FirstHit;
+use geometry3d::*;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+/// Accumulates the first hits of the primary rays of a pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AovAccumulator {
+    normal: Vec3,
+    depth: Float,
+    albedo: FloatRgb,
+    hits: u32,
+    count: u32,
+}
+
+impl AovAccumulator {
+    pub fn new() -> AovAccumulator {
+        AovAccumulator::default()
+    }
+
+    pub fn add(&mut self, first_hit: Option<FirstHit>) {
+        self.count += 1;
+        if let Some(hit) = first_hit {
+            self.normal += hit.normal;
+            self.depth += hit.t;
+            self.albedo = self.albedo + hit.albedo;
+            self.hits += 1;
+        }
+    }
+
+    /// The unit average normal of the hits, or the zero vector if there were
+    /// none.
+    pub fn normal(&self) -> Vec3 {
+        self.normal.unit().unwrap_or_default()
+    }
+
+    /// The average hit distance, or infinity if every ray missed.
+    pub fn depth(&self) -> Float {
+        if self.hits == 0 {
+            Float::INFINITY
+        } else {
+            self.depth / self.hits as Float
+        }
+    }
+
+    /// The average albedo over all rays, with misses counting as black.
+    pub fn albedo(&self) -> FloatRgb {
+        if self.count == 0 {
+            FloatRgb::default()
+        } else {
+            let n = self.count as Float;
+            FloatRgb::new(self.albedo.r() / n, self.albedo.g() / n, self.albedo.b() / n)
+        }
+    }
+}
+
+/// Per pixel first hit accumulators for the auxiliary outputs of a render.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AovBuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<AovAccumulator>,
+}
+
+impl AovBuffer {
+    pub fn new(width: u32, height: u32) -> AovBuffer {
+        let size = width as usize * height as usize;
+        AovBuffer {
+            width,
+            height,
+            pixels: vec![AovAccumulator::new(); size],
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "Pixel ({x}, {y}) out of bounds.");
+        y as usize * self.width as usize + x as usize
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> AovAccumulator {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: AovAccumulator) {
+        let i = self.index(x, y);
+        self.pixels[i] = pixel;
+    }
+
+    pub fn add_sample(&mut self, x: u32, y: u32, first_hit: Option<FirstHit>) {
+        let i = self.index(x, y);
+        self.pixels[i].add(first_hit);
+    }
+
+    /// The name of the file `aov` is written to for an image written to
+    /// `filename`.
+    pub fn filename(filename: &str, aov: Aov) -> String {
+        let name = match aov {
+            Aov::Normal => "normal.png",
+            Aov::Depth => "depth.png",
+            Aov::Albedo => "albedo.png",
+        };
+        std::path::Path::new(filename)
+            .with_extension(name)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Writes each of the AOVs requested by `image` to files next to it.
+    pub fn write(&self, image: &Image) -> std::io::Result<()> {
+        for &aov in &image.aovs {
+            let filename = Self::filename(&image.filename, aov);
+            write_file(&filename, |w| self.encode(aov, image.gamma, w))?;
+        }
+        Ok(())
+    }
+
+    /// Encodes `aov` as a PNG. Normals are remapped from [-1, 1] to [0, 1],
+    /// depth is written as 16-bit grayscale normalized by the furthest hit
+    /// with misses at the maximum, and albedo is gamma corrected like color.
+    pub fn encode<W: Write>(&self, aov: Aov, gamma: Gamma, w: W) -> std::io::Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        let bytes = match aov {
+            Aov::Normal => {
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                self.normal_bytes()
+            }
+            Aov::Depth => {
+                encoder.set_color(png::ColorType::Grayscale);
+                encoder.set_depth(png::BitDepth::Sixteen);
+                self.depth_bytes()
+            }
+            Aov::Albedo => {
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                self.albedo_bytes(gamma)
+            }
+        };
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&bytes)?;
+
+        Ok(())
+    }
+
+    fn normal_bytes(&self) -> Vec<u8> {
+        let remap = |x: Float| ((x * 0.5 + 0.5).clamp(0.0, 1.0) * 255.0).round() as u8;
+        self.pixels
+            .iter()
+            .flat_map(|p| {
+                let n = p.normal();
+                [remap(n.x()), remap(n.y()), remap(n.z())]
+            })
+            .collect()
+    }
+
+    fn depth_bytes(&self) -> Vec<u8> {
+        let max = self
+            .pixels
+            .iter()
+            .map(|p| p.depth())
+            .filter(|d| d.is_finite())
+            .fold(0.0, Float::max);
+        self.pixels
+            .iter()
+            .flat_map(|p| {
+                let depth = match p.depth() {
+                    d if d.is_finite() && max > 0.0 => d / max,
+                    _ => 1.0,
+                };
+                // png stores 16-bit samples big endian
+                ((depth * u16::MAX as Float).round() as u16).to_be_bytes()
+            })
+            .collect()
+    }
+
+    fn albedo_bytes(&self, gamma: Gamma) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|p| {
+                let rgb = Rgb::from_linear(p.albedo(), gamma);
+                [rgb.r(), rgb.g(), rgb.b()]
+            })
+            .collect()
+    }
+}
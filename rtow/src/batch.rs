@@ -0,0 +1,452 @@
+use crate::aov::AovAccumulator;
+use crate::checkpoint::Checkpoint;
+use crate::cli::{print_progress, Args};
+use crate::color::{FRgbAccumulator, FloatRgb};
+use crate::config::{CameraConfig, Config, ConfigFormat, InvalidConfig};
+use crate::object::{BvhStats, Object};
+use crate::progress::ProgressReporter;
+use crate::renderer::{FrameBvh, RenderState, Renderer};
+use crate::texture::{ImageTexture, TextureHeader};
+use geometry3d::*;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// A summary of a single rendered image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderReport {
+    pub output: String,
+    pub width: u32,
+    pub height: u32,
+    pub samples_per_pixel: f64,
+    pub seed: u64,
+    pub elapsed: Duration,
+}
+
+/// A description of a scene checked by a dry run, which found it could be
+/// rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneReport {
+    pub scene: String,
+    /// The number of objects, counting those in lists separately.
+    pub objects: usize,
+    pub bvh: BvhStats,
+    /// The bounding box of the scene, if any of it is bounded.
+    pub bounds: Option<(Point3, Point3)>,
+    pub width: u32,
+    pub height: u32,
+    pub camera: CameraConfig,
+    pub textures: Vec<(String, TextureHeader)>,
+    /// A rough estimate of the memory taken by the render, in bytes.
+    pub memory: u64,
+    /// The memory taken by the nodes of the BVH, in bytes, flattened as it
+    /// is rendered and as the tree it is flattened from.
+    pub bvh_memory: (u64, u64),
+}
+
+/// The outcome of rendering every scene of a batch.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchReport {
+    /// Whether the scenes were only checked, not rendered.
+    pub dry_run: bool,
+    pub rendered: Vec<RenderReport>,
+    /// The scenes that could not be rendered and why.
+    pub failed: Vec<(String, String)>,
+    pub scenes: usize,
+    /// The scenes left unrendered after a failure ended the batch early.
+    pub skipped: usize,
+    pub elapsed: Duration,
+}
+
+impl BatchReport {
+    pub fn succeeded(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Renders each of the scenes named by `args` in turn. A scene that fails
+/// ends the batch unless `continue_on_error` is set, and is recorded in the
+/// report either way.
+///
+/// Errors are only returned for arguments that cannot apply to the whole
+/// batch, i.e. an output file or checkpoint shared by several scenes.
+pub fn render_batch(args: &Args) -> Result<BatchReport, Box<dyn Error>> {
+    let start = Instant::now();
+    let mut report = BatchReport::default();
+
+    let mut scenes = Vec::new();
+    for pattern in &args.filenames {
+        match expand(pattern) {
+            Ok(matches) => scenes.extend(matches.into_iter().map(Ok)),
+            Err(e) => scenes.push(Err((pattern.clone(), e.to_string()))),
+        }
+    }
+    let shared_file = args.output.is_some()
+        || args.checkpoint.is_some()
+        || matches!(args.emit_resolved, Some(Some(_)));
+    if scenes.len() > 1 && shared_file {
+        return Err("--output, --checkpoint and --emit-resolved=<file> can only be used with a single scene.".into());
+    }
+
+    report.scenes = scenes.len();
+    report.dry_run = args.dry_run;
+    for (i, scene) in scenes.into_iter().enumerate() {
+        let result = scene.and_then(|scene| {
+            let result = if args.dry_run {
+                check_scene(&scene, args).map(|checked| {
+                    println!("{checked}");
+                    Vec::new()
+                })
+            } else {
+                render_scene(&scene, args)
+            };
+            result.map_err(|e| (scene, e.to_string()))
+        });
+        match result {
+            Ok(rendered) => {
+                for image in &rendered {
+                    eprintln!("{image}");
+                }
+                report.rendered.extend(rendered);
+            }
+            Err((scene, error)) => {
+                eprintln!("{scene}: {error}");
+                report.failed.push((scene, error));
+                if !args.continue_on_error {
+                    report.skipped = report.scenes - i - 1;
+                    break;
+                }
+            }
+        }
+    }
+
+    report.elapsed = start.elapsed();
+    Ok(report)
+}
+
+/// Reads the scene in `filename` with the changes given by `args` and
+/// validates the result.
+pub fn load_scene(filename: &str, args: &Args) -> Result<Config, Box<dyn Error>> {
+    let format = args.format.unwrap_or_else(|| ConfigFormat::from_path(filename));
+    let mut config = Config::load(filename, format, !args.literal_paths)?;
+    args.apply(&mut config)?;
+    config.validate().map_err(InvalidConfig)?;
+    for warning in config.warnings() {
+        eprintln!("{filename}: warning: {warning}");
+    }
+    Ok(config)
+}
+
+/// Does everything short of rendering the scene in `filename`: reads and
+/// validates it, reads the headers of its image textures and builds its BVH.
+pub fn check_scene(filename: &str, args: &Args) -> Result<SceneReport, Box<dyn Error>> {
+    let config = load_scene(filename, args)?.frame(args.frame.unwrap_or(0))?;
+
+    let mut resolved = config.clone();
+    resolved.resolve_paths();
+    let mut textures = Vec::new();
+    let mut errors = Vec::new();
+    resolved.scene_list.for_each_path(&mut |filename| match ImageTexture::probe(filename) {
+        Ok(header) => textures.push((filename.clone(), header)),
+        Err(e) => errors.push(e),
+    });
+    if !errors.is_empty() {
+        return Err(errors.join("\n").into());
+    }
+
+    let mut list = resolved.scene_list.clone();
+    list.expand();
+    let objects: usize = list.iter().map(count_objects).sum();
+    let camera = config.camera;
+    let renderer = Renderer::new(config)?;
+    let scene = renderer.scene();
+    let bvh = renderer.bvh_stats().clone();
+    let flat_memory = match scene {
+        Object::FlatBVH(flat) => size_of_val(flat.nodes()),
+        Object::BVH4(bvh) => size_of_val(bvh.nodes()),
+        _ => 0,
+    };
+    let bvh_nodes = bvh.nodes;
+    // each node of the tree is an object of its own, where the objects at
+    // its leaves are the same either way
+    let bvh_memory = (
+        flat_memory as u64,
+        (bvh_nodes * size_of::<Object>()) as u64,
+    );
+
+    let image = renderer.image();
+    let pixels = image.width as u64 * image.height as u64;
+    let pixel_size = size_of::<FRgbAccumulator>() + size_of::<AovAccumulator>() + size_of::<FloatRgb>();
+    let memory = pixels * pixel_size as u64
+        + objects as u64 * size_of::<Object>() as u64
+        + bvh_memory.0
+        + textures.iter().map(|(_, header)| header.decoded_size()).sum::<u64>();
+
+    Ok(SceneReport {
+        scene: filename.to_string(),
+        objects,
+        bvh,
+        bounds: scene.bounding_box(TRange::new(camera.time_min, camera.time_max)).map(|b| (b.lo(), b.hi())),
+        width: image.width,
+        height: image.height,
+        camera,
+        textures,
+        memory,
+        bvh_memory,
+    })
+}
+
+fn count_objects(object: &Object) -> usize {
+    match object {
+        Object::List(list) => list.iter().map(count_objects).sum(),
+        Object::Named(named) => count_objects(&named.object),
+        _ => 1,
+    }
+}
+
+/// Renders the scene in `filename`, or the frames of it selected by `args`
+/// if it is an animation.
+pub fn render_scene(filename: &str, args: &Args) -> Result<Vec<RenderReport>, Box<dyn Error>> {
+    let config = load_scene(filename, args)?;
+    if config.animation.is_some() && args.checkpoint.is_some() && args.frame.is_none() {
+        return Err("Checkpoints of an animation require --frame.".into());
+    }
+
+    // overrides that do not change the image are left out of the checkpoint
+    // hash so that they can differ when resuming
+    let mut output = config.clone();
+    if let Some(filename) = &args.output {
+        output.image.filename = filename.clone();
+        output.image.output_relative_to_scene = false;
+    }
+    if let Some(budget) = args.time_budget {
+        match &mut output.progressive {
+            Some(progressive) => progressive.time_budget = Some(budget),
+            None => return Err("--time-budget requires a progressive render.".into()),
+        }
+    }
+    // a resumed render carries on with the seed it was started with, which
+    // a seed given in the scene or by --seed must agree with
+    let mut resumed = None;
+    if let (Some(path), true) = (&args.checkpoint, args.resume) {
+        let config_hash = checkpoint_hash(&config, args.frame.unwrap_or(0))?;
+        let saved = Checkpoint::new(path, config_hash).load()?;
+        if let Some(seed) = config.seed.filter(|&seed| seed != saved.seed) {
+            return Err(format!("The seed {seed} differs from the seed {} of checkpoint {path}.", saved.seed).into());
+        }
+        output.seed = Some(saved.seed);
+        resumed = Some(saved.state);
+    }
+    // every frame shares the seed, so that it can be written out
+    output.seed.get_or_insert_with(rand::random);
+
+    if let Some(path) = &args.emit_resolved {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => Path::new(filename).with_extension("resolved.json"),
+        };
+        std::fs::write(path, output.resolved_json()?)?;
+    }
+
+    let frames = match args.frame {
+        Some(frame) => frame..frame + 1,
+        None => 0..config.frames(),
+    };
+    let mut bvh = config.animation.is_some_and(|animation| animation.refit).then(FrameBvh::default);
+    let mut reports = Vec::new();
+    for frame in frames {
+        if config.animation.is_some() {
+            eprintln!("Frame {frame}");
+        }
+        let config_hash = checkpoint_hash(&config, frame)?;
+        reports.push(render_image(output.frame(frame)?, config_hash, resumed.take(), bvh.as_mut(), args)?);
+    }
+    Ok(reports)
+}
+
+/// The hash that the checkpoint of a frame is saved under. The seed is left
+/// out, as it is saved along with the render and checked on its own.
+fn checkpoint_hash(config: &Config, frame: u32) -> Result<u64, Box<dyn Error>> {
+    let mut config = config.frame(frame)?;
+    config.seed = None;
+    Ok(config.hash())
+}
+
+/// Renders the image of `config`, carrying on from the `resumed` state of a
+/// checkpoint if there is one.
+fn render_image(
+    config: Config,
+    config_hash: u64,
+    resumed: Option<RenderState>,
+    bvh: Option<&mut FrameBvh>,
+    args: &Args,
+) -> Result<RenderReport, Box<dyn Error>> {
+    let start = Instant::now();
+    let checkpoint = args
+        .checkpoint
+        .as_ref()
+        .map(|path| Checkpoint::new(path, config_hash));
+
+    let mut renderer = match bvh {
+        Some(bvh) => Renderer::for_frame(config, bvh)?,
+        None => Renderer::new(config)?,
+    };
+    eprintln!("Seed: {}", renderer.seed());
+    let mut state = resumed.unwrap_or_else(|| renderer.new_state());
+
+    let interval = renderer.image().width as u64;
+    let mut progress = ProgressReporter::new(renderer.total_pixels(), interval, Some(print_progress));
+    progress.advance((0..state.completed).map(|unit| renderer.unit_pixels(unit)).sum());
+
+    while !renderer.finished(&state) {
+        renderer.render_unit(&mut state);
+        progress.advance(renderer.unit_pixels(state.completed - 1));
+        if let Some(checkpoint) = &checkpoint {
+            if state.completed.is_multiple_of(args.checkpoint_interval) {
+                checkpoint.save(renderer.seed(), &state)?;
+            }
+        }
+        if renderer.snapshot_due(&state) {
+            renderer.write(&state)?;
+        }
+    }
+
+    renderer.write(&state)?;
+    eprint!("\nDone.\n");
+    if args.time_budget.is_some() {
+        eprintln!("Completed {} of {} passes.", state.completed, renderer.units());
+    }
+    if cfg!(feature = "stats") {
+        eprintln!("{}", state.stats);
+    }
+
+    let image = renderer.image();
+    Ok(RenderReport {
+        output: image.filename.clone(),
+        width: image.width,
+        height: image.height,
+        samples_per_pixel: state.framebuffer.samples_per_pixel(),
+        seed: renderer.seed(),
+        elapsed: start.elapsed(),
+    })
+}
+
+/// The files matching `pattern`, in name order, where `*` and `?` in the
+/// file name match any run of characters and any single character. Patterns
+/// without wildcards are returned as they are.
+pub fn expand(pattern: &str) -> std::io::Result<Vec<String>> {
+    let path = Path::new(pattern);
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) if name.contains(['*', '?']) => name,
+        _ => return Ok(vec![pattern.to_string()]),
+    };
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+
+    let name: Vec<char> = name.chars().collect();
+    let mut matches = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let chars: Vec<char> = file_name.chars().collect();
+        if entry.path().is_file() && wildcard_match(&name, &chars) {
+            matches.push(path.with_file_name(file_name).to_string_lossy().into_owned());
+        }
+    }
+    if matches.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "No scene files match the pattern.",
+        ));
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| wildcard_match(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && wildcard_match(rest, &name[1..]),
+        Some((&c, rest)) => name.first() == Some(&c) && wildcard_match(rest, &name[1..]),
+    }
+}
+
+impl fmt::Display for RenderReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} x {}, {:.1} samples per pixel, seed {}, {:.1}s",
+            self.output,
+            self.width,
+            self.height,
+            self.samples_per_pixel,
+            self.seed,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+impl fmt::Display for SceneReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let point = |p: Point3| format!("({}, {}, {})", p.x(), p.y(), p.z());
+        write!(f, "{}: {} objects, BVH depth {}", self.scene, self.objects, self.bvh.max_depth)?;
+        if let Some((lo, hi)) = self.bounds {
+            write!(f, ", bounds {} to {}", point(lo), point(hi))?;
+        }
+        let camera = &self.camera;
+        write!(
+            f,
+            "\n  Camera: from {} towards {}, {} degree vertical field of view, aperture {}, \
+             focus distance {}, time {} to {}",
+            point(camera.look_from),
+            point(camera.look_at),
+            camera.vertical_fov,
+            camera.aperture,
+            camera.focus_distance,
+            camera.time_min,
+            camera.time_max
+        )?;
+        write!(f, "\n  Image: {} x {}", self.width, self.height)?;
+        for (filename, header) in &self.textures {
+            write!(f, "\n  Texture {filename}: {} x {}", header.width, header.height)?;
+        }
+        let bvh = &self.bvh;
+        write!(
+            f,
+            "\n  BVH: {} nodes, {} leaves, {:.1} kB flattened against {:.1} kB as a tree",
+            bvh.nodes,
+            bvh.leaves,
+            self.bvh_memory.0 as f64 / 1e3,
+            self.bvh_memory.1 as f64 / 1e3
+        )?;
+        write!(
+            f,
+            "\n  BVH shape: average depth {:.1}, {:.1} objects per leaf, node area {:.1}, sibling overlap {:.1}",
+            bvh.average_depth, bvh.average_leaf_objects, bvh.interior_surface_area, bvh.sibling_overlap
+        )?;
+        write!(f, "\n  Estimated memory: {:.1} MB", self.memory as f64 / 1e6)
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.dry_run {
+            write!(f, "Checked {} scenes", self.scenes)?;
+        } else {
+            write!(f, "Rendered {} images from {} scenes", self.rendered.len(), self.scenes)?;
+        }
+        write!(f, " in {:.1}s, {} failed", self.elapsed.as_secs_f64(), self.failed.len())?;
+        if self.skipped > 0 {
+            write!(f, " and {} skipped", self.skipped)?;
+        }
+        write!(f, ".")?;
+        for (scene, error) in &self.failed {
+            write!(f, "\nFailed {scene}: {error}")?;
+        }
+        Ok(())
+    }
+}
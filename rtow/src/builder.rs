@@ -0,0 +1,372 @@
+use crate::color::{FloatRgb, Gamma};
+use crate::config::*;
+use crate::material::Material;
+use crate::object::*;
+use geometry3d::*;
+
+/// Builds a `Config` from code, as an alternative to writing the JSON by hand.
+///
+/// Objects are added by naming a shape, optionally giving it a material, and
+/// finishing it with `add`:
+///
+/// ```
+/// use geometry3d::*;
+/// use rtow::builder::SceneBuilder;
+/// use rtow::color::FloatRgb;
+/// use rtow::material::Material;
+///
+/// let config = SceneBuilder::new()
+///     .sphere(Point3::new(0.0, 0.0, -1.0), 0.5)
+///     .material(Material::lambertian(FloatRgb::new(0.1, 0.2, 0.5)))
+///     .add()
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct SceneBuilder {
+    image: ImageConfig,
+    camera: CameraConfig,
+    sampler: SamplerConfig,
+    background_color: FloatRgb,
+    scene_list: List,
+}
+
+/// A shape waiting for its material before being added to the scene.
+#[derive(Debug, Clone)]
+pub struct ObjectBuilder {
+    scene: SceneBuilder,
+    shape: Shape,
+    material: Material,
+    name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Shape {
+    Sphere { location: Ray3, radius: Float },
+    XYRect { x: TRange<Float>, y: TRange<Float>, z: Float },
+    XZRect { x: TRange<Float>, z: TRange<Float>, y: Float },
+    YZRect { y: TRange<Float>, z: TRange<Float>, x: Float },
+    RectPrism { p0: Point3, p1: Point3 },
+}
+
+impl SceneBuilder {
+    /// An empty scene under a sky blue background, seen through the default
+    /// camera and image, with 16 samples per pixel.
+    pub fn new() -> SceneBuilder {
+        SceneBuilder {
+            image: ImageBuilder::new().build(),
+            camera: CameraBuilder::new().build(),
+            sampler: SamplerConfig {
+                n: 4,
+                ..SamplerConfig::default()
+            },
+            background_color: Config::default_background_color(),
+            scene_list: List::new(),
+        }
+    }
+
+    pub fn image(mut self, image: ImageConfig) -> SceneBuilder {
+        self.image = image;
+        self
+    }
+
+    pub fn camera(mut self, camera: CameraConfig) -> SceneBuilder {
+        self.camera = camera;
+        self
+    }
+
+    pub fn sampler(mut self, sampler: SamplerConfig) -> SceneBuilder {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn background(mut self, color: FloatRgb) -> SceneBuilder {
+        self.background_color = color;
+        self
+    }
+
+    /// Adds an already constructed object.
+    pub fn object<O: Into<Object>>(mut self, object: O) -> SceneBuilder {
+        self.scene_list.add(object.into());
+        self
+    }
+
+    pub fn sphere(self, center: Point3, radius: Float) -> ObjectBuilder {
+        let location = Ray3 {
+            origin: center,
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+        self.shape(Shape::Sphere { location, radius })
+    }
+
+    /// A sphere moving in a straight line from `center0` at `time0` to
+    /// `center1` at `time1`.
+    pub fn moving_sphere(
+        self,
+        (center0, time0): (Point3, Float),
+        (center1, time1): (Point3, Float),
+        radius: Float,
+    ) -> ObjectBuilder {
+        let location = Ray3 {
+            origin: center0,
+            direction: (center1 - center0) / (time1 - time0),
+            time: time0,
+        };
+        self.shape(Shape::Sphere { location, radius })
+    }
+
+    /// A rectangle in the plane at `z`.
+    pub fn xy_rect(self, x: (Float, Float), y: (Float, Float), z: Float) -> ObjectBuilder {
+        let (x, y) = (TRange::new(x.0, x.1), TRange::new(y.0, y.1));
+        self.shape(Shape::XYRect { x, y, z })
+    }
+
+    /// A rectangle in the plane at `y`.
+    pub fn xz_rect(self, x: (Float, Float), z: (Float, Float), y: Float) -> ObjectBuilder {
+        let (x, z) = (TRange::new(x.0, x.1), TRange::new(z.0, z.1));
+        self.shape(Shape::XZRect { x, z, y })
+    }
+
+    /// A rectangle in the plane at `x`.
+    pub fn yz_rect(self, y: (Float, Float), z: (Float, Float), x: Float) -> ObjectBuilder {
+        let (y, z) = (TRange::new(y.0, y.1), TRange::new(z.0, z.1));
+        self.shape(Shape::YZRect { y, z, x })
+    }
+
+    /// An axis aligned box with opposite corners `p0` and `p1`.
+    pub fn rect_prism(self, p0: Point3, p1: Point3) -> ObjectBuilder {
+        self.shape(Shape::RectPrism { p0, p1 })
+    }
+
+    fn shape(self, shape: Shape) -> ObjectBuilder {
+        ObjectBuilder {
+            scene: self,
+            shape,
+            material: Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5)),
+            name: None,
+        }
+    }
+
+    pub fn build(self) -> Config {
+        Config {
+            image: self.image,
+            camera: self.camera,
+            sampler: self.sampler,
+            scene_list: self.scene_list,
+            background_color: self.background_color,
+            progressive: None,
+            render_mode: RenderMode::default(),
+            heat_map: HeatMapConfig::default(),
+            ao: AoConfig::default(),
+            depth: DepthConfig::default(),
+            bvh: BvhViewConfig::default(),
+            bvh_builder: BvhBuilder::default(),
+            accelerator: Accelerator::default(),
+            clamp: None,
+            light_sampling: false,
+            lights: Vec::new(),
+            seed: None,
+            rng: RngKind::default(),
+            animation: None,
+            base_dir: None,
+        }
+    }
+}
+
+impl Default for SceneBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ObjectBuilder {
+    /// Sets the material of the object, which is otherwise grey Lambertian.
+    pub fn material<M: Into<Material>>(mut self, material: M) -> ObjectBuilder {
+        self.material = material.into();
+        self
+    }
+
+    /// Names the object so that it can be referred to, e.g. in the lights.
+    pub fn name<S: Into<String>>(mut self, name: S) -> ObjectBuilder {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Adds the object to the scene.
+    pub fn add(self) -> SceneBuilder {
+        let material = self.material;
+        let object: Object = match self.shape {
+            Shape::Sphere { location, radius } => Sphere::new(location, radius, material).into(),
+            Shape::XYRect { x, y, z } => XYRect::new(x, y, z, material).into(),
+            Shape::XZRect { x, z, y } => XZRect::new(x, z, y, material).into(),
+            Shape::YZRect { y, z, x } => YZRect::new(y, z, x, material).into(),
+            Shape::RectPrism { p0, p1 } => RectPrism::new(p0, p1, material).into(),
+        };
+        match self.name {
+            Some(name) => self.scene.object(Named::new(name, object)),
+            None => self.scene.object(object),
+        }
+    }
+}
+
+/// Builds a `CameraConfig`, by default a pinhole camera at (0, 0, 1) looking
+/// towards the origin with a 90 degree field of view and the shutter open from
+/// time 0 to 1.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraBuilder {
+    config: CameraConfig,
+    focus_distance: Option<Float>,
+}
+
+impl CameraBuilder {
+    pub fn new() -> CameraBuilder {
+        CameraBuilder {
+            config: CameraConfig {
+                look_from: Point3::new(0.0, 0.0, 1.0),
+                look_at: Point3::new(0.0, 0.0, 0.0),
+                up: Vec3::new(0.0, 1.0, 0.0),
+                vertical_fov: 90.0,
+                aperture: 0.0,
+                focus_distance: 1.0,
+                time_min: 0.0,
+                time_max: 1.0,
+            },
+            focus_distance: None,
+        }
+    }
+
+    pub fn look_from(mut self, look_from: Point3) -> CameraBuilder {
+        self.config.look_from = look_from;
+        self
+    }
+
+    pub fn look_at(mut self, look_at: Point3) -> CameraBuilder {
+        self.config.look_at = look_at;
+        self
+    }
+
+    pub fn up(mut self, up: Vec3) -> CameraBuilder {
+        self.config.up = up;
+        self
+    }
+
+    /// The vertical field of view in degrees.
+    pub fn vertical_fov(mut self, degrees: Float) -> CameraBuilder {
+        self.config.vertical_fov = degrees;
+        self
+    }
+
+    pub fn aperture(mut self, aperture: Float) -> CameraBuilder {
+        self.config.aperture = aperture;
+        self
+    }
+
+    /// The distance to the plane in perfect focus, by default the distance
+    /// from `look_from` to `look_at`.
+    pub fn focus_distance(mut self, distance: Float) -> CameraBuilder {
+        self.focus_distance = Some(distance);
+        self
+    }
+
+    pub fn time(mut self, time_min: Float, time_max: Float) -> CameraBuilder {
+        self.config.time_min = time_min;
+        self.config.time_max = time_max;
+        self
+    }
+
+    pub fn build(self) -> CameraConfig {
+        let focus_distance = self
+            .focus_distance
+            .unwrap_or_else(|| (self.config.look_at - self.config.look_from).length());
+        CameraConfig {
+            focus_distance,
+            ..self.config
+        }
+    }
+}
+
+impl Default for CameraBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds an `ImageConfig`, by default a 400 x 225 sRGB `render.png`.
+#[derive(Debug, Clone)]
+pub struct ImageBuilder {
+    config: ImageConfig,
+}
+
+impl ImageBuilder {
+    pub fn new() -> ImageBuilder {
+        ImageBuilder {
+            config: ImageConfig::default(),
+        }
+    }
+
+    pub fn filename<S: Into<String>>(mut self, filename: S) -> ImageBuilder {
+        self.config.filename = filename.into();
+        self
+    }
+
+    pub fn size(mut self, width: u32, height: u32) -> ImageBuilder {
+        self.config.width = width;
+        self.config.height = Some(height);
+        self.config.aspect_ratio = None;
+        self
+    }
+
+    /// Sets the width, with the height following from the aspect ratio.
+    pub fn aspect_ratio(mut self, width: u32, aspect_ratio: Float) -> ImageBuilder {
+        self.config.width = width;
+        self.config.height = None;
+        self.config.aspect_ratio = Some(aspect_ratio);
+        self
+    }
+
+    pub fn tile_size(mut self, tile_size: u32) -> ImageBuilder {
+        self.config.tile_size = tile_size;
+        self
+    }
+
+    pub fn format(mut self, format: ImageFormat) -> ImageBuilder {
+        self.config.format = Some(format);
+        self
+    }
+
+    pub fn alpha(mut self, alpha: bool) -> ImageBuilder {
+        self.config.alpha = alpha;
+        self
+    }
+
+    pub fn gamma(mut self, gamma: Gamma) -> ImageBuilder {
+        self.config.gamma = gamma;
+        self
+    }
+
+    pub fn aov(mut self, aov: Aov) -> ImageBuilder {
+        self.config.aovs.push(aov);
+        self
+    }
+
+    pub fn denoise(mut self, denoise: bool) -> ImageBuilder {
+        self.config.denoise = denoise;
+        self
+    }
+
+    /// Brightens the image by `stops`.
+    pub fn exposure(mut self, stops: Float) -> ImageBuilder {
+        self.config.exposure = stops;
+        self
+    }
+
+    pub fn build(self) -> ImageConfig {
+        self.config
+    }
+}
+
+impl Default for ImageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
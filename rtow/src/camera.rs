@@ -1,4 +1,4 @@
-use crate::random::Random;
+use crate::random;
 use crate::image::Image;
 use crate::config::*;
 use geometry3d::*;
@@ -10,15 +10,15 @@ pub struct Camera {
     lower_left_corner: Point3,
     u: Vec3,
     v: Vec3,
-    lens_radius: f64,
-    pub time_min: f64,
-    pub time_max: f64,
+    lens_radius: Float,
+    pub time_min: Float,
+    pub time_max: Float,
 }
 
 impl Camera {
     pub fn new(config: CameraConfig, image: &Image) -> Camera {
         let theta = config.vertical_fov.to_radians();
-        let h = f64::tan(theta / 2.0);
+        let h = Float::tan(theta / 2.0);
         let viewport_height = 2.0 * h;
         let viewport_width = image.aspect_ratio * viewport_height;
 
@@ -58,23 +58,29 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray3 {
-        let mut rng = Random::new(rand::thread_rng());
-        let rd = self.lens_radius * rng.in_unit_disk();
+    pub fn get_ray(&self, s: Float, t: Float) -> Ray3 {
+        let rd = random::rng().in_unit_disk();
+        self.ray_through_lens(s, t, rd)
+    }
+
+    /// The ray through (s, t) from the point of the lens that the point
+    /// `lens` of the unit square maps to.
+    pub fn get_ray_with_lens(&self, s: Float, t: Float, lens: (Float, Float)) -> Ray3 {
+        self.ray_through_lens(s, t, random::square_to_disk(lens.0, lens.1))
+    }
+
+    /// The ray through (s, t) from the point `rd` of the unit disk scaled to
+    /// the lens.
+    fn ray_through_lens(&self, s: Float, t: Float, rd: Vec3) -> Ray3 {
+        let rd = self.lens_radius * rd;
         let offset = rd.x() * self.u + rd.y() * self.v;
 
         let origin = self.origin + offset;
-        let direction = (self.lower_left_corner + s * self.horizontal + t * self.vertical
+        let direction = self.lower_left_corner + s * self.horizontal + t * self.vertical
             - self.origin
-            - offset)
-            .unit()
-            .unwrap();
-        let time = rng.random_range(self.time_min..=self.time_max);
+            - offset;
+        let time = random::rng().random_range(self.time_min..=self.time_max);
 
-        Ray3 {
-            origin,
-            direction,
-            time,
-        }
+        Ray3::new(origin, direction, time).expect("The focus distance of the camera is positive.")
     }
 }
@@ -0,0 +1,103 @@
+use crate::renderer::RenderState;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+/// Saves and restores the accumulated state of a render so that it can be
+/// resumed after being interrupted.
+///
+/// Progress is recorded as the number of completed units of work, i.e. tiles
+/// or progressive passes, which are always performed in the same order,
+/// along with the seed of the render, which may have been chosen at random
+/// and must be used again for the rest of the units.
+pub struct Checkpoint {
+    path: PathBuf,
+    config_hash: u64,
+}
+
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    config_hash: u64,
+    seed: u64,
+    state: &'a RenderState,
+}
+
+#[derive(Deserialize)]
+struct OwnedSnapshot {
+    config_hash: u64,
+    seed: u64,
+    state: RenderState,
+}
+
+/// A render restored from a checkpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedRender {
+    /// The seed the render was started with.
+    pub seed: u64,
+    pub state: RenderState,
+}
+
+impl Checkpoint {
+    pub fn new<P: Into<PathBuf>>(path: P, config_hash: u64) -> Checkpoint {
+        Checkpoint {
+            path: path.into(),
+            config_hash,
+        }
+    }
+
+    /// Saves the state of a render made with `seed`.
+    pub fn save(&self, seed: u64, state: &RenderState) -> Result<(), Box<dyn Error>> {
+        let snapshot = Snapshot {
+            config_hash: self.config_hash,
+            seed,
+            state,
+        };
+
+        // write to a temporary file first so that an interrupted write never
+        // replaces the previous checkpoint with a truncated one
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        {
+            let file = File::create(&tmp_path)?;
+            let w = BufWriter::new(file);
+            bincode::serialize_into(w, &snapshot)?;
+        }
+        std::fs::rename(tmp_path, &self.path)?;
+
+        Ok(())
+    }
+
+    /// Loads the seed and state of the render, failing if the checkpoint was
+    /// made with a different config.
+    pub fn load(&self) -> Result<SavedRender, Box<dyn Error>> {
+        let file = File::open(&self.path)?;
+        let reader = BufReader::new(file);
+        let snapshot: OwnedSnapshot = bincode::deserialize_from(reader)?;
+
+        if snapshot.config_hash != self.config_hash {
+            return Err(format!(
+                "Checkpoint {} was made with a different config.",
+                self.path.display()
+            )
+            .into());
+        }
+
+        Ok(SavedRender {
+            seed: snapshot.seed,
+            state: snapshot.state,
+        })
+    }
+}
+
+/// The 64 bit FNV-1a hash of `bytes`, which unlike the hashers of the
+/// standard library is fixed, so it can key files kept on disk.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes
+        .iter()
+        .fold(OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
@@ -0,0 +1,234 @@
+use crate::config::{Config, ConfigFormat, RenderMode};
+use crate::generate::Generator;
+use crate::progress::Progress;
+use std::error::Error;
+
+/// What the renderer has been asked to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Render(Args),
+    /// Prints the differences between two PNG images.
+    Compare(String, String),
+    /// Writes a built-in scene to the given file, or else to stdout as JSON.
+    Generate(Generator, Option<String>),
+}
+
+impl Command {
+    const COMPARE_USAGE: &'static str = "Usage: rtow compare <a.png> <b.png>";
+    const GENERATE_USAGE: &'static str =
+        "Usage: rtow generate cornell [--out <scene.json>]\n       \
+         rtow generate random-spheres [--seed <n>] [--out <scene.json>]";
+
+    /// Parses the arguments of the current process.
+    pub fn parse() -> Result<Command, Box<dyn Error>> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    /// Parses `args`, which should not include the program name.
+    pub fn parse_from<I: IntoIterator<Item = String>>(args: I) -> Result<Command, Box<dyn Error>> {
+        let mut args = args.into_iter().peekable();
+        match args.peek().map(String::as_str) {
+            Some("compare") => {
+                args.next();
+                match (args.next(), args.next(), args.next()) {
+                    (Some(a), Some(b), None) => Ok(Command::Compare(a, b)),
+                    _ => Err(Self::COMPARE_USAGE.into()),
+                }
+            }
+            Some("generate") => {
+                args.next();
+                Self::parse_generate(args)
+            }
+            _ => Ok(Command::Render(Args::parse_from(args)?)),
+        }
+    }
+
+    fn parse_generate<I: Iterator<Item = String>>(mut args: I) -> Result<Command, Box<dyn Error>> {
+        let scene = args.next().ok_or(Self::GENERATE_USAGE)?;
+        let mut out = None;
+        let mut seed = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--out" => out = Some(args.next().ok_or(Self::GENERATE_USAGE)?),
+                "--seed" if scene == "random-spheres" => {
+                    seed = Some(args.next().ok_or(Self::GENERATE_USAGE)?.parse()?)
+                }
+                _ => return Err(Self::GENERATE_USAGE.into()),
+            }
+        }
+
+        let generator = match scene.as_str() {
+            "cornell" => Generator::Cornell,
+            "random-spheres" => Generator::RandomSpheres {
+                seed: seed.unwrap_or_else(rand::random),
+            },
+            _ => return Err(Self::GENERATE_USAGE.into()),
+        };
+        Ok(Command::Generate(generator, out))
+    }
+}
+
+/// The command line arguments of the renderer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    /// The scene files to render in turn, which may include `*` and `?`
+    /// wildcards in their file names.
+    pub filenames: Vec<String>,
+    /// Reads the scene files in this format rather than by extension.
+    pub format: Option<ConfigFormat>,
+    pub output: Option<String>,
+    pub render_mode: Option<RenderMode>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// The sampler's `n`.
+    pub samples: Option<u32>,
+    pub max_depth: Option<u32>,
+    pub seed: Option<u64>,
+    /// Fields of the config to change, as a dotted path and a value, from
+    /// `--set path.to.field=value`.
+    pub set: Vec<(String, String)>,
+    /// The time budget of a progressive render in seconds.
+    pub time_budget: Option<f64>,
+    /// Renders only this frame of an animation.
+    pub frame: Option<u32>,
+    pub checkpoint: Option<String>,
+    pub checkpoint_interval: u32,
+    pub resume: bool,
+    /// Carries on with the rest of the scenes after one fails.
+    pub continue_on_error: bool,
+    /// Writes the config of each scene as rendered, to the given file or
+    /// else to `{name}.resolved.json` next to the scene file.
+    pub emit_resolved: Option<Option<String>>,
+    /// Checks that each scene can be rendered and describes it, rather than
+    /// rendering it.
+    pub dry_run: bool,
+    /// Leaves environment variables and `~` in the paths of the config as
+    /// written, so that the render doesn't depend on the environment.
+    pub literal_paths: bool,
+}
+
+impl Args {
+    const USAGE: &'static str =
+        "Usage: rtow [scene.json...] [--format <json|toml|yaml>] [--output <file|->] \
+         [--render-mode <mode>] [--width <pixels>] [--height <pixels>] [--samples <n>] \
+         [--max-depth <n>] [--seed <n>] [--set <path.to.field=value>]... \
+         [--time-budget <seconds>] [--frame <n>] [--continue-on-error] [--emit-resolved[=<file>]] \
+         [--dry-run] [--literal-paths] [--checkpoint <file> [--checkpoint-interval <n>] [--resume]]\n       \
+         rtow compare <a.png> <b.png>\n       \
+         rtow generate <cornell|random-spheres> [--seed <n>] [--out <scene.json>]";
+
+    /// Parses the arguments of the current process.
+    pub fn parse() -> Result<Args, Box<dyn Error>> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    /// Parses `args`, which should not include the program name.
+    pub fn parse_from<I: IntoIterator<Item = String>>(args: I) -> Result<Args, Box<dyn Error>> {
+        let mut parsed = Args {
+            filenames: Vec::new(),
+            format: None,
+            output: None,
+            render_mode: None,
+            width: None,
+            height: None,
+            samples: None,
+            max_depth: None,
+            seed: None,
+            set: Vec::new(),
+            time_budget: None,
+            frame: None,
+            checkpoint: None,
+            checkpoint_interval: 16,
+            resume: false,
+            continue_on_error: false,
+            emit_resolved: None,
+            dry_run: false,
+            literal_paths: false,
+        };
+
+        let mut iter = args.into_iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--format" => parsed.format = Some(iter.next().ok_or(Self::USAGE)?.parse()?),
+                "--output" => parsed.output = Some(iter.next().ok_or(Self::USAGE)?),
+                "--render-mode" => parsed.render_mode = Some(iter.next().ok_or(Self::USAGE)?.parse()?),
+                "--width" => parsed.width = Some(iter.next().ok_or(Self::USAGE)?.parse()?),
+                "--height" => parsed.height = Some(iter.next().ok_or(Self::USAGE)?.parse()?),
+                "--samples" => parsed.samples = Some(iter.next().ok_or(Self::USAGE)?.parse()?),
+                "--max-depth" => parsed.max_depth = Some(iter.next().ok_or(Self::USAGE)?.parse()?),
+                "--seed" => parsed.seed = Some(iter.next().ok_or(Self::USAGE)?.parse()?),
+                "--set" => {
+                    let set = iter.next().ok_or(Self::USAGE)?;
+                    let (path, value) = set.split_once('=').ok_or("--set takes path.to.field=value.")?;
+                    parsed.set.push((path.to_string(), value.to_string()));
+                }
+                "--time-budget" => parsed.time_budget = Some(iter.next().ok_or(Self::USAGE)?.parse()?),
+                "--frame" => parsed.frame = Some(iter.next().ok_or(Self::USAGE)?.parse()?),
+                "--checkpoint" => parsed.checkpoint = Some(iter.next().ok_or(Self::USAGE)?),
+                "--checkpoint-interval" => {
+                    parsed.checkpoint_interval = iter.next().ok_or(Self::USAGE)?.parse()?;
+                    if parsed.checkpoint_interval == 0 {
+                        return Err("The checkpoint interval must be at least one.".into());
+                    }
+                }
+                "--resume" => parsed.resume = true,
+                "--continue-on-error" => parsed.continue_on_error = true,
+                "--emit-resolved" => parsed.emit_resolved = Some(None),
+                "--dry-run" => parsed.dry_run = true,
+                "--literal-paths" => parsed.literal_paths = true,
+                _ if arg.starts_with("--emit-resolved=") => {
+                    let path = &arg["--emit-resolved=".len()..];
+                    parsed.emit_resolved = Some(Some(path.to_string()));
+                }
+                _ if arg.starts_with("--") => return Err(Self::USAGE.into()),
+                _ => parsed.filenames.push(arg),
+            }
+        }
+
+        if parsed.filenames.is_empty() {
+            parsed.filenames.push(String::from("scene.json"));
+        }
+        if parsed.resume && parsed.checkpoint.is_none() {
+            return Err("--resume requires --checkpoint.".into());
+        }
+
+        Ok(parsed)
+    }
+
+    /// Changes the values of `config` given on the command line, other than
+    /// those that do not affect the image, which are left to the renderer.
+    pub fn apply(&self, config: &mut Config) -> Result<(), Box<dyn Error>> {
+        if let Some(render_mode) = self.render_mode {
+            config.render_mode = render_mode;
+        }
+        if let Some(width) = self.width {
+            config.image.width = width;
+        }
+        if let Some(height) = self.height {
+            // the height replaces an aspect ratio rather than conflicting
+            config.image.height = Some(height);
+            config.image.aspect_ratio = None;
+        }
+        if let Some(samples) = self.samples {
+            config.sampler.n = samples;
+        }
+        if let Some(max_depth) = self.max_depth {
+            config.sampler.max_depth = max_depth;
+        }
+        if let Some(seed) = self.seed {
+            config.seed = Some(seed);
+        }
+        for (path, value) in &self.set {
+            config.set(path, value)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn print_progress(progress: Progress) {
+    eprint!(
+        "\rProgress: {:5.1}% ({:.1}s elapsed) ",
+        100.0 * progress.fraction(),
+        progress.elapsed.as_secs_f64()
+    );
+}
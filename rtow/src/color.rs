@@ -1,8 +1,10 @@
+use geometry3d::Float;
 use ntuple::*;
 use ntuple_derive::*;
 use serde::{Serialize, Deserialize};
 
 #[derive(Copy, Clone, Default, PartialEq, Debug, NTupleNewtype)]
+#[ntuple(index)]
 pub struct Rgb(NTuple<u8, 3>);
 
 impl Rgb {
@@ -23,9 +25,77 @@ impl Rgb {
     }
 }
 
-impl std::convert::From<FloatRgb> for Rgb {
-    fn from(frgb: FloatRgb) -> Self {
-        Self(frgb.0.map(|x| (x.sqrt() * (256.0 - f64::EPSILON)) as u8))
+impl Rgb {
+    /// Converts a linear color to 8-bit display values using the transfer
+    /// function of `gamma`.
+    pub fn from_linear(frgb: FloatRgb, gamma: Gamma) -> Self {
+        Self(frgb.0.map(|x| (gamma.encode(x) * (256.0 - Float::EPSILON)) as u8))
+    }
+}
+
+/// Transfer function from linear light to display values.
+#[derive(Copy, Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "GammaRepr", into = "GammaRepr")]
+pub enum Gamma {
+    /// The piecewise sRGB transfer function.
+    #[default]
+    Srgb,
+    /// A pure power law with the given gamma, i.e. `x^(1/gamma)`.
+    Power(Float),
+}
+
+impl Gamma {
+    /// Encodes a linear value, clamped to [0, 1], as a display value in [0, 1].
+    pub fn encode(self, x: Float) -> Float {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            Gamma::Srgb => {
+                if x <= 0.0031308 {
+                    12.92 * x
+                } else {
+                    1.055 * x.powf(1.0 / 2.4) - 0.055
+                }
+            }
+            Gamma::Power(gamma) => x.powf(1.0 / gamma),
+        }
+    }
+}
+
+/// Gamma as written in configs, either `"srgb"` or a numeric gamma.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum GammaRepr {
+    Named(String),
+    Power(Float),
+}
+
+impl std::convert::TryFrom<GammaRepr> for Gamma {
+    type Error = String;
+
+    fn try_from(repr: GammaRepr) -> Result<Self, Self::Error> {
+        match repr {
+            GammaRepr::Named(name) if name.eq_ignore_ascii_case("srgb") => Ok(Gamma::Srgb),
+            GammaRepr::Named(name) => Err(format!("Unknown gamma \"{name}\", expected \"srgb\" or a number.")),
+            GammaRepr::Power(gamma) if gamma > 0.0 => Ok(Gamma::Power(gamma)),
+            GammaRepr::Power(gamma) => Err(format!("Gamma must be positive, not {gamma}.")),
+        }
+    }
+}
+
+impl std::convert::From<Gamma> for GammaRepr {
+    fn from(gamma: Gamma) -> Self {
+        match gamma {
+            Gamma::Srgb => GammaRepr::Named(String::from("srgb")),
+            Gamma::Power(gamma) => GammaRepr::Power(gamma),
+        }
+    }
+}
+
+impl std::ops::Mul<FloatRgb> for Float {
+    type Output = FloatRgb;
+
+    fn mul(self, rhs: FloatRgb) -> Self::Output {
+        FloatRgb(rhs.0 * self)
     }
 }
 
@@ -34,36 +104,88 @@ impl std::convert::From<FloatRgb> for Rgb {
  * - Multiply by scalar or vector attenuation values
  */
 #[derive(Copy, Clone, Default, PartialEq, Debug, Serialize, Deserialize, NTupleNewtype)]
-pub struct FloatRgb(NTuple<f64, 3>);
+#[ntuple(index)]
+pub struct FloatRgb(NTuple<Float, 3>);
 
 impl FloatRgb {
-    pub fn new(red: f64, green: f64, blue: f64) -> Self {
+    pub fn new(red: Float, green: Float, blue: Float) -> Self {
         Self(ntuple!(red, green, blue))
     }
 
-    pub fn r(self) -> f64 {
+    pub fn r(self) -> Float {
         self.0[0]
     }
 
-    pub fn g(self) -> f64 {
+    pub fn g(self) -> Float {
         self.0[1]
     }
 
-    pub fn b(self) -> f64 {
+    pub fn b(self) -> Float {
         self.0[2]
     }
 
-    pub fn mix(self, rhs: Self, t: f64) -> Self {
+    pub fn mix(self, rhs: Self, t: Float) -> Self {
         let s = 1.0 - t;
         Self(self.0.combine(rhs.0, |x, y| t * x + s * y))
     }
+
+    /// The relative luminance of the linear color, using the Rec. 709
+    /// primaries shared by sRGB.
+    pub fn luminance(self) -> Float {
+        0.2126 * self.r() + 0.7152 * self.g() + 0.0722 * self.b()
+    }
+
+    /// The color scaled by `2^stops`.
+    pub fn expose(self, stops: Float) -> Self {
+        let scale = stops.exp2();
+        Self(self.0 * scale)
+    }
+
+    /// The color scaled down, keeping its hue, so that no channel is above
+    /// `max`.
+    pub fn limit(self, max: Float) -> Self {
+        let brightest = self.r().max(self.g()).max(self.b());
+        if brightest > max {
+            let scale = max / brightest;
+            Self(self.0 * scale)
+        } else {
+            self
+        }
+    }
+}
+
+/// The geometric mean of the luminance of the colors, which unlike the
+/// arithmetic mean is not dominated by a few bright pixels such as lights.
+pub fn log_average_luminance<I>(colors: I) -> Float
+where
+    I: IntoIterator<Item = FloatRgb>,
+{
+    // keeps black pixels from sending the log to negative infinity
+    const DELTA: Float = 1e-4;
+
+    let (sum, count) = colors
+        .into_iter()
+        .fold((0.0, 0), |(sum, count), c| (sum + (DELTA + c.luminance()).ln(), count + 1));
+    if count == 0 {
+        0.0
+    } else {
+        (sum / count as Float).exp()
+    }
 }
 
 impl std::ops::Add<FloatRgb> for FloatRgb {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
-        Self(self.0.combine(rhs.0, |x, y| x + y))
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl std::ops::Sub<FloatRgb> for FloatRgb {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0)
     }
 }
 
@@ -71,38 +193,125 @@ impl std::ops::Mul<FloatRgb> for FloatRgb {
     type Output = Self;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        Self(self.0.combine(rhs.0, |x, y| x * y))
+        Self(self.0 * rhs.0)
+    }
+}
+
+impl std::ops::Mul<Float> for FloatRgb {
+    type Output = Self;
+
+    fn mul(self, rhs: Float) -> Self::Output {
+        Self(self.0 * rhs)
+    }
+}
+
+impl geometry3d::ApproxEq for FloatRgb {
+    fn approx_eq_within(self, rhs: Self, tolerance: geometry3d::Tolerance) -> bool {
+        self.0.combine(rhs.0, |x, y| tolerance.equal(x, y)).all(|equal| equal)
     }
 }
 
 /* Behaviours
  * - Create empty accumulator
- * - Add FRGB values to accumulator
- * - Calculate averaged FRGB value
+ * - Add FRGB values to accumulator, optionally weighted and with an alpha
+ * - Calculate weighted average FRGB and alpha values
+ * - Estimate the variance of the accumulated values
  */
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct FRgbAccumulator {
-    sum: NTuple<f64, 3>,
+    sum: NTuple<Float, 3>,
+    sum_sq: NTuple<Float, 3>,
+    alpha: Float,
+    weight: Float,
     count: u32,
 }
 
 impl FRgbAccumulator {
     pub fn new() -> Self {
         Self {
-            sum: ntuple!(0.0, 0.0, 0.0),
+            sum: NTuple::repeat(0.0),
+            sum_sq: NTuple::repeat(0.0),
+            alpha: 0.0,
+            weight: 0.0,
             count: 0,
         }
     }
 
+    pub fn count(self) -> u32 {
+        self.count
+    }
+
+    pub fn weight(self) -> Float {
+        self.weight
+    }
+
+    /// Adds an opaque value that contributes to the average in proportion to
+    /// `weight`.
+    pub fn add_weighted(&mut self, color: FloatRgb, weight: Float) {
+        self.add_weighted_alpha(color, 1.0, weight);
+    }
+
+    /// Adds a value with the given alpha that contributes to the average in
+    /// proportion to `weight`.
+    pub fn add_weighted_alpha(&mut self, color: FloatRgb, alpha: Float, weight: Float) {
+        let sq = color.0 * color.0;
+        self.sum += color.0 * weight;
+        self.sum_sq += sq * weight;
+        self.alpha += weight * alpha;
+        self.weight += weight;
+        self.count += 1;
+    }
+
+    /// The weighted average of the accumulated values, or black if none of
+    /// the values carried any weight.
     pub fn average(self) -> FloatRgb {
-        FloatRgb(self.sum.map(|x| x / self.count as f64))
+        if self.weight == 0.0 {
+            return FloatRgb::default();
+        }
+        FloatRgb(self.sum / self.weight)
+    }
+
+    /// The weighted average alpha of the accumulated values, or zero if none
+    /// of the values carried any weight.
+    pub fn average_alpha(self) -> Float {
+        if self.weight == 0.0 {
+            return 0.0;
+        }
+        self.alpha / self.weight
+    }
+
+    /// The per channel sample variance of the accumulated values, or None if
+    /// fewer than two values have been accumulated.
+    pub fn variance(self) -> Option<FloatRgb> {
+        if self.count < 2 || self.weight == 0.0 {
+            return None;
+        }
+        let n = self.count as Float;
+        let mean = self.sum / self.weight;
+        let mean_sq = self.sum_sq / self.weight;
+        // clamp to zero as rounding can push a zero variance slightly negative
+        let var = mean_sq.combine(mean, |x, y| (x - y * y).max(0.0) * n / (n - 1.0));
+        Some(FloatRgb(var))
+    }
+
+    /// Determines if the 95% confidence interval of the mean is narrower than
+    /// `tolerance` on either side of the mean in every channel.
+    pub fn converged(self, tolerance: Float) -> bool {
+        const Z_95: Float = 1.96;
+
+        match self.variance() {
+            Some(var) => {
+                let n = self.count as Float;
+                var.0.all(|x| Z_95 * (x / n).sqrt() < tolerance)
+            }
+            None => false,
+        }
     }
 }
 
 impl std::ops::AddAssign<FloatRgb> for FRgbAccumulator {
     fn add_assign(&mut self, rhs: FloatRgb) {
-        self.sum = self.sum.combine(rhs.0, |x, y| x + y);
-        self.count += 1;
+        self.add_weighted(rhs, 1.0);
     }
 }
 
@@ -0,0 +1,202 @@
+use crate::builder::*;
+use crate::color::FloatRgb;
+use crate::config::Config;
+use crate::framebuffer::Framebuffer;
+use crate::material::Material;
+use crate::renderer::Renderer;
+use geometry3d::*;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// The colors of an image, row by row from the top left, for comparison
+/// against another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pixels {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<FloatRgb>,
+}
+
+/// The per channel differences between two images of the same size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difference {
+    /// The root mean square difference of each channel.
+    pub rmse: FloatRgb,
+    /// The largest absolute difference of each channel.
+    pub max: FloatRgb,
+}
+
+impl Pixels {
+    /// Decodes an 8-bit RGB or RGBA PNG, ignoring alpha, with each channel
+    /// scaled to [0, 1] as stored, i.e. without undoing the gamma.
+    pub fn decode_png<R: Read>(r: R) -> Result<Pixels, Box<dyn Error>> {
+        let mut reader = png::Decoder::new(r).read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let channels = match (info.color_type, info.bit_depth) {
+            (png::ColorType::Rgb, png::BitDepth::Eight) => 3,
+            (png::ColorType::Rgba, png::BitDepth::Eight) => 4,
+            _ => return Err("Only 8-bit RGB and RGBA images can be compared.".into()),
+        };
+
+        let scale = |x: u8| x as Float / 255.0;
+        let data = buffer[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|p| FloatRgb::new(scale(p[0]), scale(p[1]), scale(p[2])))
+            .collect();
+        Ok(Pixels {
+            width: info.width,
+            height: info.height,
+            data,
+        })
+    }
+
+    pub fn read_png<P: AsRef<Path>>(path: P) -> Result<Pixels, Box<dyn Error>> {
+        let file = File::open(path)?;
+        Self::decode_png(BufReader::new(file))
+    }
+
+    /// The per channel differences from `other`, which must be the same size.
+    pub fn compare(&self, other: &Pixels) -> Result<Difference, Box<dyn Error>> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return Err(format!(
+                "Cannot compare a {} x {} image with a {} x {} image.",
+                self.width, self.height, other.width, other.height
+            )
+            .into());
+        }
+
+        let mut squared = [0.0; 3];
+        let mut max: [Float; 3] = [0.0; 3];
+        for (a, b) in self.data.iter().zip(&other.data) {
+            let d = *a - *b;
+            for (i, d) in [d.r(), d.g(), d.b()].into_iter().enumerate() {
+                squared[i] += d * d;
+                max[i] = max[i].max(d.abs());
+            }
+        }
+        let n = self.data.len().max(1) as Float;
+        let rmse = squared.map(|s| (s / n).sqrt());
+        Ok(Difference {
+            rmse: FloatRgb::new(rmse[0], rmse[1], rmse[2]),
+            max: FloatRgb::new(max[0], max[1], max[2]),
+        })
+    }
+}
+
+impl From<&Framebuffer> for Pixels {
+    /// The linear average color of each pixel.
+    fn from(framebuffer: &Framebuffer) -> Pixels {
+        let (width, height) = (framebuffer.width(), framebuffer.height());
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| framebuffer.pixel(x, y).average())
+            .collect();
+        Pixels {
+            width,
+            height,
+            data,
+        }
+    }
+}
+
+impl Difference {
+    /// The largest RMSE of any channel.
+    pub fn max_rmse(&self) -> Float {
+        self.rmse.r().max(self.rmse.g()).max(self.rmse.b())
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "RMSE: {:.6} {:.6} {:.6}", self.rmse.r(), self.rmse.g(), self.rmse.b())?;
+        write!(f, "Max difference: {:.6} {:.6} {:.6}", self.max.r(), self.max.g(), self.max.b())
+    }
+}
+
+/// Tiny built-in scenes with a fixed seed, each showing off one material, to
+/// catch unintended changes to the output of the renderer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestScene {
+    Diffuse,
+    Metal,
+    Dielectric,
+}
+
+impl TestScene {
+    pub fn name(self) -> &'static str {
+        match self {
+            TestScene::Diffuse => "diffuse",
+            TestScene::Metal => "metal",
+            TestScene::Dielectric => "dielectric",
+        }
+    }
+
+    /// A sphere of the scene's material on a diffuse ground under the sky,
+    /// with a diffuse sphere either side to show in reflections.
+    pub fn config(self) -> Config {
+        let material = match self {
+            TestScene::Diffuse => Material::lambertian(FloatRgb::new(0.1, 0.2, 0.5)),
+            TestScene::Metal => Material::metal(FloatRgb::new(0.8, 0.6, 0.2), 0.1),
+            TestScene::Dielectric => Material::dielectric(1.5),
+        };
+        let mut config = SceneBuilder::new()
+            .image(ImageBuilder::new().filename(format!("{}.png", self.name())).size(32, 24).build())
+            .camera(CameraBuilder::new().look_from(Point3::new(0.0, 0.5, 2.0)).vertical_fov(50.0).build())
+            .sphere(Point3::new(0.0, 0.0, 0.0), 0.5)
+            .material(material)
+            .add()
+            .sphere(Point3::new(-1.0, 0.0, -0.5), 0.5)
+            .material(Material::lambertian(FloatRgb::new(0.7, 0.2, 0.2)))
+            .add()
+            .sphere(Point3::new(1.0, 0.0, -0.5), 0.5)
+            .material(Material::lambertian(FloatRgb::new(0.2, 0.7, 0.2)))
+            .add()
+            .sphere(Point3::new(0.0, -100.5, 0.0), 100.0)
+            .material(Material::lambertian(FloatRgb::new(0.8, 0.8, 0.0)))
+            .add()
+            .build();
+        config.sampler.n = 2;
+        config.sampler.max_depth = 8;
+        config.seed = Some(1);
+        config
+    }
+
+    /// Renders the scene and encodes it as a PNG.
+    pub fn render_png(self) -> Vec<u8> {
+        let (image, _) = Renderer::new(self.config())
+            .expect("Test scenes are valid.")
+            .render();
+        let mut bytes = Vec::new();
+        image.write(&mut bytes).expect("Writing to memory cannot fail.");
+        bytes
+    }
+
+    /// Renders the scene and panics unless it is within `tolerance` RMSE in
+    /// every channel of the golden image `{name}.png` in `golden_dir`.
+    ///
+    /// With the environment variable `RTOW_BLESS` set the golden image is
+    /// replaced by the render instead, to accept an intended change.
+    pub fn assert_golden<P: AsRef<Path>>(self, golden_dir: P, tolerance: Float) {
+        let golden = golden_dir.as_ref().join(format!("{}.png", self.name()));
+        let bytes = self.render_png();
+        if std::env::var_os("RTOW_BLESS").is_some() {
+            std::fs::write(&golden, &bytes).expect("Failed to write the golden image.");
+            return;
+        }
+
+        let render = Pixels::decode_png(&bytes[..]).expect("Renders are valid PNGs.");
+        let expected = Pixels::read_png(&golden)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {e}", golden.display()));
+        let difference = render.compare(&expected).unwrap();
+        assert!(
+            difference.max_rmse() <= tolerance,
+            "The {} scene differs from {}:\n{difference}",
+            self.name(),
+            golden.display()
+        );
+    }
+}
@@ -1,54 +1,1067 @@
 use serde::{Serialize, Deserialize};
 use geometry3d::*;
-use crate::object::List;
-use crate::color::FloatRgb;
+use crate::object::{List, Object};
+use crate::color::{FloatRgb, Gamma};
+use crate::expand::expand;
+use crate::include;
 use std::error::Error;
-use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+/// A scene and how to render it. Only `scene_list` is required, every other
+/// section takes its `Default` when left out.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default)]
     pub image: ImageConfig,
+    #[serde(default)]
     pub camera: CameraConfig,
+    #[serde(default)]
     pub sampler: SamplerConfig,
     pub scene_list: List,
+    #[serde(default = "Config::default_background_color")]
     pub background_color: FloatRgb,
+    #[serde(default)]
+    pub progressive: Option<ProgressiveConfig>,
+    #[serde(default)]
+    pub render_mode: RenderMode,
+    #[serde(default)]
+    pub heat_map: HeatMapConfig,
+    #[serde(default)]
+    pub ao: AoConfig,
+    #[serde(default)]
+    pub depth: DepthConfig,
+    #[serde(default)]
+    pub bvh: BvhViewConfig,
+    /// How the objects of the scene are split up into the BVH.
+    #[serde(default)]
+    pub bvh_builder: BvhBuilder,
+    /// How the BVH is laid out to be hit.
+    #[serde(default)]
+    pub accelerator: Accelerator,
+    #[serde(default)]
+    pub clamp: Option<ClampConfig>,
+    /// Light diffuse surfaces by also sampling points on the emissive
+    /// spheres and rectangles of the scene, rather than only finding lights
+    /// by chance, which greatly reduces the noise from small lights.
+    #[serde(default)]
+    pub light_sampling: bool,
+    /// The lights sampled by `light_sampling`. When empty every emissive
+    /// sphere and rectangle in the scene is sampled equally often.
+    #[serde(default)]
+    pub lights: Vec<LightConfig>,
+    /// Makes the render reproducible, as every random decision is derived
+    /// from it. When absent a seed is chosen at random.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// The generator of the random numbers of each sample.
+    #[serde(default)]
+    pub rng: RngKind,
+    /// Renders a sequence of frames rather than a single image.
+    #[serde(default)]
+    pub animation: Option<AnimationConfig>,
+    /// The directory that relative file names in the config are resolved
+    /// against, which `read` sets to the directory of the config file. When
+    /// None they are left relative to the working directory.
+    #[serde(skip)]
+    pub base_dir: Option<PathBuf>,
 }
 
+/// What the color of each primary ray shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderMode {
+    /// The light arriving along the ray, found by path tracing.
+    #[default]
+    PathTrace,
+    /// The number of bounding boxes tested while finding the closest hit.
+    HeatMap,
+    /// The fraction of the hemisphere above the first hit that is not
+    /// blocked by nearby geometry, ignoring materials.
+    Ao,
+    /// The distance from the camera to the first hit.
+    Depth,
+    /// The bounding boxes of the BVH that the ray passes through.
+    Bvh,
+}
+
+impl std::str::FromStr for RenderMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_owned()))
+            .map_err(|_| format!("Unknown render mode \"{s}\"."))
+    }
+}
+
+/// Maps bounding box test counts to colors in the heat map render mode. Counts
+/// from zero up to `max` are spread evenly across the colors of `gradient`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeatMapConfig {
+    #[serde(default = "HeatMapConfig::default_max")]
+    pub max: u32,
+    #[serde(default = "HeatMapConfig::default_gradient")]
+    pub gradient: Vec<FloatRgb>,
+}
+
+/// Refers to an object in the scene, either by its index in the top level of
+/// `scene_list` or by the name given to it with a `Named` object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ObjectRef {
+    Index(usize),
+    Name(String),
+}
+
+impl std::fmt::Display for ObjectRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjectRef::Index(i) => write!(f, "object {i}"),
+            ObjectRef::Name(name) => write!(f, "object \"{name}\""),
+        }
+    }
+}
+
+/// A light to sample directly, chosen in proportion to its `weight`. Written
+/// either as just the object reference, or as
+/// `{"object": <ref>, "weight": <weight>}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "LightEntry")]
+pub struct LightConfig {
+    pub object: ObjectRef,
+    pub weight: Float,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LightEntry {
+    Object(ObjectRef),
+    Weighted {
+        object: ObjectRef,
+        #[serde(default = "LightEntry::default_weight")]
+        weight: Float,
+    },
+}
+
+impl LightEntry {
+    fn default_weight() -> Float {
+        1.0
+    }
+}
+
+impl From<LightEntry> for LightConfig {
+    fn from(entry: LightEntry) -> LightConfig {
+        match entry {
+            LightEntry::Object(object) => LightConfig {
+                object,
+                weight: LightEntry::default_weight(),
+            },
+            LightEntry::Weighted { object, weight } => LightConfig { object, weight },
+        }
+    }
+}
+
+/// Limits the radiance of each path traced sample to suppress fireflies, the
+/// isolated bright pixels left by rare paths that find a small light. This
+/// trades them for a darkening bias.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ClampConfig {
+    /// The highest value any color channel of a sample may take.
+    pub max_radiance: Float,
+    /// Only clamp light that has bounced at least once, leaving lights and
+    /// the background seen directly untouched.
+    #[serde(default)]
+    pub indirect_only: bool,
+}
+
+/// Shades the first hit of each primary ray by casting `rays` cosine
+/// distributed rays from it and counting those that travel `max_distance`,
+/// or escape the scene when there is no limit, without hitting anything.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AoConfig {
+    #[serde(default = "AoConfig::default_rays")]
+    pub rays: u32,
+    #[serde(default)]
+    pub max_distance: Option<Float>,
+}
+
+/// How distances are written in the depth render mode. Distances from `near`
+/// to `far` are mapped linearly from black to white, with misses at white,
+/// so use a gamma of 1 to keep the mapping linear in 8-bit formats. With
+/// `raw` the distances are written unchanged, and misses as `far`, which
+/// needs a floating-point format such as EXR to be useful.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DepthConfig {
+    #[serde(default)]
+    pub near: Float,
+    #[serde(default = "DepthConfig::default_far")]
+    pub far: Float,
+    #[serde(default)]
+    pub raw: bool,
+}
+
+/// How the BVH render mode shows the bounding boxes. Each pixel is colored
+/// through the heat map gradient by the level of the deepest box hit, from
+/// the root to the deepest level of the tree. With `edges` only the outlines
+/// of the boxes are drawn, `edge_width` wide as a fraction of the size of
+/// each box.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BvhViewConfig {
+    #[serde(default)]
+    pub edges: bool,
+    #[serde(default = "BvhViewConfig::default_edge_width")]
+    pub edge_width: Float,
+}
+
+/// How a BVH is built from the objects of a scene.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BvhBuilder {
+    /// Splits each node where the surface area heuristic estimates rays
+    /// will test the fewest boxes and objects, leaving a few objects
+    /// together in a leaf where splitting them costs more than it saves.
+    #[default]
+    Sah,
+    /// Splits each node into two halves by count at the median along
+    /// whichever axis gives the halves the most similar surface areas,
+    /// down to single objects.
+    Median,
+}
+
+/// The layout of the BVH that rays are traced through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Accelerator {
+    /// The binary hierarchy as built, flattened into an array of nodes.
+    #[default]
+    Bvh2,
+    /// The binary hierarchy collapsed into nodes of up to four children,
+    /// whose boxes are tested together.
+    Bvh4,
+}
+
+/// The generator behind the stream of random numbers of each sample.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RngKind {
+    /// PCG-XSH-RR with 64 bits of state, O'Neill's permuted congruential
+    /// generator.
+    Pcg32,
+    /// xoshiro256++, Blackman and Vigna's xor/shift/rotate generator with
+    /// 256 bits of state.
+    Xoshiro256,
+    /// The standard generator of `rand`, currently ChaCha with 12 rounds,
+    /// which is much slower than the others but of cryptographic quality.
+    #[default]
+    Std,
+}
+
+/// Any field left out takes its value from the `Default`, a 400 x 225 sRGB
+/// `render.png`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ImageConfig {
     pub filename: String,
     pub width: u32,
-    pub height: u32,
+    /// The height in pixels. Either this or `aspect_ratio` may be given, and
+    /// when neither is the image is 16:9.
+    pub height: Option<u32>,
+    /// The width divided by the height, from which the height is found.
+    pub aspect_ratio: Option<Float>,
+    pub tile_size: u32,
+    pub format: Option<ImageFormat>,
+    pub alpha: bool,
+    pub gamma: Gamma,
+    /// Render the image upside down, with the bottom of the camera's view at
+    /// the top of the image.
+    pub flip_y: bool,
+    /// Auxiliary buffers to write alongside the image.
+    pub aovs: Vec<Aov>,
+    /// Smooth out noise with an edge-aware filter guided by the normal and
+    /// depth of each pixel before writing.
+    pub denoise: bool,
+    /// The color difference, in linear units, over which the denoiser stops
+    /// averaging neighbouring pixels.
+    pub denoise_strength: Float,
+    pub denoise_iterations: u32,
+    /// Brightens the image by this many stops, i.e. scales the linear color
+    /// by `2^exposure`, before it is encoded.
+    pub exposure: Float,
+    /// Picks the exposure that brings the log-average luminance of the image
+    /// to middle grey, with `exposure` added on top as compensation.
+    pub auto_exposure: bool,
+    /// Writes the image relative to the directory of the config file rather
+    /// than the working directory.
+    pub output_relative_to_scene: bool,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// Output file format. When not given it is inferred from the extension of
+/// the output filename, with `.ppm` files written as binary P6. EXR files hold
+/// linear floating-point color, all other formats are gamma corrected 8-bit.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Png,
+    P6,
+    P3,
+    Exr,
+}
+
+/// Arbitrary output variables describing the first surface hit by the primary
+/// rays of each pixel. Each is written to its own PNG named after the image,
+/// e.g. `render.normal.png` for `render.png`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aov {
+    Normal,
+    Depth,
+    Albedo,
+}
+
+impl ImageConfig {
+    pub const DEFAULT_ASPECT_RATIO: Float = 16.0 / 9.0;
+
+    /// The height in pixels, either as given or found from the aspect ratio
+    /// and rounded to the nearest pixel.
+    pub fn height(&self) -> u32 {
+        match self.height {
+            Some(height) => height,
+            None => {
+                let aspect_ratio = self.aspect_ratio.unwrap_or(Self::DEFAULT_ASPECT_RATIO);
+                (self.width as Float / aspect_ratio).round() as u32
+            }
+        }
+    }
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        ImageConfig {
+            filename: String::from("render.png"),
+            width: 400,
+            height: None,
+            aspect_ratio: None,
+            tile_size: 32,
+            format: None,
+            alpha: false,
+            gamma: Gamma::default(),
+            flip_y: false,
+            aovs: Vec::new(),
+            denoise: false,
+            denoise_strength: 1.0,
+            denoise_iterations: 5,
+            exposure: 0.0,
+            auto_exposure: false,
+            output_relative_to_scene: false,
+        }
+    }
+}
+
+/// Any field left out takes its value from the `Default`, a pinhole camera
+/// looking at the origin from (13, 2, 3) with a 20 degree field of view and
+/// the shutter open only at time 0.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CameraConfig {
     pub look_from: Point3,
     pub look_at: Point3,
     pub up: Vec3,
-    pub vertical_fov: f64,
-    pub aperture: f64,
-    pub focus_distance: f64,
-    pub time_min: f64,
-    pub time_max: f64,
+    pub vertical_fov: Float,
+    pub aperture: Float,
+    pub focus_distance: Float,
+    pub time_min: Float,
+    pub time_max: Float,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            look_from: Point3::new(13.0, 2.0, 3.0),
+            look_at: Point3::new(0.0, 0.0, 0.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+            vertical_fov: 20.0,
+            aperture: 0.0,
+            focus_distance: 10.0,
+            time_min: 0.0,
+            time_max: 0.0,
+        }
+    }
+}
+
+/// Any field left out takes its value from the `Default`, an n x n square
+/// grid of samples per pixel with n = 10 and paths of up to 50 bounces.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SamplerConfig {
+    #[serde(rename = "type")]
+    pub kind: SamplerKind,
     pub n: u32,
     pub max_depth: u32,
+    pub filter: Filter,
+    pub adaptive: Option<AdaptiveConfig>,
+    pub regions: Vec<SampleRegion>,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        SamplerConfig {
+            kind: SamplerKind::default(),
+            n: 10,
+            max_depth: 50,
+            filter: Filter::default(),
+            adaptive: None,
+            regions: Vec::new(),
+        }
+    }
+}
+
+/// A rectangle of pixels, with its top left pixel at (x, y), that takes
+/// `samples` samples per pixel in place of the sampler's base count.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SampleRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub samples: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SamplerKind {
+    #[default]
+    #[serde(alias = "stratified")]
+    Square,
+    Random,
+}
+
+/// Pixel reconstruction filter used to weight each sample by its offset from
+/// the pixel center.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    #[default]
+    Box,
+    Tent,
+    Gaussian { sigma: Float },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AdaptiveConfig {
+    pub min_samples: u32,
+    pub max_samples: u32,
+    pub tolerance: Float,
+}
+
+impl HeatMapConfig {
+    fn default_max() -> u32 {
+        64
+    }
+
+    /// Blue through cyan, green, and yellow to red.
+    fn default_gradient() -> Vec<FloatRgb> {
+        vec![
+            FloatRgb::new(0.0, 0.0, 1.0),
+            FloatRgb::new(0.0, 1.0, 1.0),
+            FloatRgb::new(0.0, 1.0, 0.0),
+            FloatRgb::new(1.0, 1.0, 0.0),
+            FloatRgb::new(1.0, 0.0, 0.0),
+        ]
+    }
+}
+
+impl Default for HeatMapConfig {
+    fn default() -> Self {
+        HeatMapConfig {
+            max: Self::default_max(),
+            gradient: Self::default_gradient(),
+        }
+    }
+}
+
+impl AoConfig {
+    fn default_rays() -> u32 {
+        16
+    }
+}
+
+impl Default for AoConfig {
+    fn default() -> Self {
+        AoConfig {
+            rays: Self::default_rays(),
+            max_distance: None,
+        }
+    }
+}
+
+impl DepthConfig {
+    fn default_far() -> Float {
+        100.0
+    }
+}
+
+impl Default for DepthConfig {
+    fn default() -> Self {
+        DepthConfig {
+            near: 0.0,
+            far: Self::default_far(),
+            raw: false,
+        }
+    }
+}
+
+impl BvhViewConfig {
+    fn default_edge_width() -> Float {
+        0.02
+    }
+}
+
+impl Default for BvhViewConfig {
+    fn default() -> Self {
+        BvhViewConfig {
+            edges: false,
+            edge_width: Self::default_edge_width(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ProgressiveConfig {
+    pub write_interval: u32,
+    /// Stops adding passes once the next one is not expected to finish
+    /// within this many seconds of rendering. At least one pass is always
+    /// completed.
+    #[serde(default)]
+    pub time_budget: Option<f64>,
+}
+
+/// A sequence of frames starting from the camera's `time_min`, each showing
+/// the scene over the part of its frame the shutter is open for.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnimationConfig {
+    pub frames: u32,
+    pub fps: Float,
+    /// The fraction of each frame the shutter is open for, from 0 for no
+    /// motion blur to 1 for a shutter that never closes.
+    pub shutter_fraction: Float,
+    /// Refits the BVH built for the first frame to each frame after it,
+    /// rather than building it again, until it has grown too much.
+    #[serde(default = "AnimationConfig::default_refit")]
+    pub refit: bool,
+}
+
+impl AnimationConfig {
+    fn default_refit() -> bool {
+        true
+    }
 }
 
 impl Config {
+    /// A sky blue.
+    pub fn default_background_color() -> FloatRgb {
+        FloatRgb::new(0.7, 0.8, 1.0)
+    }
+
+    /// The number of images rendered, which is one without an animation.
+    pub fn frames(&self) -> u32 {
+        self.animation.map_or(1, |animation| animation.frames)
+    }
+
+    /// The config of a single frame of the animation, with the camera time
+    /// range set to the frame's shutter interval and a zero padded frame
+    /// number added to the image filename, e.g. `render_0007.png`. Without an
+    /// animation frame 0 is the config unchanged.
+    pub fn frame(&self, frame: u32) -> Result<Config, Box<dyn Error>> {
+        if frame >= self.frames() {
+            return Err(format!("Frame {frame} is out of range as there are {} frames.", self.frames()).into());
+        }
+        let Some(animation) = self.animation else {
+            return Ok(self.clone());
+        };
+        if animation.fps.is_nan() || animation.fps <= 0.0 {
+            return Err("The animation fps must be positive.".into());
+        }
+        if !(0.0..=1.0).contains(&animation.shutter_fraction) {
+            return Err("The animation shutter_fraction must be between 0 and 1.".into());
+        }
+
+        let mut config = self.clone();
+        config.animation = None;
+        let time_min = self.camera.time_min + frame as Float / animation.fps;
+        config.camera.time_min = time_min;
+        config.camera.time_max = time_min + animation.shutter_fraction / animation.fps;
+        if config.image.filename != "-" {
+            let digits = (animation.frames - 1).to_string().len().max(4);
+            let path = Path::new(&self.image.filename);
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let name = match path.extension() {
+                Some(extension) => format!("{stem}_{frame:0digits$}.{}", extension.to_string_lossy()),
+                None => format!("{stem}_{frame:0digits$}"),
+            };
+            config.image.filename = path.with_file_name(name).to_string_lossy().into_owned();
+        }
+        Ok(config)
+    }
+
+    /// Sets the field at the dotted `path`, e.g. `camera.vertical_fov` or
+    /// `scene_list.objects.0.Sphere.radius`, to `value`. The value is read as
+    /// JSON, or as a string if it is not valid JSON.
+    pub fn set(&mut self, path: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        let mut config = serde_json::to_value(&*self)?;
+        let mut field = &mut config;
+        for key in path.split('.') {
+            // an absent optional section is created with just this field
+            let absent = field.is_null();
+            if absent {
+                *field = serde_json::Value::Object(serde_json::Map::new());
+            }
+            let missing = || format!("Cannot set {path} as there is no `{key}`.");
+            field = match field {
+                serde_json::Value::Object(map) => {
+                    if absent {
+                        map.entry(key).or_insert(serde_json::Value::Null)
+                    } else {
+                        map.get_mut(key).ok_or_else(missing)?
+                    }
+                }
+                serde_json::Value::Array(values) => key
+                    .parse()
+                    .ok()
+                    .and_then(|i: usize| values.get_mut(i))
+                    .ok_or_else(missing)?,
+                _ => return Err(missing().into()),
+            };
+        }
+        *field = serde_json::from_str(value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+        *self = Self::from_value(config).map_err(|e| format!("Cannot set {path}: {e}"))?;
+        Ok(())
+    }
+
+    /// Checks the values of the config make sense together, returning every
+    /// problem found rather than just the first.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        let mut check = |valid: bool, path: &str, message: &str| {
+            if !valid {
+                errors.push(ValidationError {
+                    path: path.to_string(),
+                    message: message.to_string(),
+                });
+            }
+        };
+
+        let image = &self.image;
+        check(image.width > 0, "image.width", "must be at least one");
+        check(image.height != Some(0), "image.height", "must be at least one");
+        match image.aspect_ratio {
+            Some(_) if image.height.is_some() => {
+                check(false, "image.aspect_ratio", "cannot be given together with height")
+            }
+            Some(aspect_ratio) => {
+                check(aspect_ratio > 0.0, "image.aspect_ratio", "must be positive");
+                check(
+                    aspect_ratio <= 0.0 || image.height() > 0,
+                    "image.aspect_ratio",
+                    "leaves the image less than one pixel high",
+                );
+            }
+            None => (),
+        }
+        check(image.tile_size > 0, "image.tile_size", "must be at least one");
+        check(
+            !image.denoise || image.denoise_strength > 0.0,
+            "image.denoise_strength",
+            "must be positive",
+        );
+        check(
+            image.aovs.is_empty() || image.filename != "-",
+            "image.aovs",
+            "can only be written when the image is written to a file",
+        );
+
+        let camera = &self.camera;
+        let w = (camera.look_from - camera.look_at).unit();
+        check(w.is_some(), "camera.look_at", "cannot be the same point as look_from");
+        check(
+            w.is_none_or(|w| camera.up.cross(w).unit().is_some()),
+            "camera.up",
+            "cannot be parallel to the look direction",
+        );
+        check(
+            camera.vertical_fov > 0.0 && camera.vertical_fov < 180.0,
+            "camera.vertical_fov",
+            "must be between 0 and 180 degrees",
+        );
+        check(camera.aperture >= 0.0, "camera.aperture", "cannot be negative");
+        check(camera.focus_distance > 0.0, "camera.focus_distance", "must be positive");
+        check(
+            camera.time_max >= camera.time_min,
+            "camera.time_max",
+            "cannot be before time_min",
+        );
+
+        let sampler = &self.sampler;
+        check(sampler.n > 0, "sampler.n", "must be at least one");
+        if let Filter::Gaussian { sigma } = sampler.filter {
+            check(sigma > 0.0 && sigma.is_finite(), "sampler.filter.sigma", "must be positive");
+        }
+        for (i, region) in sampler.regions.iter().enumerate() {
+            let path = format!("sampler.regions[{i}]");
+            check(region.samples > 0, &format!("{path}.samples"), "must be at least one");
+            check(
+                region.x.checked_add(region.width).is_some(),
+                &format!("{path}.width"),
+                "extends past the largest pixel column",
+            );
+            check(
+                region.y.checked_add(region.height).is_some(),
+                &format!("{path}.height"),
+                "extends past the largest pixel row",
+            );
+        }
+        if let Some(adaptive) = sampler.adaptive {
+            check(adaptive.min_samples > 0, "sampler.adaptive.min_samples", "must be at least one");
+            check(
+                adaptive.min_samples <= adaptive.max_samples,
+                "sampler.adaptive.max_samples",
+                "cannot be less than min_samples",
+            );
+        }
+
+        if let Some(progressive) = self.progressive {
+            check(
+                sampler.adaptive.is_none(),
+                "progressive",
+                "cannot be combined with adaptive sampling",
+            );
+            check(progressive.write_interval > 0, "progressive.write_interval", "must be at least one");
+            check(
+                progressive.time_budget.is_none_or(|budget| budget > 0.0),
+                "progressive.time_budget",
+                "must be positive",
+            );
+        }
+
+        if let Some(clamp) = self.clamp {
+            check(clamp.max_radiance >= 0.0, "clamp.max_radiance", "cannot be negative");
+        }
+
+        if let Some(animation) = self.animation {
+            check(animation.frames > 0, "animation.frames", "must be at least one");
+            check(animation.fps > 0.0, "animation.fps", "must be positive");
+            check(
+                (0.0..=1.0).contains(&animation.shutter_fraction),
+                "animation.shutter_fraction",
+                "must be between 0 and 1",
+            );
+        }
+
+        for (i, object) in self.scene_list.iter().enumerate() {
+            if let Object::RandomSpheres(spheres) = object {
+                for (field, message) in spheres.validate() {
+                    let path = format!("scene_list.objects[{i}].RandomSpheres.{field}");
+                    check(false, &path, message);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Finds values that are valid but likely to be mistakes, such as the
+    /// camera starting inside the bounding box of an object, which often
+    /// renders black. Unlike `validate` these do not stop a render.
+    pub fn warnings(&self) -> Vec<ValidationError> {
+        let camera = &self.camera;
+        let t_range = TRange::new(camera.time_min, camera.time_max);
+        let mut warnings = Vec::new();
+        for (i, object) in self.scene_list.iter().enumerate() {
+            let inside = object
+                .bounding_box(t_range)
+                .is_some_and(|aabb| aabb.contains_point(camera.look_from));
+            if inside {
+                warnings.push(ValidationError {
+                    path: "camera.look_from".to_string(),
+                    message: format!("is inside the bounding box of scene_list.objects[{i}]"),
+                });
+            }
+        }
+        warnings
+    }
+
+    /// A hash of the full config, used to check that saved render state
+    /// belongs to this config. It is the same from one build to the next so
+    /// that checkpoints outlive the toolchain they were made with.
+    pub fn hash(&self) -> u64 {
+        let json = serde_json::to_string(self).expect("Config is always serializable.");
+        crate::checkpoint::stable_hash(json.as_bytes())
+    }
+
+    /// Reads a config in the format given by the file extension.
     pub fn read<P: AsRef<Path>>(path: P) -> Result<Config, Box<dyn Error>> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
+        let format = ConfigFormat::from_path(&path);
+        Self::read_as(path, format)
+    }
 
-        let config = serde_json::from_reader(reader)?;
+    /// Reads and validates a config, along with any files it includes.
+    pub fn read_as<P: AsRef<Path>>(path: P, format: ConfigFormat) -> Result<Config, Box<dyn Error>> {
+        let config = Self::load(path, format, true)?;
+        config.validate().map_err(InvalidConfig)?;
+        Ok(config)
+    }
 
+    /// Reads a config along with any files it includes without validating
+    /// it, so that it can be changed first. With `expand_paths` set,
+    /// environment variables and `~` in the paths of the config and of the
+    /// files it includes are expanded.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        format: ConfigFormat,
+        expand_paths: bool,
+    ) -> Result<Config, Box<dyn Error>> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        // the included files are merged before deserializing, so errors in
+        // a config with includes have a path but not a line
+        let mut config = match include::resolve(&text, format, path, expand_paths)? {
+            Some(value) => Self::from_value(value)?,
+            None => Self::parse(&text, format)?,
+        };
+        if expand_paths {
+            config.expand_paths()?;
+        }
+        config.base_dir = Some(path.parent().unwrap_or(Path::new("")).to_path_buf());
         Ok(config)
     }
+
+    /// Expands the environment variables and leading `~` in the file names
+    /// of image textures and the image itself. See `expand::expand`.
+    pub fn expand_paths(&mut self) -> Result<(), String> {
+        let mut result = Ok(());
+        self.scene_list.for_each_path(&mut |filename| {
+            if result.is_ok() {
+                result = expand(filename).map(|expanded| *filename = expanded);
+            }
+        });
+        result?;
+        if self.image.filename != "-" {
+            self.image.filename = expand(&self.image.filename)?;
+        }
+        Ok(())
+    }
+
+    /// Resolves the file names of image textures, and the image itself if
+    /// `output_relative_to_scene` is set, against `base_dir`, which is then
+    /// cleared.
+    pub fn resolve_paths(&mut self) {
+        let Some(base_dir) = self.base_dir.take() else {
+            return;
+        };
+        self.scene_list.resolve_paths(&base_dir);
+        if self.image.output_relative_to_scene && self.image.filename != "-" {
+            self.image.filename = base_dir.join(&self.image.filename).to_string_lossy().into_owned();
+        }
+        self.image.output_relative_to_scene = false;
+    }
+
+    /// The config as JSON with every field written out and the file names
+    /// of textures resolved to absolute paths, so that it renders the same
+    /// image from anywhere. A random seed should be chosen beforehand as
+    /// otherwise one is chosen for each render.
+    pub fn resolved_json(&self) -> Result<String, Box<dyn Error>> {
+        let mut config = self.clone();
+        config.image.height = Some(config.image.height());
+        config.image.aspect_ratio = None;
+        let base_dir = match config.base_dir.as_deref() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+        config.base_dir = Some(std::path::absolute(base_dir)?);
+        config.resolve_paths();
+        config.to_string_as(ConfigFormat::Json)
+    }
+
+    /// Parses a config, describing any error with the path to the value at
+    /// fault, e.g. `scene_list.objects[12].Sphere.material`, its location in
+    /// the text and, for a misspelt variant, the closest known one.
+    pub fn parse(text: &str, format: ConfigFormat) -> Result<Config, Box<dyn Error>> {
+        match format {
+            ConfigFormat::Json => {
+                let mut json = serde_json::Deserializer::from_str(text);
+                let config = serde_path_to_error::deserialize(&mut json).map_err(describe)?;
+                json.end().map_err(|e| describe_message(e.to_string()))?;
+                Ok(config)
+            }
+            ConfigFormat::Toml => {
+                let toml = toml::Deserializer::new(text);
+                serde_path_to_error::deserialize(toml).map_err(describe)
+            }
+            ConfigFormat::Yaml => {
+                // enums are single entry maps like in JSON rather than YAML
+                // tags, and errors already include the path
+                let yaml = serde_yaml::Deserializer::from_str(text);
+                serde_yaml::with::singleton_map_recursive::deserialize(yaml)
+                    .map_err(|e| describe_message(e.to_string()))
+            }
+        }
+    }
+
+    /// Deserializes a config from a generic value, describing errors like
+    /// `parse`.
+    pub fn from_value(value: serde_json::Value) -> Result<Config, Box<dyn Error>> {
+        serde_path_to_error::deserialize(value).map_err(describe)
+    }
+
+    pub fn to_string_as(&self, format: ConfigFormat) -> Result<String, Box<dyn Error>> {
+        let text = match format {
+            ConfigFormat::Json => serde_json::to_string_pretty(self)?,
+            ConfigFormat::Toml => toml::to_string(self)?,
+            ConfigFormat::Yaml => {
+                let mut text = Vec::new();
+                let mut yaml = serde_yaml::Serializer::new(&mut text);
+                serde_yaml::with::singleton_map_recursive::serialize(self, &mut yaml)?;
+                String::from_utf8(text)?
+            }
+        };
+        Ok(text)
+    }
+}
+
+/// A problem with a value of a config found by `Config::validate`, or a
+/// likely mistake found by `Config::warnings`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    /// Where the value is in the config, e.g. `camera.vertical_fov`.
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Every problem found with a config, reported together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidConfig(pub Vec<ValidationError>);
+
+impl std::fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The config is invalid:")?;
+        for error in &self.0 {
+            write!(f, "\n  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for InvalidConfig {}
+
+fn describe<E: std::fmt::Display>(error: serde_path_to_error::Error<E>) -> Box<dyn Error> {
+    let path = error.path().to_string();
+    let message = error.into_inner().to_string();
+    match path.as_str() {
+        "." => describe_message(message),
+        _ => describe_message(format!("{path}: {message}")),
+    }
+}
+
+/// Adds a suggestion to the message of an unknown variant error.
+fn describe_message(message: String) -> Box<dyn Error> {
+    match suggest_variant(&message) {
+        Some(variant) => format!("{message}\nDid you mean `{variant}`?").into(),
+        None => message.into(),
+    }
+}
+
+/// The known variant closest to the unknown one in a serde error message
+/// like "unknown variant `Sphre`, expected one of `Sphere`, `XYRect`, ...",
+/// if any is close enough to be a likely typo.
+fn suggest_variant(message: &str) -> Option<&str> {
+    let (_, rest) = message.split_once("unknown variant `")?;
+    let (unknown, rest) = rest.split_once('`')?;
+    let (_, expected) = rest.split_once("expected")?;
+    let expected = expected.lines().next().unwrap_or_default();
+
+    expected
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(|variant| {
+            let distance = edit_distance(&unknown.to_lowercase(), &variant.to_lowercase());
+            (distance, variant)
+        })
+        .filter(|&(distance, variant)| distance <= 2.max(variant.len() / 3))
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, variant)| variant)
+}
+
+/// The number of single character insertions, deletions and substitutions
+/// needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + (ca != cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The file formats a config can be written in.
+///
+/// Every format shares the same structure, so e.g. a sphere in TOML is
+/// written with its variant name as the key, like in JSON:
+///
+/// ```toml
+/// [[scene_list.objects]]
+/// Sphere = { location = { origin = [0.0, 0.0, -1.0], direction = [0.0, 0.0, 0.0], time = 0.0 }, radius = 0.5, material = { Lambertian = { albedo = { SolidColor = [0.5, 0.5, 0.5] } } } }
+/// ```
+///
+/// YAML anchors and aliases can be used to share e.g. a material between
+/// objects, as they are expanded while reading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    #[default]
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// The format of a config file by its extension, JSON unless it is
+    /// `.toml`, `.yaml` or `.yml`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> ConfigFormat {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json,
+        }
+    }
+}
+
+impl std::str::FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_owned()))
+            .map_err(|_| format!("Unknown config format \"{s}\"."))
+    }
 }
 
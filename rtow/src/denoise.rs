@@ -0,0 +1,142 @@
+use crate::aov::AovBuffer;
+use crate::color::FloatRgb;
+use crate::image::Image;
+use geometry3d::*;
+
+/// Edge-avoiding À-Trous wavelet denoiser.
+///
+/// Each iteration blurs the image with a 5 x 5 B3-spline kernel whose taps
+/// are spread twice as far apart as the last, so a few iterations cover a
+/// wide area cheaply. Neighbours are weighted down by how much their color,
+/// normal, and depth differ from the pixel being filtered, which keeps the
+/// blur from crossing geometric edges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Denoiser {
+    pub strength: Float,
+    pub iterations: u32,
+}
+
+impl Denoiser {
+    const KERNEL: [Float; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+    const NORMAL_POWER: i32 = 64;
+    const DEPTH_TOLERANCE: Float = 0.05;
+
+    pub fn new(strength: Float, iterations: u32) -> Denoiser {
+        assert!(strength > 0.0, "The denoise strength must be positive.");
+        Denoiser {
+            strength,
+            iterations,
+        }
+    }
+
+    /// Filters the colors of `image` in place, guided by the normal and depth
+    /// of each pixel in `aovs`.
+    pub fn apply(self, image: &mut Image, aovs: &AovBuffer) {
+        let (width, height) = (image.width, image.height);
+        let pixels = || (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)));
+        let guide = Guide {
+            width,
+            height,
+            normals: pixels().map(|(x, y)| aovs.pixel(x, y).normal()).collect(),
+            depths: pixels().map(|(x, y)| aovs.pixel(x, y).depth()).collect(),
+        };
+
+        let mut colors: Vec<FloatRgb> = pixels().map(|(x, y)| image.pixel(x, y)).collect();
+        for i in 0..self.iterations {
+            // later iterations average over larger, smoother areas so only
+            // accept closer colors
+            let sigma = self.strength / (1 << i) as Float;
+            colors = guide.filter(&colors, 1 << i, sigma);
+        }
+
+        for ((x, y), color) in pixels().zip(colors) {
+            image.set_pixel(x, y, color);
+        }
+    }
+
+    fn color_weight(a: FloatRgb, b: FloatRgb, sigma: Float) -> Float {
+        let (dr, dg, db) = (a.r() - b.r(), a.g() - b.g(), a.b() - b.b());
+        let d2 = dr * dr + dg * dg + db * db;
+        (-d2 / (sigma * sigma)).exp()
+    }
+
+    /// Pixels whose rays all missed have a zero normal and only match each
+    /// other.
+    fn normal_weight(a: Vec3, b: Vec3) -> Float {
+        match (a == Vec3::default(), b == Vec3::default()) {
+            (true, true) => 1.0,
+            (false, false) => a.dot(b).max(0.0).powi(Self::NORMAL_POWER),
+            _ => 0.0,
+        }
+    }
+
+    /// Depth differences are relative to the distance and the spacing of the
+    /// kernel taps, so that surfaces seen at a grazing angle are still
+    /// smoothed. Misses have infinite depth and only match each other.
+    fn depth_weight(a: Float, b: Float, step: u32) -> Float {
+        match (a.is_finite(), b.is_finite()) {
+            (false, false) => 1.0,
+            (true, true) => {
+                let tolerance = Self::DEPTH_TOLERANCE * step as Float * a.max(b);
+                if tolerance == 0.0 {
+                    1.0
+                } else {
+                    (-(a - b).abs() / tolerance).exp()
+                }
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// The normal and depth of each pixel, stored from the top row down.
+struct Guide {
+    width: u32,
+    height: u32,
+    normals: Vec<Vec3>,
+    depths: Vec<Float>,
+}
+
+impl Guide {
+    /// One À-Trous iteration with kernel taps `step` pixels apart.
+    fn filter(&self, colors: &[FloatRgb], step: u32, sigma: Float) -> Vec<FloatRgb> {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let mut filtered = Vec::with_capacity(colors.len());
+
+        for y in 0..height {
+            for x in 0..width {
+                let p = (y * width + x) as usize;
+                let (mut r, mut g, mut b, mut total) = (0.0, 0.0, 0.0, 0.0);
+
+                for (j, ky) in Denoiser::KERNEL.iter().enumerate() {
+                    let qy = y + (j as i64 - 2) * step as i64;
+                    if !(0..height).contains(&qy) {
+                        continue;
+                    }
+                    for (i, kx) in Denoiser::KERNEL.iter().enumerate() {
+                        let qx = x + (i as i64 - 2) * step as i64;
+                        if !(0..width).contains(&qx) {
+                            continue;
+                        }
+
+                        let q = (qy * width + qx) as usize;
+                        let weight = kx
+                            * ky
+                            * Denoiser::color_weight(colors[p], colors[q], sigma)
+                            * Denoiser::normal_weight(self.normals[p], self.normals[q])
+                            * Denoiser::depth_weight(self.depths[p], self.depths[q], step);
+                        r += weight * colors[q].r();
+                        g += weight * colors[q].g();
+                        b += weight * colors[q].b();
+                        total += weight;
+                    }
+                }
+
+                // the pixel itself always has a non-zero weight
+                filtered.push(FloatRgb::new(r / total, g / total, b / total));
+            }
+        }
+
+        filtered
+    }
+}
@@ -0,0 +1,50 @@
+/// Expands the environment variables in `path`, written as `$VAR` or
+/// `${VAR}`, and a leading `~` for the home directory, so that a config can
+/// name files in places that differ between machines. `$$` is a literal `$`.
+///
+/// A variable that isn't set is an error rather than being left as written.
+pub fn expand(path: &str) -> Result<String, String> {
+    expand_with(path, |name| std::env::var(name).ok())
+}
+
+/// Expands `path` as `expand` does, looking up variables with `var`, where
+/// `~` stands for `HOME`.
+pub fn expand_with<F: Fn(&str) -> Option<String>>(path: &str, var: F) -> Result<String, String> {
+    let lookup = |name: &str| {
+        var(name).ok_or_else(|| format!("Cannot expand {path} as the environment variable {name} is not set."))
+    };
+
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+    if rest == "~" || rest.starts_with("~/") {
+        expanded.push_str(&lookup("HOME")?);
+        rest = &rest[1..];
+    }
+
+    while let Some(i) = rest.find('$') {
+        expanded.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+        } else if let Some(braced) = rest.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| format!("Cannot expand {path} as a ${{ is never closed."))?;
+            expanded.push_str(&lookup(&braced[..end])?);
+            rest = &braced[end + 1..];
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            match end {
+                // a lone `$` is left as it is
+                0 => expanded.push('$'),
+                _ => expanded.push_str(&lookup(&rest[..end])?),
+            }
+            rest = &rest[end..];
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
@@ -0,0 +1,89 @@
+use crate::color::*;
+use crate::image::Image;
+use geometry3d::Float;
+use serde::{Deserialize, Serialize};
+
+/// Per pixel color accumulators that persist across rendering passes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<FRgbAccumulator>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        let size = width as usize * height as usize;
+        let pixels = vec![FRgbAccumulator::new(); size];
+        Framebuffer {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "Pixel ({x}, {y}) out of bounds.");
+        y as usize * self.width as usize + x as usize
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> FRgbAccumulator {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: FRgbAccumulator) {
+        let i = self.index(x, y);
+        self.pixels[i] = pixel;
+    }
+
+    /// The mean number of samples taken per pixel.
+    pub fn samples_per_pixel(&self) -> f64 {
+        let samples: u64 = self.pixels.iter().map(|p| p.count() as u64).sum();
+        samples as f64 / self.pixels.len().max(1) as f64
+    }
+
+    pub fn add_sample(&mut self, x: u32, y: u32, color: FloatRgb, alpha: Float, weight: Float) {
+        let i = self.index(x, y);
+        self.pixels[i].add_weighted_alpha(color, alpha, weight);
+    }
+
+    /// Adds one sample to every pixel, where `f` gives the color, alpha, and
+    /// filter weight of the sample for the pixel (x, y), or None if the pixel
+    /// takes no further samples.
+    pub fn add_pass<F>(&mut self, mut f: F)
+    where
+        F: FnMut(u32, u32) -> Option<(FloatRgb, Float, Float)>,
+    {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if let Some((color, alpha, weight)) = f(x, y) {
+                    self.add_sample(x, y, color, alpha, weight);
+                }
+            }
+        }
+    }
+
+    /// Replaces the contents of `image` with the current pixel averages.
+    pub fn write_to(&self, image: &mut Image) {
+        assert!(
+            image.width == self.width && image.height == self.height,
+            "Image and framebuffer dimensions must match."
+        );
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let pixel = self.pixel(x, y);
+                image.set_pixel(x, y, pixel.average());
+                image.set_alpha(x, y, pixel.average_alpha());
+            }
+        }
+    }
+}
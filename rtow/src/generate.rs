@@ -0,0 +1,256 @@
+use crate::builder::*;
+use crate::color::FloatRgb;
+use crate::config::{Config, SamplerConfig};
+use crate::material::Material;
+use crate::object::{Object, Sphere};
+use crate::random::Random;
+use crate::texture::CheckerTexture;
+use geometry3d::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+/// The scenes written by `rtow generate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generator {
+    Cornell,
+    RandomSpheres { seed: u64 },
+}
+
+impl Generator {
+    pub fn config(self) -> Config {
+        match self {
+            Generator::Cornell => cornell_box(),
+            Generator::RandomSpheres { seed } => random_spheres(seed),
+        }
+    }
+}
+
+/// The Cornell box of The Next Week: a white box lit from a square in the
+/// ceiling, with a green wall on the left, a red wall on the right and two
+/// white boxes on the floor.
+pub fn cornell_box() -> Config {
+    let red = Material::lambertian(FloatRgb::new(0.65, 0.05, 0.05));
+    let white = Material::lambertian(FloatRgb::new(0.73, 0.73, 0.73));
+    let green = Material::lambertian(FloatRgb::new(0.12, 0.45, 0.15));
+    let light = Material::diffuse_light(FloatRgb::new(15.0, 15.0, 15.0));
+
+    SceneBuilder::new()
+        .image(ImageBuilder::new().filename("cornell_box_render.png").size(600, 600).build())
+        .camera(
+            CameraBuilder::new()
+                .look_from(Point3::new(277.5, 277.5, -800.0))
+                .look_at(Point3::new(277.5, 277.5, 0.0))
+                .vertical_fov(40.0)
+                .build(),
+        )
+        .sampler(SamplerConfig {
+            n: 14,
+            ..SamplerConfig::default()
+        })
+        .background(FloatRgb::new(0.0, 0.0, 0.0))
+        .xz_rect((213.0, 343.0), (227.0, 332.0), 554.0)
+        .material(light)
+        .name("light")
+        .add()
+        .yz_rect((0.0, 555.0), (0.0, 555.0), 555.0)
+        .material(green)
+        .add()
+        .yz_rect((0.0, 555.0), (0.0, 555.0), 0.0)
+        .material(red)
+        .add()
+        .xz_rect((0.0, 555.0), (0.0, 555.0), 0.0)
+        .material(white.clone())
+        .add()
+        .xz_rect((0.0, 555.0), (0.0, 555.0), 555.0)
+        .material(white.clone())
+        .add()
+        .xy_rect((0.0, 555.0), (0.0, 555.0), 555.0)
+        .material(white.clone())
+        .add()
+        .rect_prism(Point3::new(130.0, 0.0, 65.0), Point3::new(295.0, 165.0, 230.0))
+        .material(white.clone())
+        .add()
+        .rect_prism(Point3::new(265.0, 0.0, 295.0), Point3::new(430.0, 330.0, 460.0))
+        .material(white)
+        .add()
+        .build()
+}
+
+/// The final scene of Ray Tracing in One Weekend, with the moving spheres of
+/// The Next Week. The small spheres are placed and coloured by `seed`.
+pub fn random_spheres(seed: u64) -> Config {
+    let checker = CheckerTexture::new(
+        FloatRgb::new(0.2, 0.3, 0.1).into(),
+        FloatRgb::new(0.9, 0.9, 0.9).into(),
+    );
+    let mut scene = SceneBuilder::new()
+        .image(ImageBuilder::new().filename("random_scene_render.png").size(400, 300).build())
+        .camera(
+            CameraBuilder::new()
+                .look_from(Point3::new(13.0, 2.0, 3.0))
+                .look_at(Point3::new(0.0, 0.0, 0.0))
+                .vertical_fov(20.0)
+                .aperture(0.1)
+                .focus_distance(10.0)
+                .build(),
+        )
+        .sphere(Point3::new(0.0, -1000.0, 0.0), 1000.0)
+        .material(Material::lambertian(checker))
+        .add();
+
+    let spheres = RandomSpheres {
+        seed,
+        ..RandomSpheres::default()
+    };
+    for sphere in spheres.expand() {
+        scene = scene.object(sphere);
+    }
+
+    scene
+        .sphere(Point3::new(0.0, 1.0, 0.0), 1.0)
+        .material(Material::dielectric(1.5))
+        .add()
+        .sphere(Point3::new(-4.0, 1.0, 0.0), 1.0)
+        .material(Material::lambertian(FloatRgb::new(0.4, 0.2, 0.1)))
+        .add()
+        .sphere(Point3::new(4.0, 1.0, 0.0), 1.0)
+        .material(Material::metal(FloatRgb::new(0.7, 0.6, 0.5), 0.0))
+        .add()
+        .build()
+}
+
+/// A grid of small spheres scattered over the ground, as in the final scene
+/// of Ray Tracing in One Weekend, which stands in for the spheres in the
+/// config and is replaced by them before rendering. The same seed always
+/// gives the same spheres.
+///
+/// A sphere is placed at a random point within each cell of the grid, and is
+/// diffuse and moving, metal or glass with the given probabilities.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RandomSpheres {
+    pub seed: u64,
+    /// The spheres cover the unit cells from -grid to grid along x and z.
+    pub grid: u32,
+    pub small_radius: Float,
+    /// How far across its cell a sphere may be placed.
+    pub jitter: Float,
+    pub diffuse_probability: Float,
+    pub metal_probability: Float,
+    pub refractive_index: Float,
+    /// Diffuse spheres rise by up to this much over the shutter time from
+    /// 0 to 1, or stay still when it is zero.
+    pub max_bounce: Float,
+    /// Spheres closer than `exclusion_radius` to this point are left out,
+    /// to make room for a larger sphere.
+    pub exclusion_center: Point3,
+    pub exclusion_radius: Float,
+}
+
+impl Default for RandomSpheres {
+    fn default() -> Self {
+        RandomSpheres {
+            seed: 0,
+            grid: 11,
+            small_radius: 0.2,
+            jitter: 0.9,
+            diffuse_probability: 0.8,
+            metal_probability: 0.15,
+            refractive_index: 1.5,
+            max_bounce: 0.5,
+            exclusion_center: Point3::new(4.0, 0.2, 0.0),
+            exclusion_radius: 0.9,
+        }
+    }
+}
+
+impl From<RandomSpheres> for Object {
+    fn from(spheres: RandomSpheres) -> Object {
+        Object::RandomSpheres(spheres)
+    }
+}
+
+impl RandomSpheres {
+    /// The spheres, in grid order.
+    pub fn expand(&self) -> Vec<Object> {
+        let mut rng = Random::new(StdRng::seed_from_u64(self.seed));
+        let grid = self.grid as i64;
+        let radius = self.small_radius;
+        let still = |center: Point3| Ray3 {
+            origin: center,
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+
+        let mut spheres = Vec::new();
+        for a in -grid..grid {
+            for b in -grid..grid {
+                let choose_mat: Float = rng.random();
+                let center = Point3::new(
+                    a as Float + self.jitter * rng.random::<Float>(),
+                    radius,
+                    b as Float + self.jitter * rng.random::<Float>(),
+                );
+
+                if (center - self.exclusion_center).length() <= self.exclusion_radius {
+                    continue;
+                }
+
+                let sphere = if choose_mat < self.diffuse_probability {
+                    let albedo = rng.color() * rng.color();
+                    let mut location = still(center);
+                    if self.max_bounce > 0.0 {
+                        location.direction = Vec3::new(0.0, rng.random_range(0.0..self.max_bounce), 0.0);
+                    }
+                    Sphere::new(location, radius, Material::lambertian(albedo))
+                } else if choose_mat < self.diffuse_probability + self.metal_probability {
+                    let albedo = FloatRgb::new(
+                        rng.random_range(0.5..1.0),
+                        rng.random_range(0.5..1.0),
+                        rng.random_range(0.5..1.0),
+                    );
+                    let fuzz = rng.random_range(0.0..0.5);
+                    Sphere::new(still(center), radius, Material::metal(albedo, fuzz))
+                } else {
+                    let material = Material::dielectric(self.refractive_index);
+                    Sphere::new(still(center), radius, material)
+                };
+                spheres.push(sphere.into());
+            }
+        }
+        spheres
+    }
+
+    /// The problems with the parameters, as the name of the field at fault
+    /// and what is wrong with it.
+    pub fn validate(&self) -> Vec<(&'static str, &'static str)> {
+        let mut errors = Vec::new();
+        let mut check = |valid: bool, field, message| {
+            if !valid {
+                errors.push((field, message));
+            }
+        };
+
+        let probability = 0.0..=1.0;
+        check(self.small_radius > 0.0, "small_radius", "must be positive");
+        check(probability.contains(&self.jitter), "jitter", "must be between 0 and 1");
+        check(
+            probability.contains(&self.diffuse_probability),
+            "diffuse_probability",
+            "must be between 0 and 1",
+        );
+        check(
+            probability.contains(&self.metal_probability),
+            "metal_probability",
+            "must be between 0 and 1",
+        );
+        check(
+            self.diffuse_probability + self.metal_probability <= 1.0,
+            "metal_probability",
+            "cannot add up to more than one with diffuse_probability",
+        );
+        check(self.max_bounce >= 0.0, "max_bounce", "cannot be negative");
+        errors
+    }
+}
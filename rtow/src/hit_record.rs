@@ -5,9 +5,9 @@ pub struct HitRecord {
     pub point: Point3,
     pub normal: Vec3,
     pub ray_in: Ray3,
-    pub t: f64,
-    pub u: f64,
-    pub v: f64,
+    pub t: Float,
+    pub u: Float,
+    pub v: Float,
     pub front_face: bool,
 }
 
@@ -16,9 +16,9 @@ impl HitRecord {
         point: Point3,
         outward_normal: Vec3,
         ray_in: Ray3,
-        t: f64,
-        u: f64,
-        v: f64,
+        t: Float,
+        u: Float,
+        v: Float,
     ) -> HitRecord {
         let front_face = ray_in.direction.dot(outward_normal) < 0.0;
         let normal = if front_face {
@@ -38,3 +38,14 @@ impl HitRecord {
         }
     }
 }
+
+/// Per ray state carried down the hit path, used to count the work done
+/// finding the closest hit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HitContext {
+    /// The number of bounding boxes the ray was tested against.
+    pub aabb_tests: u32,
+    /// The number of nodes of a BVH whose children's boxes the ray was
+    /// tested against, together for a node with more than two children.
+    pub node_tests: u32,
+}
@@ -1,68 +1,347 @@
 use crate::color::*;
-use crate::config::ImageConfig;
+use crate::config::{Aov, ImageConfig, ImageFormat};
+use crate::denoise::Denoiser;
+use geometry3d::Float;
+use std::borrow::Cow;
+use std::io::Write;
+
+/// Writes a file through `encode`, first writing to a temporary file so that
+/// an interrupted write never leaves a truncated file in place of a complete
+/// one.
+pub fn write_file<F>(filename: &str, encode: F) -> std::io::Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> std::io::Result<()>,
+{
+    use std::fs::File;
+    use std::io::BufWriter;
+
+    let tmp_filename = format!("{filename}.tmp");
+    {
+        let file = File::create(&tmp_filename)?;
+        let mut w = BufWriter::new(file);
+        encode(&mut w)?;
+        w.flush()?;
+    }
+    std::fs::rename(tmp_filename, filename)
+}
 
 pub struct Image {
     pub filename: String,
     pub width: u32,
     pub height: u32,
-    pub aspect_ratio: f64,
-    data: Vec<u8>,
+    /// The width divided by the height, which the camera's view matches.
+    pub aspect_ratio: Float,
+    pub tile_size: u32,
+    pub format: ImageFormat,
+    pub gamma: Gamma,
+    pub flip_y: bool,
+    pub aovs: Vec<Aov>,
+    pub denoiser: Option<Denoiser>,
+    pub exposure: Float,
+    pub auto_exposure: bool,
+    /// The seed of the render, recorded in the metadata of PNG images.
+    pub seed: Option<u64>,
+    /// The number of samples taken for each pixel, or passes of a
+    /// progressive render, recorded in the metadata of PNG images. Unknown
+    /// when sampling adaptively.
+    pub samples_per_pixel: Option<u32>,
+    /// The config of the render as resolved JSON, with the overrides given
+    /// on the command line applied, recorded in the metadata of PNG images
+    /// so that the image can be rendered again.
+    pub config: Option<String>,
+    data: Vec<FloatRgb>,
+    alpha: Option<Vec<Float>>,
 }
 
+/// Iterates over the pixels of an image in memory order, i.e. row by row
+/// from the top left.
 pub struct ImageIter {
     width: u32,
+    height: u32,
     x: u32,
-    y: i32,
+    y: u32,
+}
+
+/// Iterates over the rectangular tiles that cover an image, starting from the
+/// top left. Tiles on the right and bottom edges are clipped to the image.
+pub struct TileIter {
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    tiles_x: u32,
+    tiles: u32,
+    tile: u32,
+}
+
+/// A rectangular region of an image with its top left pixel at (x, y).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct TilePixelIter {
+    tile: Tile,
+    i: u32,
 }
 
 impl Image {
+    const BYTES_PER_PIXEL: usize = 3;
+
     pub fn new(config: ImageConfig) -> Image {
-        let filename = config.filename;
+        assert!(config.tile_size > 0, "tile_size must be at least one.");
         let width = config.width;
-        let height = config.height;
-        let aspect_ratio = width as f64 / height as f64;
-        let size = (width * height * 3) as usize;
-        let data = Vec::with_capacity(size);
+        let height = config.height();
+        let filename = config.filename;
+        let aspect_ratio = width as Float / height as Float;
+        let tile_size = config.tile_size;
+        let format = config
+            .format
+            .unwrap_or_else(|| Self::infer_format(&filename));
+        let gamma = config.gamma;
+        let flip_y = config.flip_y;
+        let aovs = config.aovs;
+        let denoiser = config
+            .denoise
+            .then(|| Denoiser::new(config.denoise_strength, config.denoise_iterations));
+        let exposure = config.exposure;
+        let auto_exposure = config.auto_exposure;
+        let size = width as usize * height as usize;
+        let data = vec![FloatRgb::default(); size];
+        let alpha = config.alpha.then(|| vec![1.0; size]);
         Image {
             filename,
             width,
             height,
             aspect_ratio,
+            tile_size,
+            format,
+            gamma,
+            flip_y,
+            aovs,
+            denoiser,
+            exposure,
+            auto_exposure,
+            seed: None,
+            samples_per_pixel: None,
+            config: None,
             data,
+            alpha,
+        }
+    }
+
+    fn infer_format(filename: &str) -> ImageFormat {
+        let extension = std::path::Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("ppm") => ImageFormat::P6,
+            Some("exr") => ImageFormat::Exr,
+            _ => ImageFormat::Png,
         }
     }
 
     pub fn iter(&self) -> ImageIter {
         ImageIter {
             width: self.width,
+            height: self.height,
             x: 0,
-            y: (self.height - 1) as i32,
+            y: 0,
         }
     }
 
-    pub fn write(&self) -> std::io::Result<()> {
-        use std::fs::File;
-        use std::io::BufWriter;
-        use std::path::Path;
+    pub fn tiles(&self) -> TileIter {
+        let tiles_x = self.width.div_ceil(self.tile_size);
+        let tiles_y = self.height.div_ceil(self.tile_size);
+        TileIter {
+            width: self.width,
+            height: self.height,
+            tile_size: self.tile_size,
+            tiles_x,
+            tiles: tiles_x * tiles_y,
+            tile: 0,
+        }
+    }
 
-        let path = Path::new(&self.filename);
-        let file = File::create(path)?;
-        let w = BufWriter::new(file);
+    /// Whether the image is written to stdout rather than a file, which is
+    /// requested with a filename of `-`.
+    pub fn writes_to_stdout(&self) -> bool {
+        self.filename == "-"
+    }
 
+    /// The total exposure in stops, including the automatic exposure if it
+    /// is enabled.
+    pub fn total_exposure(&self) -> Float {
+        // middle grey, i.e. 18% reflectance
+        const KEY: Float = 0.18;
+
+        if self.auto_exposure {
+            let average = log_average_luminance(self.data.iter().copied());
+            (KEY / average).log2() + self.exposure
+        } else {
+            self.exposure
+        }
+    }
+
+    /// Encodes the image in its output format to `w`, after applying the
+    /// exposure.
+    pub fn write<W: Write>(&self, w: W) -> std::io::Result<()> {
+        let stops = self.total_exposure();
+        let data: Cow<[FloatRgb]> = if stops == 0.0 {
+            Cow::Borrowed(&self.data)
+        } else {
+            Cow::Owned(self.data.iter().map(|c| c.expose(stops)).collect())
+        };
+
+        match self.format {
+            ImageFormat::Png => self.encode_png(&data, w),
+            ImageFormat::P6 => self.encode_p6(&data, w),
+            ImageFormat::P3 => self.encode_p3(&data, w),
+            ImageFormat::Exr => self.encode_exr(&data, w),
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.alpha.is_some()
+    }
+
+    /// The image as 8-bit RGB values, from the top row down.
+    fn bytes(&self, data: &[FloatRgb]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(data.len() * Self::BYTES_PER_PIXEL);
+        for &color in data {
+            let rgb = Rgb::from_linear(color, self.gamma);
+            bytes.extend(rgb.as_ntuple());
+        }
+        bytes
+    }
+
+    /// The image as 8-bit straight (not premultiplied) RGBA values, from the
+    /// top row down.
+    fn bytes_rgba(&self, data: &[FloatRgb], alpha: &[Float]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(data.len() * (Self::BYTES_PER_PIXEL + 1));
+        for (&color, &a) in data.iter().zip(alpha) {
+            let straight = if a > 0.0 {
+                FloatRgb::new(color.r() / a, color.g() / a, color.b() / a)
+            } else {
+                color
+            };
+            let rgb = Rgb::from_linear(straight, self.gamma);
+            let a = (a.clamp(0.0, 1.0) * 255.0).round() as u8;
+            bytes.extend([rgb.r(), rgb.g(), rgb.b(), a]);
+        }
+        bytes
+    }
+
+    fn encode_png<W: Write>(&self, data: &[FloatRgb], w: W) -> std::io::Result<()> {
         let mut encoder = png::Encoder::new(w, self.width, self.height);
-        encoder.set_color(png::ColorType::Rgb);
         encoder.set_depth(png::BitDepth::Eight);
+        if let Some(seed) = self.seed {
+            encoder.add_text_chunk(String::from("Seed"), seed.to_string())?;
+        }
+        if let Some(samples) = self.samples_per_pixel {
+            encoder.add_text_chunk(String::from("Samples per pixel"), samples.to_string())?;
+        }
+        // compressed, as it holds the whole scene, and UTF-8 for file names
+        let config = match &self.config {
+            Some(json) => {
+                let mut chunk = png::text_metadata::ITXtChunk::new("Config", json);
+                chunk.compress_text()?;
+                Some(chunk)
+            }
+            None => None,
+        };
 
+        encoder.set_color(match self.alpha {
+            Some(_) => png::ColorType::Rgba,
+            None => png::ColorType::Rgb,
+        });
         let mut writer = encoder.write_header()?;
-        writer.write_image_data(&self.data)?;
+        if let Some(chunk) = &config {
+            writer.write_text_chunk(chunk)?;
+        }
+        match &self.alpha {
+            Some(alpha) => writer.write_image_data(&self.bytes_rgba(data, alpha))?,
+            None => writer.write_image_data(&self.bytes(data))?,
+        }
+
+        Ok(())
+    }
+
+    fn encode_p6<W: Write>(&self, data: &[FloatRgb], mut w: W) -> std::io::Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        w.write_all(&self.bytes(data))
+    }
 
+    fn encode_p3<W: Write>(&self, data: &[FloatRgb], mut w: W) -> std::io::Result<()> {
+        write!(w, "P3\n{} {}\n255\n", self.width, self.height)?;
+        // one pixel per line keeps lines well under the 70 character limit
+        for &color in data {
+            let rgb = Rgb::from_linear(color, self.gamma);
+            writeln!(w, "{} {} {}", rgb.r(), rgb.g(), rgb.b())?;
+        }
         Ok(())
     }
 
-    pub fn add_pixel(&mut self, color: Rgb) {
-        self.data.push(color.r());
-        self.data.push(color.g());
-        self.data.push(color.b());
+    /// Writes linear RGB, and premultiplied alpha if present, as uncompressed
+    /// 32-bit floats.
+    // the casts are only needed when `Float` is f64
+    #[allow(clippy::unnecessary_cast)]
+    fn encode_exr<W: Write>(&self, data: &[FloatRgb], mut w: W) -> std::io::Result<()> {
+        use exr::prelude::{SpecificChannels, Vec2, WritableImage};
+
+        let width = self.width as usize;
+        let size = (width, self.height as usize);
+        let rgb = |x: usize, y: usize| {
+            let color: FloatRgb = data[y * width + x];
+            (color.r() as f32, color.g() as f32, color.b() as f32)
+        };
+
+        // the exr encoder needs to seek so encode into memory first
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        if let Some(alpha) = &self.alpha {
+            let channels = SpecificChannels::rgba(|Vec2(x, y)| {
+                let (r, g, b) = rgb(x, y);
+                (r, g, b, alpha[y * width + x] as f32)
+            });
+            exr::image::Image::from_channels(size, channels)
+                .write()
+                .to_buffered(&mut buffer)
+                .map_err(std::io::Error::other)?;
+        } else {
+            let channels = SpecificChannels::rgb(|Vec2(x, y)| rgb(x, y));
+            exr::image::Image::from_channels(size, channels)
+                .write()
+                .to_buffered(&mut buffer)
+                .map_err(std::io::Error::other)?;
+        }
+        w.write_all(buffer.get_ref())
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> FloatRgb {
+        self.data[self.index(x, y)]
+    }
+
+    /// Sets the color of the pixel (x, y), using top left as (0, 0).
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: FloatRgb) {
+        let i = self.index(x, y);
+        self.data[i] = color;
+    }
+
+    /// Sets the alpha of the pixel (x, y). Has no effect on images without an
+    /// alpha channel.
+    pub fn set_alpha(&mut self, x: u32, y: u32, alpha: Float) {
+        let i = self.index(x, y);
+        if let Some(a) = &mut self.alpha {
+            a[i] = alpha;
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "Pixel ({x}, {y}) out of bounds.");
+        y as usize * self.width as usize + x as usize
     }
 }
 
@@ -70,18 +349,64 @@ impl Iterator for ImageIter {
     type Item = (u32, u32);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.y < 0 {
+        if self.y == self.height {
             None
         } else {
-            let ret = Some(( self.x, self.y as u32 ));
+            let ret = Some((self.x, self.y));
 
             self.x = (self.x + 1) % self.width;
             if self.x == 0 {
-                eprint!("\rScanlines remaining: {} ", self.y);
-                self.y -= 1;
+                self.y += 1;
             }
 
             ret
         }
     }
 }
+
+impl Iterator for TileIter {
+    type Item = Tile;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.tile == self.tiles {
+            None
+        } else {
+            let column = self.tile % self.tiles_x;
+            let row = self.tile / self.tiles_x;
+
+            let x = column * self.tile_size;
+            let y = row * self.tile_size;
+            let width = self.tile_size.min(self.width - x);
+            let height = self.tile_size.min(self.height - y);
+
+            self.tile += 1;
+
+            Some(Tile { x, y, width, height })
+        }
+    }
+}
+
+impl Tile {
+    pub fn iter(self) -> TilePixelIter {
+        TilePixelIter { tile: self, i: 0 }
+    }
+
+    pub fn pixels(self) -> u32 {
+        self.width * self.height
+    }
+}
+
+impl Iterator for TilePixelIter {
+    type Item = (u32, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.i == self.tile.pixels() {
+            None
+        } else {
+            let x = self.tile.x + self.i % self.tile.width;
+            let y = self.tile.y + self.i / self.tile.width;
+            self.i += 1;
+            Some((x, y))
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::config::ConfigFormat;
+use crate::expand::expand;
+use serde_json::Value;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// The deepest chain of files including one another that is followed.
+pub const MAX_DEPTH: usize = 16;
+
+/// Replaces every `{"include": "path"}` in the config file at `path`, holding
+/// `text`, with the contents of the named file, resolved relative to the file
+/// it appears in. An include in a list whose file holds a list is spliced
+/// into it, so e.g. `scene_list.objects` can be split across several files.
+///
+/// Included files are read in the format of their extension and may include
+/// further files. Environment variables and `~` in their paths are expanded
+/// when `expand_paths` is set. Returns None if the file has no includes.
+pub fn resolve(
+    text: &str,
+    format: ConfigFormat,
+    path: &Path,
+    expand_paths: bool,
+) -> Result<Option<Value>, Box<dyn Error>> {
+    let mut resolver = Resolver {
+        chain: vec![canonical(path)],
+        found: false,
+        expand_paths,
+    };
+    // errors in the file itself are left for `Config::parse` to describe
+    let Ok(mut value) = resolver.parse(text, format) else {
+        return Ok(None);
+    };
+    resolver.expand(&mut value, directory(path))?;
+    Ok(resolver.found.then_some(value))
+}
+
+struct Resolver {
+    /// The files currently being read, each included by the one before.
+    chain: Vec<PathBuf>,
+    found: bool,
+    expand_paths: bool,
+}
+
+impl Resolver {
+    fn load(&mut self, path: &Path) -> Result<Value, Box<dyn Error>> {
+        let path = canonical(path);
+        if self.chain.contains(&path) {
+            self.chain.push(path);
+            return Err(format!("Include cycle: {}", self.describe_chain()).into());
+        }
+        self.chain.push(path);
+        if self.chain.len() > MAX_DEPTH {
+            let chain = self.describe_chain();
+            return Err(format!("Includes are nested more than {MAX_DEPTH} deep: {chain}").into());
+        }
+
+        let path = self.chain.last().unwrap().clone();
+        let text = std::fs::read_to_string(&path).map_err(|e| self.error(e))?;
+        let mut value = self.parse(&text, ConfigFormat::from_path(&path))?;
+        self.expand(&mut value, directory(&path))?;
+        self.chain.pop();
+        Ok(value)
+    }
+
+    fn parse(&self, text: &str, format: ConfigFormat) -> Result<Value, Box<dyn Error>> {
+        match format {
+            ConfigFormat::Json => serde_json::from_str(text).map_err(|e| self.error(e)),
+            ConfigFormat::Toml => toml::from_str(text).map_err(|e| self.error(e)),
+            ConfigFormat::Yaml => serde_yaml::from_str(text).map_err(|e| self.error(e)),
+        }
+    }
+
+    fn expand(&mut self, value: &mut Value, dir: &Path) -> Result<(), Box<dyn Error>> {
+        match value {
+            Value::Object(map) => match include(map) {
+                Some(file) => {
+                    self.found = true;
+                    let file = match self.expand_paths {
+                        true => expand(file).map_err(|e| self.error(e))?,
+                        false => file.to_string(),
+                    };
+                    *value = self.load(&dir.join(file))?;
+                }
+                None => {
+                    for value in map.values_mut() {
+                        self.expand(value, dir)?;
+                    }
+                }
+            },
+            Value::Array(values) => {
+                let mut expanded = Vec::with_capacity(values.len());
+                for mut value in values.drain(..) {
+                    let spliced = matches!(&value, Value::Object(map) if include(map).is_some());
+                    self.expand(&mut value, dir)?;
+                    match value {
+                        Value::Array(values) if spliced => expanded.extend(values),
+                        value => expanded.push(value),
+                    }
+                }
+                *values = expanded;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// The files of the chain, e.g. `scene.json -> objects.json`.
+    fn describe_chain(&self) -> String {
+        let files: Vec<_> = self.chain.iter().map(|path| path.display().to_string()).collect();
+        files.join(" -> ")
+    }
+
+    /// An error in the last file of the chain, prefixed by the chain when the
+    /// file was included.
+    fn error<E: std::fmt::Display>(&self, error: E) -> Box<dyn Error> {
+        match self.chain.len() {
+            1 => error.to_string().into(),
+            _ => format!("{}: {error}", self.describe_chain()).into(),
+        }
+    }
+}
+
+/// The file named by an object of the form `{"include": "path"}`.
+fn include(map: &serde_json::Map<String, Value>) -> Option<&str> {
+    match map.get("include") {
+        Some(Value::String(file)) if map.len() == 1 => Some(file),
+        _ => None,
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn directory(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new("."))
+}
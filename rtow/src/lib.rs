@@ -3,10 +3,26 @@ pub mod hit_record;
 pub mod material;
 pub mod object;
 pub mod image;
+pub mod framebuffer;
+pub mod checkpoint;
+pub mod aov;
+pub mod denoise;
+pub mod progress;
+pub mod stats;
 pub mod sampler;
 pub mod config;
+pub mod include;
+pub mod expand;
 pub mod color;
 pub mod random;
 pub mod texture;
 pub mod perlin;
+pub mod light;
+pub mod render;
+pub mod renderer;
+pub mod builder;
+pub mod generate;
+pub mod cli;
+pub mod batch;
+pub mod compare;
 
@@ -0,0 +1,116 @@
+use crate::config::{LightConfig, ObjectRef};
+use crate::object::{List, Object};
+use crate::random::Random;
+use geometry3d::*;
+use rand::Rng;
+use std::error::Error;
+
+/// The emitters of a scene that can be sampled directly.
+///
+/// Directions are picked by choosing one of the lights in proportion to its
+/// weight and then a point on its surface, so the density of a direction is
+/// the weighted average of the densities of every light in that direction.
+#[derive(Debug, Clone, Default)]
+pub struct Lights {
+    lights: Vec<Object>,
+    weights: Vec<Float>,
+    total_weight: Float,
+}
+
+impl Lights {
+    pub fn new() -> Lights {
+        Lights::default()
+    }
+
+    /// Gathers every object in the list, and in lists nested within it, that
+    /// `Object::is_light`, all with the same weight.
+    pub fn from_list(list: &List) -> Lights {
+        fn gather(object: &Object, lights: &mut Lights) {
+            match object {
+                Object::List(list) => list.iter().for_each(|o| gather(o, lights)),
+                Object::Named(named) => gather(&named.object, lights),
+                o if o.is_light() => lights.push(o.clone(), 1.0),
+                _ => (),
+            }
+        }
+
+        let mut lights = Lights::new();
+        list.iter().for_each(|o| gather(o, &mut lights));
+        lights
+    }
+
+    /// Resolves the lights listed in the config, falling back to
+    /// `from_list` when there are none. Referring to an object that doesn't
+    /// exist or isn't a light is an error.
+    pub fn from_config(list: &List, configs: &[LightConfig]) -> Result<Lights, Box<dyn Error>> {
+        if configs.is_empty() {
+            return Ok(Lights::from_list(list));
+        }
+
+        let mut lights = Lights::new();
+        for config in configs {
+            let object = match &config.object {
+                ObjectRef::Index(i) => list.get(*i),
+                ObjectRef::Name(name) => list.find(name),
+            }
+            .ok_or_else(|| format!("The light {} does not exist.", config.object))?;
+
+            if !object.is_light() {
+                return Err(format!(
+                    "The light {} is not an emissive sphere or rectangle.",
+                    config.object
+                )
+                .into());
+            }
+            if !(config.weight.is_finite() && config.weight > 0.0) {
+                return Err(format!("The weight of light {} must be positive.", config.object).into());
+            }
+            lights.push(object.clone(), config.weight);
+        }
+
+        Ok(lights)
+    }
+
+    fn push(&mut self, light: Object, weight: Float) {
+        self.lights.push(light);
+        self.weights.push(weight);
+        self.total_weight += weight;
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /// A unit direction from `origin` towards a random point on a randomly
+    /// chosen light.
+    pub fn sample<R: Rng>(&self, origin: Point3, time: Float, rng: &mut Random<R>) -> Option<Vec3> {
+        let mut pick = rng.random::<Float>() * self.total_weight;
+        for (light, weight) in self.lights.iter().zip(&self.weights) {
+            if pick < *weight {
+                return light.sample_light(origin, time, rng);
+            }
+            pick -= weight;
+        }
+        // rounding can leave a sliver past the last light
+        self.lights.last()?.sample_light(origin, time, rng)
+    }
+
+    /// The probability density, over solid angle, of `sample` picking
+    /// `direction` from `origin`.
+    pub fn pdf(&self, origin: Point3, direction: Vec3, time: Float) -> Float {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let total: Float = self
+            .lights
+            .iter()
+            .zip(&self.weights)
+            .map(|(light, weight)| weight * light.light_pdf(origin, direction, time))
+            .sum();
+        total / self.total_weight
+    }
+}
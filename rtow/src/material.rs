@@ -1,6 +1,6 @@
 use crate::hit_record::HitRecord;
 use crate::color::FloatRgb;
-use crate::random::Random;
+use crate::random;
 use crate::texture::*;
 use geometry3d::*;
 use serde::{Serialize, Deserialize};
@@ -29,8 +29,69 @@ impl Material {
             _ => FloatRgb::new(0.0, 0.0, 0.0)
         }
     }
+
+    pub fn lambertian<T: Into<Texture>>(albedo: T) -> Material {
+        Lambertian::new(albedo.into()).into()
+    }
+
+    pub fn metal(albedo: FloatRgb, fuzz: Float) -> Material {
+        Metal::new(albedo, fuzz).into()
+    }
+
+    pub fn dielectric(index_of_refraction: Float) -> Material {
+        Dielectric::new(index_of_refraction).into()
+    }
+
+    pub fn diffuse_light<T: Into<Texture>>(emit: T) -> Material {
+        DiffuseLight::new(emit.into()).into()
+    }
+
+    /// Calls `f` on the file name of each image texture of the material.
+    pub fn for_each_path(&mut self, f: &mut dyn FnMut(&mut String)) {
+        match self {
+            Material::Lambertian(Lambertian { albedo: texture })
+            | Material::DiffuseLight(DiffuseLight { emit: texture }) => texture.for_each_path(f),
+            Material::Metal(_) | Material::Dielectric(_) => (),
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, Material::DiffuseLight(_))
+    }
+
+    /// Whether the material scatters light with a cosine weighted Lambertian
+    /// distribution, and so can be lit by sampling the lights.
+    pub fn is_diffuse(&self) -> bool {
+        matches!(self, Material::Lambertian(_))
+    }
+
+    /// The base color of the surface at the hit, ignoring lighting. Clear
+    /// dielectrics are white and lights give their emitted color.
+    pub fn albedo(&mut self, rec: HitRecord) -> FloatRgb {
+        match self {
+            Material::Lambertian(m) => m.albedo.value(rec),
+            Material::Metal(m) => m.albedo,
+            Material::Dielectric(_) => FloatRgb::new(1.0, 1.0, 1.0),
+            Material::DiffuseLight(m) => m.emit(rec),
+        }
+    }
+}
+
+macro_rules! material_from {
+    ( $name:ident ) => {
+        impl From<$name> for Material {
+            fn from(m: $name) -> Material {
+                Material::$name(m)
+            }
+        }
+    };
 }
 
+material_from!(Lambertian);
+material_from!(Metal);
+material_from!(Dielectric);
+material_from!(DiffuseLight);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Lambertian {
     albedo: Texture,
@@ -50,7 +111,7 @@ impl Lambertian {
         // unit normal + unit vector guaranteed to lie in or above the
         // tangent plane, thus only need to account for the case of
         // a direction vector of zero length
-        let mut rng = Random::new(rand::thread_rng());
+        let mut rng = random::rng();
         let scatter = rec.normal + rng.unit_vector();
         let direction = match scatter.unit() {
             Some(vec) => vec,
@@ -62,18 +123,18 @@ impl Lambertian {
 
         let attenuation = self.albedo.value(rec);
 
-        Some((attenuation, Ray3 { origin, direction, time }))
+        Ray3::new(origin, direction, time).map(|ray| (attenuation, ray))
     }
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Metal {
     albedo: FloatRgb,
-    fuzz: f64,
+    fuzz: Float,
 }
 
 impl Metal {
-    pub fn new(albedo: FloatRgb, fuzz: f64) -> Metal {
+    pub fn new(albedo: FloatRgb, fuzz: Float) -> Metal {
         Metal { albedo, fuzz }
     }
 
@@ -85,7 +146,7 @@ impl Metal {
 
         // calculate pure specular reflection vector
         let reflection = rec.ray_in.direction.reflection(rec.normal);
-        let mut rng = Random::new(rand::thread_rng());
+        let mut rng = random::rng();
         let mut direction;
         loop {
             direction = reflection + self.fuzz * rng.in_unit_sphere();
@@ -99,41 +160,31 @@ impl Metal {
                 break;
             }
         }
-        direction = direction.unit().unwrap();
 
         let origin = rec.point;
         let time = rec.ray_in.time;
 
-        Some((self.albedo, Ray3 { origin, direction, time }))
+        Ray3::new(origin, direction, time).map(|ray| (self.albedo, ray))
     }
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Dielectric {
-    index_of_refraction: f64,
+    index_of_refraction: Float,
 }
 
 impl Dielectric {
-    pub fn new(index_of_refraction: f64) -> Dielectric {
+    pub fn new(index_of_refraction: Float) -> Dielectric {
         Dielectric { index_of_refraction }
     }
 
-    fn reflectance(cosine: f64, refractive_index: f64) -> f64 {
+    fn reflectance(cosine: Float, refractive_index: Float) -> Float {
         // Use Schlick's approximation for reflectance.
         let mut r0 = (1.0 - refractive_index) / (1.0 + refractive_index);
         r0 = r0 * r0;
         r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
     }
 
-    fn refraction(vec: Vec3, normal: Vec3, eta_over_eta_prime: f64) -> Vec3 {
-        let cos_theta = -vec.dot(normal);
-        let r_in_perp = vec + cos_theta * normal;
-        let r_out_perp = eta_over_eta_prime * r_in_perp;
-        let parallel_len = (1.0 - r_out_perp.quadrance()).sqrt();
-        let r_out_parallel = -parallel_len * normal;
-        r_out_perp + r_out_parallel
-    }
-
     fn scatter(&self, rec: HitRecord) -> Option<(FloatRgb, Ray3)> {
         // calculate refraction ratio depending on in internal/external reflection
         let refraction_ratio = match rec.front_face {
@@ -142,21 +193,18 @@ impl Dielectric {
         };
 
         let cos_theta = -rec.normal.dot(rec.ray_in.direction);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
-
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let reflectance = Self::reflectance(cos_theta, self.index_of_refraction);
-        let reflect = cannot_refract || reflectance > rand::random();
 
-        let direction = match reflect {
-            true => rec.ray_in.direction.reflection(rec.normal),
-            false => Self::refraction(rec.ray_in.direction, rec.normal, refraction_ratio),
+        // rays that cannot refract are reflected, as are some that can
+        let direction = match rec.ray_in.direction.refraction(rec.normal, refraction_ratio) {
+            Some(refracted) if reflectance <= random::rng().random() => refracted,
+            _ => rec.ray_in.direction.reflection(rec.normal),
         };
 
         let origin = rec.point;
         let time = rec.ray_in.time;
 
-        Some((FloatRgb::new(1.0, 1.0, 1.0), Ray3 { origin, direction, time }))
+        Ray3::new(origin, direction, time).map(|ray| (FloatRgb::new(1.0, 1.0, 1.0), ray))
     }
 }
 
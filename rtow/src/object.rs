@@ -1,10 +1,13 @@
-use crate::hit_record::HitRecord;
+use crate::generate::RandomSpheres;
+use crate::hit_record::{HitContext, HitRecord};
+use crate::config::BvhBuilder;
 use crate::material::Material;
+use crate::random::{self, Random};
 use geometry3d::*;
 use ntuple::*;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
-use std::rc::Rc;
+use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Object {
@@ -15,22 +18,37 @@ pub enum Object {
     RectPrism(RectPrism),
     List(List),
     BVHNode(BVHNode),
+    FlatBVH(FlatBVH),
+    BVH4(BVH4),
+    Named(Named),
+    /// Stands in for the spheres it generates until `expand` is called, and
+    /// is never hit before then.
+    RandomSpheres(RandomSpheres),
 }
 
 impl Object {
-    pub fn hit(&mut self, ray: Ray3, t_range: TRange<f64>) -> Option<(HitRecord, &mut Material)> {
+    pub fn hit(
+        &mut self,
+        ray: Ray3,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+    ) -> Option<(HitRecord, &mut Material)> {
         match self {
-            Object::Sphere(o) => o.hit(ray, t_range),
-            Object::XYRect(o) => o.hit(ray, t_range),
-            Object::XZRect(o) => o.hit(ray, t_range),
-            Object::YZRect(o) => o.hit(ray, t_range),
-            Object::RectPrism(o) => o.hit(ray, t_range),
-            Object::List(o) => o.hit(ray, t_range),
-            Object::BVHNode(o) => o.hit(ray, t_range),
+            Object::Sphere(o) => o.hit(ray, t_range, ctx),
+            Object::XYRect(o) => o.hit(ray, t_range, ctx),
+            Object::XZRect(o) => o.hit(ray, t_range, ctx),
+            Object::YZRect(o) => o.hit(ray, t_range, ctx),
+            Object::RectPrism(o) => o.hit(ray, t_range, ctx),
+            Object::List(o) => o.hit(ray, t_range, ctx),
+            Object::BVHNode(o) => o.hit(ray, t_range, ctx),
+            Object::FlatBVH(o) => o.hit(ray, t_range, ctx),
+            Object::BVH4(o) => o.hit(ray, t_range, ctx),
+            Object::Named(o) => o.object.hit(ray, t_range, ctx),
+            Object::RandomSpheres(_) => None,
         }
     }
 
-    pub fn bounding_box(&self, t_range: TRange<f64>) -> Option<AABB> {
+    pub fn bounding_box(&self, t_range: TRange<Float>) -> Option<AABB> {
         match self {
             Object::Sphere(o) => o.bounding_box(t_range),
             Object::XYRect(o) => o.bounding_box(t_range),
@@ -39,6 +57,137 @@ impl Object {
             Object::RectPrism(o) => o.bounding_box(t_range),
             Object::List(o) => o.bounding_box(t_range),
             Object::BVHNode(o) => o.bounding_box(t_range),
+            Object::FlatBVH(o) => o.bounding_box(t_range),
+            Object::BVH4(o) => o.bounding_box(t_range),
+            Object::Named(o) => o.object.bounding_box(t_range),
+            Object::RandomSpheres(_) => None,
+        }
+    }
+
+    /// Whether the object is an emitter whose surface can be sampled by
+    /// `sample_light`.
+    pub fn is_light(&self) -> bool {
+        match self {
+            Object::Sphere(Sphere { material, .. })
+            | Object::XYRect(XYRect { material, .. })
+            | Object::XZRect(XZRect { material, .. })
+            | Object::YZRect(YZRect { material, .. }) => material.is_emissive(),
+            Object::Named(o) => o.object.is_light(),
+            _ => false,
+        }
+    }
+
+    /// A unit direction from `origin` towards a random point on the surface
+    /// of the object, or None if the object can't be sampled.
+    pub fn sample_light<R: Rng>(&self, origin: Point3, time: Float, rng: &mut Random<R>) -> Option<Vec3> {
+        match self {
+            Object::Sphere(o) => o.sample_light(origin, time, rng),
+            Object::XYRect(o) => o.sample_light(origin, rng),
+            Object::XZRect(o) => o.sample_light(origin, rng),
+            Object::YZRect(o) => o.sample_light(origin, rng),
+            Object::Named(o) => o.object.sample_light(origin, time, rng),
+            _ => None,
+        }
+    }
+
+    /// The probability density, over solid angle, of `sample_light` picking
+    /// `direction` from `origin`.
+    pub fn light_pdf(&self, origin: Point3, direction: Vec3, time: Float) -> Float {
+        match self {
+            Object::Sphere(o) => o.light_pdf(origin, direction, time),
+            Object::XYRect(o) => o.light_pdf(origin, direction),
+            Object::XZRect(o) => o.light_pdf(origin, direction),
+            Object::YZRect(o) => o.light_pdf(origin, direction),
+            Object::Named(o) => o.object.light_pdf(origin, direction, time),
+            _ => 0.0,
+        }
+    }
+
+    /// The material of the object, if it is made of a single one.
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        match self {
+            Object::Sphere(Sphere { material, .. })
+            | Object::XYRect(XYRect { material, .. })
+            | Object::XZRect(XZRect { material, .. })
+            | Object::YZRect(YZRect { material, .. }) => Some(material),
+            Object::Named(o) => o.object.material_mut(),
+            _ => None,
+        }
+    }
+
+    /// Resolves the relative file names of the image textures of the object
+    /// against `base_dir`.
+    pub fn resolve_paths(&mut self, base_dir: &Path) {
+        self.for_each_path(&mut |filename| {
+            *filename = base_dir.join(&*filename).to_string_lossy().into_owned();
+        });
+    }
+
+    /// Calls `f` on the file name of each image texture of the object.
+    pub fn for_each_path(&mut self, f: &mut dyn FnMut(&mut String)) {
+        match self {
+            Object::Sphere(Sphere { material, .. })
+            | Object::XYRect(XYRect { material, .. })
+            | Object::XZRect(XZRect { material, .. })
+            | Object::YZRect(YZRect { material, .. })
+            | Object::RectPrism(RectPrism::U(RectPrismU { material, .. })) => material.for_each_path(f),
+            Object::RectPrism(RectPrism::I(RectPrismI { sides })) => sides.for_each_path(f),
+            Object::BVHNode(sides) => {
+                sides.left.for_each_path(f);
+                sides.right.for_each_path(f);
+            }
+            Object::List(o) => o.for_each_path(f),
+            Object::FlatBVH(o) => o.objects.iter_mut().for_each(|o| o.for_each_path(f)),
+            Object::BVH4(o) => o.objects.iter_mut().for_each(|o| o.for_each_path(f)),
+            Object::Named(o) => o.object.for_each_path(f),
+            Object::RandomSpheres(_) => (),
+        }
+    }
+
+    /// Replaces the generated objects within the object, e.g.
+    /// `RandomSpheres`, with what they generate.
+    pub fn expand(&mut self) {
+        match self {
+            Object::RandomSpheres(o) => {
+                let mut list = List::new();
+                o.expand().into_iter().for_each(|object| list.add(object));
+                *self = Object::List(list);
+            }
+            Object::List(o) => o.expand(),
+            Object::Named(o) => o.object.expand(),
+            _ => (),
+        }
+    }
+
+    /// Finds the object called `name`, searching through nested lists.
+    pub fn find(&self, name: &str) -> Option<&Object> {
+        match self {
+            Object::Named(o) if o.name == name => Some(self),
+            Object::Named(o) => o.object.find(name),
+            Object::List(o) => o.find(name),
+            _ => None,
+        }
+    }
+}
+
+/// An object given a name so that other parts of the config can refer to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Named {
+    pub name: String,
+    pub object: Box<Object>,
+}
+
+impl From<Named> for Object {
+    fn from(n: Named) -> Object {
+        Object::Named(n)
+    }
+}
+
+impl Named {
+    pub fn new<S: Into<String>>(name: S, object: Object) -> Named {
+        Named {
+            name: name.into(),
+            object: Box::new(object),
         }
     }
 }
@@ -46,7 +195,7 @@ impl Object {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sphere {
     location: Ray3,
-    radius: f64,
+    radius: Float,
     material: Material,
 }
 
@@ -57,7 +206,7 @@ impl From<Sphere> for Object {
 }
 
 impl Sphere {
-    pub fn new(location: Ray3, radius: f64, material: Material) -> Sphere {
+    pub fn new(location: Ray3, radius: Float, material: Material) -> Sphere {
         Sphere {
             location,
             radius,
@@ -65,15 +214,15 @@ impl Sphere {
         }
     }
 
-    pub fn center(&self, time: f64) -> Point3 {
+    pub fn center(&self, time: Float) -> Point3 {
         self.location.at(time - self.location.time)
     }
 
     /// Given a point (p) on a sphere of radius one, centered at the origin,
     /// calculates a uv mapping on the surface of the sphere such that u and v
     /// lie in [0, 1].
-    pub fn uv(p: Point3) -> (f64, f64) {
-        let pi = std::f64::consts::PI;
+    pub fn uv(p: Point3) -> (Float, Float) {
+        let pi = geometry3d::consts::PI;
 
         let theta = (-p.y()).acos();
         let phi = (-p.z()).atan2(p.x()) + pi;
@@ -84,30 +233,14 @@ impl Sphere {
         (u, v)
     }
 
-    fn hit(&mut self, ray: Ray3, t_range: TRange<f64>) -> Option<(HitRecord, &mut Material)> {
+    fn hit(
+        &mut self,
+        ray: Ray3,
+        t_range: TRange<Float>,
+        _ctx: &mut HitContext,
+    ) -> Option<(HitRecord, &mut Material)> {
         let center = self.center(ray.time);
-
-        let oc = ray.origin - center;
-        let a = ray.direction.dot(ray.direction);
-        let half_b = ray.direction.dot(oc);
-        let c = oc.dot(oc) - self.radius * self.radius;
-        let delta = half_b * half_b - a * c;
-        if delta < 0.0 {
-            // no solutions -> no intersection
-            return None;
-        }
-
-        // find the nearest root that lies in the acceptable range
-        let sqrtd = delta.sqrt();
-        let mut root = (-half_b - sqrtd) / a;
-        if !t_range.contains(&root) {
-            root = (-half_b + sqrtd) / a;
-            if !t_range.contains(&root) {
-                return None;
-            }
-        }
-
-        let t = root;
+        let t = intersect_ray_sphere(ray, center, self.radius, t_range)?;
         let point = ray.at(t);
         let outward_normal = (point - center) / self.radius;
         let (u, v) = Sphere::uv(outward_normal.into());
@@ -119,8 +252,35 @@ impl Sphere {
         ))
     }
 
-    fn bounding_box(&self, t_range: TRange<f64>) -> Option<AABB> {
-        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+    /// Samples the cone of directions subtended by the sphere, or every
+    /// direction when `origin` is inside it.
+    fn sample_light<R: Rng>(&self, origin: Point3, time: Float, rng: &mut Random<R>) -> Option<Vec3> {
+        let to_center = self.center(time) - origin;
+        let (direction, _) = rng.to_sphere(to_center, to_center.quadrance(), self.radius);
+        Some(direction)
+    }
+
+    fn light_pdf(&self, origin: Point3, direction: Vec3, time: Float) -> Float {
+        let to_center = self.center(time) - origin;
+        let distance_squared = to_center.quadrance();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 1.0 / (4.0 * geometry3d::consts::PI);
+        }
+
+        let Some(direction) = direction.unit() else {
+            return 0.0;
+        };
+        let along = direction.dot(to_center);
+        if along <= 0.0 || distance_squared - along * along > radius_squared {
+            return 0.0;
+        }
+
+        random::cone_pdf((1.0 - radius_squared / distance_squared).sqrt())
+    }
+
+    fn bounding_box(&self, t_range: TRange<Float>) -> Option<AABB> {
+        let rvec = Vec3::from(NTuple::repeat(self.radius));
 
         let center0 = self.center(t_range.start);
         let center1 = self.center(t_range.end);
@@ -154,7 +314,49 @@ impl List {
         self.objects.push(object);
     }
 
-    fn hit(&mut self, ray: Ray3, t_range: TRange<f64>) -> Option<(HitRecord, &mut Material)> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Object> {
+        self.objects.iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Object> {
+        self.objects.get(index)
+    }
+
+    pub fn resolve_paths(&mut self, base_dir: &Path) {
+        self.objects.iter_mut().for_each(|o| o.resolve_paths(base_dir));
+    }
+
+    pub fn for_each_path(&mut self, f: &mut dyn FnMut(&mut String)) {
+        self.objects.iter_mut().for_each(|o| o.for_each_path(f));
+    }
+
+    /// Replaces the generated objects in the list with what they generate,
+    /// spliced directly into the list so that the BVH can separate them.
+    pub fn expand(&mut self) {
+        let mut expanded = Vec::with_capacity(self.objects.len());
+        for mut object in self.objects.drain(..) {
+            match object {
+                Object::RandomSpheres(o) => expanded.extend(o.expand()),
+                _ => {
+                    object.expand();
+                    expanded.push(object);
+                }
+            }
+        }
+        self.objects = expanded;
+    }
+
+    /// Finds the object called `name` within the list.
+    pub fn find(&self, name: &str) -> Option<&Object> {
+        self.objects.iter().find_map(|object| object.find(name))
+    }
+
+    fn hit(
+        &mut self,
+        ray: Ray3,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+    ) -> Option<(HitRecord, &mut Material)> {
         let mut closest: Option<(HitRecord, &mut Material)> = None;
 
         for object in &mut self.objects {
@@ -168,7 +370,7 @@ impl List {
                 end: t_max,
             };
 
-            if let Some(hit) = object.hit(ray, new_range) {
+            if let Some(hit) = object.hit(ray, new_range, ctx) {
                 closest = Some(hit);
             }
         }
@@ -176,7 +378,7 @@ impl List {
         closest
     }
 
-    fn bounding_box(&self, t_range: TRange<f64>) -> Option<AABB> {
+    fn bounding_box(&self, t_range: TRange<Float>) -> Option<AABB> {
         let mut aabb = None;
         for object in &self.objects {
             aabb = AABB::merge(aabb, object.bounding_box(t_range));
@@ -191,6 +393,38 @@ impl Default for List {
     }
 }
 
+/// Measures of the shape of a BVH, from `BVHNode::stats`, for telling how
+/// well it was built.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BvhStats {
+    pub nodes: usize,
+    /// The number of children that are not nodes, each an object or a list
+    /// of objects.
+    pub leaves: usize,
+    /// The number of levels of nodes, as `BVHNode::depth` counts them.
+    pub max_depth: u32,
+    /// The number of nodes above a leaf, averaged over the leaves.
+    pub average_depth: f64,
+    /// The number of objects in a leaf, averaged over the leaves.
+    pub average_leaf_objects: f64,
+    /// The total surface area of the boxes of the nodes, which the chance of
+    /// a ray having to test them is in proportion to.
+    pub interior_surface_area: Float,
+    /// The total surface area of the overlap between the boxes of sibling
+    /// nodes, where a ray has to test both.
+    pub sibling_overlap: Float,
+}
+
+/// How many times over the total surface area of the boxes of a BVH's nodes
+/// can grow by refitting before `BVHNode::refit` asks for it to be rebuilt.
+/// Rays test boxes in proportion to their area, so a BVH that has grown this
+/// much is slower to hit than one built afresh would be.
+pub const REFIT_AREA_GROWTH: Float = 1.5;
+
+/// The number of far children that `BVHNode::hit` keeps to come back to.
+/// Deeper trees are still hit, with a new stack for each this many levels.
+const BVH_STACK_SIZE: usize = 64;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BVHNode {
     aabb: AABB,
@@ -205,8 +439,149 @@ impl From<BVHNode> for Object {
 }
 
 impl BVHNode {
-    /// Produces a Bounding Volume Hierarchy (BVH) from a `List` of `Object`.
-    pub fn from_list(olist: &mut List, t_range: TRange<f64>) -> BVHNode {
+    pub fn aabb(&self) -> AABB {
+        self.aabb
+    }
+
+    pub fn left(&self) -> &Object {
+        &self.left
+    }
+
+    pub fn right(&self) -> &Object {
+        &self.right
+    }
+
+    /// The number of levels of nodes in the hierarchy below and including
+    /// this one.
+    pub fn depth(&self) -> u32 {
+        let depth = |object: &Object| match object {
+            Object::BVHNode(node) => node.depth(),
+            _ => 0,
+        };
+        1 + depth(&self.left).max(depth(&self.right))
+    }
+
+    /// Measures the hierarchy below and including this node.
+    pub fn stats(&self) -> BvhStats {
+        // the sums over the leaves of their depths and objects
+        fn visit(node: &BVHNode, depth: u32, stats: &mut BvhStats, sums: &mut (u64, u64)) {
+            stats.nodes += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+            stats.interior_surface_area += node.aabb.surface_area();
+            for child in [&*node.left, &*node.right] {
+                match child {
+                    Object::BVHNode(child) => visit(child, depth + 1, stats, sums),
+                    leaf => {
+                        stats.leaves += 1;
+                        sums.0 += depth as u64;
+                        sums.1 += match leaf {
+                            Object::List(list) => list.objects.len() as u64,
+                            _ => 1,
+                        };
+                    }
+                }
+            }
+            if let (Object::BVHNode(left), Object::BVHNode(right)) = (&*node.left, &*node.right) {
+                if left.aabb.intersects(right.aabb) {
+                    let lo = left.aabb.lo().max(right.aabb.lo());
+                    let hi = left.aabb.hi().min(right.aabb.hi());
+                    stats.sibling_overlap += AABB::new(lo, hi).surface_area();
+                }
+            }
+        }
+
+        let mut stats = BvhStats::default();
+        let mut sums = (0, 0);
+        visit(self, 1, &mut stats, &mut sums);
+        stats.average_depth = sums.0 as f64 / stats.leaves as f64;
+        stats.average_leaf_objects = sums.1 as f64 / stats.leaves as f64;
+        stats
+    }
+
+    /// Checks that the box of every node below this one is inside the box of
+    /// its parent, and that the box of every object at a leaf, over
+    /// `t_range`, is inside the box of the node above it. The error says
+    /// where the first box that is not was found, as the path to it from
+    /// this node.
+    pub fn validate(&self, t_range: TRange<Float>) -> Result<(), String> {
+        fn visit(node: &BVHNode, t_range: TRange<Float>, path: &mut Vec<&'static str>) -> Result<(), String> {
+            for (side, child) in [("left", &*node.left), ("right", &*node.right)] {
+                path.push(side);
+                let boxes: Vec<_> = match child {
+                    Object::BVHNode(child) => vec![Some(child.aabb)],
+                    Object::List(list) => list.iter().map(|o| o.bounding_box(t_range)).collect(),
+                    object => vec![object.bounding_box(t_range)],
+                };
+                for aabb in boxes {
+                    match aabb {
+                        Some(aabb) if node.aabb.contains_aabb(aabb) => (),
+                        Some(_) => return Err(format!("{} is not inside its parent's box", path.join("."))),
+                        None => return Err(format!("{} has no bounding box", path.join("."))),
+                    }
+                }
+                if let Object::BVHNode(child) = child {
+                    visit(child, t_range, path)?;
+                }
+                path.pop();
+            }
+            Ok(())
+        }
+
+        visit(self, t_range, &mut Vec::new())
+    }
+
+    /// Fits the boxes of this node and every node below it to the objects at
+    /// its leaves over `t_range`, as for another frame of an animation in
+    /// which they have moved, keeping the hierarchy as it is.
+    ///
+    /// Returns whether the total surface area of the boxes has grown to more
+    /// than `REFIT_AREA_GROWTH` times `built_area`, that of the hierarchy as
+    /// it was built, when it is worth building it again instead.
+    pub fn refit(&mut self, t_range: TRange<Float>, built_area: Float) -> bool {
+        // returns the total area of the boxes of the nodes
+        fn refit(node: &mut BVHNode, t_range: TRange<Float>) -> Float {
+            let mut area = 0.0;
+            let mut fit = |child: &mut Object| match child {
+                Object::BVHNode(child) => {
+                    area += refit(child, t_range);
+                    child.aabb
+                }
+                leaf => BVHNode::bounded(leaf, t_range),
+            };
+            let (left, right) = (fit(&mut node.left), fit(&mut node.right));
+            node.aabb = AABB::merge(Some(left), Some(right)).unwrap();
+            area + node.aabb.surface_area()
+        }
+
+        refit(self, t_range) > REFIT_AREA_GROWTH * built_area
+    }
+
+    /// Produces a Bounding Volume Hierarchy (BVH) from a `List` of `Object`,
+    /// split up by `builder`.
+    ///
+    /// Objects without a bounding box can never be hit, so are left out.
+    /// With fewer than two objects left there is nothing to split, and the
+    /// result is the one object itself, or an empty list if there are none.
+    /// An object whose bounding box is not finite is an error, which names
+    /// it by its index in the list.
+    pub fn from_list(olist: &mut List, t_range: TRange<Float>, builder: BvhBuilder) -> Result<Object, String> {
+        for (i, object) in olist.objects.iter().enumerate() {
+            if let Some(aabb) = object.bounding_box(t_range) {
+                let finite = |p: Point3| [p.x(), p.y(), p.z()].iter().all(|c| c.is_finite());
+                if !finite(aabb.lo()) || !finite(aabb.hi()) {
+                    let name = match object {
+                        Object::Named(named) => format!(" ({})", named.name),
+                        _ => String::new(),
+                    };
+                    return Err(format!(
+                        "Object {i}{name} of the scene has a bounding box that is not finite, from {:?} to {:?}.",
+                        aabb.lo(),
+                        aabb.hi()
+                    ));
+                }
+            }
+        }
+
         // preprocess list to remove all objects without AABBs
         let mut objects = &mut olist.objects[..];
         let mut i = 0;
@@ -218,76 +593,62 @@ impl BVHNode {
         }
         objects = &mut objects[i..];
 
-        Self::from_vec(objects, t_range)
-    }
-
-    fn object_lo(t_range: TRange<f64>) -> impl Fn(&Object) -> Point3 {
-        move |obj| obj.bounding_box(t_range).unwrap().lo()
+        Ok(match (objects.len(), builder) {
+            (0, _) => List::new().into(),
+            (_, BvhBuilder::Sah) => Self::from_vec_sah(objects, t_range),
+            (_, BvhBuilder::Median) => Self::from_vec(objects, t_range),
+        })
     }
 
-    fn axis(
-        f: Rc<impl Fn(&Object) -> Point3 + 'static>,
-        g: impl Fn(Point3) -> f64 + 'static,
-    ) -> impl Fn(&Object) -> f64 {
-        move |obj| g(f(obj))
+    /// The bounding box of an object that `from_list` has checked has one.
+    fn bounded(object: &Object, t_range: TRange<Float>) -> AABB {
+        object
+            .bounding_box(t_range)
+            .expect("from_list leaves out objects without a bounding box")
     }
 
-    fn cmp(f: impl Fn(&Object) -> f64) -> impl Fn(&Object, &Object) -> Ordering {
-        move |a, b| {
-            let a = f(a);
-            let b = f(b);
-            a.partial_cmp(&b).unwrap()
-        }
-    }
-
-    fn lr(
-        objects: &mut [Object],
-        f: impl Fn(&Object) -> f64,
-        t_range: TRange<f64>,
-    ) -> (Object, Object, f64) {
-        objects.sort_unstable_by(Self::cmp(f));
+    /// Splits the objects in half along `axis`, returning the two halves and
+    /// the ratio of the larger surface area to the smaller. Surface area,
+    /// unlike volume, stays positive for halves that are flat.
+    fn lr(objects: &mut [Object], axis: Axis, t_range: TRange<Float>) -> (Object, Object, Float) {
+        let lo = |object: &Object| Self::bounded(object, t_range).lo()[axis];
+        objects.sort_unstable_by(|a, b| lo(a).total_cmp(&lo(b)));
         let (lhs, rhs) = objects.split_at_mut(objects.len() / 2);
-        let left = Object::from(Self::from_vec(lhs, t_range));
-        let right = Object::from(Self::from_vec(rhs, t_range));
-        let vl = left.bounding_box(t_range).unwrap().volume();
-        let vr = right.bounding_box(t_range).unwrap().volume();
-        let ratio = vl.max(vr) / vl.min(vr);
+        let left = Self::from_vec(lhs, t_range);
+        let right = Self::from_vec(rhs, t_range);
+        let al = Self::bounded(&left, t_range).surface_area();
+        let ar = Self::bounded(&right, t_range).surface_area();
+        let ratio = al.max(ar) / al.min(ar);
         (left, right, ratio)
     }
 
-    fn from_vec(objects: &mut [Object], t_range: TRange<f64>) -> BVHNode {
-        let lo = Rc::new(Self::object_lo(t_range));
-        let x = Self::axis(lo.clone(), |p3| p3.x());
-        let y = Self::axis(lo.clone(), |p3| p3.y());
-        let z = Self::axis(lo, |p3| p3.z());
-
+    /// The node over `objects` split at their median, or the object itself
+    /// if there is only one.
+    fn from_vec(objects: &mut [Object], t_range: TRange<Float>) -> Object {
         let (left, right);
-        if objects.len() <= 2 {
-            left = objects[0].clone();
-            right = if objects.len() == 2 {
-                objects[1].clone()
-            } else {
-                left.clone()
-            };
-        } else {
-            let (xl, xr, xratio) = Self::lr(objects, x, t_range);
-            let (yl, yr, yratio) = Self::lr(objects, y, t_range);
-            let (zl, zr, zratio) = Self::lr(objects, z, t_range);
-            let smallest = xratio.min(yratio.min(zratio));
-            if xratio == smallest {
-                left = xl;
-                right = xr;
-            } else if yratio == smallest {
-                left = yl;
-                right = yr;
-            } else {
-                left = zl;
-                right = zr;
+        match objects {
+            [object] => return object.clone(),
+            [l, r] => (left, right) = (l.clone(), r.clone()),
+            _ => {
+                // keep the split whose halves are closest in surface area,
+                // preferring the earlier axis on a tie
+                let mut best: Option<(Object, Object, Float)> = None;
+                for axis in Axis::ALL {
+                    let split = Self::lr(objects, axis, t_range);
+                    if best.as_ref().is_none_or(|best| split.2 < best.2) {
+                        best = Some(split);
+                    }
+                }
+                (left, right, _) = best.unwrap();
             }
         }
 
-        let left_aabb = left.bounding_box(t_range).unwrap();
-        let right_aabb = right.bounding_box(t_range).unwrap();
+        Self::from_children(left, right, t_range).into()
+    }
+
+    fn from_children(left: Object, right: Object, t_range: TRange<Float>) -> BVHNode {
+        let left_aabb = Self::bounded(&left, t_range);
+        let right_aabb = Self::bounded(&right, t_range);
         let aabb = AABB::merge(Some(left_aabb), Some(right_aabb)).unwrap();
 
         let left = Box::new(left);
@@ -296,63 +657,794 @@ impl BVHNode {
         BVHNode { aabb, left, right }
     }
 
-    fn bounding_box(&self, _t_range: TRange<f64>) -> Option<AABB> {
-        Some(self.aabb)
+    /// The node over `objects` split by the surface area heuristic. The root
+    /// is always split, even where a leaf would be cheaper, so that it is a
+    /// node, unless there is only one object.
+    fn from_vec_sah(objects: &mut [Object], t_range: TRange<Float>) -> Object {
+        if objects.len() <= 2 {
+            return Self::from_vec(objects, t_range);
+        }
+        let mid = Self::sah_split(objects, t_range, true).unwrap();
+        let (lhs, rhs) = objects.split_at_mut(mid);
+        Self::from_children(Self::sah_child(lhs, t_range), Self::sah_child(rhs, t_range), t_range).into()
     }
 
-    fn hit(&mut self, ray_in: Ray3, t_range: TRange<f64>) -> Option<(HitRecord, &mut Material)> {
-        if !self.aabb.hit(ray_in, t_range) {
+    /// A child of a node built by the surface area heuristic: the object
+    /// itself, a list of objects cheaper to test one by one than to split, or
+    /// a node over them.
+    fn sah_child(objects: &mut [Object], t_range: TRange<Float>) -> Object {
+        if objects.len() == 1 {
+            return objects[0].clone();
+        }
+        match Self::sah_split(objects, t_range, false) {
+            Some(mid) => {
+                let (lhs, rhs) = objects.split_at_mut(mid);
+                Self::from_children(Self::sah_child(lhs, t_range), Self::sah_child(rhs, t_range), t_range).into()
+            }
+            None => List {
+                objects: objects.to_vec(),
+            }
+            .into(),
+        }
+    }
+
+    /// Partitions `objects` into the two groups that the surface area
+    /// heuristic finds cheapest to hit, returning the index of the first
+    /// object of the second group, or None if the objects are few enough to
+    /// be left as a leaf and testing them all is cheaper than splitting them.
+    ///
+    /// The centroids of the objects' boxes are put into `SAH_BINS` bins of
+    /// equal width along each axis, and the boundaries between bins are the
+    /// splits tried. A split costs the traversal of a node plus the number
+    /// of objects on each side times the chance that a ray through the node
+    /// passes through that side's box, which is in proportion to its surface
+    /// area.
+    fn sah_split(objects: &mut [Object], t_range: TRange<Float>, force: bool) -> Option<usize> {
+        const SAH_BINS: usize = 16;
+        // the cost of testing a node's box relative to hitting an object
+        const TRAVERSAL_COST: Float = 1.0;
+        const MAX_LEAF_OBJECTS: usize = 4;
+
+        let mut boxes: Vec<AABB> = objects.iter().map(|o| Self::bounded(o, t_range)).collect();
+        let centroids = boxes.iter().fold(None, |b, aabb| {
+            AABB::merge(b, Some(AABB::new(aabb.centroid(), aabb.centroid())))
+        })?;
+        let bounds = boxes.iter().fold(None, |b, aabb| AABB::merge(b, Some(*aabb)))?;
+        let bin = |aabb: &AABB, axis: Axis| {
+            let (lo, extent) = (centroids.lo()[axis], centroids.extent()[axis]);
+            let i = ((aabb.centroid()[axis] - lo) / extent * SAH_BINS as Float) as usize;
+            i.min(SAH_BINS - 1)
+        };
+
+        // costs are scaled by the surface area of the node, which leaves
+        // their order unchanged and avoids dividing by a zero area
+        let n = objects.len();
+        let leaf_cost = n as Float * bounds.surface_area();
+        let mut best: Option<(Float, Axis, usize)> = None;
+        for axis in Axis::ALL {
+            if centroids.extent()[axis] <= 0.0 {
+                continue;
+            }
+            let mut counts = [0; SAH_BINS];
+            let mut bin_boxes = [None; SAH_BINS];
+            for aabb in &boxes {
+                let i = bin(aabb, axis);
+                counts[i] += 1;
+                bin_boxes[i] = AABB::merge(bin_boxes[i], Some(*aabb));
+            }
+
+            // the area times count of the bins above each split, swept down
+            let mut above = [0.0; SAH_BINS];
+            let (mut count, mut aabb) = (0, None);
+            for split in (1..SAH_BINS).rev() {
+                count += counts[split];
+                aabb = AABB::merge(aabb, bin_boxes[split]);
+                above[split] = aabb.map_or(0.0, |b| count as Float * b.surface_area());
+            }
+            let (mut count, mut aabb) = (0, None);
+            for split in 1..SAH_BINS {
+                count += counts[split - 1];
+                aabb = AABB::merge(aabb, bin_boxes[split - 1]);
+                if count == 0 || count == n {
+                    continue;
+                }
+                let below = aabb.map_or(0.0, |b| count as Float * b.surface_area());
+                let cost = TRAVERSAL_COST * bounds.surface_area() + below + above[split];
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, split));
+                }
+            }
+        }
+
+        let Some((cost, axis, split)) = best else {
+            // every centroid is at the same point, so no plane separates them
+            return (force || n > MAX_LEAF_OBJECTS).then_some(n / 2);
+        };
+        if !force && n <= MAX_LEAF_OBJECTS && cost >= leaf_cost {
             return None;
         }
 
-        if let Some((hit_l, mat_l)) = self.left.hit(ray_in, t_range) {
-            let new_range = TRange {
+        // move the objects below the split to the front
+        let mut mid = 0;
+        for j in 0..n {
+            if bin(&boxes[j], axis) < split {
+                objects.swap(mid, j);
+                boxes.swap(mid, j);
+                mid += 1;
+            }
+        }
+        Some(mid)
+    }
+
+    fn bounding_box(&self, _t_range: TRange<Float>) -> Option<AABB> {
+        Some(self.aabb)
+    }
+
+    fn hit(
+        &mut self,
+        ray_in: Ray3,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+    ) -> Option<(HitRecord, &mut Material)> {
+        self.hit_slab(&ray_in.precompute(), t_range, ctx)
+    }
+
+    /// `hit` with the reciprocal direction of the ray worked out once for the
+    /// whole hierarchy below this node.
+    fn hit_slab(
+        &mut self,
+        ray_in: &SlabRay,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+    ) -> Option<(HitRecord, &mut Material)> {
+        ctx.aabb_tests += 1;
+        self.aabb.hit_slab(ray_in, t_range)?;
+        self.hit_children(ray_in, t_range, ctx)
+    }
+
+    /// Hits the children of a node whose own box the ray passes through,
+    /// walking the hierarchy below it with a stack rather than recursion. At
+    /// each node the child the ray enters first is visited first and the
+    /// other is pushed, so that it can be skipped when a hit is found before
+    /// the ray reaches it.
+    fn hit_children<'a>(
+        &'a mut self,
+        ray_in: &SlabRay,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+    ) -> Option<(HitRecord, &'a mut Material)> {
+        // the far children still to visit, with the t at which the ray
+        // enters them
+        let mut stack: [Option<(Float, &'a mut Object)>; BVH_STACK_SIZE] = std::array::from_fn(|_| None);
+        let mut len = 0;
+        let mut closest: Option<(HitRecord, &'a mut Material)> = None;
+        let mut node = Some(self);
+        loop {
+            let range = TRange {
                 start: t_range.start,
-                end: hit_l.t,
+                end: closest.as_ref().map_or(t_range.end, |(rec, _)| rec.t),
             };
-            if let Some((hit_r, mat_r)) = self.right.hit(ray_in, new_range) {
-                Some((hit_r, mat_r))
+            let next = if let Some(BVHNode { left, right, .. }) = node.take() {
+                ctx.node_tests += 1;
+                let l = Self::entry(left, ray_in, range, ctx);
+                let r = Self::entry(right, ray_in, range, ctx);
+                let (left, right) = (&mut **left, &mut **right);
+                let (near, far) = match (l, r) {
+                    (Some(l), Some(r)) if r < l => (Some(right), Some((l, left))),
+                    (Some(_), r) => (Some(left), r.map(|r| (r, right))),
+                    (None, Some(_)) => (Some(right), None),
+                    (None, None) => (None, None),
+                };
+                match far {
+                    Some(far) if len < BVH_STACK_SIZE => {
+                        stack[len] = Some(far);
+                        len += 1;
+                        near
+                    }
+                    Some((entry, far)) => {
+                        // out of stack, so the near child is finished with a
+                        // stack of its own before going on to the far one
+                        if let Some(hit) = near.and_then(|near| Self::hit_child(near, ray_in, range, ctx)) {
+                            closest = Some(hit);
+                        }
+                        let end = closest.as_ref().map_or(t_range.end, |(rec, _)| rec.t);
+                        (entry <= end && t_range.start < end).then_some(far)
+                    }
+                    None => near,
+                }
+            } else if len > 0 {
+                len -= 1;
+                let (entry, far) = stack[len].take().unwrap();
+                (entry <= range.end && range.start < range.end).then_some(far)
             } else {
-                Some((hit_l, mat_l))
+                return closest;
+            };
+
+            let range = TRange {
+                start: t_range.start,
+                end: closest.as_ref().map_or(t_range.end, |(rec, _)| rec.t),
+            };
+            match next {
+                Some(Object::BVHNode(child)) => node = Some(child),
+                Some(object) => {
+                    if let Some(hit) = object.hit(ray_in.ray, range, ctx) {
+                        closest = Some(hit);
+                    }
+                }
+                None => (),
+            }
+        }
+    }
+
+    /// The t-value at which the ray enters the box of a child node, or None
+    /// if it misses. Other objects have no box to test, so are entered at the
+    /// start of the range.
+    fn entry(child: &Object, ray_in: &SlabRay, t_range: TRange<Float>, ctx: &mut HitContext) -> Option<Float> {
+        match child {
+            Object::BVHNode(node) => {
+                ctx.aabb_tests += 1;
+                node.aabb.hit_slab(ray_in, t_range).map(|interval| interval.start)
+            }
+            _ => Some(t_range.start),
+        }
+    }
+
+    /// Hits a child whose box, if it has one, has already been tested.
+    fn hit_child<'a>(
+        child: &'a mut Object,
+        ray_in: &SlabRay,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+    ) -> Option<(HitRecord, &'a mut Material)> {
+        match child {
+            Object::BVHNode(node) => node.hit_children(ray_in, t_range, ctx),
+            object => object.hit(ray_in.ray, t_range, ctx),
+        }
+    }
+}
+
+/// A child of a `FlatNode` or `Bvh4Node`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum FlatChild {
+    /// The node at this index.
+    Node(u32),
+    /// The objects `start..start + len`, which like the objects at the leaves
+    /// of a `BVHNode` have no box of their own.
+    Leaf { start: u32, len: u32 },
+}
+
+/// A node of a `FlatBVH`, with its children kept in it so that telling
+/// whether they are leaves needs nothing more to be read.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FlatNode {
+    pub aabb: AABB,
+    pub left: FlatChild,
+    pub right: FlatChild,
+}
+
+/// A `BVHNode` laid out in two arrays, the nodes in depth first order and
+/// the objects at the leaves in the order they are reached, so that walking
+/// it follows indices rather than pointers to separate allocations. It is
+/// hit in the same order as the tree it is built from, so gives the same
+/// hits after the same bounding box tests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlatBVH {
+    nodes: Vec<FlatNode>,
+    objects: Vec<Object>,
+}
+
+impl From<FlatBVH> for Object {
+    fn from(f: FlatBVH) -> Object {
+        Object::FlatBVH(f)
+    }
+}
+
+impl From<BVHNode> for FlatBVH {
+    fn from(tree: BVHNode) -> FlatBVH {
+        let mut flat = FlatBVH {
+            nodes: Vec::new(),
+            objects: Vec::new(),
+        };
+        flat.flatten(Object::BVHNode(tree));
+        flat
+    }
+}
+
+/// The closest hit found so far by `FlatBVH::hit`, without its material.
+struct FlatHit {
+    rec: HitRecord,
+    object: usize,
+    /// The end of the range the object was hit within.
+    end: Float,
+}
+
+impl FlatHit {
+    /// The hit along with the material of the object it was on, once no
+    /// more objects are to be hit.
+    fn material(self, objects: &mut [Object], ray_in: Ray3, t_range: TRange<Float>) -> Option<(HitRecord, &mut Material)> {
+        let FlatHit { rec, object, end } = self;
+        let object = &mut objects[object];
+        if object.material_mut().is_none() {
+            // objects made of others have no one material, so are hit again
+            // within the same range to find the one that was hit
+            let range = TRange {
+                start: t_range.start,
+                end,
+            };
+            return object.hit(ray_in, range, &mut HitContext::default());
+        }
+        object.material_mut().map(|material| (rec, material))
+    }
+}
+
+impl FlatBVH {
+    pub fn nodes(&self) -> &[FlatNode] {
+        &self.nodes
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    /// The number of levels of nodes in the hierarchy, as `BVHNode::depth`
+    /// counts them.
+    pub fn depth(&self) -> u32 {
+        fn depth(nodes: &[FlatNode], child: FlatChild) -> u32 {
+            match child {
+                FlatChild::Node(i) => {
+                    let node = &nodes[i as usize];
+                    1 + depth(nodes, node.left).max(depth(nodes, node.right))
+                }
+                FlatChild::Leaf { .. } => 0,
             }
+        }
+        if self.nodes.is_empty() {
+            0
         } else {
-            self.right.hit(ray_in, t_range)
+            depth(&self.nodes, FlatChild::Node(0))
+        }
+    }
+
+    /// Appends `object` and everything below it, returning it as a child.
+    /// Lists at the leaves are spread into the objects of the leaf.
+    fn flatten(&mut self, object: Object) -> FlatChild {
+        let start = self.objects.len() as u32;
+        match object {
+            Object::BVHNode(node) => {
+                let index = self.nodes.len();
+                self.nodes.push(FlatNode {
+                    aabb: node.aabb,
+                    left: FlatChild::Leaf { start, len: 0 },
+                    right: FlatChild::Leaf { start, len: 0 },
+                });
+                self.nodes[index].left = self.flatten(*node.left);
+                self.nodes[index].right = self.flatten(*node.right);
+                FlatChild::Node(index as u32)
+            }
+            Object::List(list) => {
+                self.objects.extend(list.objects);
+                let len = self.objects.len() as u32 - start;
+                FlatChild::Leaf { start, len }
+            }
+            object => {
+                self.objects.push(object);
+                FlatChild::Leaf { start, len: 1 }
+            }
+        }
+    }
+
+    fn bounding_box(&self, _t_range: TRange<Float>) -> Option<AABB> {
+        self.nodes.first().map(|node| node.aabb)
+    }
+
+    /// Finds the closest hit without holding on to any material, which would
+    /// keep the objects borrowed, then borrows the material of the object it
+    /// was on.
+    fn hit(
+        &mut self,
+        ray_in: Ray3,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+    ) -> Option<(HitRecord, &mut Material)> {
+        let ray = ray_in.precompute();
+        ctx.aabb_tests += 1;
+        self.nodes.first()?.aabb.hit_slab(&ray, t_range)?;
+        let mut closest = None;
+        Self::hit_node(&self.nodes, &mut self.objects, 0, &ray, t_range, ctx, &mut closest);
+
+        closest?.material(&mut self.objects, ray_in, t_range)
+    }
+
+    /// Walks the hierarchy below the node at `index`, whose own box the ray
+    /// passes through, as `BVHNode::hit_children` does, updating `closest`
+    /// with any nearer hit.
+    #[allow(clippy::too_many_arguments)]
+    fn hit_node(
+        nodes: &[FlatNode],
+        objects: &mut [Object],
+        index: u32,
+        ray_in: &SlabRay,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+        closest: &mut Option<FlatHit>,
+    ) {
+        let end = |closest: &Option<FlatHit>| closest.as_ref().map_or(t_range.end, |hit| hit.rec.t);
+        let mut stack = [(0.0, FlatChild::Node(0)); BVH_STACK_SIZE];
+        let mut len = 0;
+        let mut node = Some(&nodes[index as usize]);
+        loop {
+            let range = TRange {
+                start: t_range.start,
+                end: end(closest),
+            };
+            let next = if let Some(&FlatNode { left, right, .. }) = node.take() {
+                ctx.node_tests += 1;
+                let l = Self::entry(nodes, left, ray_in, range, ctx);
+                let r = Self::entry(nodes, right, ray_in, range, ctx);
+                let (near, far) = match (l, r) {
+                    (Some(l), Some(r)) if r < l => (Some(right), Some((l, left))),
+                    (Some(_), r) => (Some(left), r.map(|r| (r, right))),
+                    (None, Some(_)) => (Some(right), None),
+                    (None, None) => (None, None),
+                };
+                match far {
+                    Some(far) if len < BVH_STACK_SIZE => {
+                        stack[len] = far;
+                        len += 1;
+                        near
+                    }
+                    Some((entry, far)) => {
+                        // out of stack, so the near child is finished with a
+                        // stack of its own before going on to the far one
+                        match near {
+                            Some(FlatChild::Node(near)) => {
+                                Self::hit_node(nodes, objects, near, ray_in, range, ctx, closest)
+                            }
+                            Some(FlatChild::Leaf { start, len }) => {
+                                Self::hit_leaf(objects, start, len, ray_in, t_range, ctx, closest)
+                            }
+                            None => (),
+                        }
+                        let end = end(closest);
+                        (entry <= end && t_range.start < end).then_some(far)
+                    }
+                    None => near,
+                }
+            } else if len > 0 {
+                len -= 1;
+                let (entry, far) = stack[len];
+                (entry <= range.end && range.start < range.end).then_some(far)
+            } else {
+                return;
+            };
+
+            match next {
+                Some(FlatChild::Node(index)) => node = Some(&nodes[index as usize]),
+                Some(FlatChild::Leaf { start, len }) => {
+                    Self::hit_leaf(objects, start, len, ray_in, t_range, ctx, closest)
+                }
+                None => (),
+            }
+        }
+    }
+
+    /// Hits the objects `start..start + len` in turn, as `List` does.
+    #[allow(clippy::too_many_arguments)]
+    fn hit_leaf(
+        objects: &mut [Object],
+        start: u32,
+        len: u32,
+        ray_in: &SlabRay,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+        closest: &mut Option<FlatHit>,
+    ) {
+        let start = start as usize;
+        for (i, object) in objects[start..start + len as usize].iter_mut().enumerate() {
+            let range = TRange {
+                start: t_range.start,
+                end: closest.as_ref().map_or(t_range.end, |hit| hit.rec.t),
+            };
+            if let Some((rec, _)) = object.hit(ray_in.ray, range, ctx) {
+                *closest = Some(FlatHit {
+                    rec,
+                    object: start + i,
+                    end: range.end,
+                });
+            }
+        }
+    }
+
+    /// The t-value at which the ray enters the box of a child node, or for a
+    /// leaf the start of the range, as `BVHNode::entry`.
+    fn entry(
+        nodes: &[FlatNode],
+        child: FlatChild,
+        ray_in: &SlabRay,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+    ) -> Option<Float> {
+        match child {
+            FlatChild::Node(index) => {
+                ctx.aabb_tests += 1;
+                nodes[index as usize].aabb.hit_slab(ray_in, t_range).map(|interval| interval.start)
+            }
+            FlatChild::Leaf { .. } => Some(t_range.start),
+        }
+    }
+}
+
+/// A node of a `BVH4`, with the boxes of its children stored together to be
+/// tested at once. Children after the last are None.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Bvh4Node {
+    pub boxes: AABB4,
+    pub children: [Option<FlatChild>; 4],
+}
+
+/// A `BVHNode` collapsed into nodes of up to four children, each node taking
+/// the place of a node of the binary hierarchy and the nodes below it, so
+/// that a ray visits half as many levels and tests the boxes of a node's
+/// children together. Laid out in arrays as `FlatBVH` is. Unlike those of
+/// the binary hierarchy, the objects at its leaves are tested against their
+/// own boxes, which come with those of the nodes for free.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BVH4 {
+    aabb: AABB,
+    nodes: Vec<Bvh4Node>,
+    objects: Vec<Object>,
+}
+
+impl From<BVH4> for Object {
+    fn from(b: BVH4) -> Object {
+        Object::BVH4(b)
+    }
+}
+
+impl BVH4 {
+    /// Collapses `tree`, with the boxes of the objects at its leaves over
+    /// `t_range`.
+    pub fn new(tree: BVHNode, t_range: TRange<Float>) -> BVH4 {
+        let mut bvh = BVH4 {
+            aabb: tree.aabb,
+            nodes: Vec::new(),
+            objects: Vec::new(),
+        };
+        bvh.collapse(tree, t_range);
+        bvh
+    }
+
+    pub fn nodes(&self) -> &[Bvh4Node] {
+        &self.nodes
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    /// The box of the root, which the nodes do not hold.
+    pub fn aabb(&self) -> AABB {
+        self.aabb
+    }
+
+    /// The number of levels of nodes in the hierarchy.
+    pub fn depth(&self) -> u32 {
+        fn depth(nodes: &[Bvh4Node], index: u32) -> u32 {
+            let children = nodes[index as usize].children.into_iter().flatten();
+            let below = children.map(|child| match child {
+                FlatChild::Node(i) => depth(nodes, i),
+                FlatChild::Leaf { .. } => 0,
+            });
+            1 + below.max().unwrap_or(0)
+        }
+        depth(&self.nodes, 0)
+    }
+
+    /// Appends the node in place of `tree` and the nodes below it, whose
+    /// children are the grandchildren through the children of `tree` that
+    /// are nodes and the children that are not, returning its index.
+    fn collapse(&mut self, tree: BVHNode, t_range: TRange<Float>) -> u32 {
+        let mut children = Vec::with_capacity(4);
+        for child in [*tree.left, *tree.right] {
+            match child {
+                Object::BVHNode(node) => children.extend([*node.left, *node.right]),
+                leaf => children.push(leaf),
+            }
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(Bvh4Node {
+            boxes: AABB4::new([None; 4]),
+            children: [None; 4],
+        });
+        let mut boxes = [None; 4];
+        let mut flat = [None; 4];
+        for (i, child) in children.into_iter().enumerate() {
+            boxes[i] = Some(BVHNode::bounded(&child, t_range));
+            let start = self.objects.len() as u32;
+            flat[i] = Some(match child {
+                Object::BVHNode(node) => FlatChild::Node(self.collapse(node, t_range)),
+                Object::List(list) => {
+                    self.objects.extend(list.objects);
+                    FlatChild::Leaf {
+                        start,
+                        len: self.objects.len() as u32 - start,
+                    }
+                }
+                object => {
+                    self.objects.push(object);
+                    FlatChild::Leaf { start, len: 1 }
+                }
+            });
+        }
+        self.nodes[index] = Bvh4Node {
+            boxes: AABB4::new(boxes),
+            children: flat,
+        };
+        index as u32
+    }
+
+    fn bounding_box(&self, _t_range: TRange<Float>) -> Option<AABB> {
+        Some(self.aabb)
+    }
+
+    fn hit(
+        &mut self,
+        ray_in: Ray3,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+    ) -> Option<(HitRecord, &mut Material)> {
+        let ray = ray_in.precompute();
+        ctx.aabb_tests += 1;
+        self.aabb.hit_slab(&ray, t_range)?;
+        let mut closest = None;
+        Self::hit_node(&self.nodes, &mut self.objects, 0, &ray, t_range, ctx, &mut closest);
+        closest?.material(&mut self.objects, ray_in, t_range)
+    }
+
+    /// Walks the hierarchy below the node at `index`, whose own box the ray
+    /// passes through, updating `closest` with any nearer hit. At each node
+    /// the children the ray passes through are visited nearest first, and
+    /// the others are pushed to be skipped if a hit is found before the ray
+    /// reaches them.
+    #[allow(clippy::too_many_arguments)]
+    fn hit_node(
+        nodes: &[Bvh4Node],
+        objects: &mut [Object],
+        index: u32,
+        ray_in: &SlabRay,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+        closest: &mut Option<FlatHit>,
+    ) {
+        let end = |closest: &Option<FlatHit>| closest.as_ref().map_or(t_range.end, |hit| hit.rec.t);
+        let mut stack = [(0.0, FlatChild::Node(0)); BVH_STACK_SIZE];
+        let mut len = 0;
+        let mut next = Some(FlatChild::Node(index));
+        loop {
+            match next.take() {
+                Some(FlatChild::Node(index)) => {
+                    let node = &nodes[index as usize];
+                    let range = TRange {
+                        start: t_range.start,
+                        end: end(closest),
+                    };
+                    let hits = node.boxes.hit_slab(ray_in, range);
+                    ctx.node_tests += 1;
+                    ctx.aabb_tests += node.children.iter().flatten().count() as u32;
+
+                    // the children hit, nearest first
+                    let mut entered = [(0.0, FlatChild::Node(0)); 4];
+                    let mut count = 0;
+                    for (hit, child) in hits.into_iter().zip(node.children) {
+                        if let (Some(hit), Some(child)) = (hit, child) {
+                            let mut i = count;
+                            while i > 0 && entered[i - 1].0 > hit.start {
+                                entered[i] = entered[i - 1];
+                                i -= 1;
+                            }
+                            entered[i] = (hit.start, child);
+                            count += 1;
+                        }
+                    }
+                    if count == 0 {
+                        continue;
+                    }
+
+                    if len + count - 1 <= BVH_STACK_SIZE {
+                        for &far in entered[1..count].iter().rev() {
+                            stack[len] = far;
+                            len += 1;
+                        }
+                        next = Some(entered[0].1);
+                    } else {
+                        // out of stack, so the children are finished with
+                        // stacks of their own
+                        for (entry, child) in entered.into_iter().take(count) {
+                            let end = end(closest);
+                            if entry > end || t_range.start >= end {
+                                break;
+                            }
+                            match child {
+                                FlatChild::Node(child) => {
+                                    let range = TRange {
+                                        start: t_range.start,
+                                        end,
+                                    };
+                                    Self::hit_node(nodes, objects, child, ray_in, range, ctx, closest)
+                                }
+                                FlatChild::Leaf { start, len } => {
+                                    FlatBVH::hit_leaf(objects, start, len, ray_in, t_range, ctx, closest)
+                                }
+                            }
+                        }
+                    }
+                }
+                Some(FlatChild::Leaf { start, len }) => {
+                    FlatBVH::hit_leaf(objects, start, len, ray_in, t_range, ctx, closest)
+                }
+                None => {
+                    if len == 0 {
+                        return;
+                    }
+                    len -= 1;
+                    let (entry, far) = stack[len];
+                    let end = end(closest);
+                    if entry <= end && t_range.start < end {
+                        next = Some(far);
+                    }
+                }
+            }
         }
     }
 }
 
 macro_rules! rect {
-    ( $x:ident, $y:ident, $z:ident, $X:ident, $Y:ident, $Z:ident, $name:ident) => {
+    ( $x:ident, $y:ident, $z:ident, $axes:ident, $name:ident) => {
         #[derive(Debug, Clone, Serialize, Deserialize)]
         pub struct $name {
             material: Material,
-            $x: TRange<f64>,
-            $y: TRange<f64>,
-            $z: f64,
+            $x: TRange<Float>,
+            $y: TRange<Float>,
+            $z: Float,
         }
 
         impl $name {
+            pub fn new($x: TRange<Float>, $y: TRange<Float>, $z: Float, material: Material) -> $name {
+                $name {
+                    material,
+                    $x,
+                    $y,
+                    $z,
+                }
+            }
+
+            /// A point on the plane of the rectangle and the unit normal to
+            /// the plane, along the last axis of the rect's permutation.
+            fn plane(&self) -> (Point3, Vec3) {
+                let [.., normal_axis] = Permutation::$axes.axes();
+                let point = Point3::new(0.0, 0.0, self.$z).unpermute(Permutation::$axes);
+                (point, Vec3::from(normal_axis))
+            }
+
             fn hit(
                 &mut self,
                 ray_in: Ray3,
-                t_range: TRange<f64>,
+                t_range: TRange<Float>,
+                _ctx: &mut HitContext,
             ) -> Option<(HitRecord, &mut Material)> {
-                let t = (self.$z - ray_in.origin.$z()) / ray_in.direction.$z();
-                if !t_range.contains(&t) {
-                    return None;
-                }
+                let (plane_point, normal) = self.plane();
+                let t = intersect_ray_plane(ray_in, plane_point, normal, t_range)?;
 
                 let p = ray_in.at(t);
-                if !self.$x.contains(&p.$x()) || !self.$y.contains(&p.$y()) {
+                if !self.$x.contains_inclusive(&p.$x()) || !self.$y.contains_inclusive(&p.$y()) {
                     return None;
                 }
 
                 let u = (p.$x() - self.$x.start) / (self.$x.end - self.$x.start);
                 let v = (p.$y() - self.$y.start) / (self.$y.end - self.$y.start);
 
-                let mut outward_normal = [0.0; 3];
-                outward_normal[Axis::$Z as usize] = -ray_in.direction.$z().signum();
-                let outward_normal = Vec3::from(NTuple::from(outward_normal));
+                let outward_normal = -ray_in.direction.dot(normal).signum() * normal;
 
                 Some((
                     HitRecord::new(p, outward_normal, ray_in, t, u, v),
@@ -360,14 +1452,42 @@ macro_rules! rect {
                 ))
             }
 
-            fn bounding_box(&self, _t_range: TRange<f64>) -> Option<AABB> {
-                let epsilon = 1.0;
-                let axes = [Axis::$X, Axis::$Y, Axis::$Z];
-                let lower =
-                    Point3::new(self.$x.start, self.$y.start, self.$z - epsilon).unpermute(axes);
-                let upper =
-                    Point3::new(self.$x.end, self.$y.end, self.$z + epsilon).unpermute(axes);
-                Some(AABB::new(lower, upper))
+            /// Samples a point uniformly over the area of the rectangle.
+            fn sample_light<R: Rng>(&self, origin: Point3, rng: &mut Random<R>) -> Option<Vec3> {
+                let $x = rng.random_range(self.$x.start..=self.$x.end);
+                let $y = rng.random_range(self.$y.start..=self.$y.end);
+                let point = Point3::new($x, $y, self.$z).unpermute(Permutation::$axes);
+                (point - origin).unit()
+            }
+
+            fn light_pdf(&self, origin: Point3, direction: Vec3) -> Float {
+                let (plane_point, normal) = self.plane();
+                let ray = Ray3 {
+                    origin,
+                    direction,
+                    time: 0.0,
+                };
+                let Some(t) = intersect_ray_plane(ray, plane_point, normal, TRange::new(0.0, Float::INFINITY)) else {
+                    return 0.0;
+                };
+                let p = ray.at(t);
+                if !self.$x.contains_inclusive(&p.$x()) || !self.$y.contains_inclusive(&p.$y()) {
+                    return 0.0;
+                }
+
+                // convert the uniform density over the area to solid angle
+                let area = (self.$x.end - self.$x.start) * (self.$y.end - self.$y.start);
+                let distance_squared = (p - origin).quadrance();
+                let cosine = direction.$z().abs() / direction.length();
+                distance_squared / (cosine * area)
+            }
+
+            fn bounding_box(&self, _t_range: TRange<Float>) -> Option<AABB> {
+                // padded so that the box keeps a little thickness about the
+                // plane of the rectangle
+                let lower = Point3::new(self.$x.start, self.$y.start, self.$z).unpermute(Permutation::$axes);
+                let upper = Point3::new(self.$x.end, self.$y.end, self.$z).unpermute(Permutation::$axes);
+                Some(AABB::new(lower, upper).pad(1e-4))
             }
         }
 
@@ -379,9 +1499,9 @@ macro_rules! rect {
     };
 }
 
-rect!(x, y, z, X, Y, Z, XYRect);
-rect!(x, z, y, X, Z, Y, XZRect);
-rect!(y, z, x, Y, Z, X, YZRect);
+rect!(x, y, z, XY, XYRect);
+rect!(x, z, y, XZ, XZRect);
+rect!(y, z, x, YZ, YZRect);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RectPrismU {
@@ -392,7 +1512,7 @@ pub struct RectPrismU {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RectPrismI {
-    sides: BVHNode,
+    sides: Box<Object>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -401,7 +1521,18 @@ pub enum RectPrism {
     I(RectPrismI),
 }
 
+impl From<RectPrism> for Object {
+    fn from(r: RectPrism) -> Object {
+        Object::RectPrism(r)
+    }
+}
+
 impl RectPrism {
+    /// An axis aligned box with opposite corners `p0` and `p1`.
+    pub fn new(p0: Point3, p1: Point3, material: Material) -> RectPrism {
+        RectPrism::U(RectPrismU { material, p0, p1 })
+    }
+
     fn init(&mut self) -> &mut RectPrismI {
         match self {
             RectPrism::U(u) => {
@@ -435,19 +1566,27 @@ impl RectPrism {
         list.add(YZRect { material: material.clone(), y: r[1], z: r[2], x: min[0] }.into());
         list.add(YZRect { material: material.clone(), y: r[1], z: r[2], x: max[0] }.into());
 
-        let sides = BVHNode::from_list(&mut list, TRange::new(0.0, 0.0));
-        RectPrismI { sides }
+        // the box's own bounding box, between the same corners, was checked
+        // to be finite when the BVH of the scene was built
+        let sides = BVHNode::from_list(&mut list, TRange::new(0.0, 0.0), BvhBuilder::default())
+            .expect("the sides of a box have finite bounding boxes");
+        RectPrismI { sides: Box::new(sides) }
     }
 
-    fn hit(&mut self, ray_in: Ray3, t_range: TRange<f64>) -> Option<(HitRecord, &mut Material)> {
+    fn hit(
+        &mut self,
+        ray_in: Ray3,
+        t_range: TRange<Float>,
+        ctx: &mut HitContext,
+    ) -> Option<(HitRecord, &mut Material)> {
         let i = self.init();
-        i.sides.hit(ray_in, t_range)
+        i.sides.hit(ray_in, t_range, ctx)
     }
 
-    fn bounding_box(&self, _t_range: TRange<f64>) -> Option<AABB> {
+    fn bounding_box(&self, _t_range: TRange<Float>) -> Option<AABB> {
         match self {
             RectPrism::U(u) => Some(AABB::new(u.p0, u.p1)),
-            RectPrism::I(i) => Some(i.sides.aabb),
+            RectPrism::I(i) => i.sides.bounding_box(TRange::new(0.0, 0.0)),
         }
     }
 }
@@ -26,7 +26,7 @@ pub enum Perlin {
 }
 
 impl Perlin {
-    pub fn turbulence(&mut self, p: Point3, depth: usize) -> f64 {
+    pub fn turbulence(&mut self, p: Point3, depth: usize) -> Float {
         let mut accum = 0.0;
         let mut p = p;
         let mut weight = 1.0;
@@ -40,7 +40,7 @@ impl Perlin {
         accum.abs()
     }
 
-    pub fn noise(&mut self, p: Point3) -> f64 {
+    pub fn noise(&mut self, p: Point3) -> Float {
         if let Perlin::U(u) = self {
             *self = Perlin::I(Perlin::init(u));
         }
@@ -52,7 +52,7 @@ impl Perlin {
         }
     }
 
-    fn noise_calc(s: &Init, p: Point3) -> f64 {
+    fn noise_calc(s: &Init, p: Point3) -> Float {
         let p = NTuple::from(p);
         let int = p.map(|x| x.floor() as i64);
         let dec = p.map(|x| x - x.floor());
@@ -60,9 +60,9 @@ impl Perlin {
         for (i, u) in c.iter_mut().enumerate() {
             for (j, v) in u.iter_mut().enumerate() {
                 for (k, w) in v.iter_mut().enumerate() {
-                    let offset = ntuple!(i as i64, j as i64, k as i64);
+                    let offset = ntuple!(i, j, k);
                     let indices =
-                        int.combine(offset, |x, y| (x + y).rem_euclid(s.size as i64) as usize);
+                        int.combine(offset, |x, y| (x + y as i64).rem_euclid(s.size as i64) as usize);
                     let index = s.perm_x[indices[0]] ^ s.perm_y[indices[1]] ^ s.perm_z[indices[2]];
                     *w = s.ranvec[index];
                 }
@@ -72,15 +72,18 @@ impl Perlin {
         Self::trilinear_interp(c, dec)
     }
 
-    fn trilinear_interp(c: [[[Vec3; 2]; 2]; 2], dec: NTuple<f64, 3>) -> f64 {
+    fn trilinear_interp(c: [[[Vec3; 2]; 2]; 2], dec: NTuple<Float, 3>) -> Float {
         let dec_smooth = dec.map(|x| x * x * (3.0 - 2.0 * x));
         let mut accum = 0.0;
         for (i, u) in c.iter().enumerate() {
             for (j, v) in u.iter().enumerate() {
                 for (k, w) in v.iter().enumerate() {
-                    let indices = ntuple!(i, j, k).map(|x| x as f64);
-                    let weight = Vec3::from(dec.combine(indices, |x, y| x - y));
-                    let interp = indices.combine(dec_smooth, |x, y| x * y + (1.0 - x) * (1.0 - y));
+                    let indices = ntuple!(i, j, k);
+                    let weight = Vec3::from(dec.combine(indices, |x, y| x - y as Float));
+                    let interp = dec_smooth.combine(indices, |x, y| {
+                        let y = y as Float;
+                        y * x + (1.0 - y) * (1.0 - x)
+                    });
                     accum += interp.fold(w.dot(weight), |acc, x| acc * x);
                 }
             }
@@ -121,7 +124,7 @@ impl Perlin {
         p
     }
 
-    fn permute(p: &mut Vec<usize>, rng: &mut Random<rand::rngs::StdRng>) {
+    fn permute(p: &mut [usize], rng: &mut Random<rand::rngs::StdRng>) {
         for i in (1..p.len()).rev() {
             let target = rng.random_range(0..i);
             p.swap(i, target);
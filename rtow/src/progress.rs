@@ -0,0 +1,76 @@
+use std::time::{Duration, Instant};
+
+/// A snapshot of how far through a render is.
+///
+/// Pixels are counted once per unit of work, so a progressive render counts
+/// each pixel once per pass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub completed: u64,
+    pub total: u64,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// The completed fraction of the render, between 0 and 1.
+    pub fn fraction(self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.completed as f64 / self.total as f64
+        }
+    }
+}
+
+/// Tracks the progress of a render and passes it to an optional callback
+/// every time at least `interval` more pixels have been completed, and once
+/// more when the render finishes.
+pub struct ProgressReporter<F> {
+    callback: Option<F>,
+    total: u64,
+    interval: u64,
+    completed: u64,
+    next_report: u64,
+    start: Instant,
+}
+
+impl<F> ProgressReporter<F>
+where
+    F: FnMut(Progress),
+{
+    pub fn new(total: u64, interval: u64, callback: Option<F>) -> ProgressReporter<F> {
+        assert!(interval > 0, "The progress interval must be at least one.");
+        ProgressReporter {
+            callback,
+            total,
+            interval,
+            completed: 0,
+            next_report: interval,
+            start: Instant::now(),
+        }
+    }
+
+    /// Records that `pixels` more pixels have been completed.
+    pub fn advance(&mut self, pixels: u64) {
+        self.completed = (self.completed + pixels).min(self.total);
+        if self.completed < self.next_report && self.completed < self.total {
+            return;
+        }
+
+        // skip past every interval boundary crossed by this advance so that a
+        // large advance only reports once
+        self.next_report = (self.completed / self.interval + 1) * self.interval;
+        let progress = self.progress();
+        if let Some(callback) = &mut self.callback {
+            callback(progress);
+        }
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            completed: self.completed,
+            total: self.total,
+            elapsed: self.start.elapsed(),
+        }
+    }
+}
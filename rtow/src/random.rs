@@ -1,17 +1,308 @@
 /* Random value generation
- * - random unit vector
+ * - random unit vector, sampled in closed form
  * - random vector in unit disk
  * - random vector in unit sphere
+ * - seedable per thread sample streams
+ * - small fast generators: PCG32 and xoshiro256++
+ * - stratified and latin hypercube 2D sample sequences
+ * - random direction within a cone, or towards a sphere
  */
 
 use crate::color::*;
+use crate::config::RngKind;
 use geometry3d::*;
-use rand::{Rng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+use std::cell::RefCell;
 use rand::distributions::{Standard, Distribution, uniform::{SampleUniform, SampleRange}};
 
 #[derive(Debug, Clone)]
 pub struct Random<T>(T);
 
+thread_local! {
+    static STREAM: RefCell<Generator> = RefCell::new(Generator::Std(StdRng::from_entropy()));
+}
+
+/// A handle to the random number stream of the current thread, which is
+/// seeded from entropy until `PixelStreams::restart` is called.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StreamRng;
+
+impl RngCore for StreamRng {
+    fn next_u32(&mut self) -> u32 {
+        STREAM.with(|rng| rng.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        STREAM.with(|rng| rng.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        STREAM.with(|rng| rng.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        STREAM.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+/// The random number stream of the current thread, used for every random
+/// decision made while rendering.
+pub fn rng() -> Random<StreamRng> {
+    Random::new(StreamRng)
+}
+
+/// The streams of random numbers of the samples of a render, each derived
+/// from the render `seed`, the pixel and the index of the sample, so that
+/// the random decisions of a sample do not depend on what was rendered
+/// before it, or on which thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelStreams {
+    pub seed: u64,
+    pub generator: RngKind,
+}
+
+impl PixelStreams {
+    pub fn new(seed: u64, generator: RngKind) -> Self {
+        Self { seed, generator }
+    }
+
+    /// The stream of the `sample`th sample of the pixel (x, y).
+    pub fn stream(self, x: u32, y: u32, sample: u32) -> Random<Generator> {
+        Random(Generator::seed_from_u64(self.generator, pixel_seed(self.seed, x, y, sample)))
+    }
+
+    /// Restarts the stream of the current thread as the stream of the
+    /// `sample`th sample of the pixel (x, y).
+    pub fn restart(self, x: u32, y: u32, sample: u32) {
+        let Random(stream) = self.stream(x, y, sample);
+        STREAM.with(|rng| *rng.borrow_mut() = stream);
+    }
+}
+
+/// The seed of the stream of the `sample`th sample of the pixel (x, y).
+fn pixel_seed(seed: u64, x: u32, y: u32, sample: u32) -> u64 {
+    [x, y, sample]
+        .into_iter()
+        .fold(splitmix64(seed), |state, value| splitmix64(state ^ value as u64))
+}
+
+// spreads nearby inputs over the whole range
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl Random<Generator> {
+    /// A stream of random numbers from the default generator for the
+    /// `sample`th sample of the pixel (x, y) of a render with the given
+    /// `seed`, which is the same whatever order the samples are taken in.
+    pub fn for_pixel(seed: u64, x: u32, y: u32, sample: u32) -> Self {
+        PixelStreams::new(seed, RngKind::default()).stream(x, y, sample)
+    }
+}
+
+/// One of the generators that `RngKind` chooses between.
+///
+/// Boxing the large state of `StdRng` would allocate for every sample, as
+/// each sample restarts the stream.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum Generator {
+    Pcg32(Pcg32),
+    Xoshiro256(Xoshiro256),
+    Std(StdRng),
+}
+
+impl Generator {
+    pub fn seed_from_u64(kind: RngKind, seed: u64) -> Self {
+        match kind {
+            RngKind::Pcg32 => Generator::Pcg32(Pcg32::seed_from_u64(seed)),
+            RngKind::Xoshiro256 => Generator::Xoshiro256(Xoshiro256::seed_from_u64(seed)),
+            RngKind::Std => Generator::Std(StdRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl RngCore for Generator {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            Generator::Pcg32(rng) => rng.next_u32(),
+            Generator::Xoshiro256(rng) => rng.next_u32(),
+            Generator::Std(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            Generator::Pcg32(rng) => rng.next_u64(),
+            Generator::Xoshiro256(rng) => rng.next_u64(),
+            Generator::Std(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            Generator::Pcg32(rng) => rng.fill_bytes(dest),
+            Generator::Xoshiro256(rng) => rng.fill_bytes(dest),
+            Generator::Std(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Fills `dest` from successive little-endian outputs of `next`.
+fn fill_from<const N: usize>(dest: &mut [u8], mut next: impl FnMut() -> [u8; N]) {
+    for chunk in dest.chunks_mut(N) {
+        let bytes = next();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+/// The PCG-XSH-RR generator with a 64 bit state and a 64 bit stream
+/// selector, giving 32 bits per step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    /// The generator started from `state` on the stream `stream`, as
+    /// `pcg32_srandom_r` of the reference implementation.
+    pub fn new(state: u64, stream: u64) -> Self {
+        let mut rng = Pcg32 {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(state);
+        rng.step();
+        rng
+    }
+
+    fn step(&mut self) {
+        self.state = self.state.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.increment);
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        (high << 32) | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_from(dest, || self.next_u32().to_le_bytes())
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Pcg32 {
+    /// The state followed by the stream, little-endian.
+    type Seed = [u8; 16];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let (state, stream) = seed.split_at(8);
+        Pcg32::new(
+            u64::from_le_bytes(state.try_into().unwrap()),
+            u64::from_le_bytes(stream.try_into().unwrap()),
+        )
+    }
+
+    fn seed_from_u64(seed: u64) -> Self {
+        Pcg32::new(splitmix64(seed), splitmix64(!seed))
+    }
+}
+
+/// The xoshiro256++ generator, giving 64 bits per step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xoshiro256([u64; 4]);
+
+impl Xoshiro256 {
+    /// The generator with the given state, which must not be all zeros.
+    pub fn new(state: [u64; 4]) -> Self {
+        assert!(state != [0; 4], "The state of xoshiro256++ cannot be all zeros.");
+        Xoshiro256(state)
+    }
+}
+
+impl RngCore for Xoshiro256 {
+    fn next_u32(&mut self) -> u32 {
+        // the high bits are the better ones
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.0;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_from(dest, || self.next_u64().to_le_bytes())
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Xoshiro256 {
+    /// The four words of the state, little-endian.
+    type Seed = [u8; 32];
+
+    /// An all zero seed, which would only ever give zeros, is replaced by
+    /// the seed of `seed_from_u64(0)`.
+    fn from_seed(seed: Self::Seed) -> Self {
+        let state: [u64; 4] = std::array::from_fn(|i| u64::from_le_bytes(seed[8 * i..8 * i + 8].try_into().unwrap()));
+        if state == [0; 4] {
+            Self::seed_from_u64(0)
+        } else {
+            Xoshiro256(state)
+        }
+    }
+
+    /// Expands the seed with splitmix64, as its authors recommend.
+    fn seed_from_u64(seed: u64) -> Self {
+        let mut state = seed;
+        Xoshiro256(std::array::from_fn(|_| {
+            let word = splitmix64(state);
+            state = state.wrapping_add(0x9e3779b97f4a7c15);
+            word
+        }))
+    }
+}
+
 impl<T: Rng> Random<T> {
     pub fn new(rng: T) -> Self {
         Random(rng)
@@ -41,30 +332,136 @@ impl<T: Rng> Random<T> {
         (2.0 * v) - Vec3::new(1.0, 1.0, 1.0)
     }
 
+    /// A point uniformly distributed within the unit sphere, from three
+    /// random numbers.
     pub fn in_unit_sphere(&mut self) -> Vec3 {
-        loop {
-            let v = self.in_unit_cube();
-            if v.quadrance() <= 1.0 {
-                return v;
-            }
-        }
+        self.in_sphere_uniform()
     }
 
+    /// A direction uniformly distributed over the unit sphere, from two
+    /// random numbers.
     pub fn unit_vector(&mut self) -> Vec3 {
-        loop {
-            if let Some(vec) = self.in_unit_sphere().unit() {
-                return vec;
-            }
-        }
+        self.on_sphere_uniform()
     }
 
+    /// A point uniformly distributed within the unit disk in the xy plane,
+    /// from two random numbers.
     pub fn in_unit_disk(&mut self) -> Vec3 {
-        loop {
-            let v = self.in_unit_cube();
-            let v = Vec3::new(v.x(), v.y(), 0.0);
-            if v.quadrance() <= 1.0 {
-                return v;
-            }
+        self.in_disk_uniform()
+    }
+
+    /// Samples the sphere by height and angle, as the area of a band of the
+    /// sphere is proportional to its height (Archimedes' hat-box theorem).
+    pub fn on_sphere_uniform(&mut self) -> Vec3 {
+        let z = 1.0 - 2.0 * self.random::<Float>();
+        let phi = 2.0 * consts::PI * self.random::<Float>();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    /// Scales a direction by the cube root of a random number, as the volume
+    /// within radius r is proportional to r³.
+    pub fn in_sphere_uniform(&mut self) -> Vec3 {
+        let direction = self.on_sphere_uniform();
+        self.random::<Float>().cbrt() * direction
+    }
+
+    /// One point jittered within each cell of an `nx` by `ny` grid over the
+    /// unit square, with the cells visited in a random order.
+    pub fn stratified_2d(&mut self, nx: usize, ny: usize) -> impl Iterator<Item = (Float, Float)> + '_ {
+        let mut cells: Vec<usize> = (0..nx * ny).collect();
+        cells.shuffle(&mut self.0);
+        cells.into_iter().map(move |cell| {
+            let x = ((cell % nx) as Float + self.random::<Float>()) / nx as Float;
+            let y = ((cell / nx) as Float + self.random::<Float>()) / ny as Float;
+            (x.min(ONE_BELOW), y.min(ONE_BELOW))
+        })
+    }
+
+    /// `n` points over the unit square with exactly one in each of the `n`
+    /// columns and each of the `n` rows, the rows shuffled independently of
+    /// the columns so the coordinates are uncorrelated.
+    pub fn latin_hypercube(&mut self, n: usize) -> impl Iterator<Item = (Float, Float)> + '_ {
+        let mut columns: Vec<usize> = (0..n).collect();
+        let mut rows = columns.clone();
+        columns.shuffle(&mut self.0);
+        rows.shuffle(&mut self.0);
+        columns.into_iter().zip(rows).map(move |(column, row)| {
+            let x = (column as Float + self.random::<Float>()) / n as Float;
+            let y = (row as Float + self.random::<Float>()) / n as Float;
+            (x.min(ONE_BELOW), y.min(ONE_BELOW))
+        })
+    }
+
+    /// A direction uniformly distributed over the solid angle of the cone
+    /// around the non-zero `axis` whose half-angle has the cosine
+    /// `cos_theta_max`, so that its dot product with the unit axis is at
+    /// least `cos_theta_max`. Its density is `cone_pdf(cos_theta_max)`.
+    pub fn in_cone(&mut self, axis: Vec3, cos_theta_max: Float) -> Vec3 {
+        let axis = axis.unit().expect("The axis of a cone is not zero.");
+        // the solid angle of the cap above height z is proportional to 1 - z
+        let z = 1.0 + self.random::<Float>() * (cos_theta_max - 1.0);
+        let phi = 2.0 * consts::PI * self.random::<Float>();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        to_world(axis, Vec3::new(phi.cos() * r, phi.sin() * r, z))
+    }
+
+    /// A direction uniformly distributed over the cone subtended by a sphere
+    /// of `radius` whose center lies along `center_dir` at the squared
+    /// distance `distance_sq`, along with its density over solid angle. From
+    /// within the sphere every direction is seen, so any direction is
+    /// sampled.
+    pub fn to_sphere(&mut self, center_dir: Vec3, distance_sq: Float, radius: Float) -> (Vec3, Float) {
+        let radius_sq = radius * radius;
+        if distance_sq <= radius_sq {
+            return (self.unit_vector(), 1.0 / (4.0 * consts::PI));
         }
+        let cos_theta_max = (1.0 - radius_sq / distance_sq).sqrt();
+        (self.in_cone(center_dir, cos_theta_max), cone_pdf(cos_theta_max))
+    }
+
+    /// Samples the disk in polar coordinates with the square root of a random
+    /// number as the radius, as the area within radius r is proportional to
+    /// r².
+    pub fn in_disk_uniform(&mut self) -> Vec3 {
+        let r = self.random::<Float>().sqrt();
+        let theta = 2.0 * consts::PI * self.random::<Float>();
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+    }
+}
+
+/// The density over solid angle of the directions of `Random::in_cone`,
+/// one over the solid angle of the cone.
+pub fn cone_pdf(cos_theta_max: Float) -> Float {
+    1.0 / (2.0 * consts::PI * (1.0 - cos_theta_max))
+}
+
+/// Rotates `local`, given relative to the z axis, so that the z axis lies
+/// along the unit vector `w`.
+fn to_world(w: Vec3, local: Vec3) -> Vec3 {
+    let a = if w.x().abs() > 0.9 { Vec3::e1() } else { Vec3::e0() };
+    let v = w.cross(a).unit().unwrap();
+    let u = w.cross(v);
+    local.x() * u + local.y() * v + local.z() * w
+}
+
+// the largest value below one, to keep rounding from pushing a point onto
+// the far edge of the square
+const ONE_BELOW: Float = 1.0 - Float::EPSILON / 2.0;
+
+/// Maps a point of the unit square onto the unit disk in the xy plane,
+/// preserving area and keeping neighboring points together (Shirley and
+/// Chiu's concentric mapping), so stratified points stay stratified.
+pub fn square_to_disk(u: Float, v: Float) -> Vec3 {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return Vec3::default();
     }
+    let quarter = consts::PI / 4.0;
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, quarter * (b / a))
+    } else {
+        (b, 2.0 * quarter - quarter * (a / b))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
 }
@@ -0,0 +1,447 @@
+use crate::color::FloatRgb;
+use crate::config::{AoConfig, BvhViewConfig, ClampConfig, DepthConfig, HeatMapConfig};
+use crate::hit_record::{HitContext, HitRecord};
+use crate::light::Lights;
+use crate::material::Material;
+use crate::object::{Bvh4Node, FlatChild, FlatNode, Object};
+use crate::random;
+use crate::stats;
+use geometry3d::*;
+use geometry3d::consts::PI;
+
+// minimize hitting the same point due to floating point approximation
+const RANGE: TRange<Float> = TRange {
+    start: 0.001,
+    end: Float::INFINITY,
+};
+
+/// The closest hit of a ray cast into the scene while rendering, which is
+/// counted in the render statistics.
+fn cast<'a>(
+    ray: Ray3,
+    t_range: TRange<Float>,
+    world: &'a mut Object,
+    ctx: &mut HitContext,
+) -> Option<(HitRecord, &'a mut Material)> {
+    debug_assert!(ray.is_normalized(), "Rays should be made with Ray3::new: {ray}");
+    let hit = world.hit(ray, t_range, ctx);
+    stats::count_ray(ctx);
+    hit
+}
+
+/// The surface first hit by a primary ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FirstHit {
+    /// The shading normal, facing back towards the ray.
+    pub normal: Vec3,
+    /// The distance along the ray to the hit, in units of the ray direction.
+    pub t: Float,
+    pub albedo: FloatRgb,
+}
+
+/// The light travelling back along a primary ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RadianceSample {
+    pub color: FloatRgb,
+    /// The part of `color` seen directly: the light emitted by the first
+    /// surface hit, or the background if the ray missed the scene.
+    pub direct: FloatRgb,
+    pub alpha: Float,
+    /// The first surface hit by the ray, or None if it missed the scene.
+    pub first_hit: Option<FirstHit>,
+}
+
+/// The color of the light travelling back along the ray, and the first
+/// surface it hit. Samples from `ray_color` are always opaque.
+///
+/// The path is followed iteratively, so `depth` is not limited by the stack.
+/// Light emitted at each bounce is scaled by the product of the attenuations
+/// of the bounces before it, the throughput, and the path ends when it
+/// misses the scene, is absorbed, or runs out of bounces.
+pub fn ray_color(ray: Ray3, background: FloatRgb, world: &mut Object, depth: u32) -> RadianceSample {
+    trace_path(ray, background, world, &Lights::new(), depth)
+}
+
+/// `ray_color` with next event estimation: at each diffuse bounce a shadow
+/// ray is also cast towards a point sampled on `lights`.
+///
+/// Light reached by both the shadow ray and the bounced ray is weighted
+/// between them with the power heuristic, so that each strategy counts most
+/// where it is the better estimate, i.e. sampling the lights for small
+/// lights and following the bounce for large ones.
+pub fn trace_path(
+    mut ray: Ray3,
+    background: FloatRgb,
+    world: &mut Object,
+    lights: &Lights,
+    depth: u32,
+) -> RadianceSample {
+    let mut rng = random::rng();
+    let mut radiance = FloatRgb::new(0.0, 0.0, 0.0);
+    let mut throughput = FloatRgb::new(1.0, 1.0, 1.0);
+    let mut direct = FloatRgb::new(0.0, 0.0, 0.0);
+    let mut first_hit = None;
+    // the MIS weight of light emitted by the next surface hit
+    let mut emission_weight = 1.0;
+    // the number of surfaces hit so far
+    let mut length = 0;
+
+    for bounce in 0..depth {
+        let Some((rec, mat)) = cast(ray, RANGE, world, &mut HitContext::default()) else {
+            radiance = radiance + throughput * background;
+            if bounce == 0 {
+                direct = background;
+            }
+            break;
+        };
+        length += 1;
+
+        // only evaluate the albedo texture for the primary ray
+        if bounce == 0 {
+            first_hit = Some(FirstHit {
+                normal: rec.normal,
+                t: rec.t,
+                albedo: mat.albedo(rec),
+            });
+        }
+
+        let emitted = mat.emit(rec);
+        if bounce == 0 {
+            direct = emitted;
+        }
+        radiance = radiance + throughput * (emission_weight * emitted);
+
+        let sample_lights = !lights.is_empty() && mat.is_diffuse();
+        let Some((attenuation, scattered)) = mat.scatter(rec) else {
+            break;
+        };
+
+        emission_weight = 1.0;
+        if sample_lights {
+            let origin = rec.point;
+            let time = ray.time;
+
+            // the Lambertian density is cos(theta) / pi and the BRDF times
+            // the cosine is attenuation * cos(theta) / pi
+            let bsdf_pdf = |direction: Vec3| rec.normal.dot(direction).max(0.0) / PI;
+
+            let shadow_ray = lights
+                .sample(origin, time, &mut rng)
+                .and_then(|direction| Ray3::new(origin, direction, time));
+            if let Some(shadow_ray) = shadow_ray {
+                let light_pdf = lights.pdf(origin, shadow_ray.direction, time);
+                let bsdf_pdf = bsdf_pdf(shadow_ray.direction);
+                if light_pdf > 0.0 && bsdf_pdf > 0.0 {
+                    if let Some((light_rec, light_mat)) = cast(shadow_ray, RANGE, world, &mut HitContext::default()) {
+                        let weight = power_heuristic(light_pdf, bsdf_pdf) * bsdf_pdf / light_pdf;
+                        let light = light_mat.emit(light_rec);
+                        radiance = radiance + throughput * attenuation * (weight * light);
+                    }
+                }
+            }
+
+            let light_pdf = lights.pdf(origin, scattered.direction, time);
+            emission_weight = power_heuristic(bsdf_pdf(scattered.direction), light_pdf);
+        }
+
+        throughput = throughput * attenuation;
+        ray = scattered;
+    }
+    stats::count_path(length);
+
+    RadianceSample {
+        color: radiance,
+        direct,
+        alpha: 1.0,
+        first_hit,
+    }
+}
+
+/// The weight given to a sample drawn with density `a` when the same value
+/// could also have been drawn by a second strategy with density `b`.
+fn power_heuristic(a: Float, b: Float) -> Float {
+    let (a2, b2) = (a * a, b * b);
+    if a2 + b2 == 0.0 {
+        0.0
+    } else {
+        a2 / (a2 + b2)
+    }
+}
+
+/// The radiance of a primary ray. With `alpha` enabled rays that miss the
+/// scene are transparent black rather than the background color, so that
+/// averaged pixel colors come out premultiplied by their coverage.
+pub fn sample_color(
+    ray: Ray3,
+    background: FloatRgb,
+    world: &mut Object,
+    lights: &Lights,
+    depth: u32,
+    alpha: bool,
+) -> RadianceSample {
+    match trace_path(ray, background, world, lights, depth) {
+        sample if alpha && sample.first_hit.is_none() => RadianceSample {
+            color: FloatRgb::new(0.0, 0.0, 0.0),
+            direct: FloatRgb::new(0.0, 0.0, 0.0),
+            alpha: 0.0,
+            ..sample
+        },
+        sample => sample,
+    }
+}
+
+/// The number of bounding boxes tested while finding the closest hit of the
+/// ray, colored through the heat map gradient.
+pub fn heat_map_sample(ray: Ray3, world: &mut Object, heat_map: &HeatMapConfig) -> RadianceSample {
+    let mut ctx = HitContext::default();
+    let first_hit = cast(ray, RANGE, world, &mut ctx).map(|(rec, mat)| FirstHit {
+        normal: rec.normal,
+        t: rec.t,
+        albedo: mat.albedo(rec),
+    });
+
+    let color = heat_map.color(ctx.aabb_tests);
+    RadianceSample {
+        color,
+        direct: color,
+        alpha: 1.0,
+        first_hit,
+    }
+}
+
+/// The unoccluded fraction of the cosine weighted hemisphere above the first
+/// hit of the ray, as a shade of grey. Misses are fully unoccluded.
+pub fn ambient_occlusion_sample(ray: Ray3, world: &mut Object, ao: &AoConfig) -> RadianceSample {
+    let Some((rec, mat)) = cast(ray, RANGE, world, &mut HitContext::default()) else {
+        return RadianceSample {
+            color: FloatRgb::new(1.0, 1.0, 1.0),
+            direct: FloatRgb::new(1.0, 1.0, 1.0),
+            alpha: 1.0,
+            first_hit: None,
+        };
+    };
+    let first_hit = FirstHit {
+        normal: rec.normal,
+        t: rec.t,
+        albedo: mat.albedo(rec),
+    };
+
+    let mut rng = random::rng();
+    let range = TRange {
+        start: RANGE.start,
+        end: ao.max_distance.unwrap_or(Float::INFINITY),
+    };
+    let unoccluded = (0..ao.rays)
+        .filter(|_| {
+            let direction = (rec.normal + rng.unit_vector()).unit().unwrap_or(rec.normal);
+            Ray3::new(rec.point, direction, ray.time)
+                .is_some_and(|ray| cast(ray, range, world, &mut HitContext::default()).is_none())
+        })
+        .count();
+
+    let shade = if ao.rays == 0 {
+        1.0
+    } else {
+        unoccluded as Float / ao.rays as Float
+    };
+    let color = FloatRgb::new(shade, shade, shade);
+    RadianceSample {
+        color,
+        direct: color,
+        alpha: 1.0,
+        first_hit: Some(first_hit),
+    }
+}
+
+/// The distance along the ray to its first hit, as a shade of grey.
+pub fn depth_sample(ray: Ray3, world: &mut Object, depth: &DepthConfig) -> RadianceSample {
+    let first_hit = cast(ray, RANGE, world, &mut HitContext::default()).map(|(rec, mat)| FirstHit {
+            normal: rec.normal,
+            t: rec.t,
+            albedo: mat.albedo(rec),
+        });
+
+    let distance = first_hit.map(|hit| hit.t * ray.direction.length());
+    let value = depth.value(distance);
+    let color = FloatRgb::new(value, value, value);
+    RadianceSample {
+        color,
+        direct: color,
+        alpha: 1.0,
+        first_hit,
+    }
+}
+
+/// A bounding box of a BVH passed through by a ray.
+#[derive(Debug, Clone, Copy)]
+pub struct BoxHit {
+    pub aabb: AABB,
+    /// The level of the node in the hierarchy, starting from 1 at the root.
+    pub depth: u32,
+}
+
+/// Every bounding box of the BVH nodes in `world` that the ray passes
+/// through, parents before their children. The primitives themselves are
+/// not tested.
+pub fn bvh_hits(ray: Ray3, world: &Object) -> Vec<BoxHit> {
+    fn visit(ray: Ray3, object: &Object, depth: u32, hits: &mut Vec<BoxHit>) {
+        if let Object::BVHNode(node) = object {
+            if node.aabb().hit_bool(ray, RANGE) {
+                hits.push(BoxHit {
+                    aabb: node.aabb(),
+                    depth,
+                });
+                visit(ray, node.left(), depth + 1, hits);
+                visit(ray, node.right(), depth + 1, hits);
+            }
+        }
+    }
+
+    fn visit_flat(ray: Ray3, nodes: &[FlatNode], child: FlatChild, depth: u32, hits: &mut Vec<BoxHit>) {
+        if let FlatChild::Node(index) = child {
+            let FlatNode { aabb, left, right } = nodes[index as usize];
+            if aabb.hit_bool(ray, RANGE) {
+                hits.push(BoxHit { aabb, depth });
+                visit_flat(ray, nodes, left, depth + 1, hits);
+                visit_flat(ray, nodes, right, depth + 1, hits);
+            }
+        }
+    }
+
+    fn visit_bvh4(ray: Ray3, nodes: &[Bvh4Node], index: u32, depth: u32, hits: &mut Vec<BoxHit>) {
+        let node = &nodes[index as usize];
+        for (i, child) in node.children.into_iter().enumerate() {
+            if let (Some(FlatChild::Node(child)), Some(aabb)) = (child, node.boxes.get(i)) {
+                if aabb.hit_bool(ray, RANGE) {
+                    hits.push(BoxHit { aabb, depth });
+                    visit_bvh4(ray, nodes, child, depth + 1, hits);
+                }
+            }
+        }
+    }
+
+    let mut hits = Vec::new();
+    match world {
+        Object::FlatBVH(bvh) if !bvh.nodes().is_empty() => {
+            visit_flat(ray, bvh.nodes(), FlatChild::Node(0), 1, &mut hits)
+        }
+        Object::BVH4(bvh) => {
+            if bvh.aabb().hit_bool(ray, RANGE) {
+                hits.push(BoxHit {
+                    aabb: bvh.aabb(),
+                    depth: 1,
+                });
+                visit_bvh4(ray, bvh.nodes(), 0, 2, &mut hits);
+            }
+        }
+        world => visit(ray, world, 1, &mut hits),
+    }
+    hits
+}
+
+/// Colors the ray by the deepest bounding box of the BVH it passes through,
+/// or with `edges` the deepest whose outline it passes through, through the
+/// gradient of `colors`. Rays that pass through no boxes are black.
+pub fn bvh_sample(
+    ray: Ray3,
+    world: &mut Object,
+    view: &BvhViewConfig,
+    colors: &HeatMapConfig,
+) -> RadianceSample {
+    let deepest = bvh_hits(ray, world)
+        .into_iter()
+        .filter(|hit| !view.edges || on_edge(ray, hit.aabb, view.edge_width))
+        .map(|hit| hit.depth)
+        .max();
+
+    let first_hit = cast(ray, RANGE, world, &mut HitContext::default()).map(|(rec, mat)| FirstHit {
+            normal: rec.normal,
+            t: rec.t,
+            albedo: mat.albedo(rec),
+        });
+
+    let color = match deepest {
+        Some(depth) => colors.color(depth),
+        None => FloatRgb::new(0.0, 0.0, 0.0),
+    };
+    RadianceSample {
+        color,
+        direct: color,
+        alpha: 1.0,
+        first_hit,
+    }
+}
+
+/// Whether the ray enters or leaves the box within `width` of one of its
+/// edges, as a fraction of the size of the box along each axis.
+fn on_edge(ray: Ray3, aabb: AABB, width: Float) -> bool {
+    let (lo, hi) = (aabb.lo(), aabb.hi());
+    let lo = [lo.x(), lo.y(), lo.z()];
+    let hi = [hi.x(), hi.y(), hi.z()];
+    let origin = [ray.origin.x(), ray.origin.y(), ray.origin.z()];
+    let direction = [ray.direction.x(), ray.direction.y(), ray.direction.z()];
+
+    let (mut t_enter, mut t_exit) = (Float::NEG_INFINITY, Float::INFINITY);
+    for axis in 0..3 {
+        let t0 = (lo[axis] - origin[axis]) / direction[axis];
+        let t1 = (hi[axis] - origin[axis]) / direction[axis];
+        t_enter = t_enter.max(t0.min(t1));
+        t_exit = t_exit.min(t0.max(t1));
+    }
+
+    // a point is on an edge when it is near the faces of two axes
+    let near_edge = |t: Float| {
+        let p = ray.at(t);
+        let p = [p.x(), p.y(), p.z()];
+        let near_faces = (0..3)
+            .filter(|&axis| {
+                let tolerance = width * (hi[axis] - lo[axis]);
+                (p[axis] - lo[axis]).abs() <= tolerance || (hi[axis] - p[axis]).abs() <= tolerance
+            })
+            .count();
+        near_faces >= 2
+    };
+
+    (t_enter > RANGE.start && near_edge(t_enter)) || (t_exit > RANGE.start && near_edge(t_exit))
+}
+
+impl DepthConfig {
+    /// The value written for a hit at `distance`, or a miss.
+    pub fn value(&self, distance: Option<Float>) -> Float {
+        match (distance, self.raw) {
+            (Some(distance), true) => distance,
+            (None, true) => self.far,
+            (Some(distance), false) => ((distance - self.near) / (self.far - self.near)).clamp(0.0, 1.0),
+            (None, false) => 1.0,
+        }
+    }
+}
+
+impl ClampConfig {
+    /// Limits the radiance of the sample, or only the part of it that was
+    /// not seen directly.
+    pub fn apply(&self, sample: RadianceSample) -> RadianceSample {
+        let color = if self.indirect_only {
+            sample.direct + (sample.color - sample.direct).limit(self.max_radiance)
+        } else {
+            sample.color.limit(self.max_radiance)
+        };
+        RadianceSample { color, ..sample }
+    }
+}
+
+impl HeatMapConfig {
+    /// Linearly interpolates between the gradient colors, clamping counts
+    /// above `max` to the last color.
+    pub fn color(&self, count: u32) -> FloatRgb {
+        match self.gradient.len() {
+            0 => FloatRgb::new(0.0, 0.0, 0.0),
+            1 => self.gradient[0],
+            n => {
+                let x = (count as Float / self.max.max(1) as Float).min(1.0) * (n - 1) as Float;
+                let i = (x.floor() as usize).min(n - 2);
+                self.gradient[i + 1].mix(self.gradient[i], x - i as Float)
+            }
+        }
+    }
+}
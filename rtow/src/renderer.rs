@@ -0,0 +1,429 @@
+use crate::aov::{AovAccumulator, AovBuffer};
+use crate::camera::Camera;
+use crate::color::*;
+use crate::config::{
+    Accelerator, AoConfig, BvhBuilder, BvhViewConfig, ClampConfig, Config, DepthConfig, HeatMapConfig, ImageFormat,
+    InvalidConfig, ProgressiveConfig, RenderMode,
+};
+use crate::framebuffer::Framebuffer;
+use crate::image::{write_file, Image};
+use crate::light::Lights;
+use crate::object::*;
+use crate::random::PixelStreams;
+use crate::render::*;
+use crate::sampler::{AdaptiveSampler, Sampler};
+use crate::stats::{self, RenderStats};
+use geometry3d::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::Write;
+use std::time::Instant;
+
+/// Renders the scene described by a `Config`.
+///
+/// The render is split into units of work, either tiles or progressive
+/// passes, which are always performed in the same order so that a render can
+/// be stopped after any unit and picked up again from its `RenderState`.
+pub struct Renderer {
+    image: Image,
+    sampler: Sampler,
+    adaptive: Option<AdaptiveSampler>,
+    progressive: Option<ProgressiveConfig>,
+    /// The streams of random numbers of the samples, derived from the seed.
+    streams: PixelStreams,
+    bvh_stats: BvhStats,
+    tracer: Tracer,
+}
+
+/// The accumulated results of the units of work completed so far.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RenderState {
+    pub completed: u32,
+    pub framebuffer: Framebuffer,
+    pub aovs: AovBuffer,
+    pub stats: RenderStats,
+}
+
+/// Everything needed to find the radiance of a primary ray.
+struct Tracer {
+    camera: Camera,
+    scene: Object,
+    lights: Lights,
+    background: FloatRgb,
+    render_mode: RenderMode,
+    heat_map: HeatMapConfig,
+    ao: AoConfig,
+    depth: DepthConfig,
+    bvh: BvhViewConfig,
+    /// The heat map gradient spread over the levels of the BVH.
+    bvh_colors: HeatMapConfig,
+    clamp: Option<ClampConfig>,
+    max_depth: u32,
+    alpha: bool,
+}
+
+/// The BVH of the scene of an animation, kept from one frame to the next so
+/// that it can be refit to each frame rather than built again.
+#[derive(Debug, Clone, Default)]
+pub struct FrameBvh {
+    tree: Option<BVHNode>,
+    /// The total surface area of the boxes of the nodes as built.
+    built_area: Float,
+    /// The number of times the BVH has been built.
+    pub builds: u32,
+}
+
+impl FrameBvh {
+    /// The objects of `list` for the frame over `t_range`, within the BVH of
+    /// the frame before refit to this one, or within a new BVH if there was
+    /// none or refitting has grown it too much. The list should hold the
+    /// same objects for every frame.
+    fn scene(&mut self, list: &mut List, t_range: TRange<Float>, builder: BvhBuilder) -> Result<Object, String> {
+        if let Some(tree) = &mut self.tree {
+            if !tree.refit(t_range, self.built_area) {
+                return Ok(tree.clone().into());
+            }
+        }
+        let scene = BVHNode::from_list(list, t_range, builder)?;
+        self.builds += 1;
+        if let Object::BVHNode(tree) = &scene {
+            self.built_area = tree.stats().interior_surface_area;
+            self.tree = Some(tree.clone());
+        }
+        Ok(scene)
+    }
+}
+
+impl Renderer {
+    pub fn new(config: Config) -> Result<Renderer, Box<dyn Error>> {
+        Self::build(config, None)
+    }
+
+    /// A renderer for a frame of an animation, whose BVH is refit from that
+    /// of the frame rendered before it by `bvh`.
+    pub fn for_frame(config: Config, bvh: &mut FrameBvh) -> Result<Renderer, Box<dyn Error>> {
+        Self::build(config, Some(bvh))
+    }
+
+    fn build(mut config: Config, bvh: Option<&mut FrameBvh>) -> Result<Renderer, Box<dyn Error>> {
+        config.validate().map_err(InvalidConfig)?;
+        let seed = config.seed.unwrap_or_else(rand::random);
+        // kept from before the paths are resolved against the directory of
+        // the scene, which resolving the JSON does itself
+        let mut recorded = config.clone();
+        config.resolve_paths();
+
+        let mut image = Image::new(config.image);
+        image.seed = Some(seed);
+        if matches!(image.format, ImageFormat::Png) {
+            recorded.seed = Some(seed);
+            image.config = Some(recorded.resolved_json()?);
+        }
+        let camera = Camera::new(config.camera, &image);
+        let sampler = Sampler::new(config.sampler.clone(), &image);
+        let adaptive = config
+            .sampler
+            .adaptive
+            .map(|adaptive| AdaptiveSampler::new(adaptive, config.sampler.filter, &image));
+        let lights = if config.light_sampling {
+            Lights::from_config(&config.scene_list, &config.lights)?
+        } else {
+            Lights::new()
+        };
+        // after the lights, whose indices refer to the list as written
+        config.scene_list.expand();
+        let t_range = TRange {
+            start: camera.time_min,
+            end: camera.time_max,
+        };
+        let scene = match bvh {
+            Some(bvh) => bvh.scene(&mut config.scene_list, t_range, config.bvh_builder)?,
+            None => BVHNode::from_list(&mut config.scene_list, t_range, config.bvh_builder)?,
+        };
+        let (scene, bvh_stats) = match scene {
+            Object::BVHNode(tree) => {
+                let stats = tree.stats();
+                let scene = match config.accelerator {
+                    Accelerator::Bvh2 => Object::from(FlatBVH::from(tree)),
+                    Accelerator::Bvh4 => Object::from(BVH4::new(tree, t_range)),
+                };
+                (scene, stats)
+            }
+            // too few objects to need a hierarchy
+            scene => (scene, BvhStats::default()),
+        };
+
+        let bvh_colors = HeatMapConfig {
+            max: match &scene {
+                Object::BVHNode(node) => node.depth(),
+                Object::FlatBVH(bvh) => bvh.depth(),
+                Object::BVH4(bvh) => bvh.depth(),
+                _ => 1,
+            },
+            gradient: config.heat_map.gradient.clone(),
+        };
+
+        let tracer = Tracer {
+            camera,
+            scene,
+            lights,
+            background: config.background_color,
+            render_mode: config.render_mode,
+            heat_map: config.heat_map,
+            ao: config.ao,
+            depth: config.depth,
+            bvh: config.bvh,
+            bvh_colors,
+            clamp: config.clamp,
+            max_depth: sampler.max_depth(),
+            alpha: image.has_alpha(),
+        };
+
+        Ok(Renderer {
+            image,
+            sampler,
+            adaptive,
+            progressive: config.progressive,
+            streams: PixelStreams::new(seed, config.rng),
+            bvh_stats,
+            tracer,
+        })
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// The objects of the scene, within a flattened BVH unless there are
+    /// fewer than two of them.
+    pub fn scene(&self) -> &Object {
+        &self.tracer.scene
+    }
+
+    /// The shape of the BVH of the scene, as built before it was flattened,
+    /// which is empty if the scene needed none.
+    pub fn bvh_stats(&self) -> &BvhStats {
+        &self.bvh_stats
+    }
+
+    /// The seed of the render, either from the config or chosen at random.
+    pub fn seed(&self) -> u64 {
+        self.streams.seed
+    }
+
+    /// A state with no units of work completed.
+    pub fn new_state(&self) -> RenderState {
+        RenderState {
+            completed: 0,
+            framebuffer: Framebuffer::new(self.image.width, self.image.height),
+            aovs: AovBuffer::new(self.image.width, self.image.height),
+            stats: RenderStats::default(),
+        }
+    }
+
+    /// The total number of units of work in the render.
+    pub fn units(&self) -> u32 {
+        match self.progressive {
+            Some(_) => self.sampler.max_samples(),
+            None => self.image.tiles().count() as u32,
+        }
+    }
+
+    /// The number of pixels rendered by the given unit of work.
+    pub fn unit_pixels(&self, unit: u32) -> u64 {
+        match self.progressive {
+            Some(_) => self.image.width as u64 * self.image.height as u64,
+            None => self.image.tiles().nth(unit as usize).map_or(0, |t| t.pixels() as u64),
+        }
+    }
+
+    /// The number of pixels rendered over the whole render.
+    pub fn total_pixels(&self) -> u64 {
+        (0..self.units()).map(|unit| self.unit_pixels(unit)).sum()
+    }
+
+    /// Whether the render is complete, either because every unit of work has
+    /// been performed or because the next pass of a progressive render is not
+    /// expected to finish within its time budget.
+    pub fn finished(&self, state: &RenderState) -> bool {
+        if state.completed >= self.units() {
+            return true;
+        }
+        match self.progressive.and_then(|progressive| progressive.time_budget) {
+            Some(budget) if state.completed > 0 => {
+                let elapsed = state.stats.elapsed.as_secs_f64();
+                let per_pass = elapsed / state.completed as f64;
+                elapsed + per_pass > budget
+            }
+            _ => false,
+        }
+    }
+
+    /// Performs the next unit of work.
+    pub fn render_unit(&mut self, state: &mut RenderState) {
+        assert!(state.completed < self.units(), "The render is already complete.");
+        // discard anything counted outside of a render
+        stats::take();
+        let start = Instant::now();
+        match self.progressive {
+            Some(_) => self.render_pass(state),
+            None => self.render_tile(state),
+        }
+        let unit_stats = RenderStats {
+            elapsed: start.elapsed(),
+            ..stats::take()
+        };
+        state.stats.merge(&unit_stats);
+        state.completed += 1;
+    }
+
+    /// Each pixel takes the sample of a pass from its own stream of random
+    /// numbers.
+    fn render_pass(&mut self, state: &mut RenderState) {
+        let pass = state.completed;
+        let streams = self.streams;
+        let sampler = &self.sampler;
+        let tracer = &mut self.tracer;
+        let aovs = &mut state.aovs;
+
+        state.framebuffer.add_pass(|x, y| {
+            if pass >= sampler.samples_at(x, y) {
+                return None;
+            }
+            streams.restart(x, y, pass);
+            let (u, v) = sampler.sample(x, y, pass);
+            let sample = tracer.radiance(u, v, sampler.lens(streams, x, y, pass));
+            aovs.add_sample(x, y, sample.first_hit);
+            Some((sample.color, sample.alpha, sampler.weight(x, y, u, v)))
+        });
+    }
+
+    /// Each sample of each pixel is taken from its own stream of random
+    /// numbers, the same stream as in a progressive render.
+    fn render_tile(&mut self, state: &mut RenderState) {
+        let tile = self
+            .image
+            .tiles()
+            .nth(state.completed as usize)
+            .expect("Tile out of range.");
+
+        for (x, y) in tile.iter() {
+            let mut pixel_aovs = AovAccumulator::new();
+            let mut radiance = |u, v, lens| {
+                let sample = self.tracer.radiance(u, v, lens);
+                pixel_aovs.add(sample.first_hit);
+                sample
+            };
+
+            let pixel_color = match &self.adaptive {
+                Some(adaptive) => adaptive.sample_pixel(self.streams, x, y, |u, v| {
+                    let sample = radiance(u, v, None);
+                    (sample.color, sample.alpha)
+                }),
+                None => {
+                    let mut pixel_color = FRgbAccumulator::new();
+                    for n in 0..self.sampler.samples_at(x, y) {
+                        self.streams.restart(x, y, n);
+                        let (u, v) = self.sampler.sample(x, y, n);
+                        let sample = radiance(u, v, self.sampler.lens(self.streams, x, y, n));
+                        let weight = self.sampler.weight(x, y, u, v);
+                        pixel_color.add_weighted_alpha(sample.color, sample.alpha, weight);
+                    }
+                    pixel_color
+                }
+            };
+
+            state.framebuffer.set_pixel(x, y, pixel_color);
+            state.aovs.set_pixel(x, y, pixel_aovs);
+        }
+    }
+
+    /// Whether the image should be written after the last completed unit of
+    /// work to show the progress of a progressive render. The final image is
+    /// not included.
+    ///
+    /// Snapshots are only ever due between passes so the image on disk is
+    /// always a complete, if noisy, render, and never when writing to stdout
+    /// so that it holds a single image.
+    pub fn snapshot_due(&self, state: &RenderState) -> bool {
+        match self.progressive {
+            Some(progressive) => {
+                state.completed.is_multiple_of(progressive.write_interval)
+                    && !self.finished(state)
+                    && !self.image.writes_to_stdout()
+            }
+            None => false,
+        }
+    }
+
+    /// Resolves the accumulated state into the image, denoising it if
+    /// enabled.
+    pub fn resolve(&mut self, state: &RenderState) -> &Image {
+        state.framebuffer.write_to(&mut self.image);
+        self.image.samples_per_pixel = match self.progressive {
+            Some(_) => Some(state.completed),
+            None => self.adaptive.is_none().then(|| self.sampler.samples()),
+        };
+        if let Some(denoiser) = self.image.denoiser {
+            denoiser.apply(&mut self.image, &state.aovs);
+        }
+        &self.image
+    }
+
+    /// Resolves the accumulated state and writes the image, to stdout if
+    /// requested, along with its AOVs.
+    pub fn write(&mut self, state: &RenderState) -> std::io::Result<()> {
+        let image = self.resolve(state);
+        if image.writes_to_stdout() {
+            let mut stdout = std::io::stdout().lock();
+            image.write(&mut stdout)?;
+            stdout.flush()?;
+        } else {
+            write_file(&image.filename, |w| image.write(w))?;
+        }
+        state.aovs.write(image)
+    }
+
+    /// Performs every unit of work and returns the resolved image along with
+    /// the statistics of the render.
+    pub fn render(mut self) -> (Image, RenderStats) {
+        let mut state = self.new_state();
+        while !self.finished(&state) {
+            self.render_unit(&mut state);
+        }
+        self.resolve(&state);
+        (self.image, state.stats)
+    }
+}
+
+impl Tracer {
+    /// The radiance along the camera ray through (u, v), from the given
+    /// point of the lens or a random one.
+    fn radiance(&mut self, u: Float, v: Float, lens: Option<(Float, Float)>) -> RadianceSample {
+        let ray = match lens {
+            Some(lens) => self.camera.get_ray_with_lens(u, v, lens),
+            None => self.camera.get_ray(u, v),
+        };
+        stats::count_primary_ray();
+        match self.render_mode {
+            RenderMode::PathTrace => {
+                let sample = sample_color(
+                    ray,
+                    self.background,
+                    &mut self.scene,
+                    &self.lights,
+                    self.max_depth,
+                    self.alpha,
+                );
+                match self.clamp {
+                    Some(clamp) => clamp.apply(sample),
+                    None => sample,
+                }
+            }
+            RenderMode::HeatMap => heat_map_sample(ray, &mut self.scene, &self.heat_map),
+            RenderMode::Ao => ambient_occlusion_sample(ray, &mut self.scene, &self.ao),
+            RenderMode::Depth => depth_sample(ray, &mut self.scene, &self.depth),
+            RenderMode::Bvh => bvh_sample(ray, &mut self.scene, &self.bvh, &self.bvh_colors),
+        }
+    }
+}
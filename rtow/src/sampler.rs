@@ -1,11 +1,138 @@
-use crate::config::SamplerConfig;
+use crate::color::*;
+use crate::config::{AdaptiveConfig, Filter, SampleRegion, SamplerConfig, SamplerKind};
 use crate::image::Image;
+use crate::random::{self, PixelStreams};
+use geometry3d::Float;
+
+/// Generates the (u, v) coordinates of the samples taken for each pixel.
+pub enum Sampler {
+    Square(SquareSampler),
+    Random(RandomSampler),
+}
+
+pub struct SamplerIter<'a> {
+    x: u32,
+    y: u32,
+    sample: u32,
+    samples: u32,
+    sampler: &'a Sampler,
+}
+
+impl Sampler {
+    pub fn new(config: SamplerConfig, image: &Image) -> Self {
+        if let Filter::Gaussian { sigma } = config.filter {
+            assert!(sigma > 0.0 && sigma.is_finite(), "The filter sigma must be positive.");
+        }
+        assert!(
+            config.regions.iter().all(|r| r.samples > 0),
+            "Sample regions must take at least one sample."
+        );
+        match config.kind {
+            SamplerKind::Square => Sampler::Square(SquareSampler::new(config, image)),
+            SamplerKind::Random => Sampler::Random(RandomSampler::new(config, image)),
+        }
+    }
+
+    /// The base number of samples taken per pixel.
+    pub fn samples(&self) -> u32 {
+        match self {
+            Sampler::Square(s) => s.samples(),
+            Sampler::Random(s) => s.samples(),
+        }
+    }
+
+    /// The number of samples taken for the pixel (x, y).
+    pub fn samples_at(&self, x: u32, y: u32) -> u32 {
+        match self {
+            Sampler::Square(s) => s.samples_at(x, y),
+            Sampler::Random(s) => s.samples_at(x, y),
+        }
+    }
+
+    /// The largest number of samples taken for any pixel.
+    pub fn max_samples(&self) -> u32 {
+        match self {
+            Sampler::Square(s) => s.regions.max_samples(s.samples()),
+            Sampler::Random(s) => s.regions.max_samples(s.samples()),
+        }
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        match self {
+            Sampler::Square(s) => s.max_depth,
+            Sampler::Random(s) => s.max_depth,
+        }
+    }
+
+    /// The (u, v) coordinates of the `sample`th sample of the pixel (x, y).
+    pub fn sample(&self, x: u32, y: u32, sample: u32) -> (Float, Float) {
+        match self {
+            Sampler::Square(s) => s.sample(x, y, sample),
+            Sampler::Random(s) => s.sample(x, y, sample),
+        }
+    }
+
+    /// The point of the unit square to sample the camera lens at for the
+    /// `sample`th sample of the pixel (x, y), stratified over the samples of
+    /// the pixel, or None if the pixel takes a single sample.
+    ///
+    /// Square sample counts are split into a grid of strata and others into
+    /// a latin hypercube. The points come from a stream of the pixel apart
+    /// from those of its samples, so the sequence is the same whichever
+    /// sample asks for it.
+    pub fn lens(&self, streams: PixelStreams, x: u32, y: u32, sample: u32) -> Option<(Float, Float)> {
+        let samples = self.samples_at(x, y) as usize;
+        if samples < 2 {
+            return None;
+        }
+        let mut rng = streams.stream(x, y, u32::MAX);
+        let side = (samples as Float).sqrt().round() as usize;
+        if side * side == samples {
+            rng.stratified_2d(side, side).nth(sample as usize)
+        } else {
+            rng.latin_hypercube(samples).nth(sample as usize)
+        }
+    }
+
+    /// The filter weight of the sample (u, v) taken for the pixel (x, y).
+    pub fn weight(&self, x: u32, y: u32, u: Float, v: Float) -> Float {
+        match self {
+            Sampler::Square(s) => s.weight(x, y, u, v),
+            Sampler::Random(s) => s.weight(x, y, u, v),
+        }
+    }
+
+    pub fn iter(&self, x: u32, y: u32) -> SamplerIter<'_> {
+        SamplerIter {
+            x,
+            y,
+            sample: 0,
+            samples: self.samples_at(x, y),
+            sampler: self,
+        }
+    }
+}
+
+impl Iterator for SamplerIter<'_> {
+    type Item = (Float, Float);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.sample == self.samples {
+            None
+        } else {
+            let uv = self.sampler.sample(self.x, self.y, self.sample);
+            self.sample += 1;
+            Some(uv)
+        }
+    }
+}
 
 pub struct SquareSampler {
-    width: u32,
-    height: u32,
+    grid: PixelGrid,
     n: u32,
     n2: u32,
+    filter: Filter,
+    regions: SampleRegions,
     pub max_depth: u32,
 }
 
@@ -13,52 +140,265 @@ pub struct SquareSamplerIter<'a> {
     x: u32,
     y: u32,
     sample: u32,
+    samples: u32,
     sampler: &'a SquareSampler,
 }
 
 impl SquareSampler {
     pub fn new(config: SamplerConfig, image: &Image) -> Self {
         Self {
-            width: image.width,
-            height: image.height,
+            grid: PixelGrid::new(image),
             n: config.n,
             n2: config.n * config.n,
+            filter: config.filter,
+            regions: SampleRegions(config.regions),
             max_depth: config.max_depth,
         }
     }
-    
+
     pub fn samples(&self) -> u32 {
         self.n2
     }
 
-    pub fn iter(&self, x: u32, y: u32) -> SquareSamplerIter {
+    pub fn samples_at(&self, x: u32, y: u32) -> u32 {
+        self.regions.samples_at(x, y, self.n2)
+    }
+
+    /// The (u, v) coordinates of the `sample`th sample of the pixel (x, y).
+    ///
+    /// Pixels in regions whose sample count is not a square number are split
+    /// into the smallest grid that fits the count, leaving the top row of
+    /// cells partially sampled.
+    pub fn sample(&self, x: u32, y: u32, sample: u32) -> (Float, Float) {
+        let n = match self.samples_at(x, y) {
+            samples if samples == self.n2 => self.n,
+            samples => (samples as Float).sqrt().ceil() as u32,
+        };
+
+        let (i, j) = match self.filter {
+            // the positions the box filter has always sampled, all along the
+            // bottom edge of the pixel, where a tent filter has no weight
+            Filter::Box => ((sample % n) as Float / n as Float, (sample / (n * n)) as Float),
+            // the center of each of the n * n cells the pixel is split into
+            _ => (
+                ((sample % n) as Float + 0.5) / n as Float,
+                ((sample / n) as Float + 0.5) / n as Float,
+            ),
+        };
+
+        self.grid.uv(x, y, i, j)
+    }
+
+    /// The filter weight of the sample (u, v) taken for the pixel (x, y).
+    pub fn weight(&self, x: u32, y: u32, u: Float, v: Float) -> Float {
+        let (dx, dy) = self.grid.offset(x, y, u, v);
+        self.filter.weight(dx, dy)
+    }
+
+    pub fn iter(&self, x: u32, y: u32) -> SquareSamplerIter<'_> {
         SquareSamplerIter {
             x,
             y,
             sample: 0,
+            samples: self.samples_at(x, y),
             sampler: self,
         }
     }
 }
 
 impl Iterator for SquareSamplerIter<'_> {
-    type Item = (f64, f64);
+    type Item = (Float, Float);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.sample == self.sampler.n2 {
+        if self.sample == self.samples {
             None
         } else {
-            // this creates a subpixel bias to the bottom left
-            let i = (self.sample % self.sampler.n) as f64 / self.sampler.n as f64;
-            let j = (self.sample / self.sampler.n2) as f64;
-
+            let uv = self.sampler.sample(self.x, self.y, self.sample);
             self.sample += 1;
+            Some(uv)
+        }
+    }
+}
+
+/// Places each sample uniformly at random within the pixel. Takes the same
+/// n * n samples per pixel as the `SquareSampler`.
+pub struct RandomSampler {
+    grid: PixelGrid,
+    n2: u32,
+    filter: Filter,
+    regions: SampleRegions,
+    pub max_depth: u32,
+}
+
+impl RandomSampler {
+    pub fn new(config: SamplerConfig, image: &Image) -> Self {
+        Self {
+            grid: PixelGrid::new(image),
+            n2: config.n * config.n,
+            filter: config.filter,
+            regions: SampleRegions(config.regions),
+            max_depth: config.max_depth,
+        }
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.n2
+    }
+
+    pub fn samples_at(&self, x: u32, y: u32) -> u32 {
+        self.regions.samples_at(x, y, self.n2)
+    }
+
+    /// The filter weight of the sample (u, v) taken for the pixel (x, y).
+    pub fn weight(&self, x: u32, y: u32, u: Float, v: Float) -> Float {
+        let (dx, dy) = self.grid.offset(x, y, u, v);
+        self.filter.weight(dx, dy)
+    }
+
+    /// The (u, v) coordinates of a random sample within the pixel (x, y).
+    pub fn sample(&self, x: u32, y: u32, _sample: u32) -> (Float, Float) {
+        let mut rng = random::rng();
+        self.grid.uv(x, y, rng.random(), rng.random())
+    }
+}
+
+/// Regions of the image that override the base number of samples per pixel.
+struct SampleRegions(Vec<SampleRegion>);
+
+impl SampleRegions {
+    /// The sample count of the last region containing the pixel (x, y), or
+    /// `base` if no region contains it.
+    fn samples_at(&self, x: u32, y: u32, base: u32) -> u32 {
+        self.0
+            .iter()
+            .rev()
+            .find(|r| {
+                (r.x..r.x.saturating_add(r.width)).contains(&x) && (r.y..r.y.saturating_add(r.height)).contains(&y)
+            })
+            .map_or(base, |r| r.samples)
+    }
+
+    fn max_samples(&self, base: u32) -> u32 {
+        self.0.iter().map(|r| r.samples).fold(base, u32::max)
+    }
+}
+
+/// Samples each pixel in batches of `min_samples` randomly placed samples
+/// until the pixel mean has converged to within `tolerance` or `max_samples`
+/// samples have been taken.
+pub struct AdaptiveSampler {
+    grid: PixelGrid,
+    filter: Filter,
+    min_samples: u32,
+    max_samples: u32,
+    tolerance: Float,
+}
+
+impl AdaptiveSampler {
+    pub fn new(config: AdaptiveConfig, filter: Filter, image: &Image) -> Self {
+        assert!(config.min_samples > 0, "min_samples must be at least one.");
+        assert!(
+            config.min_samples <= config.max_samples,
+            "min_samples cannot exceed max_samples."
+        );
+        Self {
+            grid: PixelGrid::new(image),
+            filter,
+            min_samples: config.min_samples,
+            max_samples: config.max_samples,
+            tolerance: config.tolerance,
+        }
+    }
+
+    /// Accumulates the colors and alphas returned by `f` for samples (u, v)
+    /// taken within the pixel (x, y), each from its own stream of random
+    /// numbers.
+    pub fn sample_pixel<F>(&self, streams: PixelStreams, x: u32, y: u32, mut f: F) -> FRgbAccumulator
+    where
+        F: FnMut(Float, Float) -> (FloatRgb, Float),
+    {
+        let mut accumulator = FRgbAccumulator::new();
+
+        while accumulator.count() < self.max_samples {
+            let batch = self.min_samples.min(self.max_samples - accumulator.count());
+            for _ in 0..batch {
+                streams.restart(x, y, accumulator.count());
+                let mut rng = random::rng();
+                let (u, v) = self.grid.uv(x, y, rng.random(), rng.random());
+                let (dx, dy) = self.grid.offset(x, y, u, v);
+                let (color, alpha) = f(u, v);
+                accumulator.add_weighted_alpha(color, alpha, self.filter.weight(dx, dy));
+            }
+
+            if accumulator.converged(self.tolerance) {
+                break;
+            }
+        }
+
+        accumulator
+    }
+}
+
+impl Filter {
+    /// The weight of a sample offset from the pixel center by (dx, dy) pixels,
+    /// where both offsets lie in [-0.5, 0.5].
+    pub fn weight(self, dx: Float, dy: Float) -> Float {
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => {
+                let wx = (1.0 - 2.0 * dx.abs()).max(0.0);
+                let wy = (1.0 - 2.0 * dy.abs()).max(0.0);
+                wx * wy
+            }
+            Filter::Gaussian { sigma } => {
+                let r2 = dx * dx + dy * dy;
+                (-r2 / (2.0 * sigma * sigma)).exp()
+            }
+        }
+    }
+}
+
+/// Maps between pixels, addressed from the top left of the image, and camera
+/// (u, v) coordinates, which run from the bottom left of the view unless the
+/// image is flipped vertically.
+#[derive(Debug, Clone, Copy)]
+struct PixelGrid {
+    width: u32,
+    height: u32,
+    flip_y: bool,
+}
 
-            // this creates another small bias
-            let u = (self.x as f64 + i) / self.sampler.width as f64;
-            let v = (self.y as f64 + j) / self.sampler.height as f64;
+impl PixelGrid {
+    fn new(image: &Image) -> Self {
+        Self {
+            width: image.width,
+            height: image.height,
+            flip_y: image.flip_y,
+        }
+    }
 
-            Some((u, v))
+    /// The row of the pixel counted from the bottom of the camera's view.
+    fn row(self, y: u32) -> u32 {
+        if self.flip_y {
+            y
+        } else {
+            self.height - 1 - y
         }
     }
+
+    /// The (u, v) coordinates of the point (i, j) within the pixel (x, y),
+    /// where i and j lie in [0, 1) along u and v respectively.
+    fn uv(self, x: u32, y: u32, i: Float, j: Float) -> (Float, Float) {
+        let u = (x as Float + i) / self.width as Float;
+        let v = (self.row(y) as Float + j) / self.height as Float;
+        (u, v)
+    }
+
+    /// The offset in pixels of the sample (u, v) from the center of the pixel
+    /// (x, y), along u and v respectively.
+    fn offset(self, x: u32, y: u32, u: Float, v: Float) -> (Float, Float) {
+        let dx = u * self.width as Float - x as Float - 0.5;
+        let dy = v * self.height as Float - self.row(y) as Float - 0.5;
+        (dx, dy)
+    }
 }
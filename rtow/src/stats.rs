@@ -0,0 +1,153 @@
+use crate::hit_record::HitContext;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "stats")]
+use std::cell::RefCell;
+use std::fmt;
+use std::time::Duration;
+
+/// Counts of the work done over a render.
+///
+/// The counters are kept per thread while rendering and taken at the end of
+/// each unit of work. Without the `stats` feature nothing is counted and
+/// every counter stays at zero.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RenderStats {
+    /// The number of rays cast from the camera.
+    pub primary_rays: u64,
+    /// The number of rays cast into the scene, including primary rays,
+    /// bounces, shadow rays and ambient occlusion rays.
+    pub rays: u64,
+    /// The number of bounding boxes rays were tested against.
+    pub aabb_tests: u64,
+    /// The number of nodes of the BVH whose children's boxes rays were
+    /// tested against.
+    pub node_tests: u64,
+    /// The number of paths that hit `i` surfaces before ending, at index `i`.
+    pub path_lengths: Vec<u64>,
+    /// The time spent rendering.
+    pub elapsed: Duration,
+}
+
+#[cfg(feature = "stats")]
+thread_local! {
+    static COUNTERS: RefCell<RenderStats> = RefCell::new(RenderStats::default());
+}
+
+/// Counts a ray cast from the camera.
+#[inline]
+pub fn count_primary_ray() {
+    #[cfg(feature = "stats")]
+    COUNTERS.with(|stats| stats.borrow_mut().primary_rays += 1);
+}
+
+/// Counts a ray cast into the scene along with the bounding boxes it was
+/// tested against.
+#[inline]
+#[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+pub fn count_ray(ctx: &HitContext) {
+    #[cfg(feature = "stats")]
+    COUNTERS.with(|stats| {
+        let mut stats = stats.borrow_mut();
+        stats.rays += 1;
+        stats.aabb_tests += ctx.aabb_tests as u64;
+        stats.node_tests += ctx.node_tests as u64;
+    });
+}
+
+/// Counts a path that hit `length` surfaces before ending.
+#[inline]
+#[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+pub fn count_path(length: u32) {
+    #[cfg(feature = "stats")]
+    COUNTERS.with(|stats| {
+        let lengths = &mut stats.borrow_mut().path_lengths;
+        let i = length as usize;
+        if lengths.len() <= i {
+            lengths.resize(i + 1, 0);
+        }
+        lengths[i] += 1;
+    });
+}
+
+/// Takes the counts of the current thread, leaving them at zero.
+#[cfg(feature = "stats")]
+pub fn take() -> RenderStats {
+    COUNTERS.with(|stats| stats.take())
+}
+
+#[cfg(not(feature = "stats"))]
+pub fn take() -> RenderStats {
+    RenderStats::default()
+}
+
+impl RenderStats {
+    /// Adds the counts of `other` to these.
+    pub fn merge(&mut self, other: &RenderStats) {
+        self.primary_rays += other.primary_rays;
+        self.rays += other.rays;
+        self.aabb_tests += other.aabb_tests;
+        self.node_tests += other.node_tests;
+        if self.path_lengths.len() < other.path_lengths.len() {
+            self.path_lengths.resize(other.path_lengths.len(), 0);
+        }
+        for (count, other) in self.path_lengths.iter_mut().zip(&other.path_lengths) {
+            *count += other;
+        }
+        self.elapsed += other.elapsed;
+    }
+
+    pub fn paths(&self) -> u64 {
+        self.path_lengths.iter().sum()
+    }
+
+    /// The mean number of surfaces hit by a path, or None if no paths were
+    /// traced.
+    pub fn average_path_length(&self) -> Option<f64> {
+        let weighted: u64 = self
+            .path_lengths
+            .iter()
+            .enumerate()
+            .map(|(length, &count)| length as u64 * count)
+            .sum();
+        match self.paths() {
+            0 => None,
+            paths => Some(weighted as f64 / paths as f64),
+        }
+    }
+
+    /// The most surfaces hit by any path, or None if no paths were traced.
+    pub fn max_path_length(&self) -> Option<u32> {
+        self.path_lengths.iter().rposition(|&count| count > 0).map(|i| i as u32)
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.rays as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Primary rays: {}", self.primary_rays)?;
+        writeln!(
+            f,
+            "Rays: {} ({:.0} per second over {:.1}s)",
+            self.rays,
+            self.rays_per_second(),
+            self.elapsed.as_secs_f64()
+        )?;
+        write!(f, "Bounding box tests: {} in {} nodes", self.aabb_tests, self.node_tests)?;
+        if let (Some(average), Some(max)) = (self.average_path_length(), self.max_path_length()) {
+            write!(f, "\nPath length: {average:.2} average, {max} max")?;
+            for (length, count) in self.path_lengths.iter().enumerate() {
+                let percent = 100.0 * *count as f64 / self.paths() as f64;
+                write!(f, "\n{length:>4}: {count} ({percent:.1}%)")?;
+            }
+        }
+        Ok(())
+    }
+}
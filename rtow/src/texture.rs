@@ -23,6 +23,18 @@ impl Texture {
             Texture::ImageTexture(t) => t.value(rec),
         }
     }
+
+    /// Calls `f` on the file name of each image texture not yet loaded.
+    pub fn for_each_path(&mut self, f: &mut dyn FnMut(&mut String)) {
+        match self {
+            Texture::CheckerTexture(t) => {
+                t.odd.for_each_path(f);
+                t.even.for_each_path(f);
+            }
+            Texture::ImageTexture(ImageTexture::U(u)) => f(&mut u.filename),
+            _ => (),
+        }
+    }
 }
 
 impl From<FloatRgb> for Texture {
@@ -32,11 +44,26 @@ impl From<FloatRgb> for Texture {
     }
 }
 
+macro_rules! texture_from {
+    ( $name:ident ) => {
+        impl From<$name> for Texture {
+            fn from(t: $name) -> Texture {
+                Texture::$name(t)
+            }
+        }
+    };
+}
+
+texture_from!(SolidColor);
+texture_from!(CheckerTexture);
+texture_from!(NoiseTexture);
+texture_from!(ImageTexture);
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct SolidColor(FloatRgb);
 
 impl SolidColor {
-    pub fn new(r: f64, g: f64, b: f64) -> SolidColor {
+    pub fn new(r: Float, g: Float, b: Float) -> SolidColor {
         SolidColor(FloatRgb::new(r, g, b))
     }
 
@@ -78,12 +105,12 @@ impl CheckerTexture {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NoiseTexture {
     noise: Perlin,
-    scale: f64,
+    scale: Float,
     depth: usize,
 }
 
 impl NoiseTexture {
-    pub fn new(noise: Perlin, scale: f64, depth: usize) -> NoiseTexture {
+    pub fn new(noise: Perlin, scale: Float, depth: usize) -> NoiseTexture {
         NoiseTexture {
             noise,
             scale,
@@ -97,7 +124,7 @@ impl NoiseTexture {
         let point = Point3::from(self.scale * Vec3::from(rec.point));
         let noise = 0.5
             * (1.0
-                + f64::sin(
+                + Float::sin(
                     self.scale * point.z() + 10.0 * self.noise.turbulence(point, self.depth),
                 ));
         white.mix(black, noise)
@@ -123,6 +150,20 @@ pub struct ImageTextureInit {
     data: Vec<u8>,
 }
 
+/// The size of an image texture, read without decoding it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureHeader {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TextureHeader {
+    /// The bytes taken by the texture once it is decoded.
+    pub fn decoded_size(&self) -> u64 {
+        self.width as u64 * self.height as u64 * ImageTexture::BYTES_PER_PIXEL as u64
+    }
+}
+
 impl ImageTexture {
     const BYTES_PER_PIXEL: usize = 3;
 
@@ -143,6 +184,37 @@ impl ImageTexture {
         }
     }
 
+    /// Reads the header of the PNG `filename`, without decoding the image,
+    /// and checks that it can be used as a texture.
+    pub fn probe(filename: &str) -> Result<TextureHeader, String> {
+        let file = File::open(filename).map_err(|e| format!("Failed to open {filename}: {e}"))?;
+        let reader = png::Decoder::new(file)
+            .read_info()
+            .map_err(|e| format!("Failed to read info in {filename}: {e}"))?;
+        let info = reader.info();
+        Self::check(info, filename)?;
+        Ok(TextureHeader {
+            width: info.width,
+            height: info.height,
+        })
+    }
+
+    /// Checks that the image is an 8-bit RGB PNG that can be sampled
+    /// directly.
+    fn check(info: &png::Info, filename: &str) -> Result<(), String> {
+        if info.is_animated() {
+            Err(format!("{filename} cannot be an APNG."))
+        } else if info.bit_depth != png::BitDepth::Eight {
+            Err(format!("The bit depth of {filename} is not eight."))
+        } else if info.color_type != png::ColorType::Rgb {
+            Err(format!("The color type of {filename} is not RGB."))
+        } else if info.interlaced {
+            Err(format!("{filename} cannot be interlaced."))
+        } else {
+            Ok(())
+        }
+    }
+
     fn init(u: &ImageTextureUninit) -> Option<ImageTextureInit> {
         let file = File::open(&u.filename);
         if let Ok(file) = file {
@@ -152,21 +224,9 @@ impl ImageTexture {
                 .read_info()
                 .unwrap_or_else(|_| panic!("Failed to read info in {}", &u.filename));
 
-            let info = reader.info();
-            assert!(!info.is_animated(), "{} cannot be an APNG.", &u.filename);
-            assert_eq!(
-                info.bit_depth,
-                png::BitDepth::Eight,
-                "The bit depth of {} is not eight.",
-                &u.filename
-            );
-            assert_eq!(
-                info.color_type,
-                png::ColorType::Rgb,
-                "The color type of {} is not RGB.",
-                &u.filename
-            );
-            assert!(!info.interlaced, "{} cannot be interlaced.", &u.filename);
+            if let Err(e) = Self::check(reader.info(), &u.filename) {
+                panic!("{e}");
+            }
 
             let mut data = vec![0; reader.output_buffer_size()];
             let output_info = reader
@@ -184,23 +244,26 @@ impl ImageTexture {
     }
 
     fn value_calc(s: &Option<ImageTextureInit>, rec: HitRecord) -> FloatRgb {
-        const COLOR_SCALE: f64 = 1.0 / 255.0;
+        const COLOR_SCALE: Float = 1.0 / 255.0;
 
-        if let Some(it) = s {
+        let pixel = s.as_ref().and_then(|it| {
             let u = rec.u.clamp(0.0, 1.0);
             let v = 1.0 - rec.v.clamp(0.0, 1.0);
 
-            let i = ((u * (it.width as f64)) as usize).clamp(0, it.width - 1);
-            let j = ((v * (it.height as f64)) as usize).clamp(0, it.height - 1);
+            let i = ((u * (it.width as Float)) as usize).clamp(0, it.width - 1);
+            let j = ((v * (it.height as Float)) as usize).clamp(0, it.height - 1);
 
             let start = j * it.bytes_per_row + i * Self::BYTES_PER_PIXEL;
             let stop = start + Self::BYTES_PER_PIXEL;
 
-            let color_tuple = NTuple::from(&it.data[start..stop]).map(|x| COLOR_SCALE * (x as f64));
-            FloatRgb::from(color_tuple)
-        } else {
-            // Empty image textures rendered as cyan
-            FloatRgb::new(0.0, 1.0, 1.0)
+            // pixels past the end of truncated data are missing
+            it.data.get(start..stop).and_then(|bytes| NTuple::<u8, 3>::try_from(bytes).ok())
+        });
+
+        match pixel {
+            Some(pixel) => FloatRgb::from(pixel.map(|x| COLOR_SCALE * (x as Float))),
+            // Empty image textures and missing pixels rendered as cyan
+            None => FloatRgb::new(0.0, 1.0, 1.0),
         }
     }
 }
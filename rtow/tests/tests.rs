@@ -0,0 +1,5147 @@
+#[cfg(test)]
+mod color_tests {
+    use geometry3d::{assert_approx_eq, Float};
+    use rtow::color::*;
+
+    #[test]
+    fn interpolate_colors() {
+        let red = FloatRgb::new(1.0, 0.0, 0.0);
+        let green = FloatRgb::new(0.0, 1.0, 0.0);
+        let blue = FloatRgb::new(0.0, 0.0, 1.0);
+        assert_eq!(red * 0.5, FloatRgb::new(0.5, 0.0, 0.0));
+        let mixed = geometry3d::interpolate((0.5, 0.25, 0.25), red, green, blue);
+        assert_approx_eq!(mixed, FloatRgb::new(0.5, 0.25, 0.25));
+    }
+
+    #[test]
+    fn srgb_reference_values() {
+        let srgb = |x| Rgb::from_linear(FloatRgb::new(x, x, x), Gamma::Srgb).r();
+        assert_eq!(srgb(0.0), 0);
+        assert_eq!(srgb(0.001), 3);
+        assert_eq!(srgb(0.18), 118);
+        assert_eq!(srgb(0.214), 127);
+        assert_eq!(srgb(0.5), 188);
+        assert_eq!(srgb(1.0), 255);
+        assert_eq!(srgb(4.0), 255);
+    }
+
+    #[test]
+    fn gamma_two_is_square_root() {
+        for i in 0..=1000 {
+            let x = i as Float / 1000.0;
+            let expected = (x.sqrt() * (256.0 - Float::EPSILON)) as u8;
+            let rgb = Rgb::from_linear(FloatRgb::new(x, x, x), Gamma::Power(2.0));
+            assert_eq!(rgb.r(), expected);
+        }
+    }
+
+    #[test]
+    fn gamma_from_config() {
+        let gamma: Gamma = serde_json::from_str(r#""srgb""#).unwrap();
+        assert_eq!(gamma, Gamma::Srgb);
+        let gamma: Gamma = serde_json::from_str("2.2").unwrap();
+        assert_eq!(gamma, Gamma::Power(2.2));
+        assert!(serde_json::from_str::<Gamma>(r#""linear""#).is_err());
+        assert!(serde_json::from_str::<Gamma>("0.0").is_err());
+    }
+
+    #[test]
+    fn exposure_stops_are_powers_of_two() {
+        let c = FloatRgb::new(0.25, 0.5, 1.0);
+        assert_eq!(c.expose(0.0), c);
+        assert_eq!(c.expose(1.0), FloatRgb::new(0.5, 1.0, 2.0));
+        assert_eq!(c.expose(-2.0), FloatRgb::new(0.0625, 0.125, 0.25));
+    }
+
+    #[test]
+    fn luminance_of_white_is_one() {
+        assert_approx_eq!(FloatRgb::new(1.0, 1.0, 1.0).luminance(), 1.0, 1e-12);
+        assert!(FloatRgb::new(0.0, 1.0, 0.0).luminance() > FloatRgb::new(1.0, 0.0, 0.0).luminance());
+    }
+
+    #[test]
+    fn log_average_is_geometric_mean() {
+        let grey = |x| FloatRgb::new(x, x, x);
+        let average = log_average_luminance([grey(0.1), grey(1.0), grey(10.0)]);
+        assert_approx_eq!(average, 1.0, abs = 1e-3, rel = 0.0);
+        assert_eq!(log_average_luminance([]), 0.0);
+    }
+}
+
+#[cfg(test)]
+mod sampler_tests {
+    use geometry3d::Float;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::image::Image;
+    use rtow::random::PixelStreams;
+    use rtow::sampler::*;
+
+    fn image(width: u32, height: u32) -> Image {
+        Image::new(ImageConfig {
+            filename: String::from("test.png"),
+            width,
+            height: Some(height),
+            aspect_ratio: None,
+            tile_size: 32,
+            format: None,
+            alpha: false,
+            gamma: Gamma::Srgb,
+            flip_y: false,
+            aovs: Vec::new(),
+            denoise: false,
+            denoise_strength: 1.0,
+            denoise_iterations: 5,
+            exposure: 0.0,
+            auto_exposure: false,
+            output_relative_to_scene: false,
+        })
+    }
+
+    fn sampler_config(kind: SamplerKind, n: u32) -> SamplerConfig {
+        SamplerConfig {
+            kind,
+            n,
+            max_depth: 1,
+            filter: Filter::Box,
+            adaptive: None,
+            regions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn square_sample_count() {
+        let sampler = Sampler::new(sampler_config(SamplerKind::Square, 3), &image(4, 4));
+        assert_eq!(sampler.samples(), 9);
+        assert_eq!(sampler.iter(1, 1).count(), 9);
+    }
+
+    #[test]
+    fn random_sample_count() {
+        let sampler = Sampler::new(sampler_config(SamplerKind::Random, 3), &image(4, 4));
+        assert_eq!(sampler.samples(), 9);
+        assert_eq!(sampler.iter(1, 1).count(), 9);
+    }
+
+    #[test]
+    fn random_samples_lie_within_pixel() {
+        let sampler = Sampler::new(sampler_config(SamplerKind::Random, 4), &image(4, 2));
+        for (u, v) in sampler.iter(2, 0) {
+            assert!((0.5..0.75).contains(&u));
+            assert!((0.5..1.0).contains(&v));
+        }
+    }
+
+    #[test]
+    fn lens_samples_are_stratified_over_the_pixel() {
+        let sampler = Sampler::new(sampler_config(SamplerKind::Random, 3), &image(4, 4));
+        let streams = PixelStreams::new(7, RngKind::Pcg32);
+        let mut strata = [[0; 3]; 3];
+        for sample in 0..9 {
+            let (u, v) = sampler.lens(streams, 1, 2, sample).unwrap();
+            strata[(u * 3.0) as usize][(v * 3.0) as usize] += 1;
+            assert_eq!(sampler.lens(streams, 1, 2, sample), Some((u, v)));
+        }
+        assert_eq!(strata, [[1; 3]; 3]);
+        assert_ne!(sampler.lens(streams, 1, 2, 0), sampler.lens(streams, 2, 1, 0));
+
+        let single = Sampler::new(sampler_config(SamplerKind::Random, 1), &image(4, 4));
+        assert_eq!(single.lens(streams, 1, 2, 0), None);
+    }
+
+    #[test]
+    fn sampler_type_defaults_to_square() {
+        let config: SamplerConfig = serde_json::from_str(r#"{"n": 2, "max_depth": 5}"#).unwrap();
+        assert_eq!(config.kind, SamplerKind::Square);
+
+        let config: SamplerConfig =
+            serde_json::from_str(r#"{"type": "random", "n": 2, "max_depth": 5}"#).unwrap();
+        assert_eq!(config.kind, SamplerKind::Random);
+
+        let config: SamplerConfig =
+            serde_json::from_str(r#"{"type": "stratified", "n": 2, "max_depth": 5}"#).unwrap();
+        assert_eq!(config.kind, SamplerKind::Square);
+    }
+
+    #[test]
+    fn region_overrides_sample_count() {
+        let mut config = sampler_config(SamplerKind::Square, 2);
+        config.regions = vec![
+            SampleRegion { x: 1, y: 1, width: 2, height: 2, samples: 9 },
+            SampleRegion { x: 2, y: 2, width: 2, height: 2, samples: 5 },
+        ];
+        let sampler = Sampler::new(config, &image(4, 4));
+
+        assert_eq!(sampler.samples_at(0, 0), 4);
+        assert_eq!(sampler.iter(0, 0).count(), 4);
+        assert_eq!(sampler.samples_at(1, 1), 9);
+        assert_eq!(sampler.iter(1, 1).count(), 9);
+        // the last matching region wins
+        assert_eq!(sampler.samples_at(2, 2), 5);
+        assert_eq!(sampler.iter(2, 2).count(), 5);
+        assert_eq!(sampler.max_samples(), 9);
+
+        // pixel rows count down from the top but v counts up from the bottom
+        for (u, v) in sampler.iter(2, 2) {
+            assert!((0.5..0.75).contains(&u));
+            assert!((0.25..0.5).contains(&v));
+        }
+    }
+
+    #[test]
+    fn square_sampler_iterates_region_sample_count() {
+        let mut config = sampler_config(SamplerKind::Square, 2);
+        config.regions = vec![SampleRegion { x: 1, y: 1, width: 2, height: 2, samples: 9 }];
+        let sampler = SquareSampler::new(config, &image(4, 4));
+        assert_eq!(sampler.iter(0, 0).count(), 4);
+        assert_eq!(sampler.iter(1, 1).count(), 9);
+    }
+
+    #[test]
+    fn regions_past_the_last_pixel_are_clipped() {
+        let mut config = sampler_config(SamplerKind::Square, 2);
+        config.regions = vec![SampleRegion { x: 1, y: 2, width: u32::MAX, height: u32::MAX, samples: 9 }];
+        let sampler = Sampler::new(config, &image(4, 4));
+        assert_eq!(sampler.samples_at(0, 3), 4);
+        assert_eq!(sampler.samples_at(3, 3), 9);
+    }
+
+    #[test]
+    fn flip_y_maps_top_row_to_bottom_of_view() {
+        let mut flipped = image(4, 4);
+        flipped.flip_y = true;
+        let config = sampler_config(SamplerKind::Square, 1);
+
+        let sampler = Sampler::new(config.clone(), &image(4, 4));
+        assert_eq!(sampler.sample(0, 0, 0), (0.0, 0.75));
+        assert_eq!(sampler.sample(1, 3, 0), (0.25, 0.0));
+
+        let sampler = Sampler::new(config, &flipped);
+        assert_eq!(sampler.sample(0, 0, 0), (0.0, 0.0));
+        assert_eq!(sampler.sample(1, 3, 0), (0.25, 0.75));
+    }
+
+    #[test]
+    fn box_filter_matches_unweighted_average() {
+        let mut config = sampler_config(SamplerKind::Square, 4);
+        config.filter = Filter::Box;
+        let sampler = Sampler::new(config, &image(4, 4));
+        let scene = |u: Float, v: Float| FloatRgb::new(u, v, u * v);
+
+        // the positions sampled before samples were weighted, along the
+        // bottom edge of the pixel, which lies on the bottom of the image
+        let baseline = (0..16).map(|sample| ((2.0 + (sample % 4) as Float / 4.0) / 4.0, 0.0));
+        assert!(sampler.iter(2, 3).eq(baseline.clone()));
+
+        let mut weighted = FRgbAccumulator::new();
+        for (u, v) in sampler.iter(2, 3) {
+            weighted.add_weighted(scene(u, v), sampler.weight(2, 3, u, v));
+        }
+        let mut unweighted = FRgbAccumulator::new();
+        for (u, v) in baseline {
+            unweighted += scene(u, v);
+        }
+        assert_eq!(weighted.average(), unweighted.average());
+    }
+
+    #[test]
+    fn tent_filter_ignores_pixel_corners() {
+        assert!(Filter::Tent.weight(-0.5, -0.5) < 1e-9);
+        assert!(Filter::Tent.weight(0.5, 0.5) < 1e-9);
+        assert_eq!(Filter::Tent.weight(0.0, 0.0), 1.0);
+
+        let mut accumulator = FRgbAccumulator::new();
+        let black = FloatRgb::new(0.0, 0.0, 0.0);
+        let white = FloatRgb::new(1.0, 1.0, 1.0);
+        accumulator.add_weighted(black, Filter::Tent.weight(0.0, 0.0));
+        accumulator.add_weighted(white, Filter::Tent.weight(-0.5, 0.5));
+        assert!(accumulator.average().r() < 1e-9);
+    }
+
+    #[test]
+    fn gaussian_filter_falls_off_from_center() {
+        let filter = Filter::Gaussian { sigma: 0.25 };
+        assert_eq!(filter.weight(0.0, 0.0), 1.0);
+        assert!(filter.weight(0.25, 0.0) < filter.weight(0.1, 0.0));
+        assert!(filter.weight(0.5, 0.5) < 0.1);
+    }
+
+    #[test]
+    fn adaptive_constant_color_stops_at_min_samples() {
+        let config = AdaptiveConfig {
+            min_samples: 16,
+            max_samples: 256,
+            tolerance: 0.01,
+        };
+        let sampler = AdaptiveSampler::new(config, Filter::Box, &image(4, 4));
+        let color = FloatRgb::new(0.25, 0.5, 0.75);
+
+        let accumulator = sampler.sample_pixel(PixelStreams::new(0, RngKind::Std), 1, 2, |_, _| (color, 1.0));
+        assert_eq!(accumulator.count(), 16);
+        assert_eq!(accumulator.average(), color);
+    }
+
+    #[test]
+    fn adaptive_noisy_color_stops_at_max_samples() {
+        let config = AdaptiveConfig {
+            min_samples: 4,
+            max_samples: 64,
+            tolerance: 1e-6,
+        };
+        let sampler = AdaptiveSampler::new(config, Filter::Box, &image(4, 4));
+        let mut odd = false;
+
+        let accumulator = sampler.sample_pixel(PixelStreams::new(0, RngKind::Std), 0, 0, |_, _| {
+            odd = !odd;
+            if odd {
+                (FloatRgb::new(1.0, 1.0, 1.0), 1.0)
+            } else {
+                (FloatRgb::new(0.0, 0.0, 0.0), 1.0)
+            }
+        });
+        assert_eq!(accumulator.count(), 64);
+    }
+}
+
+#[cfg(test)]
+mod framebuffer_tests {
+    use geometry3d::Float;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::framebuffer::Framebuffer;
+    use rtow::image::Image;
+    use rtow::sampler::*;
+
+    #[test]
+    fn two_passes_equal_two_samples() {
+        let image = Image::new(ImageConfig {
+            filename: String::from("test.png"),
+            width: 3,
+            height: Some(2),
+            aspect_ratio: None,
+            tile_size: 32,
+            format: None,
+            alpha: false,
+            gamma: Gamma::Srgb,
+            flip_y: false,
+            aovs: Vec::new(),
+            denoise: false,
+            denoise_strength: 1.0,
+            denoise_iterations: 5,
+            exposure: 0.0,
+            auto_exposure: false,
+            output_relative_to_scene: false,
+        });
+        let config = SamplerConfig {
+            kind: SamplerKind::Square,
+            n: 2,
+            max_depth: 1,
+            filter: Filter::Box,
+            adaptive: None,
+            regions: Vec::new(),
+        };
+        let sampler = SquareSampler::new(config, &image);
+        let scene = |u: Float, v: Float| FloatRgb::new(u, v, u * v);
+
+        let mut framebuffer = Framebuffer::new(image.width, image.height);
+        for pass in 0..2 {
+            framebuffer.add_pass(|x, y| {
+                let (u, v) = sampler.sample(x, y, pass);
+                Some((scene(u, v), 1.0, 1.0))
+            });
+        }
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let mut accumulator = FRgbAccumulator::new();
+                for (u, v) in sampler.iter(x, y).take(2) {
+                    accumulator += scene(u, v);
+                }
+                assert_eq!(framebuffer.pixel(x, y), accumulator);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod image_tests {
+    use geometry3d::{assert_approx_eq, Float};
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::image::*;
+
+    fn image(width: u32, height: u32, tile_size: u32) -> Image {
+        Image::new(ImageConfig {
+            filename: String::from("test.png"),
+            width,
+            height: Some(height),
+            aspect_ratio: None,
+            tile_size,
+            format: None,
+            alpha: false,
+            gamma: Gamma::Srgb,
+            flip_y: false,
+            aovs: Vec::new(),
+            denoise: false,
+            denoise_strength: 1.0,
+            denoise_iterations: 5,
+            exposure: 0.0,
+            auto_exposure: false,
+            output_relative_to_scene: false,
+        })
+    }
+
+    fn assert_tiles_cover_image(image: &Image) {
+        let mut covered = vec![0; (image.width * image.height) as usize];
+        for tile in image.tiles() {
+            assert!(tile.x + tile.width <= image.width);
+            assert!(tile.y + tile.height <= image.height);
+            for (x, y) in tile.iter() {
+                covered[(y * image.width + x) as usize] += 1;
+            }
+        }
+        assert!(covered.iter().all(|&c| c == 1));
+    }
+
+    #[test]
+    fn tiles_cover_image_exactly_once() {
+        assert_tiles_cover_image(&image(64, 64, 32));
+        assert_tiles_cover_image(&image(70, 45, 32));
+        assert_tiles_cover_image(&image(5, 3, 8));
+        assert_tiles_cover_image(&image(7, 11, 1));
+    }
+
+    /// A linear color that is gamma corrected to the given byte.
+    fn linear(byte: u8) -> Float {
+        ((byte as Float + 0.5) / 256.0).powi(2)
+    }
+
+    fn tiny_image(format: ImageFormat) -> Image {
+        let mut image = Image::new(ImageConfig {
+            filename: String::from("tiny.ppm"),
+            width: 2,
+            height: Some(2),
+            aspect_ratio: None,
+            tile_size: 32,
+            format: Some(format),
+            alpha: false,
+            gamma: Gamma::Power(2.0),
+            flip_y: false,
+            aovs: Vec::new(),
+            denoise: false,
+            denoise_strength: 1.0,
+            denoise_iterations: 5,
+            exposure: 0.0,
+            auto_exposure: false,
+            output_relative_to_scene: false,
+        });
+        image.set_pixel(0, 0, FloatRgb::new(1.0, 0.0, 0.0));
+        image.set_pixel(1, 0, FloatRgb::new(0.0, 1.0, 0.0));
+        image.set_pixel(0, 1, FloatRgb::new(0.0, 0.0, 1.0));
+        image.set_pixel(1, 1, FloatRgb::new(linear(10), linear(128), 1.0));
+        image
+    }
+
+    #[test]
+    fn encode_p6() {
+        let mut bytes = Vec::new();
+        tiny_image(ImageFormat::P6).write(&mut bytes).unwrap();
+        assert_eq!(bytes, include_bytes!("golden/tiny_p6.ppm"));
+    }
+
+    #[test]
+    fn encode_p3() {
+        let mut bytes = Vec::new();
+        tiny_image(ImageFormat::P3).write(&mut bytes).unwrap();
+        assert_eq!(bytes, include_bytes!("golden/tiny_p3.ppm"));
+    }
+
+    #[test]
+    fn exr_preserves_high_dynamic_range() {
+        let path = std::env::temp_dir().join("rtow_exr_round_trip.exr");
+        let mut image = Image::new(ImageConfig {
+            filename: path.to_str().unwrap().to_string(),
+            width: 2,
+            height: Some(1),
+            aspect_ratio: None,
+            tile_size: 32,
+            format: None,
+            alpha: false,
+            gamma: Gamma::Srgb,
+            flip_y: false,
+            aovs: Vec::new(),
+            denoise: false,
+            denoise_strength: 1.0,
+            denoise_iterations: 5,
+            exposure: 0.0,
+            auto_exposure: false,
+            output_relative_to_scene: false,
+        });
+        image.set_pixel(0, 0, FloatRgb::new(15.0, 2.5, 0.125));
+        image.set_pixel(1, 0, FloatRgb::new(0.0, 1.0, 100.0));
+        write_file(&image.filename, |w| image.write(w)).unwrap();
+
+        let read = exr::prelude::read_first_rgba_layer_from_file(
+            &path,
+            |resolution, _| vec![(0.0, 0.0, 0.0); resolution.width() * resolution.height()],
+            |pixels: &mut Vec<(f32, f32, f32)>, position, (r, g, b, _a): (f32, f32, f32, f32)| {
+                pixels[position.y() * 2 + position.x()] = (r, g, b);
+            },
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let pixels = read.layer_data.channel_data.pixels;
+        assert_eq!(pixels, vec![(15.0, 2.5, 0.125), (0.0, 1.0, 100.0)]);
+    }
+
+    #[test]
+    fn format_inferred_from_extension() {
+        let config = |filename: &str| ImageConfig {
+            filename: String::from(filename),
+            width: 1,
+            height: Some(1),
+            aspect_ratio: None,
+            tile_size: 32,
+            format: None,
+            alpha: false,
+            gamma: Gamma::Srgb,
+            flip_y: false,
+            aovs: Vec::new(),
+            denoise: false,
+            denoise_strength: 1.0,
+            denoise_iterations: 5,
+            exposure: 0.0,
+            auto_exposure: false,
+            output_relative_to_scene: false,
+        };
+        assert_eq!(Image::new(config("out.png")).format, ImageFormat::Png);
+        assert_eq!(Image::new(config("out.ppm")).format, ImageFormat::P6);
+        assert_eq!(Image::new(config("out.PPM")).format, ImageFormat::P6);
+        assert_eq!(Image::new(config("out.exr")).format, ImageFormat::Exr);
+        assert_eq!(Image::new(config("out")).format, ImageFormat::Png);
+        assert_eq!(Image::new(config("-")).format, ImageFormat::Png);
+    }
+
+    #[test]
+    fn edge_tiles_are_clipped() {
+        let tiles: Vec<Tile> = image(40, 40, 32).tiles().collect();
+        assert_eq!(tiles.len(), 4);
+        assert_eq!(tiles[0], Tile { x: 0, y: 0, width: 32, height: 32 });
+        assert_eq!(tiles[1], Tile { x: 32, y: 0, width: 8, height: 32 });
+        assert_eq!(tiles[2], Tile { x: 0, y: 32, width: 32, height: 8 });
+        assert_eq!(tiles[3], Tile { x: 32, y: 32, width: 8, height: 8 });
+    }
+
+    #[test]
+    fn pixels_are_stored_from_the_top_left() {
+        // top half white and bottom half black, in a 3 by 4 image
+        let mut image = Image::new(ImageConfig {
+            filename: String::from("two_tone.ppm"),
+            width: 3,
+            height: Some(4),
+            aspect_ratio: None,
+            tile_size: 32,
+            format: None,
+            alpha: false,
+            gamma: Gamma::Srgb,
+            flip_y: false,
+            aovs: Vec::new(),
+            denoise: false,
+            denoise_strength: 1.0,
+            denoise_iterations: 5,
+            exposure: 0.0,
+            auto_exposure: false,
+            output_relative_to_scene: false,
+        });
+        for (x, y) in image.iter() {
+            let c = if y < 2 { 1.0 } else { 0.0 };
+            image.set_pixel(x, y, FloatRgb::new(c, c, c));
+        }
+        let mut bytes = Vec::new();
+        image.write(&mut bytes).unwrap();
+
+        let header = b"P6\n3 4\n255\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let data = &bytes[header.len()..];
+        assert_eq!(data.len(), 3 * 4 * 3);
+        assert!(data[..18].iter().all(|&b| b == 255));
+        assert!(data[18..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn exposure_scales_linear_color() {
+        let mut image = tiny_image(ImageFormat::P6);
+        image.exposure = -1.0;
+        assert_eq!(image.total_exposure(), -1.0);
+
+        let mut bytes = Vec::new();
+        image.write(&mut bytes).unwrap();
+        // the bottom right pixel is linear(10), linear(128), 1.0 before
+        // exposure, and gamma 2 turns halving into dividing by root 2
+        let expected = |byte: u8| ((linear(byte) / 2.0).sqrt() * (256.0 - Float::EPSILON)) as u8;
+        let pixel = &bytes[bytes.len() - 3..];
+        assert_eq!(pixel, [expected(10), expected(128), 181]);
+    }
+
+    #[test]
+    fn auto_exposure_brings_average_to_middle_grey() {
+        let mut image = image(4, 4, 32);
+        for (x, y) in image.iter() {
+            // a log-average luminance of 0.72, two stops above middle grey
+            let c = if (x + y) % 2 == 0 { 0.36 } else { 1.44 };
+            image.set_pixel(x, y, FloatRgb::new(c, c, c));
+        }
+        image.auto_exposure = true;
+        assert_approx_eq!(image.total_exposure(), -2.0, abs = 1e-3, rel = 0.0);
+
+        image.exposure = 0.5;
+        assert_approx_eq!(image.total_exposure(), -1.5, abs = 1e-3, rel = 0.0);
+    }
+
+    #[test]
+    fn write_to_memory_gives_png() {
+        let mut stdout_image = image(4, 3, 32);
+        assert!(!stdout_image.writes_to_stdout());
+        stdout_image.filename = String::from("-");
+        assert!(stdout_image.writes_to_stdout());
+
+        let mut bytes: Vec<u8> = Vec::new();
+        stdout_image.write(&mut bytes).unwrap();
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn image_iter_visits_pixels_in_memory_order() {
+        let pixels: Vec<(u32, u32)> = image(2, 2, 32).iter().collect();
+        assert_eq!(pixels, vec![(0, 0), (1, 0), (0, 1), (1, 1)]);
+    }
+}
+
+#[cfg(test)]
+mod render_tests {
+    use geometry3d::*;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::image::Image;
+    use rtow::light::Lights;
+    use rtow::material::*;
+    use rtow::object::*;
+    use rtow::render::*;
+    use rtow::texture::Texture;
+
+    fn sphere() -> Object {
+        let location = Ray3 {
+            origin: Point3::new(0.0, 0.0, -2.0),
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+        let light = DiffuseLight::new(Texture::from(FloatRgb::new(1.0, 0.5, 0.25)));
+        Object::from(Sphere::new(location, 1.0, Material::DiffuseLight(light)))
+    }
+
+    fn ray(x: Float) -> Ray3 {
+        Ray3 {
+            origin: Point3::default(),
+            direction: Vec3::new(x, 0.0, -2.0).unit().unwrap(),
+            time: 0.0,
+        }
+    }
+
+    #[test]
+    fn alpha_marks_rays_that_miss_the_scene() {
+        let mut world = sphere();
+        let background = FloatRgb::new(0.5, 0.5, 0.5);
+
+        let sample = sample_color(ray(0.0), background, &mut world, &Lights::new(), 10, true);
+        assert_eq!(sample.color, FloatRgb::new(1.0, 0.5, 0.25));
+        assert_eq!(sample.alpha, 1.0);
+
+        let sample = sample_color(ray(4.0), background, &mut world, &Lights::new(), 10, true);
+        assert_eq!(sample.color, FloatRgb::new(0.0, 0.0, 0.0));
+        assert_eq!(sample.alpha, 0.0);
+
+        let sample = sample_color(ray(4.0), background, &mut world, &Lights::new(), 10, false);
+        assert_eq!(sample.color, background);
+        assert_eq!(sample.alpha, 1.0);
+    }
+
+    #[test]
+    fn partial_coverage_gives_semi_transparent_png() {
+        let mut world = sphere();
+        let background = FloatRgb::new(0.5, 0.5, 0.5);
+
+        // two samples on the sphere and two either side of it
+        let mut accumulator = FRgbAccumulator::new();
+        for x in [-4.0, -0.1, 0.1, 4.0] {
+            let sample = sample_color(ray(x), background, &mut world, &Lights::new(), 10, true);
+            accumulator.add_weighted_alpha(sample.color, sample.alpha, 1.0);
+        }
+        assert_eq!(accumulator.average_alpha(), 0.5);
+
+        let mut image = Image::new(ImageConfig {
+            filename: String::from("alpha.png"),
+            width: 1,
+            height: Some(1),
+            aspect_ratio: None,
+            tile_size: 32,
+            format: None,
+            alpha: true,
+            gamma: Gamma::Srgb,
+            flip_y: false,
+            aovs: Vec::new(),
+            denoise: false,
+            denoise_strength: 1.0,
+            denoise_iterations: 5,
+            exposure: 0.0,
+            auto_exposure: false,
+            output_relative_to_scene: false,
+        });
+        image.set_pixel(0, 0, accumulator.average());
+        image.set_alpha(0, 0, accumulator.average_alpha());
+
+        let mut bytes = Vec::new();
+        image.write(&mut bytes).unwrap();
+        let decoder = png::Decoder::new(&bytes[..]);
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(&data[..4], &[255, 188, 137, 128]);
+    }
+}
+
+#[cfg(test)]
+mod checkpoint_tests {
+    use geometry3d::*;
+    use rtow::builder::*;
+    use rtow::checkpoint::{stable_hash, Checkpoint};
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::material::Material;
+    use rtow::renderer::{RenderState, Renderer};
+    use std::path::PathBuf;
+
+    // a diffuse sphere seen through a thin lens over 6 tiles, or 4
+    // progressive passes
+    fn config(progressive: bool) -> Config {
+        let mut config = SceneBuilder::new()
+            .image(ImageBuilder::new().size(12, 8).tile_size(4).build())
+            .camera(CameraBuilder::new().look_from(Point3::new(0.0, 0.5, 3.0)).aperture(0.1).build())
+            .sphere(Point3::new(0.0, 0.0, 0.0), 0.5)
+            .material(Material::lambertian(FloatRgb::new(0.8, 0.3, 0.3)))
+            .add()
+            .build();
+        config.seed = Some(7);
+        if progressive {
+            config.progressive = Some(ProgressiveConfig { write_interval: 1, time_budget: None });
+        }
+        config
+    }
+
+    // unique to the process, so that concurrent test runs do not share it
+    fn checkpoint_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rtow_{name}_{}.bin", std::process::id()))
+    }
+
+    fn finish(renderer: &mut Renderer, state: &mut RenderState) {
+        while !renderer.finished(state) {
+            renderer.render_unit(state);
+        }
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted_render() {
+        for progressive in [false, true] {
+            let mut straight = Renderer::new(config(progressive)).unwrap();
+            let mut uninterrupted = straight.new_state();
+            finish(&mut straight, &mut uninterrupted);
+
+            let path = checkpoint_path(&format!("checkpoint_test_{progressive}"));
+            let checkpoint = Checkpoint::new(&path, config(progressive).hash());
+            let mut interrupted = Renderer::new(config(progressive)).unwrap();
+            let mut state = interrupted.new_state();
+            for _ in 0..2 {
+                interrupted.render_unit(&mut state);
+            }
+            checkpoint.save(interrupted.seed(), &state).unwrap();
+            drop(interrupted);
+
+            let mut other = config(progressive);
+            other.background_color = FloatRgb::new(0.0, 0.0, 0.0);
+            assert!(Checkpoint::new(&path, other.hash()).load().is_err());
+            let saved = checkpoint.load().unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(saved.seed, 7);
+            assert_eq!(saved.state, state);
+            let mut resumed = saved.state;
+
+            finish(&mut Renderer::new(config(progressive)).unwrap(), &mut resumed);
+            assert_eq!(resumed.completed, uninterrupted.completed);
+            assert!(resumed.framebuffer == uninterrupted.framebuffer, "progressive: {progressive}");
+            assert!(resumed.aovs == uninterrupted.aovs);
+        }
+    }
+
+    // the FNV-1a test vectors, as checkpoints made by one build must load
+    // in the next
+    #[test]
+    fn config_hash_is_stable() {
+        assert_eq!(stable_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(stable_hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(stable_hash(b"foobar"), 0x85944171f73967e8);
+
+        let scene = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../scenes/two_spheres.json"));
+        let config: Config = serde_json::from_str(&scene.unwrap()).unwrap();
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(config.hash(), stable_hash(json.as_bytes()));
+    }
+}
+
+#[cfg(test)]
+mod progress_tests {
+    use rtow::progress::*;
+
+    #[test]
+    fn callback_is_called_once_per_interval() {
+        let mut reports = Vec::new();
+        {
+            let mut reporter = ProgressReporter::new(100, 10, Some(|p| reports.push(p)));
+            for _ in 0..100 {
+                reporter.advance(1);
+            }
+        }
+
+        assert_eq!(reports.len(), 10);
+        let completed: Vec<u64> = reports.iter().map(|p| p.completed).collect();
+        assert_eq!(completed, (1..=10).map(|i| i * 10).collect::<Vec<_>>());
+        assert!(reports.iter().all(|p| p.total == 100));
+    }
+
+    #[test]
+    fn large_advances_report_once_and_finish_is_always_reported() {
+        let mut reports = Vec::new();
+        {
+            let mut reporter = ProgressReporter::new(95, 10, Some(|p| reports.push(p)));
+            reporter.advance(4);
+            reporter.advance(35);
+            reporter.advance(53);
+            reporter.advance(3);
+        }
+
+        let completed: Vec<u64> = reports.iter().map(|p| p.completed).collect();
+        assert_eq!(completed, vec![39, 92, 95]);
+        assert_eq!(reports.last().unwrap().fraction(), 1.0);
+    }
+
+    #[test]
+    fn reporter_without_callback_tracks_progress() {
+        let mut reporter = ProgressReporter::<fn(Progress)>::new(50, 10, None);
+        reporter.advance(25);
+        assert_eq!(reporter.progress().completed, 25);
+        assert_eq!(reporter.progress().fraction(), 0.5);
+    }
+}
+
+#[cfg(test)]
+mod aov_tests {
+    use geometry3d::*;
+    use rtow::aov::*;
+    use rtow::color::*;
+    use rtow::config::Aov;
+    use rtow::material::*;
+    use rtow::object::*;
+    use rtow::render::*;
+    use rtow::texture::Texture;
+
+    /// A unit sphere two units in front of the origin, looking down -z.
+    fn sphere() -> Object {
+        let location = Ray3 {
+            origin: Point3::new(0.0, 0.0, -2.0),
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+        let albedo = Texture::from(FloatRgb::new(0.25, 0.5, 0.75));
+        Object::from(Sphere::new(location, 1.0, Material::Lambertian(Lambertian::new(albedo))))
+    }
+
+    fn ray(direction: Vec3) -> Ray3 {
+        Ray3 {
+            origin: Point3::default(),
+            direction: direction.unit().unwrap(),
+            time: 0.0,
+        }
+    }
+
+    #[test]
+    fn center_pixel_sees_front_of_sphere() {
+        let mut world = sphere();
+        let background = FloatRgb::new(0.5, 0.5, 0.5);
+
+        let sample = ray_color(ray(Vec3::new(0.0, 0.0, -1.0)), background, &mut world, 10);
+        let hit = sample.first_hit.unwrap();
+        assert_approx_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0), 1e-12);
+        assert_approx_eq!(hit.t, 1.0, 1e-12);
+        assert_eq!(hit.albedo, FloatRgb::new(0.25, 0.5, 0.75));
+
+        let sample = ray_color(ray(Vec3::new(1.0, 0.0, -1.0)), background, &mut world, 10);
+        assert_eq!(sample.first_hit, None);
+    }
+
+    #[test]
+    fn off_center_hit_matches_analytic_normal_and_depth() {
+        let mut world = sphere();
+        let direction = Vec3::new(0.0, 0.25, -1.0).unit().unwrap();
+        let sample = ray_color(ray(direction), FloatRgb::default(), &mut world, 10);
+        let hit = sample.first_hit.unwrap();
+
+        // solve |t d - c|^2 = 1 for the nearest t
+        let c = Vec3::new(0.0, 0.0, -2.0);
+        let b = direction.dot(c);
+        let t = b - (b * b - c.quadrance() + 1.0).sqrt();
+        let normal = t * direction - c;
+        assert_approx_eq!(hit.t, t, 1e-12);
+        assert_approx_eq!(hit.normal, normal, 1e-12);
+    }
+
+    #[test]
+    fn accumulator_averages_hits_and_ignores_misses_for_depth() {
+        let hit = |t, normal| FirstHit {
+            normal,
+            t,
+            albedo: FloatRgb::new(1.0, 1.0, 1.0),
+        };
+        let mut pixel = AovAccumulator::new();
+        assert_eq!(pixel.depth(), Float::INFINITY);
+
+        pixel.add(Some(hit(1.0, Vec3::new(1.0, 0.0, 0.0))));
+        pixel.add(Some(hit(3.0, Vec3::new(0.0, 1.0, 0.0))));
+        pixel.add(None);
+        pixel.add(None);
+
+        assert_eq!(pixel.depth(), 2.0);
+        let n = (0.5 as Float).sqrt();
+        assert_approx_eq!(pixel.normal(), Vec3::new(n, n, 0.0), 1e-12);
+        assert_eq!(pixel.albedo(), FloatRgb::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn encodes_normal_and_depth_pngs() {
+        let mut aovs = AovBuffer::new(3, 1);
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let albedo = FloatRgb::default();
+        aovs.add_sample(0, 0, Some(FirstHit { normal, t: 2.0, albedo }));
+        aovs.add_sample(1, 0, Some(FirstHit { normal: -normal, t: 1.0, albedo }));
+        aovs.add_sample(2, 0, None);
+
+        let decode = |aov| {
+            let mut bytes = Vec::new();
+            aovs.encode(aov, Gamma::Srgb, &mut bytes).unwrap();
+            let decoder = png::Decoder::new(&bytes[..]);
+            let mut reader = decoder.read_info().unwrap();
+            let mut data = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut data).unwrap();
+            data
+        };
+
+        assert_eq!(decode(Aov::Normal), vec![128, 128, 255, 128, 128, 0, 128, 128, 128]);
+        // depth is relative to the furthest hit and misses are at the maximum
+        assert_eq!(decode(Aov::Depth), vec![255, 255, 128, 0, 255, 255]);
+
+        assert_eq!(AovBuffer::filename("out/render.png", Aov::Depth), "out/render.depth.png");
+    }
+}
+
+#[cfg(test)]
+mod denoise_tests {
+    use geometry3d::*;
+    use rtow::aov::AovBuffer;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::denoise::Denoiser;
+    use rtow::image::Image;
+    use rtow::render::FirstHit;
+
+    const SIZE: u32 = 16;
+
+    fn image() -> Image {
+        Image::new(ImageConfig {
+            filename: String::from("denoise.png"),
+            width: SIZE,
+            height: Some(SIZE),
+            aspect_ratio: None,
+            tile_size: 32,
+            format: None,
+            alpha: false,
+            gamma: Gamma::Srgb,
+            flip_y: false,
+            aovs: Vec::new(),
+            denoise: true,
+            denoise_strength: 1.0,
+            denoise_iterations: 5,
+            exposure: 0.0,
+            auto_exposure: false,
+            output_relative_to_scene: false,
+        })
+    }
+
+    /// A guide where the pixel (x, y) sees a surface with the given normal
+    /// and depth.
+    fn guide<F>(f: F) -> AovBuffer
+    where
+        F: Fn(u32, u32) -> (Vec3, Float),
+    {
+        let mut aovs = AovBuffer::new(SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let (normal, t) = f(x, y);
+                let albedo = FloatRgb::default();
+                aovs.add_sample(x, y, Some(FirstHit { normal, t, albedo }));
+            }
+        }
+        aovs
+    }
+
+    /// Deterministic noise in [-0.25, 0.25].
+    fn noise(x: u32, y: u32) -> Float {
+        let h = (x * 7919 + y * 104729) % 101;
+        (h as Float / 100.0 - 0.5) * 0.5
+    }
+
+    fn variance(image: &Image, mean: Float) -> Float {
+        image
+            .iter()
+            .map(|(x, y)| (image.pixel(x, y).r() - mean).powi(2))
+            .sum::<Float>()
+            / (SIZE * SIZE) as Float
+    }
+
+    #[test]
+    fn noisy_flat_region_converges_to_mean() {
+        let mut image = image();
+        for (x, y) in image.iter() {
+            let c = 0.5 + noise(x, y);
+            image.set_pixel(x, y, FloatRgb::new(c, c, c));
+        }
+        let aovs = guide(|_, _| (Vec3::new(0.0, 0.0, 1.0), 1.0));
+
+        let mean = image.iter().map(|(x, y)| image.pixel(x, y).r()).sum::<Float>()
+            / (SIZE * SIZE) as Float;
+        let before = variance(&image, mean);
+        image.denoiser.unwrap().apply(&mut image, &aovs);
+        let after = variance(&image, mean);
+
+        assert!(after < before / 10.0, "variance {before} -> {after}");
+    }
+
+    fn assert_edge_preserved(aovs: AovBuffer) {
+        // black on the left half and white on the right, both noisy
+        let mut image = image();
+        for (x, y) in image.iter() {
+            let c = if x < SIZE / 2 { 0.0 } else { 1.0 } + 0.2 * noise(x, y);
+            image.set_pixel(x, y, FloatRgb::new(c, c, c));
+        }
+
+        Denoiser::new(10.0, 5).apply(&mut image, &aovs);
+
+        for y in 0..SIZE {
+            assert!(image.pixel(SIZE / 2 - 1, y).r() < 0.1);
+            assert!(image.pixel(SIZE / 2, y).r() > 0.9);
+        }
+    }
+
+    #[test]
+    fn normal_edge_is_not_blurred() {
+        assert_edge_preserved(guide(|x, _| match x < SIZE / 2 {
+            true => (Vec3::new(0.0, 0.0, 1.0), 1.0),
+            false => (Vec3::new(1.0, 0.0, 0.0), 1.0),
+        }));
+    }
+
+    #[test]
+    fn depth_edge_is_not_blurred() {
+        assert_edge_preserved(guide(|x, _| match x < SIZE / 2 {
+            true => (Vec3::new(0.0, 0.0, 1.0), 1.0),
+            false => (Vec3::new(0.0, 0.0, 1.0), 10.0),
+        }));
+    }
+
+    #[test]
+    fn edge_without_guide_is_blurred() {
+        // with a flat guide and a generous strength the color edge is smoothed
+        let mut image = image();
+        for (x, y) in image.iter() {
+            let c = if x < SIZE / 2 { 0.0 } else { 1.0 };
+            image.set_pixel(x, y, FloatRgb::new(c, c, c));
+        }
+        let aovs = guide(|_, _| (Vec3::new(0.0, 0.0, 1.0), 1.0));
+        Denoiser::new(10.0, 5).apply(&mut image, &aovs);
+        assert!(image.pixel(SIZE / 2 - 1, 0).r() > 0.1);
+    }
+}
+
+#[cfg(test)]
+mod heat_map_tests {
+    use geometry3d::*;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::hit_record::HitContext;
+    use rtow::material::*;
+    use rtow::object::*;
+    use rtow::render::*;
+
+    fn sphere(x: Float) -> Object {
+        let location = Ray3 {
+            origin: Point3::new(x, 0.0, -2.0),
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+        let metal = Metal::new(FloatRgb::new(0.5, 0.5, 0.5), 0.0);
+        Object::from(Sphere::new(location, 1.0, Material::Metal(metal)))
+    }
+
+    fn scene() -> Object {
+        let mut list = List::new();
+        for x in [-3.0, 0.0, 3.0] {
+            list.add(sphere(x));
+        }
+        BVHNode::from_list(&mut list, TRange::new(0.0, 0.0), BvhBuilder::Median).unwrap()
+    }
+
+    fn ray(direction: Vec3) -> Ray3 {
+        Ray3 {
+            origin: Point3::default(),
+            direction,
+            time: 0.0,
+        }
+    }
+
+    #[test]
+    fn miss_tests_only_the_root_box() {
+        let mut world = scene();
+        let mut ctx = HitContext::default();
+        let hit = world.hit(ray(Vec3::new(0.0, 0.0, 1.0)), TRange::new(0.001, Float::INFINITY), &mut ctx);
+        assert!(hit.is_none());
+        assert_eq!(ctx.aabb_tests, 1);
+
+        let heat_map = HeatMapConfig::default();
+        let sample = heat_map_sample(ray(Vec3::new(0.0, 0.0, 1.0)), &mut world, &heat_map);
+        assert_eq!(sample.color, heat_map.color(1));
+        assert_eq!(sample.first_hit, None);
+    }
+
+    #[test]
+    fn hit_tests_more_than_the_root_box() {
+        let mut world = scene();
+        let mut ctx = HitContext::default();
+        let hit = world.hit(ray(Vec3::new(0.0, 0.0, -1.0)), TRange::new(0.001, Float::INFINITY), &mut ctx);
+        assert!(hit.is_some());
+        assert!(ctx.aabb_tests > 1);
+    }
+
+    #[test]
+    fn gradient_is_interpolated_and_clamped() {
+        let heat_map = HeatMapConfig {
+            max: 10,
+            gradient: vec![
+                FloatRgb::new(0.0, 0.0, 1.0),
+                FloatRgb::new(0.0, 1.0, 0.0),
+                FloatRgb::new(1.0, 0.0, 0.0),
+            ],
+        };
+        assert_eq!(heat_map.color(0), FloatRgb::new(0.0, 0.0, 1.0));
+        assert_eq!(heat_map.color(5), FloatRgb::new(0.0, 1.0, 0.0));
+        assert_eq!(heat_map.color(10), FloatRgb::new(1.0, 0.0, 0.0));
+        assert_eq!(heat_map.color(50), FloatRgb::new(1.0, 0.0, 0.0));
+
+        let quarter = heat_map.color(2);
+        assert_approx_eq!(quarter.g(), 0.4, 1e-12);
+        assert_approx_eq!(quarter.b(), 0.6, 1e-12);
+    }
+
+    #[test]
+    fn render_mode_from_config() {
+        let mode: RenderMode = serde_json::from_str(r#""heatmap""#).unwrap();
+        assert_eq!(mode, RenderMode::HeatMap);
+        assert_eq!(RenderMode::default(), RenderMode::PathTrace);
+    }
+}
+
+#[cfg(test)]
+mod ray_color_tests {
+    use geometry3d::*;
+    use rtow::color::*;
+    use rtow::hit_record::HitContext;
+    use rtow::material::*;
+    use rtow::object::*;
+    use rtow::render::*;
+    use rtow::texture::Texture;
+
+    /// The recursive formulation of `ray_color`, as a reference.
+    fn recursive(ray: Ray3, background: FloatRgb, world: &mut Object, depth: u32) -> FloatRgb {
+        let range = TRange::new(0.001, Float::INFINITY);
+        if depth == 0 {
+            FloatRgb::new(0.0, 0.0, 0.0)
+        } else if let Some((rec, mat)) = world.hit(ray, range, &mut HitContext::default()) {
+            let emitted = mat.emit(rec);
+            match mat.scatter(rec) {
+                Some((attenuation, ray)) => {
+                    emitted + attenuation * recursive(ray, background, world, depth - 1)
+                }
+                None => emitted,
+            }
+        } else {
+            background
+        }
+    }
+
+    fn sphere(z: Float, radius: Float, material: Material) -> Object {
+        let location = Ray3 {
+            origin: Point3::new(0.0, 0.0, z),
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+        Object::from(Sphere::new(location, radius, material))
+    }
+
+    /// A perfect mirror sphere with a light sphere in front of it and
+    /// another mirror behind the camera. Mirrors with no fuzz scatter
+    /// deterministically, so the paths can be compared exactly.
+    fn scene() -> Object {
+        let mut list = List::new();
+        let mirror = |color| Material::Metal(Metal::new(color, 0.0));
+        list.add(sphere(-4.0, 1.0, mirror(FloatRgb::new(0.9, 0.8, 0.7))));
+        list.add(sphere(4.0, 1.0, mirror(FloatRgb::new(0.5, 0.6, 0.95))));
+        let light = DiffuseLight::new(Texture::from(FloatRgb::new(4.0, 2.0, 1.0)));
+        list.add(sphere(-2.0, 0.25, Material::DiffuseLight(light)));
+        Object::from(list)
+    }
+
+    fn rays() -> impl Iterator<Item = Ray3> {
+        (0..50).map(|i| {
+            let a = i as Float * 0.04;
+            Ray3 {
+                origin: Point3::new(0.0, 0.0, 0.0),
+                direction: Vec3::new(0.2 * a.sin(), 0.15 * a.cos(), -1.0).unit().unwrap(),
+                time: 0.0,
+            }
+        })
+    }
+
+    #[test]
+    fn iterative_matches_recursive() {
+        let mut world = scene();
+        let background = FloatRgb::new(0.1, 0.2, 0.3);
+        for depth in [0, 1, 2, 5, 50] {
+            for ray in rays() {
+                let expected = recursive(ray, background, &mut world, depth);
+                let actual = ray_color(ray, background, &mut world, depth).color;
+                assert_approx_eq!(actual, expected, 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn deep_paths_do_not_overflow_the_stack() {
+        // two facing mirrors trap the ray for every bounce
+        let mut list = List::new();
+        let mirror = Material::Metal(Metal::new(FloatRgb::new(1.0, 1.0, 1.0), 0.0));
+        list.add(sphere(-2.0, 1.0, mirror.clone()));
+        list.add(sphere(2.0, 1.0, mirror));
+        let mut world = Object::from(list);
+
+        let ray = Ray3 {
+            origin: Point3::new(0.0, 0.0, 0.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+            time: 0.0,
+        };
+        let sample = ray_color(ray, FloatRgb::new(1.0, 1.0, 1.0), &mut world, 100_000);
+        assert_eq!(sample.color, FloatRgb::new(0.0, 0.0, 0.0));
+        assert!(sample.first_hit.is_some());
+    }
+}
+
+#[cfg(test)]
+mod renderer_tests {
+    use geometry3d::assert_approx_eq;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::renderer::Renderer;
+
+    // a light filling the middle of a 16 x 16 image against a plain
+    // background, so that every sample of a pixel sees the same color
+    fn config(progressive: bool) -> Config {
+        let progressive = if progressive { r#", "progressive": {"write_interval": 1}"# } else { "" };
+        let json = format!(
+            r#"{{
+                "image": {{"filename": "renderer_test.png", "width": 16, "height": 16, "tile_size": 5}},
+                "camera": {{
+                    "look_from": [0.0, 0.0, 5.0], "look_at": [0.0, 0.0, 0.0], "up": [0.0, 1.0, 0.0],
+                    "vertical_fov": 40.0, "aperture": 0.0, "focus_distance": 5.0,
+                    "time_min": 0.0, "time_max": 1.0
+                }},
+                "sampler": {{"n": 2, "max_depth": 5}},
+                "background_color": [0.1, 0.2, 0.3],
+                "scene_list": {{"objects": [{{"Sphere": {{
+                    "location": {{"origin": [0.0, 0.0, 0.0], "direction": [0.0, 0.0, 0.0], "time": 0.0}},
+                    "radius": 1.0,
+                    "material": {{"DiffuseLight": {{"emit": {{"SolidColor": [2.0, 1.0, 0.5]}}}}}}
+                }}}}]}}{progressive}
+            }}"#
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn renders_scene_through_library() {
+        for progressive in [false, true] {
+            let (image, _) = Renderer::new(config(progressive)).unwrap().render();
+            assert_eq!((image.width, image.height), (16, 16));
+            assert_approx_eq!(image.pixel(8, 8), FloatRgb::new(2.0, 1.0, 0.5));
+            for (x, y) in [(0, 0), (15, 0), (0, 15), (15, 15)] {
+                assert_approx_eq!(image.pixel(x, y), FloatRgb::new(0.1, 0.2, 0.3));
+            }
+        }
+    }
+
+    #[test]
+    fn units_cover_image() {
+        let renderer = Renderer::new(config(false)).unwrap();
+        assert_eq!(renderer.units(), 16);
+        assert_eq!(renderer.total_pixels(), 256);
+
+        let renderer = Renderer::new(config(true)).unwrap();
+        assert_eq!(renderer.units(), 4);
+        assert_eq!(renderer.total_pixels(), 4 * 256);
+    }
+
+    #[test]
+    fn rejects_adaptive_progressive_render() {
+        let mut config = config(true);
+        config.sampler.adaptive = serde_json::from_str(r#"{"min_samples": 4, "max_samples": 16, "tolerance": 0.01}"#).unwrap();
+        assert!(Renderer::new(config).is_err());
+    }
+}
+
+#[cfg(test)]
+mod builder_tests {
+    use geometry3d::*;
+    use rtow::builder::*;
+    use rtow::color::*;
+    use rtow::config::Config;
+    use rtow::material::Material;
+    use rtow::renderer::Renderer;
+
+    #[test]
+    fn built_scene_renders() {
+        let config = SceneBuilder::new()
+            .image(ImageBuilder::new().filename("builder_test.png").size(16, 16).build())
+            .camera(
+                CameraBuilder::new()
+                    .look_from(Point3::new(0.0, 0.0, 5.0))
+                    .vertical_fov(40.0)
+                    .build(),
+            )
+            .background(FloatRgb::new(0.1, 0.2, 0.3))
+            .sphere(Point3::new(0.0, 0.0, 0.0), 1.0)
+            .material(Material::diffuse_light(FloatRgb::new(2.0, 1.0, 0.5)))
+            .add()
+            .build();
+
+        // every sample of these pixels sees the same color
+        let (image, _) = Renderer::new(config).unwrap().render();
+        let center = image.pixel(8, 8);
+        let corner = image.pixel(0, 0);
+        assert_approx_eq!(center.r(), 2.0);
+        assert_approx_eq!(center.b(), 0.5);
+        assert_approx_eq!(corner.r(), 0.1);
+        assert_approx_eq!(corner.b(), 0.3);
+    }
+
+    #[test]
+    fn built_config_round_trips_through_json() {
+        let config = SceneBuilder::new()
+            .sphere(Point3::new(0.0, -100.0, 0.0), 100.0)
+            .add()
+            .xz_rect((-1.0, 1.0), (-1.0, 1.0), 3.0)
+            .material(Material::diffuse_light(FloatRgb::new(4.0, 4.0, 4.0)))
+            .add()
+            .rect_prism(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0))
+            .material(Material::metal(FloatRgb::new(0.8, 0.8, 0.8), 0.1))
+            .add()
+            .build();
+
+        let json = serde_json::to_string(&config).unwrap();
+        let parsed: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+
+        let value = serde_json::to_value(&config).unwrap();
+        let objects = value["scene_list"]["objects"].as_array().unwrap();
+        assert_eq!(objects.len(), 3);
+        assert!(objects[0]["Sphere"]["material"]["Lambertian"].is_object());
+        assert!(objects[1]["XZRect"]["material"]["DiffuseLight"].is_object());
+        assert!(objects[2]["RectPrism"]["U"]["material"]["Metal"].is_object());
+    }
+
+    #[test]
+    fn moving_sphere_passes_through_both_centers() {
+        let config = SceneBuilder::new()
+            .moving_sphere((Point3::new(1.0, 0.0, 0.0), 0.5), (Point3::new(2.0, 4.0, 0.0), 1.0), 0.2)
+            .add()
+            .build();
+
+        let value = serde_json::to_value(&config).unwrap();
+        let location: Ray3 =
+            serde_json::from_value(value["scene_list"]["objects"][0]["Sphere"]["location"].clone())
+                .unwrap();
+        assert_eq!(location.at(0.0), Point3::new(1.0, 0.0, 0.0));
+        assert_eq!(location.at(0.5), Point3::new(2.0, 4.0, 0.0));
+    }
+
+    #[test]
+    fn camera_focuses_on_look_at_by_default() {
+        let camera = CameraBuilder::new()
+            .look_from(Point3::new(3.0, 4.0, 0.0))
+            .build();
+        assert_eq!(camera.focus_distance, 5.0);
+
+        let camera = CameraBuilder::new().focus_distance(2.0).build();
+        assert_eq!(camera.focus_distance, 2.0);
+    }
+}
+
+#[cfg(test)]
+mod clamp_tests {
+    use rtow::color::*;
+    use rtow::config::ClampConfig;
+    use rtow::render::RadianceSample;
+
+    fn sample(color: FloatRgb, direct: FloatRgb) -> RadianceSample {
+        RadianceSample {
+            color,
+            direct,
+            alpha: 1.0,
+            first_hit: None,
+        }
+    }
+
+    #[test]
+    fn samples_above_the_clamp_are_limited() {
+        let clamp = ClampConfig {
+            max_radiance: 4.0,
+            indirect_only: false,
+        };
+
+        let bright = clamp.apply(sample(FloatRgb::new(16.0, 8.0, 2.0), FloatRgb::default()));
+        assert_eq!(bright.color, FloatRgb::new(4.0, 2.0, 0.5));
+
+        let dim = sample(FloatRgb::new(3.0, 4.0, 0.5), FloatRgb::default());
+        assert_eq!(clamp.apply(dim), dim);
+    }
+
+    #[test]
+    fn indirect_only_clamp_leaves_direct_light() {
+        let clamp = ClampConfig {
+            max_radiance: 1.0,
+            indirect_only: true,
+        };
+
+        let light = sample(FloatRgb::new(10.0, 10.0, 10.0), FloatRgb::new(10.0, 10.0, 10.0));
+        assert_eq!(clamp.apply(light), light);
+
+        let bounced = sample(FloatRgb::new(12.0, 10.0, 10.0), FloatRgb::new(10.0, 10.0, 10.0));
+        assert_eq!(clamp.apply(bounced).color, FloatRgb::new(11.0, 10.0, 10.0));
+    }
+
+    #[test]
+    fn indirect_only_defaults_to_off() {
+        let config: ClampConfig = serde_json::from_str(r#"{"max_radiance": 2.0}"#).unwrap();
+        assert!(!config.indirect_only);
+    }
+}
+
+#[cfg(test)]
+mod light_sampling_tests {
+    use geometry3d::*;
+    use rtow::color::*;
+    use rtow::light::Lights;
+    use rtow::material::Material;
+    use rtow::object::*;
+    use rtow::render::*;
+
+    fn sphere(center: Point3, radius: Float, material: Material) -> Object {
+        let location = Ray3 {
+            origin: center,
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+        Sphere::new(location, radius, material).into()
+    }
+
+    fn mean_and_variance(samples: &[Float]) -> (Float, Float) {
+        let n = samples.len() as Float;
+        let mean = samples.iter().sum::<Float>() / n;
+        let variance = samples.iter().map(|x| (x - mean) * (x - mean)).sum::<Float>() / (n - 1.0);
+        (mean, variance)
+    }
+
+    #[test]
+    fn only_sampleable_emitters_are_lights() {
+        let light = || Material::diffuse_light(FloatRgb::new(1.0, 1.0, 1.0));
+        let grey = || Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5));
+
+        let mut nested = List::new();
+        nested.add(sphere(Point3::new(0.0, 0.0, 0.0), 1.0, light()));
+        nested.add(XYRect::new(TRange::new(0.0, 1.0), TRange::new(0.0, 1.0), 0.0, grey()).into());
+        let mut list = List::new();
+        list.add(nested.into());
+        list.add(XZRect::new(TRange::new(0.0, 1.0), TRange::new(0.0, 1.0), 2.0, light()).into());
+        list.add(sphere(Point3::new(3.0, 0.0, 0.0), 1.0, grey()));
+        list.add(RectPrism::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0), light()).into());
+
+        assert_eq!(Lights::from_list(&list).len(), 2);
+    }
+
+    #[test]
+    fn sampled_directions_have_positive_density() {
+        let light = Material::diffuse_light(FloatRgb::new(1.0, 1.0, 1.0));
+        let mut list = List::new();
+        list.add(sphere(Point3::new(0.0, 5.0, 0.0), 1.0, light.clone()));
+        list.add(XZRect::new(TRange::new(-1.0, 1.0), TRange::new(-1.0, 1.0), -3.0, light).into());
+        let lights = Lights::from_list(&list);
+
+        let mut rng = rtow::random::Random::new(rand::thread_rng());
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        for _ in 0..1000 {
+            let direction = lights.sample(origin, 0.0, &mut rng).unwrap();
+            assert_approx_eq!(direction.length(), 1.0);
+            assert!(lights.pdf(origin, direction, 0.0) > 0.0);
+        }
+        assert_eq!(lights.pdf(origin, Vec3::new(1.0, 0.0, 0.0), 0.0), 0.0);
+    }
+
+    /// A grey sphere inside a uniformly glowing sphere reflects exactly its
+    /// albedo times the glow, however the light is sampled.
+    #[test]
+    fn furnace_test_is_unbiased() {
+        let mut list = List::new();
+        list.add(sphere(Point3::new(0.0, 0.0, 0.0), 1.0, Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5))));
+        list.add(sphere(Point3::new(0.0, 0.0, 0.0), 10.0, Material::diffuse_light(FloatRgb::new(1.0, 1.0, 1.0))));
+        let lights = Lights::from_list(&list);
+        assert_eq!(lights.len(), 1);
+        let mut world: Object = list.into();
+
+        let ray = Ray3 {
+            origin: Point3::new(0.0, 0.0, 5.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+            time: 0.0,
+        };
+        let background = FloatRgb::new(0.0, 0.0, 0.0);
+        let samples: Vec<Float> = (0..20_000)
+            .map(|_| trace_path(ray, background, &mut world, &lights, 10).color.g())
+            .collect();
+
+        let (mean, _) = mean_and_variance(&samples);
+        assert_approx_eq!(mean, 0.5, abs = 0.01, rel = 0.0);
+    }
+
+    /// A small square light of radiance 25 hanging one unit above a grey
+    /// floor, looking at the point on the floor directly below it.
+    #[test]
+    fn light_sampling_reduces_noise_from_small_lights() {
+        let mut list = List::new();
+        let floor = Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5));
+        let light = Material::diffuse_light(FloatRgb::new(25.0, 25.0, 25.0));
+        list.add(XZRect::new(TRange::new(-10.0, 10.0), TRange::new(-10.0, 10.0), 0.0, floor).into());
+        list.add(XZRect::new(TRange::new(-0.1, 0.1), TRange::new(-0.1, 0.1), 1.0, light).into());
+        let lights = Lights::from_list(&list);
+        let mut world: Object = list.into();
+
+        // the form factor from the point below the center to the square,
+        // summed over the four quarters of the square
+        let a: Float = 0.1;
+        let s = (1.0 + a * a).sqrt();
+        let quarter = (2.0 * a / s * (a / s).atan()) / (2.0 * geometry3d::consts::PI);
+        let expected = 0.5 * 25.0 * 4.0 * quarter;
+
+        let ray = Ray3::new(Point3::new(0.0, 0.5, 1.0), Vec3::new(0.0, -0.5, -1.0), 0.0).unwrap();
+        let background = FloatRgb::new(0.0, 0.0, 0.0);
+        let mut render = |lights: &Lights| {
+            let samples: Vec<Float> = (0..20_000)
+                .map(|_| trace_path(ray, background, &mut world, lights, 10).color.r())
+                .collect();
+            mean_and_variance(&samples)
+        };
+
+        let (sampled_mean, sampled_variance) = render(&lights);
+        let (plain_mean, plain_variance) = render(&Lights::new());
+        assert_approx_eq!(sampled_mean, expected, abs = 0.0, rel = 0.02);
+        assert_approx_eq!(plain_mean, expected, abs = 0.0, rel = 0.3);
+        assert!(16.0 * sampled_variance < plain_variance);
+    }
+}
+
+#[cfg(test)]
+mod light_list_tests {
+    use geometry3d::*;
+    use rtow::builder::SceneBuilder;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::light::Lights;
+    use rtow::material::Material;
+    use rtow::random::Random;
+
+    // a named light above the origin, an unnamed light below it, and a grey
+    // sphere off to the side
+    fn config(lights: &str) -> Config {
+        let light = || Material::diffuse_light(FloatRgb::new(4.0, 4.0, 4.0));
+        let mut config = SceneBuilder::new()
+            .xz_rect((-1.0, 1.0), (-1.0, 1.0), 2.0)
+            .material(light())
+            .name("key")
+            .add()
+            .xz_rect((-1.0, 1.0), (-1.0, 1.0), -2.0)
+            .material(light())
+            .add()
+            .sphere(Point3::new(5.0, 0.0, 0.0), 1.0)
+            .name("ball")
+            .add()
+            .build();
+        config.lights = serde_json::from_str(lights).unwrap();
+        config
+    }
+
+    fn lights(lights: &str) -> Result<Lights, String> {
+        let config = config(lights);
+        Lights::from_config(&config.scene_list, &config.lights).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn parses_plain_and_weighted_entries() {
+        let lights: Vec<LightConfig> =
+            serde_json::from_str(r#"["key", 1, {"object": "key", "weight": 2.5}, {"object": 0}]"#).unwrap();
+        let expected = [
+            (ObjectRef::Name(String::from("key")), 1.0),
+            (ObjectRef::Index(1), 1.0),
+            (ObjectRef::Name(String::from("key")), 2.5),
+            (ObjectRef::Index(0), 1.0),
+        ];
+        for (light, (object, weight)) in lights.iter().zip(expected) {
+            assert_eq!(light, &LightConfig { object, weight });
+        }
+    }
+
+    #[test]
+    fn empty_list_discovers_every_light() {
+        assert_eq!(lights("[]").unwrap().len(), 2);
+        assert_eq!(lights(r#"["key"]"#).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn lights_are_chosen_in_proportion_to_their_weight() {
+        let weighted = lights(r#"[{"object": "key", "weight": 3.0}, {"object": 1}]"#).unwrap();
+        let mut rng = Random::new(rand::thread_rng());
+        let origin = Point3::new(0.0, 0.0, 0.0);
+
+        let n = 20_000;
+        let up = (0..n)
+            .filter(|_| weighted.sample(origin, 0.0, &mut rng).unwrap().y() > 0.0)
+            .count();
+        let fraction = up as Float / n as Float;
+        assert_approx_eq!(fraction, 0.75, abs = 0.02, rel = 0.0);
+
+        // the density of each direction is weighted the same way
+        let single = lights("[]").unwrap();
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        assert_eq!(single.pdf(origin, up, 0.0), single.pdf(origin, down, 0.0));
+        assert_approx_eq!(weighted.pdf(origin, up, 0.0), 3.0 * weighted.pdf(origin, down, 0.0));
+    }
+
+    #[test]
+    fn rejects_missing_and_non_emissive_lights() {
+        assert_eq!(lights("[3]").unwrap_err(), "The light object 3 does not exist.");
+        assert_eq!(lights(r#"["fill"]"#).unwrap_err(), r#"The light object "fill" does not exist."#);
+        assert_eq!(
+            lights(r#"["ball"]"#).unwrap_err(),
+            r#"The light object "ball" is not an emissive sphere or rectangle."#
+        );
+        assert_eq!(
+            lights(r#"[{"object": "key", "weight": 0.0}]"#).unwrap_err(),
+            r#"The weight of light object "key" must be positive."#
+        );
+    }
+}
+
+#[cfg(test)]
+mod ambient_occlusion_tests {
+    use geometry3d::*;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::material::Material;
+    use rtow::object::*;
+    use rtow::render::*;
+
+    const BIG: Float = 1e6;
+
+    fn grey() -> Material {
+        Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5))
+    }
+
+    fn floor() -> Object {
+        XZRect::new(TRange::new(-BIG, BIG), TRange::new(-BIG, BIG), 0.0, grey()).into()
+    }
+
+    // looks down at the floor just beside the origin
+    fn ray() -> Ray3 {
+        Ray3::new(Point3::new(1.0, 1.0, 0.0), Vec3::new(-0.99, -1.0, 0.0), 0.0).unwrap()
+    }
+
+    fn ao(rays: u32) -> AoConfig {
+        AoConfig {
+            rays,
+            max_distance: None,
+        }
+    }
+
+    #[test]
+    fn open_floor_is_unoccluded() {
+        let mut world = floor();
+        let sample = ambient_occlusion_sample(ray(), &mut world, &ao(64));
+        assert_eq!(sample.color, FloatRgb::new(1.0, 1.0, 1.0));
+        assert!(sample.first_hit.is_some());
+    }
+
+    #[test]
+    fn wall_occludes_half_the_floor() {
+        // an infinite wall rising from the floor along the z axis hides the
+        // half of the sky on the negative x side, however close it is
+        let mut list = List::new();
+        list.add(floor());
+        list.add(YZRect::new(TRange::new(0.0, BIG), TRange::new(-BIG, BIG), 0.0, grey()).into());
+        let mut world: Object = list.into();
+
+        let sample = ambient_occlusion_sample(ray(), &mut world, &ao(20_000));
+        assert_approx_eq!(sample.color.r(), 0.5, abs = 0.02, rel = 0.0);
+    }
+
+    #[test]
+    fn occluders_beyond_max_distance_are_ignored() {
+        let mut list = List::new();
+        list.add(floor());
+        list.add(XZRect::new(TRange::new(-BIG, BIG), TRange::new(-BIG, BIG), 10.0, grey()).into());
+        let mut world: Object = list.into();
+
+        let near = AoConfig {
+            max_distance: Some(5.0),
+            ..ao(64)
+        };
+        let sample = ambient_occlusion_sample(ray(), &mut world, &near);
+        assert_eq!(sample.color, FloatRgb::new(1.0, 1.0, 1.0));
+        let sample = ambient_occlusion_sample(ray(), &mut world, &ao(64));
+        assert_eq!(sample.color, FloatRgb::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn misses_are_unoccluded() {
+        let mut world = floor();
+        let ray = Ray3 {
+            direction: Vec3::new(0.0, 1.0, 0.0),
+            ..ray()
+        };
+        let sample = ambient_occlusion_sample(ray, &mut world, &ao(4));
+        assert_eq!(sample.color, FloatRgb::new(1.0, 1.0, 1.0));
+        assert_eq!(sample.first_hit, None);
+    }
+
+    #[test]
+    fn parses_ao_mode() {
+        let mode: RenderMode = serde_json::from_str(r#""ao""#).unwrap();
+        assert_eq!(mode, RenderMode::Ao);
+        let config: AoConfig = serde_json::from_str(r#"{"max_distance": 2.0}"#).unwrap();
+        assert_eq!(config.rays, 16);
+    }
+}
+
+#[cfg(test)]
+mod depth_tests {
+    use geometry3d::*;
+    use rtow::builder::*;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::renderer::Renderer;
+
+    // a unit sphere 5 units in front of the camera, one sample per pixel
+    fn render(depth: DepthConfig) -> rtow::image::Image {
+        let mut config = SceneBuilder::new()
+            .image(ImageBuilder::new().size(15, 15).build())
+            .camera(CameraBuilder::new().look_from(Point3::new(0.0, 0.0, 5.0)).vertical_fov(30.0).build())
+            .sphere(Point3::new(0.0, 0.0, 0.0), 1.0)
+            .add()
+            .build();
+        // the tent filter samples the center of the pixel
+        config.sampler.n = 1;
+        config.sampler.filter = Filter::Tent;
+        config.render_mode = RenderMode::Depth;
+        config.depth = depth;
+        Renderer::new(config).unwrap().render().0
+    }
+
+    #[test]
+    fn raw_depth_is_distance_to_sphere() {
+        let image = render(DepthConfig {
+            near: 0.0,
+            far: 50.0,
+            raw: true,
+        });
+        assert_approx_eq!(image.pixel(7, 7).r(), 4.0);
+        assert_eq!(image.pixel(0, 0), FloatRgb::new(50.0, 50.0, 50.0));
+    }
+
+    #[test]
+    fn depth_is_normalized_between_near_and_far() {
+        let image = render(DepthConfig {
+            near: 2.0,
+            far: 6.0,
+            raw: false,
+        });
+        assert_approx_eq!(image.pixel(7, 7).g(), 0.5);
+        assert_eq!(image.pixel(0, 0), FloatRgb::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn values_are_clamped_when_normalized() {
+        let depth = DepthConfig {
+            near: 2.0,
+            far: 6.0,
+            raw: false,
+        };
+        assert_eq!(depth.value(Some(1.0)), 0.0);
+        assert_eq!(depth.value(Some(8.0)), 1.0);
+        assert_eq!(depth.value(None), 1.0);
+    }
+}
+
+#[cfg(test)]
+mod bvh_view_tests {
+    use geometry3d::*;
+    use rtow::cli::Args;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::material::Material;
+    use rtow::object::*;
+    use rtow::render::*;
+
+    // four unit spheres in a row along the x axis, each its own leaf
+    // of the median split tree
+    fn world() -> Object {
+        let mut list = List::new();
+        for i in 0..4 {
+            let location = Ray3 {
+                origin: Point3::new(3.0 * i as Float, 0.0, 0.0),
+                direction: Vec3::default(),
+                time: 0.0,
+            };
+            let material = Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5));
+            list.add(Sphere::new(location, 1.0, material).into());
+        }
+        BVHNode::from_list(&mut list, TRange::new(0.0, 1.0), BvhBuilder::Median).unwrap()
+    }
+
+    fn ray(x: Float, y: Float) -> Ray3 {
+        Ray3 {
+            origin: Point3::new(x, y, 10.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+            time: 0.0,
+        }
+    }
+
+    #[test]
+    fn ray_through_scene_hits_root_box() {
+        let world = world();
+        let hits = bvh_hits(ray(9.0, 0.0), &world);
+        assert_eq!(hits[0].depth, 1);
+        assert!(hits.len() >= 2);
+        assert!(hits.windows(2).all(|w| w[1].depth <= w[0].depth + 1));
+
+        assert!(bvh_hits(ray(0.0, 5.0), &world).is_empty());
+    }
+
+    #[test]
+    fn tree_depth_counts_levels() {
+        let Object::BVHNode(node) = world() else {
+            panic!("Expected a BVH node.");
+        };
+        // two objects fit in a leaf node so four need two levels
+        assert_eq!(node.depth(), 2);
+        assert_eq!(node.aabb().lo(), Point3::new(-1.0, -1.0, -1.0));
+        assert!(matches!(node.left(), Object::BVHNode(_)));
+    }
+
+    #[test]
+    fn colors_by_deepest_box() {
+        let mut world = world();
+        let colors = HeatMapConfig {
+            max: 2,
+            gradient: vec![FloatRgb::new(0.0, 0.0, 0.0), FloatRgb::new(2.0, 0.0, 0.0)],
+        };
+        let view = BvhViewConfig::default();
+
+        let sample = bvh_sample(ray(9.0, 0.0), &mut world, &view, &colors);
+        assert_eq!(sample.color, FloatRgb::new(2.0, 0.0, 0.0));
+        assert!(sample.first_hit.is_some());
+        // between the two halves of the scene only the root box is passed
+        let sample = bvh_sample(ray(4.5, 0.0), &mut world, &view, &colors);
+        assert_eq!(sample.color, FloatRgb::new(1.0, 0.0, 0.0));
+        assert_eq!(sample.first_hit, None);
+    }
+
+    #[test]
+    fn edges_only_outline_boxes() {
+        let mut world = world();
+        let colors = HeatMapConfig::default();
+        let view = BvhViewConfig {
+            edges: true,
+            edge_width: 0.01,
+        };
+
+        // through the middle of the face of the root box
+        let sample = bvh_sample(ray(0.0, 0.0), &mut world, &view, &colors);
+        assert_eq!(sample.color, FloatRgb::new(0.0, 0.0, 0.0));
+        // along the top edge of every box
+        let sample = bvh_sample(ray(0.5, 0.9999), &mut world, &view, &colors);
+        assert_ne!(sample.color, FloatRgb::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn render_mode_can_be_set_from_the_command_line() {
+        let args = ["scene.json", "--render-mode", "bvh"].map(String::from);
+        assert_eq!(Args::parse_from(args).unwrap().render_mode, Some(RenderMode::Bvh));
+
+        let args = ["--render-mode", "wireframe"].map(String::from);
+        assert!(Args::parse_from(args).is_err());
+    }
+}
+
+#[cfg(test)]
+mod seed_tests {
+    use geometry3d::*;
+    use rtow::builder::*;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::framebuffer::Framebuffer;
+    use rtow::material::Material;
+    use rtow::random::Random;
+    use rtow::renderer::Renderer;
+
+    // a diffuse sphere on a glossy floor seen through a thin lens, so that
+    // the camera, sampler and materials all make random decisions
+    fn config(seed: Option<u64>, progressive: bool) -> Config {
+        let mut config = SceneBuilder::new()
+            .image(ImageBuilder::new().size(24, 16).tile_size(8).build())
+            .camera(
+                CameraBuilder::new()
+                    .look_from(Point3::new(0.0, 0.5, 3.0))
+                    .aperture(0.1)
+                    .build(),
+            )
+            .sphere(Point3::new(0.0, 0.0, 0.0), 0.5)
+            .material(Material::lambertian(FloatRgb::new(0.8, 0.3, 0.3)))
+            .add()
+            .sphere(Point3::new(0.0, -100.5, 0.0), 100.0)
+            .material(Material::metal(FloatRgb::new(0.8, 0.8, 0.8), 0.3))
+            .add()
+            .build();
+        config.sampler.kind = SamplerKind::Random;
+        config.seed = seed;
+        if progressive {
+            config.progressive = Some(ProgressiveConfig { write_interval: 1, time_budget: None });
+        }
+        config
+    }
+
+    fn render_png(config: Config) -> Vec<u8> {
+        let (image, _) = Renderer::new(config).unwrap().render();
+        let mut bytes = Vec::new();
+        image.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn same_seed_gives_identical_images() {
+        for progressive in [false, true] {
+            let first = render_png(config(Some(7), progressive));
+            let second = render_png(config(Some(7), progressive));
+            assert!(first == second);
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_images() {
+        for progressive in [false, true] {
+            let first = render_png(config(Some(7), progressive));
+            let second = render_png(config(Some(8), progressive));
+            assert!(first != second);
+        }
+    }
+
+    #[test]
+    fn seed_is_chosen_when_absent() {
+        assert_eq!(Renderer::new(config(Some(7), false)).unwrap().seed(), 7);
+
+        let first = Renderer::new(config(None, false)).unwrap().seed();
+        let second = Renderer::new(config(None, false)).unwrap().seed();
+        assert_ne!(first, second);
+    }
+
+    // the first number of a few streams, which should only change along
+    // with the generator
+    #[test]
+    fn pixel_streams_are_stable() {
+        let first = |x, y, sample| Random::for_pixel(7, x, y, sample).random::<u64>();
+        let streams = [first(0, 0, 0), first(1, 0, 0), first(0, 1, 0), first(0, 0, 1)];
+        assert_eq!(
+            streams,
+            [8931902679330194167, 15438228197028015226, 3307668346649795745, 285789805131299536]
+        );
+    }
+
+    /// The pixels of a PNG image, leaving out its metadata, which records
+    /// the config the image was rendered with.
+    fn png_pixels(bytes: &[u8]) -> Vec<u8> {
+        let mut reader = png::Decoder::new(bytes).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        pixels
+    }
+
+    #[test]
+    fn tiled_and_progressive_renders_take_the_same_samples() {
+        let tiled = png_pixels(&render_png(config(Some(7), false)));
+        assert!(tiled == png_pixels(&render_png(config(Some(7), true))));
+    }
+
+    /// Renders the tiles of the image across `threads` threads, each taking
+    /// its share of the tiles in reverse order.
+    fn render_on_threads(threads: u32) -> Framebuffer {
+        let workers: Vec<_> = (0..threads)
+            .map(|thread| {
+                std::thread::spawn(move || {
+                    let mut renderer = Renderer::new(config(Some(7), false)).unwrap();
+                    let mut state = renderer.new_state();
+                    let mut pixels = Vec::new();
+                    for tile in (thread..renderer.units()).step_by(threads as usize).rev() {
+                        state.completed = tile;
+                        renderer.render_unit(&mut state);
+                        let tile = renderer.image().tiles().nth(tile as usize).unwrap();
+                        pixels.extend(tile.iter().map(|(x, y)| (x, y, state.framebuffer.pixel(x, y))));
+                    }
+                    pixels
+                })
+            })
+            .collect();
+
+        let mut framebuffer = Framebuffer::new(24, 16);
+        for worker in workers {
+            for (x, y, pixel) in worker.join().unwrap() {
+                framebuffer.set_pixel(x, y, pixel);
+            }
+        }
+        framebuffer
+    }
+
+    #[test]
+    fn render_is_independent_of_threads_and_tile_order() {
+        let renderer = Renderer::new(config(Some(7), false)).unwrap();
+        let mut state = renderer.new_state();
+        let mut renderer = renderer;
+        while !renderer.finished(&state) {
+            renderer.render_unit(&mut state);
+        }
+
+        assert!(render_on_threads(1) == state.framebuffer);
+        assert!(render_on_threads(8) == state.framebuffer);
+    }
+
+    #[test]
+    fn small_generators_render_reproducibly() {
+        let with = |rng| {
+            let mut config = config(Some(7), false);
+            config.rng = rng;
+            render_png(config)
+        };
+        let std = with(RngKind::Std);
+        for rng in [RngKind::Pcg32, RngKind::Xoshiro256] {
+            let first = with(rng);
+            assert!(first == with(rng));
+            assert!(first != std);
+        }
+    }
+
+    #[test]
+    fn seed_is_recorded_in_png() {
+        let bytes = render_png(config(Some(42), false));
+        let reader = png::Decoder::new(&bytes[..]).read_info().unwrap();
+        let text = &reader.info().uncompressed_latin1_text;
+        assert!(text.iter().any(|chunk| chunk.keyword == "Seed" && chunk.text == "42"));
+    }
+}
+
+#[cfg(all(test, feature = "stats"))]
+mod stats_tests {
+    use geometry3d::*;
+    use rtow::builder::*;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::material::Material;
+    use rtow::renderer::Renderer;
+    use rtow::stats::RenderStats;
+
+    // a diffuse sphere under the sky filling part of a 4 x 4 image
+    fn scene() -> SceneBuilder {
+        SceneBuilder::new()
+            .image(ImageBuilder::new().size(4, 4).tile_size(3).build())
+            .camera(CameraBuilder::new().look_from(Point3::new(0.0, 0.0, 2.0)).build())
+            .sphere(Point3::new(0.0, 0.0, 0.0), 0.5)
+            .material(Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5)))
+            .add()
+    }
+
+    fn config() -> Config {
+        scene().build()
+    }
+
+    #[test]
+    fn counts_primary_rays() {
+        // a second sphere, out of sight, so that the scene has a BVH
+        let mut config = scene()
+            .sphere(Point3::new(0.0, 0.0, 10.0), 0.5)
+            .material(Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5)))
+            .add()
+            .build();
+        config.sampler.n = 3;
+        let max_depth = config.sampler.max_depth;
+        let progressive = ProgressiveConfig {
+            write_interval: 1,
+            time_budget: None,
+        };
+        for progressive in [None, Some(progressive)] {
+            config.progressive = progressive;
+            let (_, stats) = Renderer::new(config.clone()).unwrap().render();
+            assert_eq!(stats.primary_rays, 4 * 4 * 9);
+            assert_eq!(stats.paths(), stats.primary_rays);
+            assert!(stats.rays > stats.primary_rays);
+            assert!(stats.aabb_tests > 0);
+            assert!(stats.max_path_length().unwrap() <= max_depth);
+        }
+    }
+
+    #[test]
+    fn counts_shadow_rays() {
+        let mut config = config();
+        config.sampler.max_depth = 1;
+        let (_, stats) = Renderer::new(config.clone()).unwrap().render();
+        // a single bounce only casts the primary ray
+        assert_eq!(stats.rays, stats.primary_rays);
+        assert_eq!(stats.max_path_length(), Some(1));
+
+        let mut config = scene()
+            .sphere(Point3::new(0.0, 2.0, 2.0), 0.5)
+            .material(Material::diffuse_light(FloatRgb::new(4.0, 4.0, 4.0)))
+            .add()
+            .build();
+        config.light_sampling = true;
+        config.sampler.max_depth = 1;
+        let (_, lit) = Renderer::new(config).unwrap().render();
+        assert!(lit.rays > lit.primary_rays);
+    }
+
+    #[test]
+    fn merges_path_lengths() {
+        let mut stats = RenderStats {
+            primary_rays: 2,
+            path_lengths: vec![1, 1],
+            ..RenderStats::default()
+        };
+        stats.merge(&RenderStats {
+            primary_rays: 2,
+            path_lengths: vec![0, 0, 0, 2],
+            ..RenderStats::default()
+        });
+        assert_eq!(stats.primary_rays, 4);
+        assert_eq!(stats.path_lengths, vec![1, 1, 0, 2]);
+        assert_eq!(stats.average_path_length(), Some(7.0 / 4.0));
+        assert_eq!(stats.max_path_length(), Some(3));
+        assert_eq!(RenderStats::default().average_path_length(), None);
+    }
+}
+
+#[cfg(test)]
+mod time_budget_tests {
+    use geometry3d::*;
+    use rtow::builder::*;
+    use rtow::cli::Args;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::material::Material;
+    use rtow::renderer::Renderer;
+
+    // far more passes than can be rendered within the budget
+    fn config(time_budget: Option<f64>) -> Config {
+        let mut config = SceneBuilder::new()
+            .image(ImageBuilder::new().size(16, 16).build())
+            .camera(CameraBuilder::new().look_from(Point3::new(0.0, 0.0, 2.0)).build())
+            .sphere(Point3::new(0.0, 0.0, 0.0), 0.5)
+            .material(Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5)))
+            .add()
+            .build();
+        config.sampler.n = 32;
+        config.progressive = Some(ProgressiveConfig {
+            write_interval: 1,
+            time_budget,
+        });
+        config
+    }
+
+    #[test]
+    fn stops_adding_passes_when_budget_is_spent() {
+        let mut renderer = Renderer::new(config(Some(0.01))).unwrap();
+        let mut state = renderer.new_state();
+        while !renderer.finished(&state) {
+            renderer.render_unit(&mut state);
+        }
+        assert!(state.completed >= 1);
+        assert!(state.completed < renderer.units());
+
+        for (x, y) in [(0, 0), (8, 8), (15, 15)] {
+            assert_eq!(state.framebuffer.pixel(x, y).count(), state.completed);
+        }
+
+        let mut bytes = Vec::new();
+        renderer.resolve(&state).write(&mut bytes).unwrap();
+        let reader = png::Decoder::new(&bytes[..]).read_info().unwrap();
+        let text = &reader.info().uncompressed_latin1_text;
+        let spp = text.iter().find(|chunk| chunk.keyword == "Samples per pixel").unwrap();
+        assert_eq!(spp.text, state.completed.to_string());
+    }
+
+    #[test]
+    fn completes_first_pass_however_small_the_budget() {
+        let mut renderer = Renderer::new(config(Some(1e-9))).unwrap();
+        let mut state = renderer.new_state();
+        assert!(!renderer.finished(&state));
+        renderer.render_unit(&mut state);
+        assert!(renderer.finished(&state));
+    }
+
+    #[test]
+    fn rejects_non_positive_budget() {
+        assert!(Renderer::new(config(Some(0.0))).is_err());
+        assert!(Renderer::new(config(Some(-1.0))).is_err());
+    }
+
+    #[test]
+    fn budget_can_be_set_from_the_command_line() {
+        let args = ["scene.json", "--time-budget", "60"].map(String::from);
+        assert_eq!(Args::parse_from(args).unwrap().time_budget, Some(60.0));
+
+        let args = ["--time-budget", "soon"].map(String::from);
+        assert!(Args::parse_from(args).is_err());
+    }
+}
+
+#[cfg(test)]
+mod animation_tests {
+    use geometry3d::*;
+    use rtow::builder::*;
+    use rtow::cli::Args;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::material::Material;
+    use rtow::object::Sphere;
+    use rtow::renderer::{FrameBvh, Renderer};
+
+    // a glowing sphere moving from left to right at one unit per second
+    // against a black background
+    fn config() -> Config {
+        let mut config = SceneBuilder::new()
+            .image(ImageBuilder::new().filename("frames/moving.png").size(32, 16).build())
+            .camera(CameraBuilder::new().look_from(Point3::new(0.0, 0.0, 3.0)).build())
+            .background(FloatRgb::new(0.0, 0.0, 0.0))
+            .moving_sphere(
+                (Point3::new(-1.0, 0.0, 0.0), 0.0),
+                (Point3::new(1.0, 0.0, 0.0), 2.0),
+                0.5,
+            )
+            .material(Material::diffuse_light(FloatRgb::new(1.0, 1.0, 1.0)))
+            .add()
+            .build();
+        config.animation = Some(AnimationConfig {
+            frames: 3,
+            fps: 1.0,
+            shutter_fraction: 0.5,
+            refit: true,
+        });
+        config.seed = Some(1);
+        config
+    }
+
+    // the mean column of the lit pixels
+    fn centroid(config: Config) -> Float {
+        let (image, _) = Renderer::new(config).unwrap().render();
+        let lit: Vec<u32> = image
+            .iter()
+            .filter(|&(x, y)| image.pixel(x, y).r() > 0.5)
+            .map(|(x, _)| x)
+            .collect();
+        assert!(!lit.is_empty());
+        lit.iter().sum::<u32>() as Float / lit.len() as Float
+    }
+
+    #[test]
+    fn frames_cover_successive_intervals() {
+        let config = config();
+        assert_eq!(config.frames(), 3);
+
+        let frame = config.frame(2).unwrap();
+        assert_eq!((frame.camera.time_min, frame.camera.time_max), (2.0, 2.5));
+        assert_eq!(frame.image.filename, "frames/moving_0002.png");
+        assert!(frame.animation.is_none());
+
+        assert!(config.frame(3).is_err());
+    }
+
+    #[test]
+    fn still_image_is_a_single_frame() {
+        let mut config = config();
+        config.animation = None;
+        assert_eq!(config.frames(), 1);
+        assert_eq!(config.frame(0).unwrap().image.filename, "frames/moving.png");
+        assert!(config.frame(1).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_animation() {
+        let mut config = config();
+        config.animation = Some(AnimationConfig {
+            frames: 2,
+            fps: 0.0,
+            shutter_fraction: 0.5,
+            refit: true,
+        });
+        assert!(config.frame(0).is_err());
+        config.animation = Some(AnimationConfig {
+            frames: 2,
+            fps: 24.0,
+            shutter_fraction: 1.5,
+            refit: true,
+        });
+        assert!(config.frame(0).is_err());
+    }
+
+    #[test]
+    fn moving_sphere_moves_between_frames() {
+        let config = config();
+        let first = centroid(config.frame(0).unwrap());
+        let last = centroid(config.frame(2).unwrap());
+        assert!(last - first > 5.0, "{first} -> {last}");
+    }
+
+    #[test]
+    fn refitting_between_frames_renders_the_same_images() {
+        let mut config = config();
+        config.scene_list.add(
+            Sphere::new(
+                Ray3 {
+                    origin: Point3::new(0.0, -1.0, -1.0),
+                    direction: Vec3::default(),
+                    time: 0.0,
+                },
+                0.5,
+                Material::diffuse_light(FloatRgb::new(1.0, 1.0, 1.0)),
+            )
+            .into(),
+        );
+        let pixels = |renderer: Renderer| {
+            let (image, _) = renderer.render();
+            image.iter().map(|(x, y)| image.pixel(x, y)).collect::<Vec<_>>()
+        };
+        let mut bvh = FrameBvh::default();
+        for frame in 0..config.frames() {
+            let frame = config.frame(frame).unwrap();
+            let refit = pixels(Renderer::for_frame(frame.clone(), &mut bvh).unwrap());
+            assert!(refit == pixels(Renderer::new(frame).unwrap()));
+        }
+        assert_eq!(bvh.builds, 1);
+    }
+
+    #[test]
+    fn frame_can_be_set_from_the_command_line() {
+        let args = ["scene.json", "--frame", "12"].map(String::from);
+        assert_eq!(Args::parse_from(args).unwrap().frame, Some(12));
+    }
+}
+
+#[cfg(test)]
+mod batch_tests {
+    use geometry3d::*;
+    use rtow::batch::*;
+    use rtow::builder::*;
+    use rtow::cli::Args;
+    use rtow::color::*;
+    use rtow::material::Material;
+    use std::path::{Path, PathBuf};
+
+    // writes tiny scenes named `{name}.json` to a fresh directory, each
+    // rendering to `{name}.png` next to it
+    fn scenes(dir: &str, names: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in names {
+            let output = dir.join(format!("{name}.png"));
+            let config = SceneBuilder::new()
+                .image(ImageBuilder::new().filename(output.to_str().unwrap()).size(4, 4).build())
+                .sphere(Point3::new(0.0, 0.0, 0.0), 0.5)
+                .material(Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5)))
+                .add()
+                .build();
+            let json = serde_json::to_string(&config).unwrap();
+            std::fs::write(dir.join(format!("{name}.json")), json).unwrap();
+        }
+        dir
+    }
+
+    fn args(dir: &Path, files: &[&str], extra: &[&str]) -> Args {
+        let files = files.iter().map(|f| dir.join(f).to_string_lossy().into_owned());
+        Args::parse_from(files.chain(extra.iter().map(|s| s.to_string()))).unwrap()
+    }
+
+    #[test]
+    fn renders_every_scene() {
+        let dir = scenes("rtow_batch_test", &["a", "b"]);
+        let report = render_batch(&args(&dir, &["a.json", "b.json"], &[])).unwrap();
+        assert!(report.succeeded());
+        assert_eq!(report.rendered.len(), 2);
+        assert!(dir.join("a.png").exists() && dir.join("b.png").exists());
+        assert_eq!(report.rendered[0].output, dir.join("a.png").to_string_lossy());
+        assert_eq!((report.rendered[1].width, report.rendered[1].height), (4, 4));
+        assert_eq!(report.rendered[1].samples_per_pixel, 16.0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn expands_wildcards() {
+        let dir = scenes("rtow_batch_glob_test", &["scene_1", "scene_2", "other"]);
+        let report = render_batch(&args(&dir, &["scene_?.json"], &[])).unwrap();
+        let outputs: Vec<_> = report.rendered.iter().map(|r| r.output.clone()).collect();
+        let expected: Vec<_> = ["scene_1.png", "scene_2.png"]
+            .iter()
+            .map(|f| dir.join(f).to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(outputs, expected);
+
+        let report = render_batch(&args(&dir, &["*.txt"], &[])).unwrap();
+        assert_eq!(report.failed.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resumed_render_keeps_the_random_seed() {
+        let dir = std::env::temp_dir().join(format!("rtow_resume_seed_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // 6 tiles without a seed, of which the checkpoint keeps the first 4
+        let config = SceneBuilder::new()
+            .image(ImageBuilder::new().size(12, 8).tile_size(4).build())
+            .sphere(Point3::new(0.0, 0.0, 0.0), 0.5)
+            .material(Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5)))
+            .add()
+            .build();
+        let scene = dir.join("scene.json");
+        std::fs::write(&scene, serde_json::to_string(&config).unwrap()).unwrap();
+        let checkpoint = dir.join("checkpoint.bin");
+        // the metadata holds the name of the output, so only the pixels match
+        let pixels = |path: &std::path::Path| {
+            let mut reader = png::Decoder::new(std::fs::File::open(path).unwrap()).read_info().unwrap();
+            let mut pixels = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut pixels).unwrap();
+            pixels
+        };
+        let render = |name: &str, extra: &[&str]| {
+            let output = dir.join(format!("{name}.png"));
+            let mut args = vec![scene.to_str().unwrap(), "--output", output.to_str().unwrap()];
+            args.extend(["--checkpoint", checkpoint.to_str().unwrap(), "--checkpoint-interval", "4"]);
+            args.extend(extra);
+            let args = Args::parse_from(args.into_iter().map(String::from)).unwrap();
+            render_scene(scene.to_str().unwrap(), &args).map(|reports| (reports[0].seed, pixels(&output)))
+        };
+
+        let (seed, straight) = render("straight", &[]).unwrap();
+        let (resumed_seed, resumed) = render("resumed", &["--resume"]).unwrap();
+        assert_eq!(resumed_seed, seed);
+        assert!(resumed == straight);
+
+        let other = (seed ^ 1).to_string();
+        assert!(render("other", &["--resume", "--seed", &other]).is_err());
+        assert!(render("same", &["--resume", "--seed", &seed.to_string()]).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failure_ends_batch_unless_continuing() {
+        let dir = scenes("rtow_batch_error_test", &["a", "b"]);
+        let files = ["a.json", "missing.json", "b.json"];
+
+        let report = render_batch(&args(&dir, &files, &[])).unwrap();
+        assert!(!report.succeeded());
+        assert_eq!((report.rendered.len(), report.failed.len(), report.skipped), (1, 1, 1));
+        assert!(!dir.join("b.png").exists());
+
+        let report = render_batch(&args(&dir, &files, &["--continue-on-error"])).unwrap();
+        assert_eq!((report.rendered.len(), report.failed.len(), report.skipped), (2, 1, 0));
+        assert!(report.failed[0].0.ends_with("missing.json"));
+        assert!(dir.join("b.png").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_shared_output() {
+        let dir = scenes("rtow_batch_output_test", &["a", "b"]);
+        let args = args(&dir, &["a.json", "b.json"], &["--output", "out.png"]);
+        assert!(render_batch(&args).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod golden_tests {
+    use geometry3d::Float;
+    use rtow::cli::Command;
+    use rtow::color::*;
+    use rtow::compare::*;
+    use rtow::framebuffer::Framebuffer;
+    use std::path::PathBuf;
+
+    // the noise of a render at this size is far larger, so any change to
+    // how rays are sampled or scattered is caught
+    const TOLERANCE: Float = 0.002;
+
+    // the golden images are rendered in f64, and f32 draws different random
+    // numbers, so they are only compared when Float is f64
+    fn assert_golden(scene: TestScene) {
+        if Float::MANTISSA_DIGITS == f64::MANTISSA_DIGITS {
+            scene.assert_golden(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden"), TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn diffuse_matches_golden() {
+        assert_golden(TestScene::Diffuse);
+    }
+
+    #[test]
+    fn metal_matches_golden() {
+        assert_golden(TestScene::Metal);
+    }
+
+    #[test]
+    fn dielectric_matches_golden() {
+        assert_golden(TestScene::Dielectric);
+    }
+
+    #[test]
+    fn scenes_differ_from_each_other() {
+        let diffuse = Pixels::decode_png(&TestScene::Diffuse.render_png()[..]).unwrap();
+        let metal = Pixels::decode_png(&TestScene::Metal.render_png()[..]).unwrap();
+        assert!(diffuse.compare(&metal).unwrap().max_rmse() > 0.01);
+        assert_eq!(diffuse.compare(&diffuse).unwrap().max_rmse(), 0.0);
+    }
+
+    #[test]
+    fn measures_per_channel_differences() {
+        let mut a = Framebuffer::new(2, 1);
+        let mut b = Framebuffer::new(2, 1);
+        a.add_sample(0, 0, FloatRgb::new(1.0, 0.5, 0.0), 1.0, 1.0);
+        a.add_sample(1, 0, FloatRgb::new(1.0, 0.5, 0.0), 1.0, 1.0);
+        b.add_sample(0, 0, FloatRgb::new(0.0, 0.5, 0.0), 1.0, 1.0);
+        b.add_sample(1, 0, FloatRgb::new(1.0, 0.5, 0.5), 1.0, 1.0);
+
+        let difference = Pixels::from(&a).compare(&Pixels::from(&b)).unwrap();
+        assert_eq!(difference.rmse, FloatRgb::new((0.5 as Float).sqrt(), 0.0, (0.125 as Float).sqrt()));
+        assert_eq!(difference.max, FloatRgb::new(1.0, 0.0, 0.5));
+
+        assert!(Pixels::from(&a).compare(&Pixels::from(&Framebuffer::new(1, 2))).is_err());
+    }
+
+    #[test]
+    fn compare_is_a_subcommand() {
+        let args = ["compare", "a.png", "b.png"].map(String::from);
+        let command = Command::parse_from(args).unwrap();
+        assert_eq!(command, Command::Compare(String::from("a.png"), String::from("b.png")));
+
+        assert!(Command::parse_from(["compare", "a.png"].map(String::from)).is_err());
+        assert!(matches!(
+            Command::parse_from(["scene.json"].map(String::from)).unwrap(),
+            Command::Render(_)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod config_format_tests {
+    use rtow::cli::Args;
+    use rtow::config::*;
+    use rtow::renderer::Renderer;
+    use std::path::Path;
+
+    // every kind of object, material and texture along with the optional
+    // sections of a config
+    const SCENE: &str = r#"{
+        "image": {"filename": "scene.png", "width": 40, "height": 30, "tile_size": 8, "aovs": ["normal"]},
+        "camera": {
+            "look_from": [0.0, 1.0, 3.0], "look_at": [0.0, 0.0, 0.0], "up": [0.0, 1.0, 0.0],
+            "vertical_fov": 40.0, "aperture": 0.1, "focus_distance": 3.0,
+            "time_min": 0.0, "time_max": 1.0
+        },
+        "sampler": {"type": "random", "n": 2, "max_depth": 8, "filter": {"gaussian": {"sigma": 0.5}}},
+        "background_color": [0.7, 0.8, 1.0],
+        "progressive": {"write_interval": 4},
+        "clamp": {"max_radiance": 10.0, "indirect_only": true},
+        "light_sampling": true,
+        "lights": [{"object": "lamp", "weight": 2.0}],
+        "seed": 12,
+        "rng": "pcg32",
+        "animation": {"frames": 2, "fps": 24.0, "shutter_fraction": 0.5},
+        "scene_list": {"objects": [
+            {"Sphere": {
+                "location": {"origin": [0.0, 0.0, -1.0], "direction": [0.0, 0.0, 0.0], "time": 0.0},
+                "radius": 0.5,
+                "material": {"Lambertian": {"albedo": {"CheckerTexture": {
+                    "odd": {"SolidColor": [0.2, 0.3, 0.1]},
+                    "even": {"NoiseTexture": {"noise": {"U": {"size": 256, "seed": 3}}, "scale": 4.0, "depth": 7}}
+                }}}}
+            }},
+            {"Sphere": {
+                "location": {"origin": [1.0, 0.0, -1.0], "direction": [0.0, 0.5, 0.0], "time": 0.0},
+                "radius": 0.5,
+                "material": {"Metal": {"albedo": [0.8, 0.6, 0.2], "fuzz": 0.1}}
+            }},
+            {"RectPrism": {"U": {
+                "p0": [-2.0, 0.0, -2.0], "p1": [-1.0, 1.0, -1.0],
+                "material": {"Dielectric": {"index_of_refraction": 1.5}}
+            }}},
+            {"Named": {"name": "lamp", "object": {"XZRect": {
+                "x": {"start": -1.0, "end": 1.0}, "z": {"start": -1.0, "end": 1.0}, "y": 3.0,
+                "material": {"DiffuseLight": {"emit": {"SolidColor": [4.0, 4.0, 4.0]}}}
+            }}}},
+            {"Sphere": {
+                "location": {"origin": [0.0, -100.5, -1.0], "direction": [0.0, 0.0, 0.0], "time": 0.0},
+                "radius": 100.0,
+                "material": {"Lambertian": {"albedo": {"ImageTexture": {"U": {"filename": "earthmap.png"}}}}}
+            }}
+        ]}
+    }"#;
+
+    fn json(config: &Config) -> String {
+        config.to_string_as(ConfigFormat::Json).unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let config = Config::parse(SCENE, ConfigFormat::Json).unwrap();
+        let toml = config.to_string_as(ConfigFormat::Toml).unwrap();
+        let from_toml = Config::parse(&toml, ConfigFormat::Toml).unwrap();
+        assert_eq!(json(&from_toml), json(&config));
+
+        let from_json = Config::parse(&json(&from_toml), ConfigFormat::Json).unwrap();
+        assert_eq!(json(&from_json), json(&config));
+    }
+
+    #[test]
+    fn reads_hand_written_toml() {
+        let toml = r#"
+            # a single diffuse sphere
+            background_color = [0.7, 0.8, 1.0]
+
+            [image]
+            filename = "sphere.png"
+            width = 40    # pixels
+            height = 30
+            tile_size = 8
+
+            [camera]
+            look_from = [0.0, 0.0, 1.0]
+            look_at = [0.0, 0.0, 0.0]
+            up = [0.0, 1.0, 0.0]
+            vertical_fov = 90.0
+            aperture = 0.0
+            focus_distance = 1.0
+            time_min = 0.0
+            time_max = 1.0
+
+            [sampler]
+            n = 2
+            max_depth = 10
+
+            [[scene_list.objects]]
+            Sphere = { location = { origin = [0.0, 0.0, -1.0], direction = [0.0, 0.0, 0.0], time = 0.0 }, radius = 0.5, material = { Lambertian = { albedo = { SolidColor = [0.5, 0.5, 0.5] } } } }
+        "#;
+        let path = std::env::temp_dir().join("rtow_config_format_test.toml");
+        std::fs::write(&path, toml).unwrap();
+        let config = Config::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((config.image.width, config.image.height()), (40, 30));
+        assert_eq!(config.scene_list.iter().count(), 1);
+        assert!(Config::parse(toml, ConfigFormat::Json).is_err());
+    }
+
+    #[test]
+    fn format_follows_extension_unless_given() {
+        assert_eq!(ConfigFormat::from_path("scenes/box.toml"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path("scenes/box.TOML"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path("scenes/box.json"), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::from_path("scenes/box"), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::from_path("scenes/box.yaml"), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path("scenes/box.yml"), ConfigFormat::Yaml);
+
+        let args = ["box.txt", "--format", "toml"].map(String::from);
+        assert_eq!(Args::parse_from(args).unwrap().format, Some(ConfigFormat::Toml));
+        assert!(Args::parse_from(["--format", "xml"].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn round_trips_through_yaml() {
+        let config = Config::parse(SCENE, ConfigFormat::Json).unwrap();
+        let yaml = config.to_string_as(ConfigFormat::Yaml).unwrap();
+        let from_yaml = Config::parse(&yaml, ConfigFormat::Yaml).unwrap();
+        assert_eq!(json(&from_yaml), json(&config));
+    }
+
+    fn render(mut config: Config) -> Vec<u8> {
+        config.image.width = 12;
+        config.image.height = Some(12);
+        config.sampler.n = 1;
+        config.seed = Some(1);
+        let (image, _) = Renderer::new(config).unwrap().render();
+        let mut bytes = Vec::new();
+        image.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn yaml_example_matches_json_scene() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let yaml = Config::read(root.join("examples/cornell_box.yaml")).unwrap();
+        let json = Config::read(root.join("../scenes/cornell_box.json")).unwrap();
+        assert_eq!(
+            yaml.to_string_as(ConfigFormat::Json).unwrap(),
+            json.to_string_as(ConfigFormat::Json).unwrap()
+        );
+        assert_eq!(render(yaml), render(json));
+    }
+
+    #[test]
+    fn yaml_errors_give_the_location() {
+        let yaml = "image:\n  width: 40\n  height: tall\n";
+        let error = Config::parse(yaml, ConfigFormat::Yaml).unwrap_err().to_string();
+        assert!(error.contains("line 3"), "{error}");
+    }
+}
+
+#[cfg(test)]
+mod config_error_tests {
+    use geometry3d::*;
+    use rtow::builder::*;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::material::Material;
+
+    fn scene(format: ConfigFormat) -> String {
+        SceneBuilder::new()
+            .sphere(Point3::new(0.0, 0.0, -1.0), 0.5)
+            .material(Material::metal(FloatRgb::new(0.8, 0.8, 0.8), 0.0))
+            .add()
+            .sphere(Point3::new(0.0, -100.5, -1.0), 100.0)
+            .material(Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5)))
+            .add()
+            .build()
+            .to_string_as(format)
+            .unwrap()
+    }
+
+    fn parse_error(text: &str, format: ConfigFormat) -> String {
+        Config::parse(text, format).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn unknown_variant_gives_path_location_and_suggestion() {
+        let text = scene(ConfigFormat::Json).replacen("Lambertian", "Lambertain", 1);
+        let error = parse_error(&text, ConfigFormat::Json);
+        assert!(error.starts_with("scene_list.objects[1].Sphere.material: "), "{error}");
+        assert!(error.contains("unknown variant `Lambertain`"), "{error}");
+        assert!(error.contains(" at line "), "{error}");
+        assert!(error.ends_with("Did you mean `Lambertian`?"), "{error}");
+    }
+
+    #[test]
+    fn suggestions_ignore_case() {
+        let text = scene(ConfigFormat::Json).replacen("\"Sphere\"", "\"sphere\"", 1);
+        let error = parse_error(&text, ConfigFormat::Json);
+        assert!(error.starts_with("scene_list.objects[0]: "), "{error}");
+        assert!(error.ends_with("Did you mean `Sphere`?"), "{error}");
+    }
+
+    #[test]
+    fn no_suggestion_without_a_close_variant() {
+        let text = scene(ConfigFormat::Json).replacen("\"Metal\"", "\"Glass\"", 1);
+        let error = parse_error(&text, ConfigFormat::Json);
+        assert!(error.contains("unknown variant `Glass`"), "{error}");
+        assert!(!error.contains("Did you mean"), "{error}");
+    }
+
+    #[test]
+    fn missing_field_gives_its_parent() {
+        let text = scene(ConfigFormat::Json).replacen("\"radius\"", "\"raduis\"", 1);
+        let error = parse_error(&text, ConfigFormat::Json);
+        assert!(error.starts_with("scene_list.objects[0].Sphere: missing field `radius`"), "{error}");
+    }
+
+    #[test]
+    fn short_point_is_an_error() {
+        let mut config: serde_json::Value = serde_json::from_str(&scene(ConfigFormat::Json)).unwrap();
+        config["camera"]["look_from"] = serde_json::json!([0.0, 1.0]);
+        let error = parse_error(&config.to_string(), ConfigFormat::Json);
+        assert!(error.starts_with("camera.look_from: invalid length 2"), "{error}");
+    }
+
+    #[test]
+    fn other_formats_are_described_too() {
+        let text = scene(ConfigFormat::Toml).replacen("Metal", "Metl", 1);
+        let error = parse_error(&text, ConfigFormat::Toml);
+        assert!(error.starts_with("scene_list.objects[0].Sphere.material: "), "{error}");
+        assert!(error.contains("line"), "{error}");
+        assert!(error.ends_with("Did you mean `Metal`?"), "{error}");
+
+        let text = scene(ConfigFormat::Yaml).replacen("Metal", "Metl", 1);
+        let error = parse_error(&text, ConfigFormat::Yaml);
+        assert!(error.contains("scene_list.objects[0].Sphere.material"), "{error}");
+        assert!(error.contains("line"), "{error}");
+        assert!(error.ends_with("Did you mean `Metal`?"), "{error}");
+    }
+}
+
+#[cfg(test)]
+mod config_default_tests {
+    use rtow::config::*;
+    use rtow::renderer::Renderer;
+
+    const SPHERE: &str = r#"{
+        "scene_list": {"objects": [{"Sphere": {
+            "location": {"origin": [0.0, 0.0, 0.0], "direction": [0.0, 0.0, 0.0], "time": 0.0},
+            "radius": 1.0,
+            "material": {"Lambertian": {"albedo": {"SolidColor": [0.8, 0.2, 0.2]}}}
+        }}]}
+    }"#;
+
+    #[test]
+    fn only_scene_list_is_required() {
+        let config = Config::parse(SPHERE, ConfigFormat::Json).unwrap();
+        let image = ImageConfig::default();
+        assert_eq!((config.image.width, config.image.height()), (image.width, image.height()));
+        assert_eq!(config.image.filename, "render.png");
+        assert_eq!(config.camera.vertical_fov, CameraConfig::default().vertical_fov);
+        assert_eq!(config.sampler.n, SamplerConfig::default().n);
+        assert_eq!(config.background_color, Config::default_background_color());
+
+        assert!(Config::parse("{}", ConfigFormat::Json).unwrap_err().to_string().contains("scene_list"));
+    }
+
+    #[test]
+    fn sections_can_be_partial() {
+        let text = SPHERE.replacen('{', r#"{"image": {"width": 64}, "sampler": {"n": 2},"#, 1);
+        let config = Config::parse(&text, ConfigFormat::Json).unwrap();
+        assert_eq!((config.image.width, config.image.height()), (64, 36));
+        assert_eq!(config.image.tile_size, ImageConfig::default().tile_size);
+        assert_eq!((config.sampler.n, config.sampler.max_depth), (2, 50));
+    }
+
+    #[test]
+    fn minimal_scene_renders() {
+        let mut config = Config::parse(SPHERE, ConfigFormat::Json).unwrap();
+        config.image.width = 40;
+        config.image.height = Some(24);
+        config.sampler.n = 1;
+        let (image, _) = Renderer::new(config).unwrap().render();
+
+        // the sphere fills the middle of the default view against the sky
+        let center = image.pixel(20, 12);
+        assert!(center.r() > center.b(), "{center:?}");
+        let corner = image.pixel(0, 0);
+        assert!(corner.b() > corner.r(), "{corner:?}");
+    }
+}
+
+#[cfg(test)]
+mod validation_tests {
+    use geometry3d::*;
+    use rtow::builder::*;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::material::Material;
+    use rtow::renderer::Renderer;
+
+    fn config() -> Config {
+        SceneBuilder::new()
+            .sphere(Point3::new(0.0, 0.0, -1.0), 0.5)
+            .material(Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5)))
+            .add()
+            .build()
+    }
+
+    // the paths of the problems with the config after `change`
+    fn problems(change: impl FnOnce(&mut Config)) -> Vec<String> {
+        let mut config = config();
+        change(&mut config);
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|e| e.path).collect(),
+        }
+    }
+
+    #[test]
+    fn valid_config() {
+        assert_eq!(problems(|_| ()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn image_rules() {
+        assert_eq!(problems(|c| c.image.width = 0), ["image.width"]);
+        assert_eq!(problems(|c| c.image.height = Some(0)), ["image.height"]);
+        assert_eq!(problems(|c| c.image.tile_size = 0), ["image.tile_size"]);
+        assert_eq!(
+            problems(|c| {
+                c.image.denoise = true;
+                c.image.denoise_strength = 0.0;
+            }),
+            ["image.denoise_strength"]
+        );
+        assert_eq!(
+            problems(|c| {
+                c.image.filename = "-".to_string();
+                c.image.aovs = vec![Aov::Normal];
+            }),
+            ["image.aovs"]
+        );
+    }
+
+    #[test]
+    fn camera_rules() {
+        assert_eq!(problems(|c| c.camera.look_at = c.camera.look_from), ["camera.look_at"]);
+        assert_eq!(problems(|c| c.camera.up = Vec3::new(0.0, 0.0, 2.0)), ["camera.up"]);
+        assert_eq!(problems(|c| c.camera.vertical_fov = 0.0), ["camera.vertical_fov"]);
+        assert_eq!(problems(|c| c.camera.vertical_fov = 180.0), ["camera.vertical_fov"]);
+        assert_eq!(problems(|c| c.camera.vertical_fov = Float::NAN), ["camera.vertical_fov"]);
+        assert_eq!(problems(|c| c.camera.aperture = -0.1), ["camera.aperture"]);
+        assert_eq!(problems(|c| c.camera.focus_distance = 0.0), ["camera.focus_distance"]);
+        assert_eq!(problems(|c| c.camera.time_max = -1.0), ["camera.time_max"]);
+    }
+
+    #[test]
+    fn camera_inside_an_object_warns() {
+        let mut config = config();
+        assert_eq!(config.warnings(), []);
+
+        // a corner of the bounding box of the sphere, outside the sphere
+        config.camera.look_from = Point3::new(0.45, 0.45, -0.55);
+        let warnings = config.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, "camera.look_from");
+        assert!(warnings[0].message.contains("scene_list.objects[0]"), "{}", warnings[0]);
+        assert_eq!(problems(|c| c.camera.look_from = Point3::new(0.0, 0.0, -1.0)), Vec::<String>::new());
+    }
+
+    #[test]
+    fn sampler_rules() {
+        assert_eq!(problems(|c| c.sampler.n = 0), ["sampler.n"]);
+        let adaptive = |min_samples, max_samples| AdaptiveConfig {
+            min_samples,
+            max_samples,
+            tolerance: 0.01,
+        };
+        assert_eq!(problems(|c| c.sampler.adaptive = Some(adaptive(0, 4))), ["sampler.adaptive.min_samples"]);
+        assert_eq!(problems(|c| c.sampler.adaptive = Some(adaptive(8, 4))), ["sampler.adaptive.max_samples"]);
+
+        for sigma in [0.0, -0.5, Float::NAN, Float::INFINITY] {
+            assert_eq!(
+                problems(|c| c.sampler.filter = Filter::Gaussian { sigma }),
+                ["sampler.filter.sigma"]
+            );
+        }
+        assert_eq!(problems(|c| c.sampler.filter = Filter::Gaussian { sigma: 0.5 }), Vec::<String>::new());
+    }
+
+    #[test]
+    fn sample_region_rules() {
+        let region = |x, width, samples| SampleRegion {
+            x,
+            y: 0,
+            width,
+            height: 1,
+            samples,
+        };
+        assert_eq!(problems(|c| c.sampler.regions = vec![region(0, 1, 4)]), Vec::<String>::new());
+        assert_eq!(
+            problems(|c| c.sampler.regions = vec![region(0, 1, 4), region(0, 1, 0)]),
+            ["sampler.regions[1].samples"]
+        );
+        assert_eq!(
+            problems(|c| c.sampler.regions = vec![region(u32::MAX, 2, 4)]),
+            ["sampler.regions[0].width"]
+        );
+    }
+
+    #[test]
+    fn render_option_rules() {
+        let progressive = |write_interval, time_budget| ProgressiveConfig {
+            write_interval,
+            time_budget,
+        };
+        assert_eq!(problems(|c| c.progressive = Some(progressive(0, None))), ["progressive.write_interval"]);
+        assert_eq!(
+            problems(|c| c.progressive = Some(progressive(1, Some(-1.0)))),
+            ["progressive.time_budget"]
+        );
+        assert_eq!(
+            problems(|c| {
+                c.progressive = Some(progressive(1, None));
+                c.sampler.adaptive = Some(AdaptiveConfig {
+                    min_samples: 4,
+                    max_samples: 16,
+                    tolerance: 0.01,
+                });
+            }),
+            ["progressive"]
+        );
+        assert_eq!(
+            problems(|c| {
+                c.clamp = Some(ClampConfig {
+                    max_radiance: -1.0,
+                    indirect_only: false,
+                })
+            }),
+            ["clamp.max_radiance"]
+        );
+
+        let animation = |frames, fps, shutter_fraction| AnimationConfig {
+            frames,
+            fps,
+            shutter_fraction,
+            refit: true,
+        };
+        assert_eq!(problems(|c| c.animation = Some(animation(0, 24.0, 0.5))), ["animation.frames"]);
+        assert_eq!(problems(|c| c.animation = Some(animation(2, 0.0, 0.5))), ["animation.fps"]);
+        assert_eq!(
+            problems(|c| c.animation = Some(animation(2, 24.0, 1.5))),
+            ["animation.shutter_fraction"]
+        );
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        assert_eq!(
+            problems(|c| {
+                c.image.width = 0;
+                c.camera.look_at = c.camera.look_from;
+                c.camera.aperture = -1.0;
+                c.sampler.n = 0;
+            }),
+            ["image.width", "camera.look_at", "camera.aperture", "sampler.n"]
+        );
+    }
+
+    #[test]
+    fn invalid_configs_are_not_read_or_rendered() {
+        let mut config = config();
+        config.camera.look_at = config.camera.look_from;
+        config.camera.time_max = -1.0;
+
+        let error = Renderer::new(config.clone()).err().unwrap().to_string();
+        assert_eq!(
+            error,
+            "The config is invalid:\n  camera.look_at: cannot be the same point as look_from\n  \
+             camera.time_max: cannot be before time_min"
+        );
+
+        let path = std::env::temp_dir().join("rtow_validation_test.json");
+        std::fs::write(&path, config.to_string_as(ConfigFormat::Json).unwrap()).unwrap();
+        let read = Config::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.err().unwrap().to_string(), error);
+    }
+}
+
+#[cfg(test)]
+mod include_tests {
+    use geometry3d::Float;
+    use rtow::config::*;
+    use rtow::renderer::Renderer;
+    use std::path::{Path, PathBuf};
+
+    const CAMERA: &str = r#"{
+        "look_from": [0.0, 1.0, 3.0], "look_at": [0.0, 0.0, -1.0], "up": [0.0, 1.0, 0.0],
+        "vertical_fov": 40.0, "aperture": 0.0, "focus_distance": 4.0,
+        "time_min": 0.0, "time_max": 0.0
+    }"#;
+    const WHITE: &str = r#"{"Lambertian": {"albedo": {"SolidColor": [0.73, 0.73, 0.73]}}}"#;
+
+    fn sphere(x: Float, material: &str) -> String {
+        format!(
+            r#"{{"Sphere": {{
+                "location": {{"origin": [{x}, 0.0, -1.0], "direction": [0.0, 0.0, 0.0], "time": 0.0}},
+                "radius": 0.5, "material": {material}
+            }}}}"#
+        )
+    }
+
+    fn scene(camera: &str, objects: &[String]) -> String {
+        format!(
+            r#"{{
+                "image": {{"filename": "include.png", "width": 16, "height": 12}},
+                "sampler": {{"n": 1, "max_depth": 4}},
+                "seed": 3,
+                "camera": {camera},
+                "scene_list": {{"objects": [{}]}}
+            }}"#,
+            objects.join(", ")
+        )
+    }
+
+    // writes each (name, text) file to a fresh directory
+    fn files(dir: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        for (name, text) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    fn render(path: &Path) -> Vec<u8> {
+        let (image, _) = Renderer::new(Config::read(path).unwrap()).unwrap().render();
+        let mut bytes = Vec::new();
+        image.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn split_scene_matches_merged_scene() {
+        let merged = scene(CAMERA, &[sphere(-1.0, WHITE), sphere(0.0, WHITE), sphere(1.0, WHITE)]);
+        let include = |file: &str| format!(r#"{{"include": "{file}"}}"#);
+        let split = scene(
+            &include("presets/camera.json"),
+            &[sphere(-1.0, WHITE), include("parts/objects.json")],
+        );
+        let objects = format!("[{}, {}]", sphere(0.0, WHITE), sphere(1.0, &include("white.json")));
+        let dir = files(
+            "rtow_include_test",
+            &[
+                ("merged.json", &merged),
+                ("split.json", &split),
+                ("presets/camera.json", CAMERA),
+                ("parts/objects.json", &objects),
+                ("parts/white.json", WHITE),
+            ],
+        );
+
+        let merged_config = Config::read(dir.join("merged.json")).unwrap();
+        let split_config = Config::read(dir.join("split.json")).unwrap();
+        assert_eq!(
+            split_config.to_string_as(ConfigFormat::Json).unwrap(),
+            merged_config.to_string_as(ConfigFormat::Json).unwrap()
+        );
+        assert_eq!(render(&dir.join("split.json")), render(&dir.join("merged.json")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn includes_can_mix_formats() {
+        let camera = "look_from: [0.0, 1.0, 3.0]\nlook_at: [0.0, 0.0, -1.0]\nvertical_fov: 40.0\n";
+        let split = scene(r#"{"include": "camera.yaml"}"#, &[sphere(0.0, WHITE)]);
+        let dir = files("rtow_include_format_test", &[("scene.json", &split), ("camera.yaml", camera)]);
+        let config = Config::read(dir.join("scene.json")).unwrap();
+        assert_eq!(config.camera.vertical_fov, 40.0);
+        assert_eq!(config.camera.focus_distance, CameraConfig::default().focus_distance);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let a = scene(CAMERA, &[r#"{"include": "b.json"}"#.to_string()]);
+        let b = r#"[{"include": "c.json"}]"#;
+        let c = r#"[{"include": "b.json"}]"#;
+        let dir = files("rtow_include_cycle_test", &[("a.json", &a), ("b.json", b), ("c.json", c)]);
+        let error = Config::read(dir.join("a.json")).unwrap_err().to_string();
+        let name = |file: &str| dir.join(file).canonicalize().unwrap().display().to_string();
+        let chain = ["a.json", "b.json", "c.json", "b.json"].map(name).join(" -> ");
+        assert_eq!(error, format!("Include cycle: {chain}"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn errors_give_the_include_chain() {
+        let a = scene(r#"{"include": "camera.json"}"#, &[]);
+        let dir = files("rtow_include_error_test", &[("a.json", &a), ("camera.json", "{\"look_from\": ")]);
+        let error = Config::read(dir.join("a.json")).unwrap_err().to_string();
+        let chain = format!(
+            "{} -> {}: ",
+            dir.join("a.json").canonicalize().unwrap().display(),
+            dir.join("camera.json").canonicalize().unwrap().display()
+        );
+        assert!(error.starts_with(&chain), "{error}");
+        assert!(error.contains("line 1"), "{error}");
+
+        std::fs::remove_file(dir.join("camera.json")).unwrap();
+        let error = Config::read(dir.join("a.json")).unwrap_err().to_string();
+        assert!(error.contains("camera.json"), "{error}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod override_tests {
+    use geometry3d::*;
+    use rtow::batch::*;
+    use rtow::builder::*;
+    use rtow::cli::Args;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::material::Material;
+    use std::path::PathBuf;
+
+    fn config() -> Config {
+        SceneBuilder::new()
+            .image(ImageBuilder::new().size(8, 6).build())
+            .sphere(Point3::new(0.0, 0.0, -1.0), 0.5)
+            .material(Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5)))
+            .add()
+            .build()
+    }
+
+    fn args(args: &[&str]) -> Args {
+        Args::parse_from(args.iter().map(|s| s.to_string())).unwrap()
+    }
+
+    // writes the config to `{name}.json` in the temporary directory
+    fn scene(name: &str, config: &Config) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}.json"));
+        std::fs::write(&path, config.to_string_as(ConfigFormat::Json).unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn flags_override_the_config() {
+        let mut config = config();
+        let args = args(&[
+            "scene.json", "--width", "20", "--height", "10", "--samples", "3", "--max-depth", "7",
+            "--seed", "9",
+        ]);
+        assert_eq!(args.filenames, ["scene.json"]);
+        args.apply(&mut config).unwrap();
+        assert_eq!((config.image.width, config.image.height()), (20, 10));
+        assert_eq!((config.sampler.n, config.sampler.max_depth), (3, 7));
+        assert_eq!(config.seed, Some(9));
+    }
+
+    #[test]
+    fn set_patches_any_field() {
+        let mut config = config();
+        args(&[
+            "--set", "camera.vertical_fov=30",
+            "--set", "image.filename=out/render.png",
+            "--set", "background_color=[0.0, 0.0, 0.0]",
+            "--set", "scene_list.objects.0.Sphere.radius=0.25",
+            "--set", "progressive.write_interval=4",
+        ])
+        .apply(&mut config)
+        .unwrap();
+        assert_eq!(config.camera.vertical_fov, 30.0);
+        assert_eq!(config.image.filename, "out/render.png");
+        assert_eq!(config.background_color, FloatRgb::new(0.0, 0.0, 0.0));
+        assert!(config.to_string_as(ConfigFormat::Json).unwrap().contains("\"radius\": 0.25"));
+        assert_eq!(config.progressive.unwrap().write_interval, 4);
+        assert_eq!(config.progressive.unwrap().time_budget, None);
+    }
+
+    #[test]
+    fn bad_sets_are_errors() {
+        assert!(Args::parse_from(["--set", "camera.vertical_fov"].map(String::from)).is_err());
+
+        let error = |set: &str| args(&["--set", set]).apply(&mut config()).unwrap_err().to_string();
+        assert_eq!(error("camera.fov=30"), "Cannot set camera.fov as there is no `fov`.");
+        assert_eq!(
+            error("scene_list.objects.3.Sphere=1"),
+            "Cannot set scene_list.objects.3.Sphere as there is no `3`."
+        );
+        assert!(error("camera.vertical_fov=wide").starts_with("Cannot set camera.vertical_fov: "));
+    }
+
+    #[test]
+    fn overrides_are_validated() {
+        let path = scene("rtow_override_validate_test", &config());
+        let error = load_scene(path.to_str().unwrap(), &args(&["--samples", "0"])).unwrap_err();
+        assert!(error.to_string().contains("sampler.n: must be at least one"), "{error}");
+        std::fs::remove_file(&path).unwrap();
+
+        // an invalid config can be fixed from the command line
+        let mut invalid = config();
+        invalid.camera.vertical_fov = 0.0;
+        let path = scene("rtow_override_fix_test", &invalid);
+        let filename = path.to_str().unwrap();
+        assert!(load_scene(filename, &args(&[])).is_err());
+        let config = load_scene(filename, &args(&["--set", "camera.vertical_fov=45"])).unwrap();
+        assert_eq!(config.camera.vertical_fov, 45.0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn overrides_are_recorded_in_png() {
+        let output = std::env::temp_dir().join("rtow_override_png_test.png");
+        let mut config = config();
+        config.image.filename = output.to_string_lossy().into_owned();
+        let path = scene("rtow_override_png_test", &config);
+        let args = args(&[
+            path.to_str().unwrap(),
+            "--samples",
+            "2",
+            "--seed",
+            "11",
+            "--width",
+            "5",
+            "--max-depth",
+            "3",
+            "--set",
+            "camera.aperture=0.25",
+        ]);
+        render_scene(path.to_str().unwrap(), &args).unwrap();
+
+        let reader = png::Decoder::new(std::fs::File::open(&output).unwrap()).read_info().unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (5, 6));
+        let text: Vec<_> = reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.as_str(), chunk.text.as_str()))
+            .collect();
+        assert_eq!(text, [("Seed", "11"), ("Samples per pixel", "4")]);
+
+        // the config as rendered, overrides and all
+        let chunk = reader.info().utf8_text.iter().find(|chunk| chunk.keyword == "Config").unwrap();
+        let recorded = Config::parse(&chunk.get_text().unwrap(), ConfigFormat::Json).unwrap();
+        assert_eq!(recorded.seed, Some(11));
+        assert_eq!((recorded.sampler.n, recorded.sampler.max_depth), (2, 3));
+        assert_eq!(recorded.image.width, 5);
+        assert_eq!(recorded.camera.aperture, 0.25);
+        std::fs::remove_file(&output).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod relative_path_tests {
+    use geometry3d::*;
+    use rtow::batch::render_scene;
+    use rtow::builder::*;
+    use rtow::cli::Args;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::material::Material;
+    use rtow::renderer::Renderer;
+    use rtow::texture::ImageTexture;
+    use std::path::{Path, PathBuf};
+
+    // a fresh directory holding a red texture at `textures/red.png`
+    fn layout(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("textures")).unwrap();
+        let file = std::fs::File::create(dir.join("textures/red.png")).unwrap();
+        let mut encoder = png::Encoder::new(file, 2, 2);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&[255, 0, 0].repeat(4)).unwrap();
+        dir
+    }
+
+    // a sphere glowing with the texture in front of a black background
+    fn config(texture: &str) -> Config {
+        SceneBuilder::new()
+            .image(ImageBuilder::new().filename("relative.png").size(8, 8).build())
+            .background(FloatRgb::new(0.0, 0.0, 0.0))
+            .sphere(Point3::new(0.0, 0.0, 0.0), 0.5)
+            .material(Material::diffuse_light(ImageTexture::new(texture.to_string())))
+            .add()
+            .build()
+    }
+
+    // the color of the middle of the sphere, cyan if the texture was not found
+    fn center(config: Config) -> FloatRgb {
+        let (image, _) = Renderer::new(config).unwrap().render();
+        image.pixel(4, 4)
+    }
+
+    fn write(config: &Config, path: &Path) {
+        std::fs::write(path, config.to_string_as(ConfigFormat::Json).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn textures_are_relative_to_the_scene_file() {
+        let dir = layout("rtow_relative_texture_test");
+        write(&config("textures/red.png"), &dir.join("scene.json"));
+        assert_ne!(std::env::current_dir().unwrap(), dir);
+
+        let config = Config::read(dir.join("scene.json")).unwrap();
+        assert_eq!(config.base_dir.as_deref(), Some(dir.as_path()));
+        assert_eq!(center(config), FloatRgb::new(1.0, 0.0, 0.0));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn base_dir_can_be_set_in_code() {
+        let dir = layout("rtow_relative_base_dir_test");
+        assert_eq!(center(config("textures/red.png")), FloatRgb::new(0.0, 1.0, 1.0));
+
+        let mut relative = config("textures/red.png");
+        relative.base_dir = Some(dir.clone());
+        assert_eq!(center(relative), FloatRgb::new(1.0, 0.0, 0.0));
+
+        let mut absolute = config(dir.join("textures/red.png").to_str().unwrap());
+        absolute.base_dir = Some(PathBuf::from("elsewhere"));
+        assert_eq!(center(absolute), FloatRgb::new(1.0, 0.0, 0.0));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn output_can_be_relative_to_the_scene_file() {
+        let dir = layout("rtow_relative_output_test");
+        let mut config = config("textures/red.png");
+        config.image.output_relative_to_scene = true;
+        let scene = dir.join("scene.json");
+        write(&config, &scene);
+
+        let args = Args::parse_from([scene.to_string_lossy().into_owned()]).unwrap();
+        let report = render_scene(scene.to_str().unwrap(), &args).unwrap();
+        assert_eq!(report[0].output, dir.join("relative.png").to_string_lossy());
+        assert!(dir.join("relative.png").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod resolved_config_tests {
+    use rtow::batch::*;
+    use rtow::cli::Args;
+    use rtow::compare::Pixels;
+    use rtow::config::*;
+    use std::path::PathBuf;
+
+    // a fresh directory holding `scene.json`, a lit, textured sphere that
+    // relies on defaults and a relative texture path
+    fn layout(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = std::fs::File::create(dir.join("checks.png")).unwrap();
+        let mut encoder = png::Encoder::new(file, 2, 2);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data = [[255, 0, 0], [0, 0, 255], [0, 0, 255], [255, 0, 0]].concat();
+        encoder.write_header().unwrap().write_image_data(&data).unwrap();
+
+        let scene = format!(
+            r#"{{
+                "image": {{"filename": "{}"}},
+                "scene_list": {{"objects": [{{"Sphere": {{
+                    "location": {{"origin": [0.0, 0.0, 0.0], "direction": [0.0, 0.0, 0.0], "time": 0.0}},
+                    "radius": 1.0,
+                    "material": {{"Lambertian": {{"albedo": {{"ImageTexture": {{"U": {{"filename": "checks.png"}}}}}}}}}}
+                }}}}]}}
+            }}"#,
+            dir.join("first.png").display()
+        );
+        std::fs::write(dir.join("scene.json"), scene).unwrap();
+        dir
+    }
+
+    fn args(args: &[&str]) -> Args {
+        Args::parse_from(args.iter().map(|s| s.to_string())).unwrap()
+    }
+
+    #[test]
+    fn resolved_config_reproduces_the_render() {
+        let dir = layout("rtow_resolved_test");
+        let scene = dir.join("scene.json");
+        let scene = scene.to_str().unwrap();
+        render_scene(scene, &args(&[scene, "--width", "16", "--height", "12", "--samples", "1", "--emit-resolved"]))
+            .unwrap();
+
+        let resolved = dir.join("scene.resolved.json");
+        let config = Config::read(&resolved).unwrap();
+        assert!(config.seed.is_some());
+        assert_eq!((config.image.width, config.image.height, config.sampler.n), (16, Some(12), 1));
+        assert_eq!(config.camera.vertical_fov, CameraConfig::default().vertical_fov);
+        let text = std::fs::read_to_string(&resolved).unwrap();
+        assert!(text.contains(&format!("\"{}\"", dir.join("checks.png").display())), "{text}");
+
+        let resolved = resolved.to_str().unwrap();
+        let second = dir.join("second.png");
+        render_scene(resolved, &args(&[resolved, "--output", second.to_str().unwrap()])).unwrap();
+        let first = Pixels::read_png(dir.join("first.png")).unwrap();
+        let second = Pixels::read_png(second).unwrap();
+        assert_eq!(first, second);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resolved_config_can_be_written_anywhere() {
+        let dir = layout("rtow_resolved_path_test");
+        let scene = dir.join("scene.json");
+        let scene = scene.to_str().unwrap();
+        let resolved = dir.join("elsewhere.json");
+        let flag = format!("--emit-resolved={}", resolved.display());
+
+        let args = args(&[scene, "--width", "4", "--height", "4", "--samples", "1", &flag]);
+        assert_eq!(args.emit_resolved, Some(Some(resolved.to_string_lossy().into_owned())));
+        render_scene(scene, &args).unwrap();
+        assert!(Config::read(&resolved).is_ok());
+
+        let both = Args::parse_from([scene, scene, &flag].map(String::from)).unwrap();
+        assert!(render_batch(&both).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod aspect_ratio_tests {
+    use rtow::config::*;
+    use rtow::image::Image;
+
+    fn image(json: &str) -> ImageConfig {
+        let text = format!(r#"{{"image": {json}, "scene_list": {{"objects": []}}}}"#);
+        Config::parse(&text, ConfigFormat::Json).unwrap().image
+    }
+
+    fn problems(json: &str) -> Vec<String> {
+        let text = format!(r#"{{"image": {json}, "scene_list": {{"objects": []}}}}"#);
+        let errors = Config::parse(&text, ConfigFormat::Json).unwrap().validate().unwrap_err();
+        errors.into_iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn height_is_given_or_follows_from_the_aspect_ratio() {
+        assert_eq!(image(r#"{"width": 300, "height": 100}"#).height(), 100);
+        assert_eq!(image(r#"{"width": 300, "aspect_ratio": 1.5}"#).height(), 200);
+        assert_eq!(image(r#"{"width": 100, "aspect_ratio": 3.0}"#).height(), 33);
+        assert_eq!(image(r#"{"width": 160}"#).height(), 90);
+    }
+
+    #[test]
+    fn camera_matches_the_image() {
+        let image = Image::new(image(r#"{"width": 300, "aspect_ratio": 1.5}"#));
+        assert_eq!((image.width, image.height), (300, 200));
+        assert_eq!(image.aspect_ratio, 1.5);
+    }
+
+    #[test]
+    fn height_and_aspect_ratio_conflict() {
+        assert_eq!(
+            problems(r#"{"width": 300, "height": 100, "aspect_ratio": 1.5}"#),
+            ["image.aspect_ratio: cannot be given together with height"]
+        );
+        assert_eq!(problems(r#"{"aspect_ratio": -1.0}"#), ["image.aspect_ratio: must be positive"]);
+        assert_eq!(
+            problems(r#"{"width": 10, "aspect_ratio": 100.0}"#),
+            ["image.aspect_ratio: leaves the image less than one pixel high"]
+        );
+    }
+
+    #[test]
+    fn resolved_config_gives_the_height() {
+        let mut config = Config::parse(
+            r#"{"image": {"width": 300, "aspect_ratio": 1.5}, "scene_list": {"objects": []}}"#,
+            ConfigFormat::Json,
+        )
+        .unwrap();
+        config.base_dir = Some(std::env::temp_dir());
+        let resolved = Config::parse(&config.resolved_json().unwrap(), ConfigFormat::Json).unwrap();
+        assert_eq!((resolved.image.height, resolved.image.aspect_ratio), (Some(200), None));
+        assert!(resolved.validate().is_ok());
+    }
+}
+
+#[cfg(test)]
+mod generate_tests {
+    use rtow::cli::Command;
+    use rtow::color::FloatRgb;
+    use rtow::config::*;
+    use rtow::generate::*;
+    use rtow::renderer::Renderer;
+
+    fn objects(config: &Config) -> usize {
+        let value = serde_json::to_value(&config.scene_list).unwrap();
+        value["objects"].as_array().unwrap().len()
+    }
+
+    // writes the scene as the subcommand would, then reads it back
+    fn round_trip(generator: Generator, name: &str) -> Config {
+        let path = std::env::temp_dir().join(name);
+        let config = generator.config();
+        std::fs::write(&path, config.to_string_as(ConfigFormat::from_path(&path)).unwrap()).unwrap();
+        let read = Config::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        read
+    }
+
+    fn renders(mut config: Config) {
+        config.image.filename = String::from("generate_test.png");
+        (config.image.width, config.image.height) = (8, Some(6));
+        config.sampler.n = 1;
+        let (image, _) = Renderer::new(config).unwrap().render();
+        assert_eq!((image.width, image.height), (8, 6));
+    }
+
+    #[test]
+    fn cornell_box_validates_and_renders() {
+        let config = round_trip(Generator::Cornell, "rtow_generate_cornell.json");
+        assert_eq!(objects(&config), 8);
+        assert_eq!(config.background_color, FloatRgb::new(0.0, 0.0, 0.0));
+        renders(config);
+
+        let yaml = round_trip(Generator::Cornell, "rtow_generate_cornell.yaml");
+        assert_eq!(objects(&yaml), 8);
+    }
+
+    #[test]
+    fn random_spheres_validate() {
+        let config = round_trip(Generator::RandomSpheres { seed: 7 }, "rtow_generate_spheres.json");
+        // the ground, the three large spheres and most of the 22 x 22 grid
+        assert!((400..=488).contains(&objects(&config)), "{}", objects(&config));
+    }
+
+    #[test]
+    fn random_spheres_follow_the_seed() {
+        let json = |seed| random_spheres(seed).to_string_as(ConfigFormat::Json).unwrap();
+        assert_eq!(json(3), json(3));
+        assert_ne!(json(3), json(4));
+    }
+
+    #[test]
+    fn generate_is_a_subcommand() {
+        let parse = |args: &[&str]| Command::parse_from(args.iter().map(|s| s.to_string()));
+        assert_eq!(
+            parse(&["generate", "cornell", "--out", "box.json"]).unwrap(),
+            Command::Generate(Generator::Cornell, Some(String::from("box.json")))
+        );
+        assert_eq!(
+            parse(&["generate", "random-spheres", "--seed", "42"]).unwrap(),
+            Command::Generate(Generator::RandomSpheres { seed: 42 }, None)
+        );
+        assert!(parse(&["generate"]).is_err());
+        assert!(parse(&["generate", "teapot"]).is_err());
+        assert!(parse(&["generate", "cornell", "--seed", "42"]).is_err());
+    }
+}
+
+#[cfg(test)]
+mod random_spheres_tests {
+    use geometry3d::*;
+    use rtow::config::*;
+    use rtow::generate::RandomSpheres;
+    use rtow::object::Object;
+    use rtow::renderer::Renderer;
+
+    fn spheres(spheres: RandomSpheres) -> Vec<(Point3, Point3, String)> {
+        let material = |object: &Object| {
+            let value = serde_json::to_value(object).unwrap();
+            let material = value["Sphere"]["material"].as_object().unwrap();
+            material.keys().next().unwrap().clone()
+        };
+        spheres
+            .expand()
+            .iter()
+            .map(|object| match object {
+                Object::Sphere(sphere) => (sphere.center(0.0), sphere.center(1.0), material(object)),
+                _ => panic!("Only spheres are generated."),
+            })
+            .collect()
+    }
+
+    fn seeded(seed: u64) -> RandomSpheres {
+        RandomSpheres {
+            seed,
+            ..RandomSpheres::default()
+        }
+    }
+
+    // the positions are drawn as Float, and f32 takes fewer random bits for
+    // each than f64, so each width draws its own
+    #[test]
+    fn expansion_is_fixed_by_the_seed() {
+        let expanded = spheres(seeded(42));
+        assert_eq!(expanded.len(), 481);
+        let expected: [[f64; 3]; 4] = if Float::MANTISSA_DIGITS < f64::MANTISSA_DIGITS {
+            [
+                [-10.526098, 0.2, -10.776135],
+                [-10.526098, 0.21717139, -10.776135],
+                [-10.626539, 0.2, -9.686993],
+                [-6.9140997, 0.2, 1.7869017],
+            ]
+        } else {
+            [
+                [-10.51154731108717, 0.2, -10.427181410770494],
+                [-10.51154731108717, 0.20162604817647994, -10.427181410770494],
+                [-10.544465573798174, 0.2, -9.648415652597357],
+                [-6.440115931716807, 0.2, 1.8154553354352874],
+            ]
+        };
+        let drawn = [expanded[0].0, expanded[0].1, expanded[1].0, expanded[100].0];
+        for (point, [x, y, z]) in drawn.into_iter().zip(expected) {
+            assert_eq!(point, Point3::new(x as Float, y as Float, z as Float));
+        }
+
+        assert_eq!(spheres(seeded(42)), expanded);
+        assert_ne!(spheres(seeded(43)), expanded);
+    }
+
+    #[test]
+    fn parameters_shape_the_spheres() {
+        let diffuse = spheres(RandomSpheres {
+            grid: 2,
+            diffuse_probability: 1.0,
+            metal_probability: 0.0,
+            max_bounce: 0.0,
+            exclusion_radius: 0.0,
+            small_radius: 0.5,
+            ..seeded(1)
+        });
+        assert_eq!(diffuse.len(), 16);
+        for (center0, center1, material) in diffuse {
+            assert_eq!((center0, center0.y(), material.as_str()), (center1, 0.5, "Lambertian"));
+        }
+
+        let glass = spheres(RandomSpheres {
+            diffuse_probability: 0.0,
+            metal_probability: 0.0,
+            ..seeded(1)
+        });
+        assert!(glass.iter().all(|(_, _, material)| material == "Dielectric"));
+
+        let excluded = RandomSpheres {
+            exclusion_center: Point3::new(0.0, 0.0, 0.0),
+            exclusion_radius: 100.0,
+            ..seeded(1)
+        };
+        assert!(excluded.expand().is_empty());
+    }
+
+    #[test]
+    fn spheres_are_expanded_before_rendering() {
+        let text = r#"{
+            "image": {"filename": "random_spheres_test.png", "width": 8, "height": 6},
+            "camera": {"look_from": [0.0, 4.0, 6.0]},
+            "sampler": {"n": 1},
+            "seed": 1,
+            "scene_list": {"objects": [
+                {"Named": {"name": "grid", "object": {"RandomSpheres": {"seed": 42, "grid": 1}}}},
+                {"RandomSpheres": {"seed": 42, "grid": 2}}
+            ]}
+        }"#;
+        let config = Config::parse(text, ConfigFormat::Json).unwrap();
+        let mut list = config.scene_list.clone();
+        list.expand();
+        assert_eq!(list.iter().count(), 17);
+        assert!(matches!(list.get(0), Some(Object::Named(_))));
+        assert!(list.iter().all(|object| !matches!(object, Object::RandomSpheres(_))));
+
+        let (image, _) = Renderer::new(config).unwrap().render();
+        assert_eq!((image.width, image.height), (8, 6));
+    }
+
+    #[test]
+    fn invalid_parameters_are_reported() {
+        let text = r#"{"scene_list": {"objects": [
+            {"Sphere": {"location": {"origin": [0.0, 0.0, 0.0], "direction": [0.0, 0.0, 0.0], "time": 0.0},
+                        "radius": 1.0, "material": {"Dielectric": {"index_of_refraction": 1.5}}}},
+            {"RandomSpheres": {"small_radius": 0.0, "diffuse_probability": 0.9, "metal_probability": 0.2}}
+        ]}}"#;
+        let errors = Config::parse(text, ConfigFormat::Json).unwrap().validate().unwrap_err();
+        let errors: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "scene_list.objects[1].RandomSpheres.small_radius: must be positive",
+                "scene_list.objects[1].RandomSpheres.metal_probability: \
+                 cannot add up to more than one with diffuse_probability",
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod dry_run_tests {
+    use rtow::batch::*;
+    use rtow::cli::Args;
+    use rtow::texture::{ImageTexture, TextureHeader};
+    use std::path::{Path, PathBuf};
+
+    fn write_png(path: &Path, width: u32, height: u32, color: png::ColorType) {
+        let file = std::fs::File::create(path).unwrap();
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(png::BitDepth::Eight);
+        let channels = match color {
+            png::ColorType::Rgb => 3,
+            _ => 4,
+        };
+        let data = vec![128; (width * height * channels) as usize];
+        encoder.write_header().unwrap().write_image_data(&data).unwrap();
+    }
+
+    // a textured sphere and a small grid of random spheres, in a fresh
+    // directory holding the texture unless `texture` is false
+    fn layout(name: &str, texture: bool) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        if texture {
+            write_png(&dir.join("wood.png"), 6, 4, png::ColorType::Rgb);
+        }
+        let scene = format!(
+            r#"{{
+                "image": {{"filename": "{}", "width": 20, "height": 10}},
+                "scene_list": {{"objects": [
+                    {{"Sphere": {{
+                        "location": {{"origin": [0.0, 1.0, 0.0], "direction": [0.0, 0.0, 0.0], "time": 0.0}},
+                        "radius": 1.0,
+                        "material": {{"Lambertian": {{"albedo": {{"ImageTexture": {{"U": {{"filename": "wood.png"}}}}}}}}}}
+                    }}}},
+                    {{"RandomSpheres": {{"seed": 1, "grid": 1, "exclusion_radius": 0.0}}}}
+                ]}}
+            }}"#,
+            dir.join("render.png").display()
+        );
+        std::fs::write(dir.join("scene.json"), scene).unwrap();
+        dir
+    }
+
+    fn args(args: &[&str]) -> Args {
+        Args::parse_from(args.iter().map(|s| s.to_string())).unwrap()
+    }
+
+    #[test]
+    fn dry_run_describes_a_good_scene() {
+        let dir = layout("rtow_dry_run_test", true);
+        let scene = dir.join("scene.json");
+        let scene = scene.to_str().unwrap();
+
+        let report = check_scene(scene, &args(&[scene])).unwrap();
+        assert_eq!(report.objects, 5);
+        assert!(report.bvh.max_depth >= 3);
+        let (lo, hi) = report.bounds.unwrap();
+        assert_eq!((lo.y(), hi.y()), (0.0, 2.0));
+        assert_eq!((report.width, report.height), (20, 10));
+        let texture = dir.join("wood.png").to_string_lossy().into_owned();
+        assert_eq!(report.textures, [(texture.clone(), TextureHeader { width: 6, height: 4 })]);
+        assert!(report.memory > 6 * 4 * 3);
+        assert!(report.to_string().contains(&format!("Texture {texture}: 6 x 4")));
+
+        let batch = render_batch(&args(&[scene, "--dry-run"])).unwrap();
+        assert!(batch.succeeded() && batch.dry_run && batch.rendered.is_empty());
+        assert!(batch.to_string().starts_with("Checked 1 scenes"));
+        assert!(!dir.join("render.png").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dry_run_fails_on_a_missing_texture() {
+        let dir = layout("rtow_dry_run_missing_test", false);
+        let scene = dir.join("scene.json");
+        let scene = scene.to_str().unwrap();
+
+        let error = check_scene(scene, &args(&[scene])).unwrap_err().to_string();
+        assert!(error.starts_with("Failed to open ") && error.contains("wood.png"), "{error}");
+
+        let batch = render_batch(&args(&[scene, "--dry-run"])).unwrap();
+        assert_eq!(batch.failed.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn probe_rejects_unsupported_textures() {
+        let path = std::env::temp_dir().join("rtow_probe_rgba.png");
+        write_png(&path, 2, 2, png::ColorType::Rgba);
+        let error = ImageTexture::probe(path.to_str().unwrap()).unwrap_err();
+        assert!(error.contains("is not RGB"), "{error}");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod path_expansion_tests {
+    use rtow::batch::load_scene;
+    use rtow::cli::Args;
+    use rtow::config::*;
+    use rtow::expand::*;
+    use std::path::PathBuf;
+
+    fn fake_env(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some(String::from("/home/rtow")),
+            "ASSETS" => Some(String::from("/srv/assets")),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expands_set_variables() {
+        let expand = |path| expand_with(path, fake_env).unwrap();
+        assert_eq!(expand("$ASSETS/wood.png"), "/srv/assets/wood.png");
+        assert_eq!(expand("${ASSETS}_old/wood.png"), "/srv/assets_old/wood.png");
+        assert_eq!(expand("textures/wood.png"), "textures/wood.png");
+        assert_eq!(expand("price$$.png"), "price$.png");
+        assert_eq!(expand("$/wood.png"), "$/wood.png");
+    }
+
+    #[test]
+    fn expands_a_leading_tilde() {
+        let expand = |path| expand_with(path, fake_env).unwrap();
+        assert_eq!(expand("~/textures/brick.png"), "/home/rtow/textures/brick.png");
+        assert_eq!(expand("~"), "/home/rtow");
+        assert_eq!(expand("~other/brick.png"), "~other/brick.png");
+        assert_eq!(expand("textures/~/brick.png"), "textures/~/brick.png");
+        let error = expand_with("~/brick.png", |_| None).unwrap_err();
+        assert!(error.contains("HOME"), "{error}");
+    }
+
+    #[test]
+    fn unset_variables_are_named() {
+        let error = expand_with("$MISSING/wood.png", fake_env).unwrap_err();
+        assert_eq!(error, "Cannot expand $MISSING/wood.png as the environment variable MISSING is not set.");
+        assert!(expand_with("${MISSING}", fake_env).unwrap_err().contains("MISSING"));
+        assert!(expand_with("${ASSETS/wood.png", fake_env).unwrap_err().contains("never closed"));
+    }
+
+    // a scene including its objects, both named through RTOW_TEST_ASSETS
+    fn layout(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("objects.json"),
+            r#"[{"Sphere": {
+                "location": {"origin": [0.0, 0.0, 0.0], "direction": [0.0, 0.0, 0.0], "time": 0.0},
+                "radius": 1.0,
+                "material": {"Lambertian": {"albedo": {"ImageTexture": {"U": {"filename": "$RTOW_TEST_ASSETS/wood.png"}}}}}
+            }}]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("scene.json"),
+            r#"{
+                "image": {"filename": "${RTOW_TEST_ASSETS}/render.png"},
+                "scene_list": {"objects": [{"include": "$RTOW_TEST_ASSETS/objects.json"}]}
+            }"#,
+        )
+        .unwrap();
+        dir
+    }
+
+    fn texture(config: &Config) -> String {
+        let value = serde_json::to_value(&config.scene_list).unwrap();
+        let sphere = &value["objects"][0]["Sphere"];
+        sphere["material"]["Lambertian"]["albedo"]["ImageTexture"]["U"]["filename"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn config_paths_are_expanded() {
+        let dir = layout("rtow_expand_test");
+        let scene = dir.join("scene.json");
+        let assets = std::env::temp_dir().join("rtow_expand_test");
+        std::env::set_var("RTOW_TEST_ASSETS", &assets);
+
+        let config = Config::read(&scene).unwrap();
+        assert_eq!(texture(&config), assets.join("wood.png").to_str().unwrap());
+        assert_eq!(config.image.filename, assets.join("render.png").to_str().unwrap());
+
+        // left as written, the include cannot be found
+        let scene = scene.to_str().unwrap();
+        let args = Args::parse_from([scene, "--literal-paths"].map(String::from)).unwrap();
+        assert!(load_scene(scene, &args).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn literal_paths_are_kept() {
+        let dir = layout("rtow_literal_paths_test");
+        let objects = std::fs::read_to_string(dir.join("objects.json")).unwrap();
+        std::fs::write(dir.join("objects.json"), objects.replace("RTOW_TEST_ASSETS", "RTOW_TEST_UNSET")).unwrap();
+        std::fs::write(
+            dir.join("scene.json"),
+            r#"{"scene_list": {"objects": [{"include": "objects.json"}]}}"#,
+        )
+        .unwrap();
+        let scene = dir.join("scene.json");
+        let scene = scene.to_str().unwrap();
+
+        let args = Args::parse_from([scene, "--literal-paths"].map(String::from)).unwrap();
+        assert!(args.literal_paths);
+        let config = load_scene(scene, &args).unwrap();
+        assert_eq!(texture(&config), "$RTOW_TEST_UNSET/wood.png");
+
+        let error = Config::read(scene).unwrap_err().to_string();
+        assert!(error.contains("the environment variable RTOW_TEST_UNSET is not set"), "{error}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod ray_tests {
+    use geometry3d::*;
+    use rtow::camera::Camera;
+    use rtow::color::*;
+    use rtow::config::*;
+    use rtow::hit_record::HitContext;
+    use rtow::image::Image;
+    use rtow::material::*;
+    use rtow::object::*;
+    use rtow::texture::Texture;
+
+    fn sphere(material: Material) -> Object {
+        let location = Ray3 {
+            origin: Point3::new(0.0, 0.0, -2.0),
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+        Object::from(Sphere::new(location, 1.0, material))
+    }
+
+    #[test]
+    fn camera_rays_are_normalized() {
+        let image = Image::new(ImageConfig::default());
+        let camera = Camera::new(CameraConfig::default(), &image);
+        for (s, t) in [(0.0, 0.0), (0.5, 0.5), (1.0, 0.25)] {
+            assert!(camera.get_ray(s, t).is_normalized());
+        }
+    }
+
+    #[test]
+    fn scattered_rays_are_normalized() {
+        let materials = [
+            Material::Lambertian(Lambertian::new(Texture::from(FloatRgb::new(0.5, 0.5, 0.5)))),
+            Material::Metal(Metal::new(FloatRgb::new(0.5, 0.5, 0.5), 0.5)),
+            Material::Dielectric(Dielectric::new(1.5)),
+        ];
+        for material in materials {
+            let mut world = sphere(material);
+            for i in 0..100 {
+                let offset = i as Float / 400.0;
+                let ray = Ray3::new(Point3::default(), Vec3::new(offset, -offset, -1.0), 0.0).unwrap();
+                let range = TRange::new(0.001, Float::INFINITY);
+                let (rec, mat) = world.hit(ray, range, &mut HitContext::default()).unwrap();
+                if let Some((_, scattered)) = mat.scatter(rec) {
+                    assert!(scattered.is_normalized(), "{scattered}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn normalizing_keeps_the_hit_point() {
+        let mut world = sphere(Material::Dielectric(Dielectric::new(1.5)));
+        let (origin, direction) = (Point3::new(0.1, 0.2, 0.0), Vec3::new(0.0, 0.0, -3.0));
+        let unnormalized = Ray3 {
+            origin,
+            direction,
+            time: 0.0,
+        };
+        let normalized = Ray3::new(origin, direction, 0.0).unwrap();
+
+        let range = TRange::new(0.001, Float::INFINITY);
+        let (a, _) = world.hit(unnormalized, range, &mut HitContext::default()).unwrap();
+        let (b, _) = world.hit(normalized, range, &mut HitContext::default()).unwrap();
+        assert_approx_eq!(a.point, b.point);
+        assert_approx_eq!(b.t, 3.0 * a.t);
+        assert_approx_eq!(normalized.at(b.t), unnormalized.at(a.t));
+    }
+}
+
+#[cfg(test)]
+mod bvh_traversal_tests {
+    use geometry3d::*;
+    use rtow::color::FloatRgb;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use rtow::config::BvhBuilder;
+    use rtow::generate::RandomSpheres;
+    use rtow::hit_record::HitContext;
+    use rtow::material::Material;
+    use rtow::object::*;
+
+    fn spheres() -> List {
+        let mut list = List::new();
+        for x in -3..=3 {
+            for z in -3..=3 {
+                let location = Ray3 {
+                    origin: Point3::new(x as Float, 0.1 * (x * z) as Float, z as Float),
+                    direction: Vec3::default(),
+                    time: 0.0,
+                };
+                let material = Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5));
+                list.add(Object::from(Sphere::new(location, 0.3, material)));
+            }
+        }
+        list
+    }
+
+    #[test]
+    fn nearest_hit_matches_a_list() {
+        for builder in [BvhBuilder::Sah, BvhBuilder::Median] {
+            let mut list = Object::from(spheres());
+            let mut bvh = BVHNode::from_list(&mut spheres(), TRange::new(0.0, 0.0), builder).unwrap();
+            let range = TRange::new(0.001, Float::INFINITY);
+            let mut hits = 0;
+            for i in 0..200 {
+                let a = i as Float * 0.1;
+                let origin = Point3::new(6.0 * a.cos(), 2.0, 6.0 * a.sin());
+                let target = Point3::new(3.0 * (a * 1.7).sin(), 0.0, 3.0 * (a * 2.3).cos());
+                let ray = Ray3::new(origin, target - origin, 0.0).unwrap();
+
+                let expected = list.hit(ray, range, &mut HitContext::default()).map(|(rec, _)| rec.t);
+                let actual = bvh.hit(ray, range, &mut HitContext::default()).map(|(rec, _)| rec.t);
+                assert_eq!(actual, expected, "{builder:?} {ray}");
+                hits += actual.is_some() as u32;
+            }
+            assert!(hits > 50, "{builder:?} {hits}");
+        }
+    }
+
+    #[test]
+    fn rect_bounding_boxes_are_thin() {
+        let material = Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5));
+        let rect = Object::from(XZRect::new(TRange::new(-1.0, 2.0), TRange::new(0.0, 1.0), 3.0, material));
+        let aabb = rect.bounding_box(TRange::new(0.0, 0.0)).unwrap();
+        assert_approx_eq!(aabb.lo(), Point3::new(-1.0, 3.0 - 5e-5, 0.0));
+        assert_approx_eq!(aabb.hi(), Point3::new(2.0, 3.0 + 5e-5, 1.0));
+    }
+
+    #[test]
+    fn nearest_hit_on_rects_matches_a_list() {
+        let rects = || {
+            let mut list = List::new();
+            for i in 0..8 {
+                let material = Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5));
+                let (x, y) = (TRange::new(-1.0, 1.0), TRange::new(-1.0, 1.0));
+                list.add(XYRect::new(x, y, i as Float * 0.5, material).into());
+            }
+            list
+        };
+        for builder in [BvhBuilder::Sah, BvhBuilder::Median] {
+            let mut list = Object::from(rects());
+            let mut bvh = BVHNode::from_list(&mut rects(), TRange::new(0.0, 0.0), builder).unwrap();
+            let range = TRange::new(0.001, Float::INFINITY);
+            let mut hits = 0;
+            for i in 0..100 {
+                let a = i as Float * 0.1;
+                let origin = Point3::new(0.5 * a.cos(), 0.5 * a.sin(), -2.0);
+                let ray = Ray3::new(origin, Vec3::new(0.2 * a.sin(), 0.1, 1.0), 0.0).unwrap();
+
+                let expected = list.hit(ray, range, &mut HitContext::default()).map(|(rec, _)| rec.t);
+                let actual = bvh.hit(ray, range, &mut HitContext::default()).map(|(rec, _)| rec.t);
+                assert_eq!(actual, expected, "{builder:?} {ray}");
+                hits += actual.is_some() as u32;
+            }
+            assert_eq!(hits, 100, "{builder:?}");
+        }
+    }
+
+    fn point(rng: &mut StdRng, size: Float) -> Point3 {
+        Point3::new(rng.gen_range(-size..size), rng.gen_range(-size..size), rng.gen_range(-size..size))
+    }
+
+    // overlapping spheres of many sizes and rectangles facing along z
+    fn random_scene(rng: &mut StdRng) -> List {
+        let mut list = List::new();
+        for _ in 0..rng.gen_range(1..60) {
+            let material = Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5));
+            if rng.gen_bool(0.8) {
+                let location = Ray3 {
+                    origin: point(rng, 5.0),
+                    direction: Vec3::default(),
+                    time: 0.0,
+                };
+                list.add(Sphere::new(location, rng.gen_range(0.05..1.5), material).into());
+            } else {
+                let x = rng.gen_range(-5.0..5.0);
+                let y = rng.gen_range(-5.0..5.0);
+                let (x, y) = (TRange::new(x, x + rng.gen_range(0.1..3.0)), TRange::new(y, y + rng.gen_range(0.1..3.0)));
+                list.add(XYRect::new(x, y, rng.gen_range(-5.0..5.0), material).into());
+            }
+        }
+        list
+    }
+
+    // the t, point and normal of the nearest hit of every ray agree
+    fn assert_matches_a_list(scene: &List, builder: BvhBuilder, rays: &[Ray3]) {
+        let mut list = Object::from(scene.clone());
+        let mut bvh = BVHNode::from_list(&mut scene.clone(), TRange::new(0.0, 0.0), builder).unwrap();
+        let range = TRange::new(0.001, Float::INFINITY);
+        for &ray in rays {
+            let nearest = |object: &mut Object| {
+                let hit = object.hit(ray, range, &mut HitContext::default());
+                hit.map(|(rec, _)| (rec.t, rec.point, rec.normal))
+            };
+            assert_eq!(nearest(&mut bvh), nearest(&mut list), "{builder:?} {ray}");
+        }
+    }
+
+    #[test]
+    fn nearest_hit_matches_a_list_on_random_scenes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..50 {
+            let scene = random_scene(&mut rng);
+            let rays: Vec<_> = (0..100)
+                .map(|_| {
+                    let origin = point(&mut rng, 8.0);
+                    Ray3::new(origin, point(&mut rng, 4.0) - origin, 0.0).unwrap()
+                })
+                .collect();
+            for builder in [BvhBuilder::Sah, BvhBuilder::Median] {
+                assert_matches_a_list(&scene, builder, &rays);
+            }
+        }
+    }
+
+    #[test]
+    fn hits_trees_deeper_than_the_stack() {
+        // the spheres span more orders of magnitude than f32 has
+        if Float::MAX_10_EXP < f64::MAX_10_EXP {
+            return;
+        }
+        // each sphere is so much larger and further out than the one before
+        // that the others all fall in the first bin, so the surface area
+        // heuristic splits off one sphere per level
+        let x = |i: i32| (20.0 as Float).powi(i);
+        let mut scene = List::new();
+        for i in 0..80 {
+            let location = Ray3 {
+                origin: Point3::new(x(i), 0.0, 0.0),
+                direction: Vec3::default(),
+                time: 0.0,
+            };
+            let material = Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5));
+            scene.add(Sphere::new(location, 0.25 * x(i), material).into());
+        }
+        let Object::BVHNode(node) = BVHNode::from_list(&mut scene.clone(), TRange::new(0.0, 0.0), BvhBuilder::Sah).unwrap()
+        else {
+            panic!("Expected a BVH node.");
+        };
+        assert!(node.depth() > 64, "{}", node.depth());
+
+        // along x, passing the smaller spheres and hitting the i-th
+        let rays: Vec<_> = (0..80)
+            .map(|i| Ray3 {
+                origin: Point3::new(-1.0, 0.2 * x(i), 0.0),
+                direction: Vec3::e0(),
+                time: 0.0,
+            })
+            .collect();
+        assert_matches_a_list(&scene, BvhBuilder::Sah, &rays);
+    }
+
+    #[test]
+    fn sah_tests_fewer_boxes_on_random_spheres() {
+        // a smaller grid than the scene's, as the median builder is slow
+        let scene = |builder| {
+            let mut list = List::new();
+            list.add(Object::RandomSpheres(RandomSpheres {
+                grid: 5,
+                ..RandomSpheres::default()
+            }));
+            list.expand();
+            BVHNode::from_list(&mut list, TRange::new(0.0, 1.0), builder).unwrap()
+        };
+        let (mut sah, mut median) = (scene(BvhBuilder::Sah), scene(BvhBuilder::Median));
+        let (mut sah_ctx, mut median_ctx) = (HitContext::default(), HitContext::default());
+        let origin = Point3::new(13.0, 2.0, 3.0);
+        let range = TRange::new(0.001, Float::INFINITY);
+        for i in 0..2000 {
+            let a = i as Float * 0.0005;
+            let ray = Ray3::new(origin, Vec3::new(-13.0 + 4.0 * a.sin(), -2.0 + a.cos(), -3.0 + a), 0.0).unwrap();
+            let expected = median.hit(ray, range, &mut median_ctx).map(|(rec, _)| rec.t);
+            let actual = sah.hit(ray, range, &mut sah_ctx).map(|(rec, _)| rec.t);
+            assert_eq!(actual, expected, "{ray}");
+        }
+        assert!(
+            (sah_ctx.aabb_tests as Float) < 0.8 * median_ctx.aabb_tests as Float,
+            "{} {}",
+            sah_ctx.aabb_tests,
+            median_ctx.aabb_tests
+        );
+    }
+}
+
+#[cfg(test)]
+mod flat_bvh_tests {
+    use geometry3d::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use rtow::color::FloatRgb;
+    use rtow::config::BvhBuilder;
+    use rtow::hit_record::HitContext;
+    use rtow::material::Material;
+    use rtow::object::*;
+
+    // the tree over at least two objects
+    fn build(list: &mut List, builder: BvhBuilder) -> BVHNode {
+        match BVHNode::from_list(list, TRange::new(0.0, 0.0), builder).unwrap() {
+            Object::BVHNode(node) => node,
+            _ => panic!("Expected a BVH node."),
+        }
+    }
+
+    fn point(rng: &mut StdRng, size: Float) -> Point3 {
+        Point3::new(rng.gen_range(-size..size), rng.gen_range(-size..size), rng.gen_range(-size..size))
+    }
+
+    // spheres, rectangles and boxes, each of a material of its own
+    fn random_scene(rng: &mut StdRng) -> List {
+        let mut list = List::new();
+        for i in 0..rng.gen_range(2..60) {
+            let material = Material::metal(FloatRgb::new(0.5, 0.5, 0.5), i as Float / 100.0);
+            let object = match rng.gen_range(0..3) {
+                0 => {
+                    let location = Ray3 {
+                        origin: point(rng, 5.0),
+                        direction: Vec3::default(),
+                        time: 0.0,
+                    };
+                    Sphere::new(location, rng.gen_range(0.05..1.5), material).into()
+                }
+                1 => {
+                    let (x, y) = (rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0));
+                    let (x, y) = (TRange::new(x, x + rng.gen_range(0.1..3.0)), TRange::new(y, y + rng.gen_range(0.1..3.0)));
+                    XYRect::new(x, y, rng.gen_range(-5.0..5.0), material).into()
+                }
+                _ => {
+                    let p0 = point(rng, 5.0);
+                    RectPrism::new(p0, p0 + Vec3::new(1.0, 0.5, 2.0), material).into()
+                }
+            };
+            list.add(object);
+        }
+        list
+    }
+
+    #[test]
+    fn hits_match_the_tree() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..30 {
+            let mut scene = random_scene(&mut rng);
+            for builder in [BvhBuilder::Sah, BvhBuilder::Median] {
+                let tree = build(&mut scene, builder);
+                let flat = FlatBVH::from(tree.clone());
+                assert_eq!(flat.depth(), tree.depth());
+                let (mut tree, mut flat) = (Object::from(tree), Object::from(flat));
+                let range = TRange::new(0.001, Float::INFINITY);
+                for _ in 0..100 {
+                    let origin = point(&mut rng, 8.0);
+                    let ray = Ray3::new(origin, point(&mut rng, 4.0) - origin, 0.0).unwrap();
+                    let (mut tree_ctx, mut flat_ctx) = (HitContext::default(), HitContext::default());
+                    let nearest = |object: &mut Object, ctx: &mut HitContext| {
+                        let hit = object.hit(ray, range, ctx);
+                        hit.map(|(rec, material)| (rec.t, rec.point, rec.normal, format!("{material:?}")))
+                    };
+                    assert_eq!(nearest(&mut flat, &mut flat_ctx), nearest(&mut tree, &mut tree_ctx), "{builder:?} {ray}");
+                    assert_eq!(flat_ctx.aabb_tests, tree_ctx.aabb_tests, "{builder:?} {ray}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hits_match_the_tree_deeper_than_the_stack() {
+        // the spheres span more orders of magnitude than f32 has
+        if Float::MAX_10_EXP < f64::MAX_10_EXP {
+            return;
+        }
+        let x = |i: i32| (20.0 as Float).powi(i);
+        let mut scene = List::new();
+        for i in 0..80 {
+            let location = Ray3 {
+                origin: Point3::new(x(i), 0.0, 0.0),
+                direction: Vec3::default(),
+                time: 0.0,
+            };
+            let material = Material::metal(FloatRgb::new(0.5, 0.5, 0.5), i as Float / 100.0);
+            scene.add(Sphere::new(location, 0.25 * x(i), material).into());
+        }
+        let tree = build(&mut scene, BvhBuilder::Sah);
+        let flat = FlatBVH::from(tree.clone());
+        assert!(flat.depth() > 64, "{}", flat.depth());
+        let (mut tree, mut flat) = (Object::from(tree), Object::from(flat));
+
+        let range = TRange::new(0.001, Float::INFINITY);
+        for i in 0..80 {
+            let ray = Ray3 {
+                origin: Point3::new(-1.0, 0.2 * x(i), 0.0),
+                direction: Vec3::e0(),
+                time: 0.0,
+            };
+            let nearest = |object: &mut Object| {
+                let hit = object.hit(ray, range, &mut HitContext::default());
+                hit.map(|(rec, material)| (rec.t, format!("{material:?}")))
+            };
+            assert_eq!(nearest(&mut flat), nearest(&mut tree), "{ray}");
+        }
+    }
+
+    #[test]
+    fn leaves_hold_every_object_once() {
+        let mut scene = random_scene(&mut StdRng::seed_from_u64(1));
+        let objects = scene.iter().count();
+        let tree = build(&mut scene, BvhBuilder::Sah);
+        let flat = FlatBVH::from(tree.clone());
+        assert_eq!(flat.objects().len(), objects);
+        let aabb = Object::from(flat).bounding_box(TRange::new(0.0, 0.0)).unwrap();
+        assert_eq!((aabb.lo(), aabb.hi()), (tree.aabb().lo(), tree.aabb().hi()));
+    }
+}
+
+#[cfg(test)]
+mod bvh4_tests {
+    use geometry3d::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use rtow::color::FloatRgb;
+    use rtow::config::{Accelerator, BvhBuilder, Config};
+    use rtow::hit_record::HitContext;
+    use rtow::material::Material;
+    use rtow::object::*;
+    use rtow::renderer::Renderer;
+
+    // the tree over at least two objects
+    fn build(list: &mut List, builder: BvhBuilder) -> BVHNode {
+        match BVHNode::from_list(list, TRange::new(0.0, 0.0), builder).unwrap() {
+            Object::BVHNode(node) => node,
+            _ => panic!("Expected a BVH node."),
+        }
+    }
+
+    fn point(rng: &mut StdRng, size: Float) -> Point3 {
+        Point3::new(rng.gen_range(-size..size), rng.gen_range(-size..size), rng.gen_range(-size..size))
+    }
+
+    fn sphere(center: Point3, radius: Float, i: usize) -> Object {
+        let location = Ray3 {
+            origin: center,
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+        Sphere::new(location, radius, Material::metal(FloatRgb::new(0.5, 0.5, 0.5), i as Float / 100.0)).into()
+    }
+
+    // spheres and boxes, each of a material of its own
+    fn random_scene(rng: &mut StdRng) -> List {
+        let mut list = List::new();
+        for i in 0..rng.gen_range(2..80) {
+            let object = if rng.gen_bool(0.7) {
+                sphere(point(rng, 5.0), rng.gen_range(0.05..1.5), i)
+            } else {
+                let p0 = point(rng, 5.0);
+                let material = Material::metal(FloatRgb::new(0.5, 0.5, 0.5), i as Float / 100.0);
+                RectPrism::new(p0, p0 + Vec3::new(1.0, 0.5, 2.0), material).into()
+            };
+            list.add(object);
+        }
+        list
+    }
+
+    #[test]
+    fn hits_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let range = TRange::new(0.001, Float::INFINITY);
+        for _ in 0..30 {
+            let scene = random_scene(&mut rng);
+            for builder in [BvhBuilder::Sah, BvhBuilder::Median] {
+                let tree = build(&mut scene.clone(), builder);
+                let mut bvh4 = Object::from(BVH4::new(tree, TRange::new(0.0, 0.0)));
+                let mut list = Object::from(scene.clone());
+                for _ in 0..100 {
+                    let origin = point(&mut rng, 8.0);
+                    let ray = Ray3::new(origin, point(&mut rng, 4.0) - origin, 0.0).unwrap();
+                    let nearest = |object: &mut Object| {
+                        let hit = object.hit(ray, range, &mut HitContext::default());
+                        hit.map(|(rec, material)| (rec.t, rec.point, rec.normal, format!("{material:?}")))
+                    };
+                    assert_eq!(nearest(&mut bvh4), nearest(&mut list), "{builder:?} {ray}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hits_match_brute_force_deeper_than_the_stack() {
+        // the spheres span more orders of magnitude than f32 has
+        if Float::MAX_10_EXP < f64::MAX_10_EXP {
+            return;
+        }
+        let x = |i: i32| (20.0 as Float).powi(i);
+        let mut scene = List::new();
+        for i in 0..80 {
+            scene.add(sphere(Point3::new(x(i), 0.0, 0.0), 0.25 * x(i), i as usize));
+        }
+        let tree = build(&mut scene.clone(), BvhBuilder::Sah);
+        let mut bvh4 = Object::from(BVH4::new(tree, TRange::new(0.0, 0.0)));
+        let mut list = Object::from(scene);
+
+        let range = TRange::new(0.001, Float::INFINITY);
+        for i in 0..80 {
+            let ray = Ray3 {
+                origin: Point3::new(-1.0, 0.2 * x(i), 0.0),
+                direction: Vec3::e0(),
+                time: 0.0,
+            };
+            let nearest = |object: &mut Object| {
+                let hit = object.hit(ray, range, &mut HitContext::default());
+                hit.map(|(rec, material)| (rec.t, format!("{material:?}")))
+            };
+            assert_eq!(nearest(&mut bvh4), nearest(&mut list), "{ray}");
+        }
+    }
+
+    #[test]
+    fn collapses_to_half_the_depth() {
+        let mut scene = random_scene(&mut StdRng::seed_from_u64(1));
+        let objects = scene.iter().count();
+        let tree = build(&mut scene, BvhBuilder::Sah);
+        let bvh4 = BVH4::new(tree.clone(), TRange::new(0.0, 0.0));
+        assert_eq!(bvh4.objects().len(), objects);
+        assert!(bvh4.depth() <= tree.depth().div_ceil(2), "{} {}", bvh4.depth(), tree.depth());
+        assert!(bvh4.nodes().len() < tree.stats().nodes);
+        assert_eq!((bvh4.aabb().lo(), bvh4.aabb().hi()), (tree.aabb().lo(), tree.aabb().hi()));
+    }
+
+    #[test]
+    fn tests_fewer_nodes_than_a_binary_bvh() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut scene = List::new();
+        for i in 0..500 {
+            scene.add(sphere(point(&mut rng, 20.0), 0.5, i));
+        }
+        let tree = build(&mut scene, BvhBuilder::Sah);
+        let mut flat = Object::from(FlatBVH::from(tree.clone()));
+        let mut bvh4 = Object::from(BVH4::new(tree, TRange::new(0.0, 0.0)));
+        let (mut flat_ctx, mut bvh4_ctx) = (HitContext::default(), HitContext::default());
+        let range = TRange::new(0.001, Float::INFINITY);
+        for _ in 0..1000 {
+            let origin = point(&mut rng, 30.0);
+            let ray = Ray3::new(origin, point(&mut rng, 10.0) - origin, 0.0).unwrap();
+            flat.hit(ray, range, &mut flat_ctx);
+            bvh4.hit(ray, range, &mut bvh4_ctx);
+        }
+        assert!(bvh4_ctx.node_tests * 3 < flat_ctx.node_tests * 2, "{} {}", bvh4_ctx.node_tests, flat_ctx.node_tests);
+    }
+
+    fn config(accelerator: &str) -> Config {
+        let sphere = |x: f64, emit: f64| {
+            format!(
+                r#"{{"Sphere": {{
+                    "location": {{"origin": [{x}, 0.0, 0.0], "direction": [0.0, 0.0, 0.0], "time": 0.0}},
+                    "radius": 0.5,
+                    "material": {{"DiffuseLight": {{"emit": {{"SolidColor": [{emit}, 1.0, 0.5]}}}}}}
+                }}}}"#
+            )
+        };
+        let objects = [sphere(-1.0, 2.0), sphere(0.0, 1.0), sphere(1.0, 0.5)].join(", ");
+        let json = format!(
+            r#"{{
+                "image": {{"filename": "bvh4_test.png", "width": 16, "height": 16}},
+                "camera": {{
+                    "look_from": [0.0, 0.0, 5.0], "look_at": [0.0, 0.0, 0.0], "up": [0.0, 1.0, 0.0],
+                    "vertical_fov": 40.0, "aperture": 0.0, "focus_distance": 5.0,
+                    "time_min": 0.0, "time_max": 1.0
+                }},
+                "sampler": {{"n": 2, "max_depth": 5}},
+                "background_color": [0.1, 0.2, 0.3],
+                "accelerator": "{accelerator}",
+                "scene_list": {{"objects": [{objects}]}}
+            }}"#
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn renders_the_same_image_as_a_binary_bvh() {
+        let (bvh2, bvh4) = (config("bvh2"), config("bvh4"));
+        assert_eq!((bvh2.accelerator, bvh4.accelerator), (Accelerator::Bvh2, Accelerator::Bvh4));
+        let renderer = Renderer::new(bvh4).unwrap();
+        assert!(matches!(renderer.scene(), Object::BVH4(_)));
+        let pixels = |renderer: Renderer| {
+            let (image, _) = renderer.render();
+            image.iter().map(|(x, y)| image.pixel(x, y)).collect::<Vec<_>>()
+        };
+        assert!(pixels(renderer) == pixels(Renderer::new(bvh2).unwrap()));
+    }
+}
+
+#[cfg(test)]
+mod bvh_stats_tests {
+    use geometry3d::*;
+    use rtow::color::FloatRgb;
+    use rtow::config::BvhBuilder;
+    use rtow::material::Material;
+    use rtow::object::*;
+
+    // the tree over at least two objects
+    fn build(list: &mut List, builder: BvhBuilder) -> BVHNode {
+        match BVHNode::from_list(list, TRange::new(0.0, 0.0), builder).unwrap() {
+            Object::BVHNode(node) => node,
+            _ => panic!("Expected a BVH node."),
+        }
+    }
+
+    fn sphere(center: Point3, radius: Float) -> Object {
+        let location = Ray3 {
+            origin: center,
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+        Sphere::new(location, radius, Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5))).into()
+    }
+
+    #[test]
+    fn stats_of_a_row_of_spheres() {
+        let mut list = List::new();
+        for x in 0..4 {
+            list.add(sphere(Point3::new(3.0 * x as Float, 0.0, 0.0), 1.0));
+        }
+        let bvh = build(&mut list, BvhBuilder::Median);
+        let stats = bvh.stats();
+        assert_eq!((stats.nodes, stats.leaves, stats.max_depth), (3, 4, 2));
+        assert_eq!((stats.average_depth, stats.average_leaf_objects), (2.0, 1.0));
+        // the root's box and the two boxes of its children, which are apart
+        let area = |x: Float| 2.0 * (2.0 * x + 2.0 * x + 4.0);
+        assert_approx_eq!(stats.interior_surface_area, area(11.0) + 2.0 * area(5.0));
+        assert_eq!(stats.sibling_overlap, 0.0);
+        assert_eq!(bvh.validate(TRange::new(0.0, 0.0)), Ok(()));
+    }
+
+    #[test]
+    fn siblings_overlap() {
+        let mut list = List::new();
+        for x in [0.0, 0.5, 1.0, 1.5] {
+            list.add(sphere(Point3::new(x, 0.0, 0.0), 1.0));
+        }
+        let stats = build(&mut list, BvhBuilder::Median).stats();
+        // the boxes of the pairs of spheres overlap from 0.0 to 1.5 along x
+        assert_approx_eq!(stats.sibling_overlap, 2.0 * (1.5 * 2.0 + 1.5 * 2.0 + 2.0 * 2.0));
+    }
+
+    #[test]
+    fn objects_at_the_same_point_still_validate() {
+        for builder in [BvhBuilder::Sah, BvhBuilder::Median] {
+            let mut list = List::new();
+            for i in 0..40 {
+                list.add(sphere(Point3::new(1.0, 2.0, 3.0), 0.1 + i as Float * 0.01));
+            }
+            let bvh = build(&mut list, builder);
+            assert_eq!(bvh.validate(TRange::new(0.0, 0.0)), Ok(()), "{builder:?}");
+            let stats = bvh.stats();
+            assert!(stats.leaves >= 2 && stats.max_depth >= 2, "{builder:?} {stats:?}");
+            let objects = stats.average_leaf_objects * stats.leaves as f64;
+            assert_eq!(objects.round(), 40.0, "{builder:?} {stats:?}");
+        }
+    }
+
+    #[test]
+    fn validate_finds_a_child_outside_its_parent() {
+        let mut list = List::new();
+        for x in 0..4 {
+            list.add(sphere(Point3::new(3.0 * x as Float, 0.0, 0.0), 1.0));
+        }
+        let bvh = build(&mut list, BvhBuilder::Median);
+        // shrink the box of the right child of the root to miss its spheres
+        let mut json = serde_json::to_value(&bvh).unwrap();
+        let right = &mut json["right"]["BVHNode"]["aabb"];
+        right["hi"] = right["lo"].clone();
+        let bvh: BVHNode = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            bvh.validate(TRange::new(0.0, 0.0)),
+            Err(String::from("right.left is not inside its parent's box"))
+        );
+    }
+}
+
+#[cfg(test)]
+mod bvh_build_tests {
+    use geometry3d::*;
+    use rtow::color::FloatRgb;
+    use rtow::config::BvhBuilder;
+    use rtow::hit_record::HitContext;
+    use rtow::material::Material;
+    use rtow::object::*;
+
+    const BUILDERS: [BvhBuilder; 2] = [BvhBuilder::Sah, BvhBuilder::Median];
+
+    fn sphere(x: Float) -> Object {
+        let location = Ray3 {
+            origin: Point3::new(x, 0.0, 0.0),
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+        Sphere::new(location, 0.5, Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5))).into()
+    }
+
+    fn hits(object: &mut Object) -> usize {
+        let range = TRange::new(0.001, Float::INFINITY);
+        (0..10)
+            .filter(|&x| {
+                let ray = Ray3::new(Point3::new(x as Float, 0.0, -5.0), Vec3::e2(), 0.0).unwrap();
+                object.hit(ray, range, &mut HitContext::default()).is_some()
+            })
+            .count()
+    }
+
+    #[test]
+    fn an_empty_scene_is_an_empty_list() {
+        for builder in BUILDERS {
+            let mut list = List::new();
+            list.add(List::new().into());
+            let mut scene = BVHNode::from_list(&mut list, TRange::new(0.0, 0.0), builder).unwrap();
+            assert!(matches!(&scene, Object::List(list) if list.iter().count() == 0));
+            assert_eq!(hits(&mut scene), 0);
+        }
+    }
+
+    #[test]
+    fn a_single_object_is_left_bare() {
+        for builder in BUILDERS {
+            let mut list = List::new();
+            list.add(sphere(3.0));
+            let mut scene = BVHNode::from_list(&mut list, TRange::new(0.0, 0.0), builder).unwrap();
+            assert!(matches!(scene, Object::Sphere(_)), "{builder:?}");
+            assert_eq!(hits(&mut scene), 1);
+        }
+    }
+
+    #[test]
+    fn two_objects_make_one_node() {
+        for builder in BUILDERS {
+            let mut list = List::new();
+            list.add(sphere(3.0));
+            list.add(sphere(6.0));
+            let mut scene = BVHNode::from_list(&mut list, TRange::new(0.0, 0.0), builder).unwrap();
+            let Object::BVHNode(node) = &scene else {
+                panic!("Expected a BVH node.");
+            };
+            assert!(matches!((node.left(), node.right()), (Object::Sphere(_), Object::Sphere(_))));
+            assert_eq!(hits(&mut scene), 2);
+        }
+    }
+
+    #[test]
+    fn a_lone_object_is_not_split_into_both_children() {
+        let mut list = List::new();
+        for x in 0..3 {
+            list.add(sphere(3.0 * x as Float));
+        }
+        let Object::BVHNode(node) = BVHNode::from_list(&mut list, TRange::new(0.0, 0.0), BvhBuilder::Median).unwrap()
+        else {
+            panic!("Expected a BVH node.");
+        };
+        let stats = node.stats();
+        assert_eq!((stats.nodes, stats.leaves), (2, 3));
+    }
+
+    #[test]
+    fn an_unbounded_box_names_the_object() {
+        let mut list = List::new();
+        list.add(sphere(0.0));
+        list.add(Named::new("sun", sphere(Float::INFINITY)).into());
+        let error = BVHNode::from_list(&mut list, TRange::new(0.0, 0.0), BvhBuilder::Sah).unwrap_err();
+        assert!(error.starts_with("Object 1 (sun) of the scene"), "{error}");
+    }
+}
+
+#[cfg(test)]
+mod bvh_refit_tests {
+    use geometry3d::*;
+    use rtow::color::FloatRgb;
+    use rtow::config::BvhBuilder;
+    use rtow::material::Material;
+    use rtow::object::*;
+
+    // a row of spheres along x, the last moving further along by `speed` a
+    // second
+    fn spheres(speed: Float) -> List {
+        let mut list = List::new();
+        for x in 0..6 {
+            let location = Ray3 {
+                origin: Point3::new(3.0 * x as Float, 0.0, 0.0),
+                direction: Vec3::new(if x == 5 { speed } else { 0.0 }, 0.0, 0.0),
+                time: 0.0,
+            };
+            list.add(Sphere::new(location, 1.0, Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5))).into());
+        }
+        list
+    }
+
+    fn build(list: &mut List, time: Float, builder: BvhBuilder) -> BVHNode {
+        match BVHNode::from_list(list, TRange::new(time, time), builder).unwrap() {
+            Object::BVHNode(node) => node,
+            _ => panic!("Expected a BVH node."),
+        }
+    }
+
+    #[test]
+    fn refit_boxes_match_a_fresh_build() {
+        for builder in [BvhBuilder::Sah, BvhBuilder::Median] {
+            let mut tree = build(&mut spheres(0.5), 0.0, builder);
+            let area = tree.stats().interior_surface_area;
+            assert!(!tree.refit(TRange::new(1.0, 1.0), area), "{builder:?}");
+            assert_eq!(tree.validate(TRange::new(1.0, 1.0)), Ok(()));
+            let fresh = build(&mut spheres(0.5), 1.0, builder);
+            assert_eq!(tree.aabb().hi().x(), 16.5);
+            assert_eq!((tree.aabb().lo(), tree.aabb().hi()), (fresh.aabb().lo(), fresh.aabb().hi()));
+        }
+
+        // the spheres are in the same order either way, so the median split
+        // gives the same hierarchy and every box, including those on the path to the moved
+        // sphere, is as built at the new time
+        let mut tree = build(&mut spheres(0.5), 0.0, BvhBuilder::Median);
+        tree.refit(TRange::new(1.0, 1.0), Float::INFINITY);
+        let fresh = build(&mut spheres(0.5), 1.0, BvhBuilder::Median);
+        let json = |tree: &BVHNode| serde_json::to_value(tree).unwrap();
+        assert_eq!(json(&tree), json(&fresh));
+    }
+
+    #[test]
+    fn refit_asks_for_a_rebuild_after_a_large_move() {
+        let mut tree = build(&mut spheres(100.0), 0.0, BvhBuilder::Sah);
+        let area = tree.stats().interior_surface_area;
+        assert!(!tree.refit(TRange::new(0.001, 0.001), area));
+        assert!(tree.refit(TRange::new(1.0, 1.0), area));
+        // still correct, if slower to hit
+        assert_eq!(tree.validate(TRange::new(1.0, 1.0)), Ok(()));
+    }
+}
+
+#[cfg(test)]
+mod hit_range_tests {
+    use geometry3d::*;
+    use rtow::color::FloatRgb;
+    use rtow::hit_record::HitContext;
+    use rtow::material::Material;
+    use rtow::object::*;
+
+    fn grey() -> Material {
+        Material::lambertian(FloatRgb::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn sphere_excludes_the_start_of_the_range() {
+        // a ray leaving the surface of the sphere hits its far side rather
+        // than the point it starts from
+        let location = Ray3 {
+            origin: Point3::default(),
+            direction: Vec3::default(),
+            time: 0.0,
+        };
+        let mut sphere = Object::from(Sphere::new(location, 1.0, grey()));
+        let ray = Ray3::new(Point3::new(-1.0, 0.0, 0.0), Vec3::e0(), 0.0).unwrap();
+        let (rec, _) = sphere.hit(ray, TRange::new(0.0, Float::INFINITY), &mut HitContext::default()).unwrap();
+        assert_eq!(rec.t, 2.0);
+        assert!(sphere.hit(ray, TRange::new(0.0, 2.0), &mut HitContext::default()).is_none());
+    }
+
+    #[test]
+    fn rect_excludes_the_ends_of_the_range() {
+        let mut rect = Object::from(XYRect::new(TRange::new(-1.0, 1.0), TRange::new(-1.0, 1.0), 1.0, grey()));
+        let ray = Ray3::new(Point3::default(), Vec3::e2(), 0.0).unwrap();
+        let mut hit = |range: std::ops::Range<Float>| rect.hit(ray, range.into(), &mut HitContext::default()).is_some();
+        assert!(hit(0.0..2.0));
+        assert!(!hit(0.0..1.0));
+        assert!(!hit(1.0..2.0));
+    }
+
+    #[test]
+    fn rect_includes_its_edges() {
+        let mut rect = Object::from(XYRect::new(TRange::new(-1.0, 1.0), TRange::new(-1.0, 1.0), 1.0, grey()));
+        let ray = Ray3::new(Point3::new(1.0, -1.0, 0.0), Vec3::e2(), 0.0).unwrap();
+        assert!(rect.hit(ray, (0.0..2.0).into(), &mut HitContext::default()).is_some());
+    }
+
+    #[test]
+    fn first_of_coincident_objects_is_kept() {
+        // with an inclusive end a second surface at the same t would replace
+        // the first
+        let mut list = List::new();
+        let (x, y) = (TRange::new(-1.0, 1.0), TRange::new(-1.0, 1.0));
+        list.add(XYRect::new(x, y, 1.0, grey()).into());
+        list.add(XYRect::new(x, y, 1.0, Material::metal(FloatRgb::new(0.5, 0.5, 0.5), 0.0)).into());
+        let mut list = Object::from(list);
+        let ray = Ray3::new(Point3::default(), Vec3::e2(), 0.0).unwrap();
+        let (rec, material) = list.hit(ray, (0.001..Float::INFINITY).into(), &mut HitContext::default()).unwrap();
+        assert_eq!(rec.t, 1.0);
+        assert!(matches!(material, Material::Lambertian(_)), "{material:?}");
+    }
+}
+
+#[cfg(test)]
+mod random_tests {
+    use geometry3d::*;
+    use rand::rngs::StdRng;
+    use rand::{RngCore, SeedableRng};
+    use rtow::config::RngKind;
+    use rtow::random::*;
+
+    const SAMPLES: usize = 80_000;
+
+    // well above the 99.9th percentile of chi-square with 63 degrees of
+    // freedom, so only a wrong distribution fails
+    const CHI_SQUARE_LIMIT: f64 = 110.0;
+
+    fn rng() -> Random<StdRng> {
+        Random::new(StdRng::seed_from_u64(7))
+    }
+
+    /// Counts samples into 8 × 8 buckets of equal probability, given the
+    /// bucket of each sample, and returns the chi-square statistic.
+    fn chi_square(mut bucket: impl FnMut() -> (usize, usize)) -> f64 {
+        let mut counts = [[0usize; 8]; 8];
+        for _ in 0..SAMPLES {
+            let (i, j) = bucket();
+            counts[i.min(7)][j.min(7)] += 1;
+        }
+        let expected = SAMPLES as f64 / 64.0;
+        counts
+            .iter()
+            .flatten()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum()
+    }
+
+    // the bucket of an angle in [-π, π]
+    fn sector(y: Float, x: Float) -> usize {
+        ((y.atan2(x) / consts::PI + 1.0) * 4.0) as usize
+    }
+
+    #[test]
+    fn unit_vectors_are_uniform_on_the_sphere() {
+        let mut rng = rng();
+        let statistic = chi_square(|| {
+            let v = rng.unit_vector();
+            assert!((v.length() - 1.0).abs() < 1e-5, "{v:?}");
+            // bands of equal height have equal area
+            (((v.z() + 1.0) * 4.0) as usize, sector(v.y(), v.x()))
+        });
+        assert!(statistic < CHI_SQUARE_LIMIT, "{statistic}");
+    }
+
+    #[test]
+    fn points_are_uniform_in_the_sphere() {
+        let mut rng = rng();
+        let statistic = chi_square(|| {
+            let v = rng.in_unit_sphere();
+            let r = v.length();
+            assert!(r <= 1.0, "{v:?}");
+            // shells of equal r³ have equal volume
+            ((r.powi(3) * 8.0) as usize, sector(v.y(), v.x()))
+        });
+        assert!(statistic < CHI_SQUARE_LIMIT, "{statistic}");
+    }
+
+    #[test]
+    fn points_are_uniform_in_the_disk() {
+        let mut rng = rng();
+        let statistic = chi_square(|| {
+            let v = rng.in_unit_disk();
+            let r2 = v.quadrance();
+            assert!(r2 <= 1.0 && v.z() == 0.0, "{v:?}");
+            // rings of equal r² have equal area
+            ((r2 * 8.0) as usize, sector(v.y(), v.x()))
+        });
+        assert!(statistic < CHI_SQUARE_LIMIT, "{statistic}");
+    }
+
+    #[test]
+    fn stratified_points_fill_each_cell_once() {
+        let mut rng = rng();
+        let (nx, ny) = (4, 3);
+        let points: Vec<_> = rng.stratified_2d(nx, ny).collect();
+        assert_eq!(points.len(), nx * ny);
+
+        let mut cells = vec![0; nx * ny];
+        for &(x, y) in &points {
+            assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y), "{x} {y}");
+            cells[(y * ny as Float) as usize * nx + (x * nx as Float) as usize] += 1;
+        }
+        assert_eq!(cells, vec![1; nx * ny]);
+
+        // the cells are visited in a shuffled order
+        let order = |points: &[(Float, Float)]| -> Vec<usize> {
+            points.iter().map(|&(x, y)| (y * ny as Float) as usize * nx + (x * nx as Float) as usize).collect()
+        };
+        assert_ne!(order(&points), (0..nx * ny).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn latin_hypercube_fills_each_row_and_column_once() {
+        let mut rng = rng();
+        let n = 7;
+        let mut columns = vec![0; n];
+        let mut rows = vec![0; n];
+        for (x, y) in rng.latin_hypercube(n) {
+            assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y), "{x} {y}");
+            columns[(x * n as Float) as usize] += 1;
+            rows[(y * n as Float) as usize] += 1;
+        }
+        assert_eq!(columns, vec![1; n]);
+        assert_eq!(rows, vec![1; n]);
+        assert_eq!(rng.latin_hypercube(0).count(), 0);
+    }
+
+    #[test]
+    fn square_maps_uniformly_onto_the_disk() {
+        let mut rng = rng();
+        let statistic = chi_square(|| {
+            let v = square_to_disk(rng.random(), rng.random());
+            assert!(v.quadrance() <= 1.0 + 1e-6 && v.z() == 0.0, "{v:?}");
+            ((v.quadrance() * 8.0) as usize, sector(v.y(), v.x()))
+        });
+        assert!(statistic < CHI_SQUARE_LIMIT, "{statistic}");
+        assert_eq!(square_to_disk(0.5, 0.5), Vec3::default());
+        assert_approx_eq!(square_to_disk(1.0, 0.5), Vec3::e0());
+    }
+
+    // the output of pcg32-demo from the reference implementation
+    #[test]
+    fn pcg32_matches_the_reference() {
+        let mut rng = Pcg32::new(42, 54);
+        let output: Vec<u32> = (0..6).map(|_| rng.next_u32()).collect();
+        assert_eq!(output, [0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e]);
+    }
+
+    #[test]
+    fn xoshiro256_matches_the_reference() {
+        let mut rng = Xoshiro256::new([1, 2, 3, 4]);
+        let output: Vec<u64> = (0..4).map(|_| rng.next_u64()).collect();
+        assert_eq!(output, [41943041, 58720359, 3588806011781223, 3591011842654386]);
+        assert_eq!(Xoshiro256::from_seed([0; 32]), Xoshiro256::seed_from_u64(0));
+    }
+
+    #[test]
+    fn every_generator_gives_uniform_numbers() {
+        let n = 100_000;
+        for kind in [RngKind::Pcg32, RngKind::Xoshiro256, RngKind::Std] {
+            let mut rng = Random::new(Generator::seed_from_u64(kind, 3));
+            let values: Vec<f64> = (0..n).map(|_| rng.random()).collect();
+            let mean = values.iter().sum::<f64>() / n as f64;
+            let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n as f64;
+            assert!((mean - 0.5).abs() < 0.005, "{kind:?} {mean}");
+            assert!((variance - 1.0 / 12.0).abs() < 0.002, "{kind:?} {variance}");
+
+            // and uniform directions
+            let statistic = chi_square(|| {
+                let v = rng.unit_vector();
+                (((v.z() + 1.0) * 4.0) as usize, sector(v.y(), v.x()))
+            });
+            assert!(statistic < CHI_SQUARE_LIMIT, "{kind:?} {statistic}");
+        }
+    }
+
+    #[test]
+    fn pixel_streams_follow_the_generator() {
+        let first = |kind| PixelStreams::new(7, kind).stream(1, 2, 3).random::<u64>();
+        assert_eq!(first(RngKind::Std), Random::for_pixel(7, 1, 2, 3).random::<u64>());
+        assert_ne!(first(RngKind::Pcg32), first(RngKind::Std));
+        assert_ne!(first(RngKind::Xoshiro256), first(RngKind::Std));
+        assert_eq!(first(RngKind::Pcg32), first(RngKind::Pcg32));
+    }
+
+    #[test]
+    fn generator_is_chosen_in_the_config() {
+        let config: RngKind = serde_json::from_str(r#""xoshiro256""#).unwrap();
+        assert_eq!(config, RngKind::Xoshiro256);
+        assert_eq!(RngKind::default(), RngKind::Std);
+        assert!(serde_json::from_str::<RngKind>(r#""mt19937""#).is_err());
+    }
+
+    #[test]
+    fn cone_directions_lie_within_the_cone() {
+        let mut rng = rng();
+        let axis = Vec3::new(1.0, -2.0, 0.5);
+        let unit_axis = axis.unit().unwrap();
+        for cos_theta_max in [-1.0, 0.0, 0.5, 0.99] {
+            for _ in 0..2000 {
+                let v = rng.in_cone(axis, cos_theta_max);
+                assert!((v.length() - 1.0).abs() < 1e-5, "{v:?}");
+                assert!(v.dot(unit_axis) >= cos_theta_max - 1e-5, "{cos_theta_max} {v:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn cone_directions_match_the_analytic_pdf() {
+        // bands of equal height about the axis cover equal solid angles, so
+        // with the pdf constant over the cone each band is equally likely
+        let mut rng = rng();
+        let cos_theta_max = 0.6;
+        let statistic = chi_square(|| {
+            let v = rng.in_cone(Vec3::e2(), cos_theta_max);
+            (((v.z() - cos_theta_max) / (1.0 - cos_theta_max) * 8.0) as usize, sector(v.y(), v.x()))
+        });
+        assert!(statistic < CHI_SQUARE_LIMIT, "{statistic}");
+
+        // and the pdf integrates to one over the solid angle of the cone
+        let solid_angle = 2.0 * consts::PI * (1.0 - cos_theta_max);
+        assert_approx_eq!(cone_pdf(cos_theta_max) * solid_angle, 1.0);
+    }
+
+    #[test]
+    fn directions_to_a_sphere_hit_it() {
+        let mut rng = rng();
+        let center = Vec3::new(0.0, 3.0, 4.0);
+        let radius = 1.5;
+        for _ in 0..2000 {
+            let (v, pdf) = rng.to_sphere(center, center.quadrance(), radius);
+            // the closest approach of the ray to the center is within the radius
+            let along = v.dot(center);
+            assert!(along > 0.0 && center.quadrance() - along * along <= radius * radius + 1e-4, "{v:?}");
+            assert_approx_eq!(pdf, cone_pdf((1.0 - radius * radius / 25.0).sqrt()));
+        }
+
+        // from inside the sphere every direction is sampled
+        let (v, pdf) = rng.to_sphere(center, center.quadrance(), 6.0);
+        assert!((v.length() - 1.0).abs() < 1e-5);
+        assert_approx_eq!(pdf, 1.0 / (4.0 * consts::PI));
+    }
+
+    #[test]
+    fn lopsided_samples_fail_the_bucket_test() {
+        // a disk sampled with a uniform radius crowds the center
+        let mut rng = rng();
+        let statistic = chi_square(|| {
+            let r: Float = rng.random();
+            let theta: Float = rng.random_range(-consts::PI..consts::PI);
+            ((r * r * 8.0) as usize, sector(theta.sin(), theta.cos()))
+        });
+        assert!(statistic > CHI_SQUARE_LIMIT, "{statistic}");
+    }
+}